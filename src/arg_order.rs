@@ -0,0 +1,174 @@
+use std::str::FromStr;
+
+pub const DEFAULT_ARG_ORDER: &str = "{base} {format} {path}";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Slot {
+    Base,
+    Format,
+    Path,
+    Literal(String),
+}
+
+/// Template controlling how the final argv is assembled, e.g.
+/// `{base} {format} {path}` or `{base} {path} -- {format}`.
+///
+/// `{base}` is the configured RSpec command, `{format}` the formatter flags
+/// and `{path}` the spec file argument. Any other token is passed through
+/// verbatim, which helps with wrappers that forward their arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgOrder {
+    slots: Vec<Slot>,
+}
+
+impl ArgOrder {
+    pub fn assemble(&self, base: &[String], format: &[String], paths: &[String]) -> Vec<String> {
+        let mut argv = Vec::new();
+
+        for slot in &self.slots {
+            match slot {
+                Slot::Base => argv.extend_from_slice(base),
+                Slot::Format => argv.extend_from_slice(format),
+                Slot::Path => argv.extend_from_slice(paths),
+                Slot::Literal(token) => argv.push(token.clone()),
+            }
+        }
+
+        argv
+    }
+}
+
+impl Default for ArgOrder {
+    fn default() -> Self {
+        DEFAULT_ARG_ORDER
+            .parse()
+            .expect("Default argument order must be valid")
+    }
+}
+
+impl FromStr for ArgOrder {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let slots: Vec<Slot> = template
+            .split_whitespace()
+            .map(|token| match token {
+                "{base}" => Slot::Base,
+                "{format}" => Slot::Format,
+                "{path}" => Slot::Path,
+                other => Slot::Literal(other.to_string()),
+            })
+            .collect();
+
+        if let Some(Slot::Literal(token)) = slots
+            .iter()
+            .find(|s| matches!(s, Slot::Literal(t) if t.starts_with('{') && t.ends_with('}')))
+        {
+            return Err(format!("Unknown placeholder in argument order: {}", token));
+        }
+
+        if slots.first() != Some(&Slot::Base) {
+            return Err("Argument order must start with {base}".to_string());
+        }
+
+        if !slots.contains(&Slot::Path) {
+            return Err("Argument order must contain {path}".to_string());
+        }
+
+        for (placeholder, slot) in [
+            ("{base}", Slot::Base),
+            ("{format}", Slot::Format),
+            ("{path}", Slot::Path),
+        ] {
+            if slots.iter().filter(|s| **s == slot).count() > 1 {
+                return Err(format!(
+                    "Argument order contains {} more than once",
+                    placeholder
+                ));
+            }
+        }
+
+        Ok(Self { slots })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_default_order() {
+        let order = ArgOrder::default();
+        let argv = order.assemble(
+            &strings(&["bundle", "exec", "rspec"]),
+            &strings(&["-f", "progress"]),
+            &strings(&["spec/models/user_spec.rb"]),
+        );
+        assert_eq!(
+            argv,
+            strings(&["bundle", "exec", "rspec", "-f", "progress", "spec/models/user_spec.rb"])
+        );
+    }
+
+    #[test]
+    fn test_path_before_format() {
+        let order: ArgOrder = "{base} {path} {format}".parse().unwrap();
+        let argv = order.assemble(
+            &strings(&["bin/docker-rspec"]),
+            &strings(&["-f", "progress"]),
+            &strings(&["spec/models/user_spec.rb:12"]),
+        );
+        assert_eq!(
+            argv,
+            strings(&["bin/docker-rspec", "spec/models/user_spec.rb:12", "-f", "progress"])
+        );
+    }
+
+    #[test]
+    fn test_literal_tokens_are_kept() {
+        let order: ArgOrder = "{base} {path} -- {format}".parse().unwrap();
+        let argv = order.assemble(
+            &strings(&["docker", "compose", "run", "web"]),
+            &strings(&["-f", "json"]),
+            &strings(&["spec/user_spec.rb"]),
+        );
+        assert_eq!(
+            argv,
+            strings(&["docker", "compose", "run", "web", "spec/user_spec.rb", "--", "-f", "json"])
+        );
+    }
+
+    #[test]
+    fn test_format_slot_is_optional() {
+        let order: ArgOrder = "{base} {path}".parse().unwrap();
+        let argv = order.assemble(
+            &strings(&["rspec"]),
+            &strings(&["-f", "progress"]),
+            &strings(&["spec/user_spec.rb"]),
+        );
+        assert_eq!(argv, strings(&["rspec", "spec/user_spec.rb"]));
+    }
+
+    #[test]
+    fn test_invalid_orders() {
+        let cases = vec![
+            ("{path} {base}", "Argument order must start with {base}"),
+            ("", "Argument order must start with {base}"),
+            ("{base} {format}", "Argument order must contain {path}"),
+            ("{base} {path} {path}", "Argument order contains {path} more than once"),
+            ("{base} {format} {path} {format}", "Argument order contains {format} more than once"),
+            ("{base} {path} {base}", "Argument order contains {base} more than once"),
+            ("{base} {paths}", "Unknown placeholder in argument order: {paths}"),
+            ("{base} {path} {seed}", "Unknown placeholder in argument order: {seed}"),
+        ];
+
+        for (template, expected) in cases {
+            let result = template.parse::<ArgOrder>();
+            assert_eq!(result.unwrap_err(), expected, "template: {}", template);
+        }
+    }
+}
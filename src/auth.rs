@@ -0,0 +1,189 @@
+use std::sync::{Arc, RwLock};
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, StatusCode, header::{AUTHORIZATION, WWW_AUTHENTICATE}};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthScope {
+    /// Every tool call requires the token
+    #[default]
+    All,
+    /// Only tools that run specs or change state require the token
+    Runs,
+}
+
+/// The transport's policy, replaced when the config file is reloaded.
+pub type SharedAuthPolicy = Arc<RwLock<AuthPolicy>>;
+
+#[derive(Debug, Clone, Default)]
+pub struct AuthPolicy {
+    token: Option<String>,
+    scope: AuthScope,
+}
+
+impl AuthPolicy {
+    pub fn new(token: Option<String>, scope: AuthScope) -> Self {
+        Self { token, scope }
+    }
+
+    /// With `--auth-scope runs`, `read_only` tools (validation, listing)
+    /// stay open for discovery; tools that run specs or change state, such
+    /// as writing to the project, need the token whatever the scope.
+    pub fn requires_token(&self, read_only: bool) -> bool {
+        self.token.is_some() && (self.scope == AuthScope::All || !read_only)
+    }
+
+    /// Whether every request to the transport needs the token, so it can be
+    /// rejected before it reaches a session. With `--auth-scope runs` some
+    /// tools stay open, so only tool calls are checked.
+    pub fn guards_transport(&self) -> bool {
+        self.token.is_some() && self.scope == AuthScope::All
+    }
+
+    /// Checks the `Authorization: Bearer` header of the HTTP request that
+    /// carried a tool call.
+    pub fn check(&self, tool: &str, read_only: bool, headers: Option<&HeaderMap>) -> Result<(), String> {
+        let Some(expected) = self.token.as_deref().filter(|_| self.requires_token(read_only)) else {
+            return Ok(());
+        };
+
+        match bearer_token(headers) {
+            Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => Ok(()),
+            Some(_) => Err(format!("Unauthorized: invalid bearer token for tool '{}'", tool)),
+            None => Err(format!("Unauthorized: tool '{}' requires a bearer token", tool)),
+        }
+    }
+}
+
+fn bearer_token(headers: Option<&HeaderMap>) -> Option<&str> {
+    headers
+        .and_then(|h| h.get(AUTHORIZATION))
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Middleware for [`axum::middleware::from_fn_with_state`], layered over the
+/// SSE and message routes. While the current policy
+/// [`guards_transport`](AuthPolicy::guards_transport), requests without the
+/// token get a 401 and never reach the MCP service.
+pub async fn require_token(State(auth): State<SharedAuthPolicy>, request: Request, next: Next) -> Response {
+    let auth = auth.read().unwrap().clone();
+    if !auth.guards_transport() {
+        return next.run(request).await;
+    }
+    let expected = auth.token.as_deref().unwrap_or_default();
+    match bearer_token(Some(request.headers())) {
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => next.run(request).await,
+        _ => {
+            tracing::warn!("Rejecting unauthenticated request to {}", request.uri().path());
+            (StatusCode::UNAUTHORIZED, [(WWW_AUTHENTICATE, "Bearer")], "Unauthorized").into_response()
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_no_token_configured() {
+        let policy = AuthPolicy::default();
+        assert!(policy.check("run_rspec", false, None).is_ok());
+        assert!(policy.check("list_spec_files", true, None).is_ok());
+    }
+
+    #[test]
+    fn test_all_scope_protects_every_tool() {
+        let policy = AuthPolicy::new(Some("secret".to_string()), AuthScope::All);
+
+        assert_eq!(
+            policy.check("list_spec_files", true, None).unwrap_err(),
+            "Unauthorized: tool 'list_spec_files' requires a bearer token"
+        );
+        assert!(policy.check("list_spec_files", true, Some(&headers("Bearer secret"))).is_ok());
+    }
+
+    #[test]
+    fn test_runs_scope_leaves_read_tools_open() {
+        let policy = AuthPolicy::new(Some("secret".to_string()), AuthScope::Runs);
+
+        assert!(policy.check("list_spec_files", true, None).is_ok());
+        assert_eq!(
+            policy.check("run_rspec", false, None).unwrap_err(),
+            "Unauthorized: tool 'run_rspec' requires a bearer token"
+        );
+        assert!(policy.check("run_rspec", false, Some(&headers("Bearer secret"))).is_ok());
+        assert_eq!(
+            policy.check("configure", false, None).unwrap_err(),
+            "Unauthorized: tool 'configure' requires a bearer token"
+        );
+    }
+
+    #[test]
+    fn test_wrong_token_rejected() {
+        let policy = AuthPolicy::new(Some("secret".to_string()), AuthScope::Runs);
+
+        assert_eq!(
+            policy.check("run_rspec", false, Some(&headers("Bearer guess"))).unwrap_err(),
+            "Unauthorized: invalid bearer token for tool 'run_rspec'"
+        );
+        assert!(policy.check("run_rspec", false, Some(&headers("Basic secret"))).is_err());
+    }
+
+    #[test]
+    fn test_guards_transport_only_for_all_scope() {
+        assert!(!AuthPolicy::default().guards_transport());
+        assert!(AuthPolicy::new(Some("secret".to_string()), AuthScope::All).guards_transport());
+        assert!(!AuthPolicy::new(Some("secret".to_string()), AuthScope::Runs).guards_transport());
+    }
+
+    #[tokio::test]
+    async fn test_require_token_middleware() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let policy = Arc::new(RwLock::new(AuthPolicy::new(Some("secret".to_string()), AuthScope::All)));
+        let router = axum::Router::new()
+            .route("/sse", axum::routing::get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(policy.clone(), require_token));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let status = |authorization: &'static str| async move {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let request = format!(
+                "GET /sse HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n",
+                authorization
+            );
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response.lines().next().unwrap_or_default().to_string()
+        };
+
+        assert_eq!(status("").await, "HTTP/1.1 401 Unauthorized");
+        assert_eq!(status("Authorization: Bearer guess\r\n").await, "HTTP/1.1 401 Unauthorized");
+        assert_eq!(status("Authorization: Bearer secret\r\n").await, "HTTP/1.1 200 OK");
+
+        *policy.write().unwrap() = AuthPolicy::new(Some("rotated".to_string()), AuthScope::All);
+        assert_eq!(status("Authorization: Bearer secret\r\n").await, "HTTP/1.1 401 Unauthorized");
+        assert_eq!(status("Authorization: Bearer rotated\r\n").await, "HTTP/1.1 200 OK");
+
+        *policy.write().unwrap() = AuthPolicy::new(Some("rotated".to_string()), AuthScope::Runs);
+        assert_eq!(status("").await, "HTTP/1.1 200 OK");
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use clap::ValueEnum;
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+
+/// What a run does when `--max-in-flight` runs are already going.
+///
+/// - `reject` fails fast, so callers can retry or go elsewhere, but a burst
+///   of requests sees errors.
+/// - `queue` waits for a slot; nothing fails, but waits are unbounded.
+/// - `queue-with-limit` waits while at most `--max-queued` runs are
+///   waiting and rejects beyond that, bounding both load and latency.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Backpressure {
+    Reject,
+    #[default]
+    Queue,
+    QueueWithLimit,
+}
+
+/// Caps concurrent runs, applying a [`Backpressure`] strategy once full.
+#[derive(Debug, Clone)]
+pub struct InFlightLimit {
+    slots: Arc<Semaphore>,
+    max_in_flight: usize,
+    strategy: Backpressure,
+    max_queued: usize,
+    queue_timeout: Option<Duration>,
+    queue: Arc<RunQueue>,
+}
+
+/// Waiting runs in arrival order, which is also the order the semaphore
+/// hands out slots.
+#[derive(Debug, Default)]
+struct RunQueue {
+    waiting: Mutex<VecDeque<u64>>,
+    next_id: AtomicU64,
+    changed: Notify,
+}
+
+/// Holds a run slot until dropped.
+#[derive(Debug)]
+pub struct RunSlot {
+    _permit: OwnedSemaphorePermit,
+}
+
+/// A run's place in the queue, given up when dropped.
+struct QueuedGuard<'a> {
+    queue: &'a RunQueue,
+    id: u64,
+}
+
+impl QueuedGuard<'_> {
+    /// 1 for the run that gets the next free slot.
+    fn position(&self) -> usize {
+        let waiting = self.queue.waiting.lock().unwrap();
+        waiting.iter().position(|id| *id == self.id).map_or(0, |index| index + 1)
+    }
+}
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.queue.waiting.lock().unwrap().retain(|id| *id != self.id);
+        self.queue.changed.notify_waiters();
+    }
+}
+
+impl InFlightLimit {
+    pub fn new(max_in_flight: usize, strategy: Backpressure, max_queued: usize) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(max_in_flight)),
+            max_in_flight,
+            strategy,
+            max_queued,
+            queue_timeout: None,
+            queue: Arc::new(RunQueue::default()),
+        }
+    }
+
+    /// Give up on a slot after waiting this long.
+    pub fn with_queue_timeout(mut self, queue_timeout: Option<Duration>) -> Self {
+        self.queue_timeout = queue_timeout;
+        self
+    }
+
+    /// Waits for a slot as the strategy allows, calling `on_position` with
+    /// the run's place in the queue when it has to wait and whenever that
+    /// place changes.
+    pub async fn acquire(&self, on_position: impl Fn(usize) + Send) -> Result<RunSlot, String> {
+        if let Ok(permit) = self.slots.clone().try_acquire_owned() {
+            return Ok(RunSlot { _permit: permit });
+        }
+        if self.strategy == Backpressure::Reject {
+            return Err(format!(
+                "{} runs already in flight (--max-in-flight); try again later",
+                self.max_in_flight
+            ));
+        }
+
+        let queued = {
+            let mut waiting = self.queue.waiting.lock().unwrap();
+            if self.strategy == Backpressure::QueueWithLimit && waiting.len() >= self.max_queued {
+                return Err(format!(
+                    "Run queue is full ({} waiting, --max-queued); try again later",
+                    self.max_queued
+                ));
+            }
+            let id = self.queue.next_id.fetch_add(1, Ordering::SeqCst);
+            waiting.push_back(id);
+            QueuedGuard { queue: &self.queue, id }
+        };
+
+        tracing::info!("{} runs in flight; waiting for a slot", self.max_in_flight);
+        let permit = self.slots.clone().acquire_owned();
+        tokio::pin!(permit);
+        let deadline = async {
+            match self.queue_timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(deadline);
+
+        let mut reported = 0;
+        loop {
+            // Registered before reading the position so no change is missed
+            let changed = self.queue.changed.notified();
+            let position = queued.position();
+            if position != reported {
+                on_position(position);
+                reported = position;
+            }
+
+            tokio::select! {
+                permit = &mut permit => {
+                    let permit = permit.map_err(|e| format!("Run slots closed: {}", e))?;
+                    return Ok(RunSlot { _permit: permit });
+                }
+                _ = changed => {}
+                _ = &mut deadline => {
+                    return Err(format!(
+                        "No run slot freed up within {} seconds (--queue-timeout); {} runs in flight, try again later",
+                        self.queue_timeout.unwrap_or_default().as_secs_f64(),
+                        self.max_in_flight
+                    ));
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn queued(&self) -> usize {
+        self.queue.waiting.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[tokio::test]
+    async fn test_reject_when_full() {
+        let limit = InFlightLimit::new(1, Backpressure::Reject, 0);
+        let slot = limit.acquire(|_| {}).await.unwrap();
+        assert_eq!(
+            limit.acquire(|_| {}).await.unwrap_err(),
+            "1 runs already in flight (--max-in-flight); try again later"
+        );
+
+        drop(slot);
+        assert!(limit.acquire(|_| {}).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_queue_waits_for_slot() {
+        let limit = InFlightLimit::new(1, Backpressure::Queue, 0);
+        let slot = limit.acquire(|_| {}).await.unwrap();
+
+        let waiting = tokio::spawn({
+            let limit = limit.clone();
+            async move { limit.acquire(|_| {}).await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+        assert_eq!(limit.queued(), 1);
+
+        drop(slot);
+        waiting.await.unwrap().unwrap();
+        assert_eq!(limit.queued(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_queue_caps_concurrent_runs() {
+        const MAX: usize = 2;
+        let limit = InFlightLimit::new(MAX, Backpressure::Queue, 0);
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let runs: Vec<_> = (0..MAX + 2)
+            .map(|_| {
+                let (limit, running, peak) = (limit.clone(), running.clone(), peak.clone());
+                tokio::spawn(async move {
+                    let _slot = limit.acquire(|_| {}).await.unwrap();
+                    peak.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(30)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for run in runs {
+            run.await.unwrap();
+        }
+
+        assert_eq!(peak.load(Ordering::SeqCst), MAX);
+        assert_eq!(limit.queued(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_queue_with_limit_rejects_beyond_limit() {
+        let limit = InFlightLimit::new(1, Backpressure::QueueWithLimit, 1);
+        let slot = limit.acquire(|_| {}).await.unwrap();
+
+        let waiting = tokio::spawn({
+            let limit = limit.clone();
+            async move { limit.acquire(|_| {}).await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(
+            limit.acquire(|_| {}).await.unwrap_err(),
+            "Run queue is full (1 waiting, --max-queued); try again later"
+        );
+
+        drop(slot);
+        waiting.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_queue_reports_positions() {
+        let limit = InFlightLimit::new(1, Backpressure::Queue, 0);
+        let slot = limit.acquire(|_| {}).await.unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let first = tokio::spawn({
+            let (limit, tx) = (limit.clone(), tx.clone());
+            async move { limit.acquire(move |position| tx.send(("first", position)).unwrap()).await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let second = tokio::spawn({
+            let limit = limit.clone();
+            async move { limit.acquire(move |position| tx.send(("second", position)).unwrap()).await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(rx.recv().await, Some(("first", 1)));
+        assert_eq!(rx.recv().await, Some(("second", 2)));
+
+        // The first waiter giving up moves the second to the front
+        first.abort();
+        assert_eq!(rx.recv().await, Some(("second", 1)));
+        drop(slot);
+        second.await.unwrap().unwrap();
+        assert_eq!(limit.queued(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_queue_timeout() {
+        let limit = InFlightLimit::new(1, Backpressure::Queue, 0).with_queue_timeout(Some(Duration::from_millis(50)));
+        let slot = limit.acquire(|_| {}).await.unwrap();
+
+        assert_eq!(
+            limit.acquire(|_| {}).await.unwrap_err(),
+            "No run slot freed up within 0.05 seconds (--queue-timeout); 1 runs in flight, try again later"
+        );
+        assert_eq!(limit.queued(), 0);
+        drop(slot);
+        assert!(limit.acquire(|_| {}).await.is_ok());
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::rspec_output::FailureReport;
+
+/// Known failures from `--baseline-failures`, so a run can report only the
+/// failures that are new. The file has one `path description` line per
+/// failing example; lines are matched on path and description, not line
+/// number, so examples that moved still match.
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    path: PathBuf,
+    entries: Arc<Mutex<BTreeSet<Entry>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Entry {
+    file: String,
+    description: String,
+}
+
+impl Entry {
+    fn parse(line: &str) -> Option<Self> {
+        let (file, description) = line.trim().split_once(' ')?;
+        Some(Self {
+            file: file.to_string(),
+            description: description.trim().to_string(),
+        })
+    }
+
+    fn for_failure(failure: &FailureReport) -> Self {
+        let file = failure.location.as_deref().unwrap_or_default();
+        let file = file.rsplit_once(':').map_or(file, |(file, _)| file);
+        Self {
+            file: file.trim_start_matches("./").to_string(),
+            description: failure.description.clone(),
+        }
+    }
+
+    /// Whether the entry is for a spec run by `target`, a file or directory.
+    fn is_under(&self, target: &str) -> bool {
+        let target = target.trim_start_matches("./").trim_end_matches('/');
+        self.file == target || self.file.starts_with(&format!("{}/", target))
+    }
+}
+
+impl Baseline {
+    /// Reads the baseline file. A missing file is an empty baseline, which
+    /// `update_baseline` creates on first use.
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents.lines().filter_map(Entry::parse).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(e) => return Err(format!("Cannot read baseline {}: {}", path.display(), e)),
+        };
+
+        Ok(Self {
+            path,
+            entries: Arc::new(Mutex::new(entries)),
+        })
+    }
+
+    /// Splits `failures` into the new ones and a count of those already in
+    /// the baseline.
+    pub fn subtract(&self, failures: Vec<FailureReport>) -> (Vec<FailureReport>, usize) {
+        let entries = self.entries.lock().unwrap();
+        let (known, new): (Vec<_>, Vec<_>) = failures
+            .into_iter()
+            .partition(|failure| entries.contains(&Entry::for_failure(failure)));
+        (new, known.len())
+    }
+
+    /// Replaces the entries for specs under `target` with `failures` and
+    /// saves the file. Returns the number of entries in the baseline.
+    pub fn update(&self, target: &str, failures: &[FailureReport]) -> Result<usize, String> {
+        let mut entries = self.entries.lock().unwrap();
+        let mut updated: BTreeSet<Entry> = entries.iter().filter(|entry| !entry.is_under(target)).cloned().collect();
+        updated.extend(failures.iter().map(Entry::for_failure));
+
+        let contents: String = updated
+            .iter()
+            .map(|entry| format!("{} {}\n", entry.file, entry.description))
+            .collect();
+        std::fs::write(&self.path, contents)
+            .map_err(|e| format!("Cannot write baseline {}: {}", self.path.display(), e))?;
+
+        *entries = updated;
+        Ok(entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(description: &str, location: &str) -> FailureReport {
+        FailureReport {
+            description: description.to_string(),
+            location: Some(location.to_string()),
+            message: "Failure/Error: boom".to_string(),
+            sub_failures: Vec::new(),
+        }
+    }
+
+    /// The baseline is removed with the returned directory.
+    fn baseline(contents: &str) -> (tempfile::TempDir, Baseline) {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("baseline.txt");
+        std::fs::write(&path, contents).unwrap();
+        (tmp, Baseline::load(path).unwrap())
+    }
+
+    #[test]
+    fn test_subtract_known_failures() {
+        let (_tmp, baseline) = baseline("spec/models/user_spec.rb User rejects blanks\n");
+        let (new, known) = baseline.subtract(vec![
+            failure("User rejects blanks", "./spec/models/user_spec.rb:12"),
+            failure("User saves", "./spec/models/user_spec.rb:4"),
+            failure("User rejects blanks", "./spec/models/order_spec.rb:12"),
+        ]);
+
+        assert_eq!(known, 1);
+        assert_eq!(
+            new.iter().map(|f| f.location.as_deref().unwrap()).collect::<Vec<_>>(),
+            vec!["./spec/models/user_spec.rb:4", "./spec/models/order_spec.rb:12"]
+        );
+    }
+
+    #[test]
+    fn test_subtract_matches_moved_examples() {
+        let (_tmp, baseline) = baseline("");
+        baseline.update("spec/models", &[failure("User rejects blanks", "./spec/models/user_spec.rb:12")]).unwrap();
+
+        let (new, known) = baseline.subtract(vec![failure("User rejects blanks", "./spec/models/user_spec.rb:40")]);
+        assert!(new.is_empty());
+        assert_eq!(known, 1);
+    }
+
+    #[test]
+    fn test_update_replaces_entries_under_target() {
+        let (_tmp, baseline) = baseline("spec/models/user_spec.rb User saves\nspec/requests/api_spec.rb API responds\n",
+        );
+
+        let total = baseline
+            .update("spec/models/", &[failure("User rejects blanks", "./spec/models/user_spec.rb:12")])
+            .unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(
+            std::fs::read_to_string(&baseline.path).unwrap(),
+            "spec/models/user_spec.rb User rejects blanks\nspec/requests/api_spec.rb API responds\n"
+        );
+
+        let reloaded = Baseline::load(baseline.path.clone()).unwrap();
+        let (new, known) = reloaded.subtract(vec![failure("User saves", "./spec/models/user_spec.rb:4")]);
+        assert_eq!((new.len(), known), (1, 0));
+    }
+
+    #[test]
+    fn test_missing_file_is_empty() {
+        let tmp = tempfile::tempdir().unwrap();
+        let baseline = Baseline::load(tmp.path().join("baseline.txt")).unwrap();
+        let (new, known) = baseline.subtract(vec![failure("User saves", "./spec/models/user_spec.rb:4")]);
+        assert_eq!((new.len(), known), (1, 0));
+    }
+}
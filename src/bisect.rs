@@ -0,0 +1,106 @@
+/// Lines `rspec --bisect` prints before its minimal reproduction command.
+const REPRODUCTION_HEADING: &str = "The minimal reproduction command is:";
+
+/// How bisect reports failures that fail on their own, in any order.
+const NOT_ORDER_DEPENDENT: &str = "do not require any non-failures to run first";
+
+/// What `rspec --bisect` concluded, from its output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BisectOutcome {
+    /// The smallest command that still fails, and whether that needs other
+    /// examples to run first.
+    Reproduced { command: String, order_dependent: bool },
+    /// The run bisect started from passed, so there was nothing to bisect.
+    NoFailures,
+    /// Bisect stopped without a result, e.g. when it timed out or a round
+    /// didn't fail consistently.
+    Incomplete,
+}
+
+impl BisectOutcome {
+    pub fn parse(output: &str) -> Self {
+        let mut lines = output.lines().map(str::trim);
+        if lines.any(|line| line == REPRODUCTION_HEADING)
+            && let Some(command) = lines.find(|line| !line.is_empty())
+        {
+            return Self::Reproduced {
+                command: command.to_string(),
+                order_dependent: !output.contains(NOT_ORDER_DEPENDENT),
+            };
+        }
+
+        match output.contains("No failures found") {
+            true => Self::NoFailures,
+            false => Self::Incomplete,
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::Reproduced { order_dependent: true, .. } => {
+                "found a minimal reproduction; the failure depends on the examples that run before it"
+            }
+            Self::Reproduced { order_dependent: false, .. } => {
+                "the failure isn't order-dependent; it fails when run on its own"
+            }
+            Self::NoFailures => "nothing failed with this seed, so there was nothing to bisect",
+            Self::Incomplete => "bisect stopped without finding a reproduction; see the output",
+        }
+    }
+}
+
+/// Lines of bisect's output worth a progress notification: its stages and
+/// each round, not the output of the runs it makes.
+pub fn is_progress_line(line: &str) -> bool {
+    let line = line.trim_start();
+    ["Bisect ", "Running suite", "Starting bisect", "Checking that", "Round "]
+        .iter()
+        .any(|stage| line.starts_with(stage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDER_DEPENDENT: &str = r#"Bisect started using options: "--seed 1234 spec"
+Running suite to find failures... (0.52 seconds)
+Starting bisect with 1 failing example and 98 non-failing examples.
+Checking that failure(s) are order-dependent... failure appears to be order-dependent
+
+Round 1: bisecting over non-failing examples 1-98 .. ignoring examples 50-98 (0.31 seconds)
+Round 2: bisecting over non-failing examples 1-49 .. ignoring examples 1-25 (0.22 seconds)
+Bisect complete! Reduced necessary non-failing examples from 98 to 1 in 1.4 seconds.
+
+The minimal reproduction command is:
+  rspec ./spec/models/order_spec.rb[1:3] ./spec/models/user_spec.rb[1:2] --seed 1234
+"#;
+
+    #[test]
+    fn test_parse_reproduction() {
+        assert_eq!(
+            BisectOutcome::parse(ORDER_DEPENDENT),
+            BisectOutcome::Reproduced {
+                command: "rspec ./spec/models/order_spec.rb[1:3] ./spec/models/user_spec.rb[1:2] --seed 1234".to_string(),
+                order_dependent: true,
+            }
+        );
+
+        let alone = "Checking that failure(s) are order-dependent... failure(s) do not require any non-failures to run first\n\nThe minimal reproduction command is:\n  rspec ./spec/models/user_spec.rb[1:2] --seed 1234\n";
+        assert!(matches!(BisectOutcome::parse(alone), BisectOutcome::Reproduced { order_dependent: false, .. }));
+    }
+
+    #[test]
+    fn test_parse_without_reproduction() {
+        let passed = "Running suite to find failures... (0.5 seconds)\nNo failures found. Bisect only works in the presence of one or more failing examples.\n";
+        assert_eq!(BisectOutcome::parse(passed), BisectOutcome::NoFailures);
+        assert_eq!(BisectOutcome::parse("Round 1: bisecting over non-failing examples 1-98 ."), BisectOutcome::Incomplete);
+        assert_eq!(BisectOutcome::parse("The minimal reproduction command is:\n"), BisectOutcome::Incomplete);
+    }
+
+    #[test]
+    fn test_progress_lines() {
+        let progress: Vec<&str> = ORDER_DEPENDENT.lines().filter(|line| is_progress_line(line)).collect();
+        assert_eq!(progress.len(), 7);
+        assert!(!is_progress_line("  rspec ./spec/models/user_spec.rb[1:2] --seed 1234"));
+    }
+}
@@ -1,13 +1,181 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
 use async_trait::async_trait;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use crate::parallel::ParallelRun;
+use crate::rspec_version::RspecVersion;
 
 #[derive(Debug, Clone)]
 pub struct CommandResult {
     pub exit_code: i32,
     pub stdout: String,
     pub stderr: String,
+    /// Set when the process was killed before finishing, e.g. for producing
+    /// more output than `--abort-on-output-bytes` allows.
+    pub abort_reason: Option<String>,
+    /// Signal that terminated the process, on Unix. `exit_code` is -1 then.
+    pub signal: Option<i32>,
+    /// Whether the process was killed for running past `RunContext::timeout`.
+    pub timed_out: bool,
+    /// Whether the process was killed because `RunContext::cancel` fired.
+    pub cancelled: bool,
+    /// Peak resident memory of the process, where the platform reports it.
+    pub peak_memory_mb: Option<f64>,
+    /// How the runner started RSpec when it had a choice, e.g. through
+    /// Spring.
+    pub launcher: Option<String>,
+}
+
+/// Per-run settings on top of the runner's configuration.
+#[derive(Debug, Clone, Default)]
+pub struct RunContext {
+    /// Additional RSpec options placed alongside the formatter flags.
+    pub extra_args: Vec<String>,
+    /// Directory to run in; the server's working directory when unset.
+    pub workdir: Option<PathBuf>,
+    /// RSpec command (program and arguments) replacing the runner's, e.g.
+    /// a project's own.
+    pub command: Option<Vec<String>>,
+    /// Environment variables for this run only, applied over the runner's.
+    pub env: Vec<(String, String)>,
+    /// Kill the process once it has run this long.
+    pub timeout: Option<Duration>,
+    /// Kill the process when this is cancelled, e.g. by the client
+    /// cancelling the request.
+    pub cancel: Option<CancellationToken>,
+    /// Messages about the run itself rather than its output, such as its
+    /// place in the `--max-in-flight` queue.
+    pub notices: Option<UnboundedSender<String>>,
+    /// Split the run across processes with `command`, a parallel_tests or
+    /// turbo_tests one, instead of running RSpec directly.
+    pub parallel: Option<ParallelRun>,
+}
+
+/// Tokens and channels can't be compared, so contexts only compare whether
+/// one is set.
+impl PartialEq for RunContext {
+    fn eq(&self, other: &Self) -> bool {
+        self.extra_args == other.extra_args
+            && self.workdir == other.workdir
+            && self.command == other.command
+            && self.env == other.env
+            && self.timeout == other.timeout
+            && self.cancel.is_some() == other.cancel.is_some()
+            && self.notices.is_some() == other.notices.is_some()
+            && self.parallel == other.parallel
+    }
 }
 
 #[async_trait]
 pub trait CommandRunner: Send + Sync {
-    async fn run(&self, path: &str) -> Result<CommandResult, String>;
+    /// Runs RSpec once for all of `paths`, passed as separate arguments.
+    async fn run_paths(&self, paths: &[String], context: &RunContext) -> Result<CommandResult, String>;
+
+    /// Runs RSpec for `path`.
+    async fn run(&self, path: &str, context: &RunContext) -> Result<CommandResult, String> {
+        self.run_paths(&[path.to_string()], context).await
+    }
+
+    /// Like `run_paths`, but sends each stdout line to `lines` as it is
+    /// produced.
+    ///
+    /// Runners that can't stream fall back to replaying the buffered output
+    /// once the command has finished.
+    async fn run_streaming(
+        &self,
+        paths: &[String],
+        context: &RunContext,
+        lines: UnboundedSender<String>,
+    ) -> Result<CommandResult, String> {
+        let result = self.run_paths(paths, context).await?;
+        for line in result.stdout.lines() {
+            let _ = lines.send(line.to_string());
+        }
+        Ok(result)
+    }
+
+    /// The argv `run` would execute for `paths`, without running anything.
+    fn command_line(&self, paths: &[String], context: &RunContext) -> Vec<String>;
+
+    /// The RSpec version behind this runner, if it can be determined.
+    async fn detect_version(&self) -> Option<RspecVersion> {
+        None
+    }
+}
+
+/// Lets the server hold a runner picked at startup, e.g. local or in a
+/// container, as `Arc<dyn CommandRunner>`.
+#[async_trait]
+impl<T: CommandRunner + ?Sized> CommandRunner for Arc<T> {
+    async fn run_paths(&self, paths: &[String], context: &RunContext) -> Result<CommandResult, String> {
+        (**self).run_paths(paths, context).await
+    }
+
+    async fn run(&self, path: &str, context: &RunContext) -> Result<CommandResult, String> {
+        (**self).run(path, context).await
+    }
+
+    async fn run_streaming(
+        &self,
+        paths: &[String],
+        context: &RunContext,
+        lines: UnboundedSender<String>,
+    ) -> Result<CommandResult, String> {
+        (**self).run_streaming(paths, context, lines).await
+    }
+
+    fn command_line(&self, paths: &[String], context: &RunContext) -> Vec<String> {
+        (**self).command_line(paths, context)
+    }
+
+    async fn detect_version(&self) -> Option<RspecVersion> {
+        (**self).detect_version().await
+    }
+}
+
+/// Joins argv into a string that can be pasted into a POSIX shell. Example
+/// ids such as `spec/a_spec.rb[1:2]` are quoted, since shells read the
+/// brackets as a glob (zsh fails with "no matches found").
+pub fn shell_join(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| {
+            let safe = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+            if safe {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_join_plain_args() {
+        let argv = vec!["bundle".to_string(), "exec".to_string(), "spec/a_spec.rb:12".to_string()];
+        assert_eq!(shell_join(&argv), "bundle exec spec/a_spec.rb:12");
+    }
+
+    #[test]
+    fn test_shell_join_quotes_special_args() {
+        let argv = vec!["rspec".to_string(), "-e".to_string(), "it's saved".to_string(), String::new()];
+        assert_eq!(shell_join(&argv), "rspec -e 'it'\\''s saved' ''");
+    }
+
+    #[test]
+    fn test_shell_join_quotes_example_ids() {
+        let argv = vec!["rspec".to_string(), "spec/a_spec.rb[1:2]".to_string()];
+        assert_eq!(shell_join(&argv), "rspec 'spec/a_spec.rb[1:2]'");
+    }
 }
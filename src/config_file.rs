@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::auth::AuthScope;
+
+/// Read when `--config` isn't given, if it exists in the working directory.
+pub const DEFAULT_CONFIG_FILE: &str = "mcp-rspec.toml";
+
+/// How long reloading waits for writes to the file to settle.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Settings from `mcp-rspec.toml`, each named like its flag with
+/// underscores (`rspec_cmd = "bin/rspec"`). A flag or environment variable
+/// that is set wins over the file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    // Read at startup only
+    pub rspec_cmd: Option<String>,
+    pub default_workdir: Option<PathBuf>,
+    pub allowed_workdirs: Option<Vec<PathBuf>>,
+    pub projects_file: Option<PathBuf>,
+    pub abort_on_output_bytes: Option<usize>,
+
+    // Reloaded
+    pub timeout: Option<u64>,
+    pub suite_timeout_secs: Option<u64>,
+    pub allowed_env: Option<Vec<String>>,
+    pub auth_token: Option<String>,
+    pub auth_scope: Option<AuthScope>,
+    pub max_output_bytes: Option<usize>,
+    pub response_budget_bytes: Option<usize>,
+    pub max_reported_failures: Option<usize>,
+}
+
+impl ConfigFile {
+    /// Every key the file takes.
+    pub const KEYS: &[&str] = &[
+        "rspec_cmd",
+        "default_workdir",
+        "allowed_workdirs",
+        "projects_file",
+        "abort_on_output_bytes",
+        "timeout",
+        "suite_timeout_secs",
+        "allowed_env",
+        "auth_token",
+        "auth_scope",
+        "max_output_bytes",
+        "response_budget_bytes",
+        "max_reported_failures",
+    ];
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read config file {}: {}", path.display(), e))?;
+        toml::from_str(&contents).map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+    }
+
+    /// Whether `other` changes settings that only apply after a restart.
+    pub fn needs_restart(&self, other: &Self) -> bool {
+        (&self.rspec_cmd, &self.default_workdir, &self.allowed_workdirs, &self.projects_file, self.abort_on_output_bytes)
+            != (&other.rspec_cmd, &other.default_workdir, &other.allowed_workdirs, &other.projects_file, other.abort_on_output_bytes)
+    }
+}
+
+/// Calls `reload` whenever the file at `path` is written and, on Unix, on
+/// SIGHUP. Reloading stops when the returned watcher drops.
+pub fn watch_config_file(
+    path: &Path,
+    reload: impl Fn() + Send + 'static,
+) -> notify::Result<notify::RecommendedWatcher> {
+    use notify::Watcher;
+
+    let (changes, mut received) = mpsc::unbounded_channel();
+    let file_name = path.file_name().map(|name| name.to_os_string());
+    let watched = changes.clone();
+    // Editors often replace the file rather than write it, so its
+    // directory is watched instead
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event
+            && !event.kind.is_access()
+            && event.paths.iter().any(|changed| changed.file_name() == file_name.as_deref())
+        {
+            let _ = watched.send(());
+        }
+    })?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    watcher.watch(dir, notify::RecursiveMode::NonRecursive)?;
+
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        let Ok(mut hangup) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) else {
+            tracing::warn!("Could not listen for SIGHUP; the config file reloads only when it changes");
+            return;
+        };
+        while hangup.recv().await.is_some() {
+            if changes.send(()).is_err() {
+                break;
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        while received.recv().await.is_some() {
+            while let Ok(Some(())) = tokio::time::timeout(RELOAD_DEBOUNCE, received.recv()).await {}
+            reload();
+        }
+    });
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            rspec_cmd = "bin/rspec"
+            timeout = 120
+            allowed_env = ["RAILS_ENV", "DATABASE_URL"]
+            auth_scope = "runs"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.rspec_cmd.as_deref(), Some("bin/rspec"));
+        assert_eq!(config.timeout, Some(120));
+        assert_eq!(config.allowed_env.unwrap(), ["RAILS_ENV", "DATABASE_URL"]);
+        assert_eq!(config.auth_scope, Some(AuthScope::Runs));
+        assert_eq!(config.max_output_bytes, None);
+    }
+
+    #[test]
+    fn test_keys() {
+        assert!(toml::from_str::<ConfigFile>("rspec_command = \"rspec\"").is_err());
+        for key in ConfigFile::KEYS {
+            let value = match *key {
+                "allowed_workdirs" | "allowed_env" => "[]",
+                "rspec_cmd" | "default_workdir" | "projects_file" | "auth_token" => "\"x\"",
+                "auth_scope" => "\"all\"",
+                _ => "1",
+            };
+            assert!(toml::from_str::<ConfigFile>(&format!("{} = {}", key, value)).is_ok(), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_needs_restart() {
+        let config = ConfigFile { rspec_cmd: Some("rspec".to_string()), ..Default::default() };
+        assert!(!config.needs_restart(&ConfigFile { timeout: Some(5), ..config.clone() }));
+        assert!(config.needs_restart(&ConfigFile { rspec_cmd: Some("bin/rspec".to_string()), ..config.clone() }));
+    }
+
+    #[tokio::test]
+    async fn test_reloads_on_change() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let path = dir.join(DEFAULT_CONFIG_FILE);
+        std::fs::write(&path, "timeout = 1\n").unwrap();
+
+        let (reloaded, mut reloads) = mpsc::unbounded_channel();
+        let _watcher = watch_config_file(&path, move || {
+            let _ = reloaded.send(());
+        })
+        .unwrap();
+        std::fs::write(&path, "timeout = 2\n").unwrap();
+
+        let reload = tokio::time::timeout(Duration::from_secs(5), reloads.recv()).await;
+        assert_eq!(reload.unwrap(), Some(()));
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use futures::StreamExt;
+
+/// Caps the number of simultaneous SSE clients. A slot is held for as long
+/// as the event stream's response body is alive, so it is released when the
+/// client disconnects.
+#[derive(Debug, Clone)]
+pub struct ConnectionLimit {
+    sse_path: String,
+    max: usize,
+    active: Arc<AtomicUsize>,
+}
+
+/// Releases a connection slot when dropped.
+#[derive(Debug)]
+pub struct ConnectionGuard {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl ConnectionLimit {
+    pub fn new(sse_path: impl Into<String>, max: usize) -> Self {
+        Self {
+            sse_path: sse_path.into(),
+            max,
+            active: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn try_acquire(&self) -> Option<ConnectionGuard> {
+        self.active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |active| {
+                (active < self.max).then_some(active + 1)
+            })
+            .ok()?;
+
+        Some(ConnectionGuard {
+            active: self.active.clone(),
+        })
+    }
+
+    #[cfg(test)]
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+}
+
+/// Middleware for [`axum::middleware::from_fn_with_state`]. Only requests to
+/// the SSE path count; message posts are short-lived and pass through.
+pub async fn limit_connections(
+    State(limit): State<ConnectionLimit>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.uri().path() != limit.sse_path {
+        return next.run(request).await;
+    }
+
+    let Some(guard) = limit.try_acquire() else {
+        tracing::warn!("Refusing SSE connection: {} clients already connected", limit.max);
+        return (StatusCode::SERVICE_UNAVAILABLE, "Too many connections").into_response();
+    };
+
+    let (parts, body) = next.run(request).await.into_parts();
+    let body = body.into_data_stream().map(move |chunk| {
+        let _ = &guard;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    #[test]
+    fn test_try_acquire_releases_on_drop() {
+        let limit = ConnectionLimit::new("/sse", 1);
+        let guard = limit.try_acquire().unwrap();
+        assert!(limit.try_acquire().is_none());
+        drop(guard);
+        assert_eq!(limit.active(), 0);
+        assert!(limit.try_acquire().is_some());
+    }
+
+    async fn connect(addr: std::net::SocketAddr, path: &str) -> (TcpStream, String) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut buf = [0u8; 64];
+        let n = stream.read(&mut buf).await.unwrap();
+        let status = String::from_utf8_lossy(&buf[..n]).lines().next().unwrap_or_default().to_string();
+        (stream, status)
+    }
+
+    #[tokio::test]
+    async fn test_refuses_connection_over_limit() {
+        let limit = ConnectionLimit::new("/sse", 2);
+        let router = axum::Router::new()
+            .route(
+                "/sse",
+                axum::routing::get(|| async {
+                    Body::from_stream(
+                        futures::stream::once(async { Ok::<_, std::io::Error>("data: hi\n\n") })
+                            .chain(futures::stream::pending()),
+                    )
+                }),
+            )
+            .route("/message", axum::routing::get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(limit.clone(), limit_connections));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let (_first, status) = connect(addr, "/sse").await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let (_second, status) = connect(addr, "/sse").await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(limit.active(), 2);
+
+        let (_third, status) = connect(addr, "/sse").await;
+        assert_eq!(status, "HTTP/1.1 503 Service Unavailable");
+
+        let (_message, status) = connect(addr, "/message").await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+    }
+}
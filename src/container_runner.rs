@@ -0,0 +1,437 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::command_runner::{CommandResult, CommandRunner, RunContext};
+use crate::rspec_version::RspecVersion;
+
+const VERSION_DETECTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Where RSpec runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum ExecMode {
+    /// As a child process of the server
+    #[default]
+    Local,
+    /// With `docker compose exec` in --service
+    DockerCompose,
+    /// With `docker exec` in --container
+    Docker,
+    /// On --ssh-host, with `ssh`
+    Ssh,
+}
+
+/// The container a run is executed in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContainerExec {
+    Compose { service: String },
+    Docker { container: String },
+}
+
+impl ContainerExec {
+    /// `None` for modes that don't run in a container.
+    pub fn from_mode(mode: ExecMode, service: Option<String>, container: Option<String>) -> Result<Option<Self>, String> {
+        match mode {
+            ExecMode::Local | ExecMode::Ssh => Ok(None),
+            ExecMode::DockerCompose => service
+                .map(|service| Some(Self::Compose { service }))
+                .ok_or_else(|| "--exec-mode docker-compose needs --service, e.g. --service web".to_string()),
+            ExecMode::Docker => container
+                .map(|container| Some(Self::Docker { container }))
+                .ok_or_else(|| "--exec-mode docker needs --container, the container's name or id".to_string()),
+        }
+    }
+
+    /// The exec command up to the service or container, which the RSpec
+    /// argv follows.
+    fn prefix(&self, env: &[(String, String)], workdir: Option<&str>) -> Vec<String> {
+        let (program, target): (&[&str], _) = match self {
+            Self::Compose { service } => (&["docker", "compose", "exec", "-T"], service),
+            Self::Docker { container } => (&["docker", "exec"], container),
+        };
+        let mut argv: Vec<String> = program.iter().map(|arg| arg.to_string()).collect();
+        for (key, value) in env {
+            argv.extend(["-e".to_string(), format!("{}={}", key, value)]);
+        }
+        if let Some(workdir) = workdir {
+            argv.extend(["-w".to_string(), workdir.to_string()]);
+        }
+        argv.push(target.clone());
+        argv
+    }
+}
+
+/// A host directory and where it is mounted in the container, written
+/// `HOST:CONTAINER`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathMapping {
+    host: String,
+    container: String,
+}
+
+impl FromStr for PathMapping {
+    type Err = String;
+
+    fn from_str(mapping: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid mount '{}'; use HOST:CONTAINER with absolute paths, e.g. /home/me/app:/app", mapping);
+        let (host, container) = mapping.split_once(':').ok_or_else(invalid)?;
+        if !host.starts_with('/') || !container.starts_with('/') {
+            return Err(invalid());
+        }
+        let trim = |path: &str| match path.trim_end_matches('/') {
+            "" => "/".to_string(),
+            trimmed => trimmed.to_string(),
+        };
+
+        Ok(Self {
+            host: trim(host),
+            container: trim(container),
+        })
+    }
+}
+
+impl PathMapping {
+    /// Whether the container sees host `path` through this mount.
+    pub fn covers(&self, path: &std::path::Path) -> bool {
+        replace_root(&path.to_string_lossy(), &self.host, &self.container).is_some()
+    }
+}
+
+/// `path` with `from` replaced by `to` when it is `from` or inside it.
+pub fn replace_root(path: &str, from: &str, to: &str) -> Option<String> {
+    let rest = path.strip_prefix(from)?;
+    if from == "/" {
+        return Some(format!("{}/{}", to.trim_end_matches('/'), rest));
+    }
+    match rest {
+        "" => Some(to.to_string()),
+        rest if rest.starts_with('/') => Some(format!("{}{}", to.trim_end_matches('/'), rest)),
+        _ => None,
+    }
+}
+
+/// `text` with every `from/` that starts a path replaced by `to/`, leaving
+/// alone ones inside a longer path such as `/home/me/app/` for `/app/`.
+pub fn replace_paths(text: &str, from: &str, to: &str) -> String {
+    let (from, to) = (format!("{}/", from), format!("{}/", to.trim_end_matches('/')));
+    let mut replaced = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find(&from) {
+        let starts_path = rest[..index]
+            .chars()
+            .next_back()
+            .is_none_or(|before| !(before.is_alphanumeric() || "/._-~".contains(before)));
+        replaced.push_str(&rest[..index]);
+        replaced.push_str(if starts_path { &to } else { &from });
+        rest = &rest[index + from.len()..];
+    }
+    replaced.push_str(rest);
+    replaced
+}
+
+/// Runs RSpec inside a container through `docker compose exec` or `docker
+/// exec`, by handing `inner` the exec command in place of its own. Host
+/// paths in the arguments become container paths per the mounts, and
+/// container paths in the output become host paths again.
+///
+/// The per-run and `with_env` environment is passed with `-e`, since the
+/// exec command's own environment doesn't reach the container. Killing a
+/// run stops the exec client; whether that stops RSpec in the container is
+/// up to Docker.
+#[derive(Clone)]
+pub struct ContainerRunner<R> {
+    inner: R,
+    exec: ContainerExec,
+    command: Vec<String>,
+    mounts: Vec<PathMapping>,
+    env: Vec<(String, String)>,
+    workdir: Option<PathBuf>,
+}
+
+impl<R: CommandRunner> ContainerRunner<R> {
+    /// `command` is the RSpec command as run inside the container.
+    pub fn new(inner: R, exec: ContainerExec, command: &str) -> Self {
+        Self {
+            inner,
+            exec,
+            command: command.split_whitespace().map(str::to_string).collect(),
+            mounts: Vec::new(),
+            env: Vec::new(),
+            workdir: None,
+        }
+    }
+
+    pub fn with_mounts(mut self, mounts: Vec<PathMapping>) -> Self {
+        self.mounts = mounts;
+        self
+    }
+
+    /// Environment variables set inside the container for every run.
+    pub fn with_env(mut self, env: Vec<(String, String)>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Host directory for runs whose context doesn't name one.
+    pub fn with_workdir(mut self, workdir: Option<PathBuf>) -> Self {
+        self.workdir = workdir;
+        self
+    }
+
+    fn to_container(&self, path: &str) -> Option<String> {
+        self.mounts
+            .iter()
+            .find_map(|mount| replace_root(path, &mount.host, &mount.container))
+    }
+
+    /// An argument with any host path in it, alone or as an option's
+    /// `=value`, moved into the container.
+    fn translate_arg(&self, arg: &str) -> String {
+        if let Some(translated) = self.to_container(arg) {
+            return translated;
+        }
+        match arg.split_once('=') {
+            Some((option, value)) if option.starts_with('-') => match self.to_container(value) {
+                Some(value) => format!("{}={}", option, value),
+                None => arg.to_string(),
+            },
+            _ => arg.to_string(),
+        }
+    }
+
+    /// Output with container paths in it pointing at the host again.
+    fn translate_output(&self, output: &str) -> String {
+        self.mounts.iter().fold(output.to_string(), |output, mount| {
+            if mount.container == "/" {
+                return output;
+            }
+            replace_paths(&output, &mount.container, &mount.host)
+        })
+    }
+
+    /// The context `inner` runs: the exec command wrapping the RSpec one,
+    /// with host paths translated and the environment moved into `-e`.
+    fn container_context(&self, context: &RunContext) -> RunContext {
+        let host_workdir = context
+            .workdir
+            .clone()
+            .or_else(|| self.workdir.clone())
+            .or_else(|| std::env::current_dir().ok());
+        let workdir = host_workdir.and_then(|dir| self.to_container(&dir.to_string_lossy()));
+        let env: Vec<(String, String)> = self.env.iter().chain(&context.env).cloned().collect();
+
+        let mut command = self.exec.prefix(&env, workdir.as_deref());
+        command.extend(context.command.clone().unwrap_or_else(|| self.command.clone()));
+
+        RunContext {
+            extra_args: context.extra_args.iter().map(|arg| self.translate_arg(arg)).collect(),
+            command: Some(command),
+            env: Vec::new(),
+            ..context.clone()
+        }
+    }
+
+    fn container_paths(&self, paths: &[String]) -> Vec<String> {
+        paths.iter().map(|path| self.translate_arg(path)).collect()
+    }
+
+    fn translate_result(&self, mut result: CommandResult) -> CommandResult {
+        result.stdout = self.translate_output(&result.stdout);
+        result.stderr = self.translate_output(&result.stderr);
+        result
+    }
+}
+
+#[async_trait]
+impl<R: CommandRunner + Clone + 'static> CommandRunner for ContainerRunner<R> {
+    async fn run_paths(&self, paths: &[String], context: &RunContext) -> Result<CommandResult, String> {
+        let result = self
+            .inner
+            .run_paths(&self.container_paths(paths), &self.container_context(context))
+            .await?;
+        Ok(self.translate_result(result))
+    }
+
+    async fn run_streaming(
+        &self,
+        paths: &[String],
+        context: &RunContext,
+        lines: UnboundedSender<String>,
+    ) -> Result<CommandResult, String> {
+        let (container_lines, mut received) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let translator = self.clone();
+        let forward = tokio::spawn(async move {
+            while let Some(line) = received.recv().await {
+                let _ = lines.send(translator.translate_output(&line));
+            }
+        });
+
+        let result = self
+            .inner
+            .run_streaming(&self.container_paths(paths), &self.container_context(context), container_lines)
+            .await;
+        let _ = forward.await;
+        result.map(|result| self.translate_result(result))
+    }
+
+    fn command_line(&self, paths: &[String], context: &RunContext) -> Vec<String> {
+        self.inner
+            .command_line(&self.container_paths(paths), &self.container_context(context))
+    }
+
+    /// Runs `--version` in the container; options before it don't stop
+    /// RSpec from only printing its version.
+    async fn detect_version(&self) -> Option<RspecVersion> {
+        let context = RunContext {
+            extra_args: vec!["--version".to_string()],
+            timeout: Some(VERSION_DETECTION_TIMEOUT),
+            ..Default::default()
+        };
+        match self.run_paths(&[], &context).await {
+            Ok(result) if result.timed_out => {
+                tracing::warn!("rspec version detection in the container timed out");
+                None
+            }
+            Ok(result) => RspecVersion::parse(&result.stdout),
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to run rspec version detection in the container");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_runner::MockRunner;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn compose(runner: MockRunner) -> ContainerRunner<MockRunner> {
+        let exec = ContainerExec::Compose { service: "web".to_string() };
+        ContainerRunner::new(runner, exec, "bundle exec rspec")
+            .with_mounts(vec!["/home/me/app:/app".parse().unwrap()])
+            .with_workdir(Some(PathBuf::from("/home/me/app")))
+    }
+
+    #[test]
+    fn test_from_mode() {
+        assert_eq!(ContainerExec::from_mode(ExecMode::Local, Some("web".to_string()), None), Ok(None));
+        assert_eq!(
+            ContainerExec::from_mode(ExecMode::DockerCompose, Some("web".to_string()), None),
+            Ok(Some(ContainerExec::Compose { service: "web".to_string() }))
+        );
+        assert_eq!(
+            ContainerExec::from_mode(ExecMode::DockerCompose, None, Some("app-1".to_string())).unwrap_err(),
+            "--exec-mode docker-compose needs --service, e.g. --service web"
+        );
+        assert!(ContainerExec::from_mode(ExecMode::Docker, None, None).is_err());
+    }
+
+    #[test]
+    fn test_parse_mount() {
+        assert_eq!(
+            "/home/me/app/:/app".parse::<PathMapping>(),
+            Ok(PathMapping { host: "/home/me/app".to_string(), container: "/app".to_string() })
+        );
+        assert_eq!(
+            "app:/app".parse::<PathMapping>().unwrap_err(),
+            "Invalid mount 'app:/app'; use HOST:CONTAINER with absolute paths, e.g. /home/me/app:/app"
+        );
+        assert!("/home/me/app".parse::<PathMapping>().is_err());
+
+        let mount: PathMapping = "/tmp:/tmp".parse().unwrap();
+        assert!(mount.covers(std::path::Path::new("/tmp/mcp-rspec-x1/example_status.rb")));
+        assert!(!mount.covers(std::path::Path::new("/tmpfs/x")));
+    }
+
+    #[test]
+    fn test_replace_root() {
+        assert_eq!(replace_root("/home/me/app/spec/a_spec.rb:3", "/home/me/app", "/app").as_deref(), Some("/app/spec/a_spec.rb:3"));
+        assert_eq!(replace_root("/home/me/app", "/home/me/app", "/app").as_deref(), Some("/app"));
+        assert_eq!(replace_root("/home/me/application", "/home/me/app", "/app"), None);
+        assert_eq!(replace_root("/tmp/report.json", "/", "/host").as_deref(), Some("/host/tmp/report.json"));
+    }
+
+    #[test]
+    fn test_replace_paths() {
+        assert_eq!(
+            replace_paths("/app/a.rb and '/app/b.rb' but not /srv/app/c.rb", "/app", "/home/me/app"),
+            "/home/me/app/a.rb and '/home/me/app/b.rb' but not /srv/app/c.rb"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_runs_in_compose_service() {
+        let mock = MockRunner::new();
+        let runner = compose(mock.clone()).with_env(vec![("RAILS_ENV".to_string(), "test".to_string())]);
+        let context = RunContext {
+            env: vec![("SEED".to_string(), "42".to_string())],
+            extra_args: strings(&["-o", "/home/me/app/tmp/report.json", "--require=/home/me/app/spec/x.rb"]),
+            ..Default::default()
+        };
+
+        runner
+            .run_paths(&strings(&["spec/a_spec.rb:3", "/home/me/app/spec/b_spec.rb"]), &context)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            mock.recorded_commands()[0],
+            strings(&[
+                "docker", "compose", "exec", "-T", "-e", "RAILS_ENV=test", "-e", "SEED=42", "-w", "/app", "web",
+                "bundle", "exec", "rspec", "-o", "/app/tmp/report.json", "--require=/app/spec/x.rb",
+                "spec/a_spec.rb:3", "/app/spec/b_spec.rb",
+            ])
+        );
+        assert!(mock.recorded_contexts()[0].env.is_empty());
+    }
+
+    #[test]
+    fn test_docker_exec_outside_mounts() {
+        let exec = ContainerExec::Docker { container: "app-1".to_string() };
+        let runner = ContainerRunner::new(MockRunner::new(), exec, "rspec");
+        let context = RunContext {
+            command: Some(strings(&["bin/rspec"])),
+            workdir: Some(PathBuf::from("/srv/other")),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            runner.command_line(&strings(&["spec"]), &context),
+            strings(&["docker", "exec", "app-1", "bin/rspec", "spec"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_output_paths_point_at_the_host() {
+        let stdout = "# /app/spec/a_spec.rb:3:in `block'\n/application/x\n".to_string();
+        let runner = compose(MockRunner::with_result(1, stdout, String::new()));
+
+        let result = runner.run("spec/a_spec.rb", &RunContext::default()).await.unwrap();
+        assert_eq!(result.stdout, "# /home/me/app/spec/a_spec.rb:3:in `block'\n/application/x\n");
+
+        let (lines, mut received) = tokio::sync::mpsc::unbounded_channel();
+        runner.run_streaming(&strings(&["spec"]), &RunContext::default(), lines).await.unwrap();
+        assert_eq!(received.recv().await.unwrap(), "# /home/me/app/spec/a_spec.rb:3:in `block'");
+    }
+
+    #[tokio::test]
+    async fn test_detect_version_runs_in_container() {
+        let stdout = "RSpec 3.13\n  - rspec-core 3.13.0\n".to_string();
+        let mock = MockRunner::with_result(0, stdout, String::new());
+        let runner = compose(mock.clone());
+
+        assert!(runner.detect_version().await.is_some());
+        assert_eq!(
+            mock.recorded_commands()[0],
+            strings(&["docker", "compose", "exec", "-T", "-w", "/app", "web", "bundle", "exec", "rspec", "--version"])
+        );
+    }
+}
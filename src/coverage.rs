@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Most files listed in a coverage report; the rest are counted.
+const MAX_REPORTED_FILES: usize = 20;
+
+const LAST_RUN_FILE: &str = ".last_run.json";
+const COVERAGE_FILE: &str = "coverage.json";
+
+/// How `run_rspec_with_coverage` turns SimpleCov on and where it finds the
+/// results, relative to the run's working directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageConfig {
+    pub env: Vec<(String, String)>,
+    pub dir: PathBuf,
+}
+
+impl Default for CoverageConfig {
+    fn default() -> Self {
+        Self {
+            env: vec![("COVERAGE".to_string(), "1".to_string())],
+            dir: PathBuf::from("coverage"),
+        }
+    }
+}
+
+/// Lines of one file SimpleCov counts, and how many of them ran.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileCoverage {
+    pub covered: usize,
+    pub relevant: usize,
+}
+
+impl FileCoverage {
+    fn percent(&self) -> f64 {
+        match self.relevant {
+            0 => 100.0,
+            relevant => self.covered as f64 * 100.0 / relevant as f64,
+        }
+    }
+}
+
+/// What SimpleCov last wrote to the coverage directory: the total from
+/// `.last_run.json` and the per-file lines from `coverage.json` (the JSON
+/// formatter's output), when they exist.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CoverageSnapshot {
+    total: Option<f64>,
+    files: BTreeMap<String, FileCoverage>,
+    written_at: Vec<Option<SystemTime>>,
+}
+
+impl CoverageSnapshot {
+    pub fn read(dir: &Path) -> Self {
+        let read_json = |name: &str| -> Option<serde_json::Value> {
+            serde_json::from_str(&std::fs::read_to_string(dir.join(name)).ok()?).ok()
+        };
+        let total = read_json(LAST_RUN_FILE).and_then(|last_run| {
+            let result = last_run.get("result")?;
+            result.get("line").or_else(|| result.get("covered_percent"))?.as_f64()
+        });
+        let files = read_json(COVERAGE_FILE)
+            .as_ref()
+            .and_then(|report| report.get("coverage")?.as_object())
+            .map(|coverage| {
+                coverage
+                    .iter()
+                    .filter_map(|(path, file)| Some((path.clone(), line_coverage(file.get("lines")?.as_array()?))))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let written_at = [LAST_RUN_FILE, COVERAGE_FILE]
+            .iter()
+            .map(|name| std::fs::metadata(dir.join(name)).and_then(|meta| meta.modified()).ok())
+            .collect();
+
+        Self { total, files, written_at }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.total.is_none() && self.files.is_empty()
+    }
+
+    /// The total, or one worked out from the files when `.last_run.json`
+    /// is missing.
+    fn total_percent(&self) -> Option<f64> {
+        self.total.or_else(|| {
+            let (covered, relevant) = self
+                .files
+                .values()
+                .fold((0, 0), |(covered, relevant), file| (covered + file.covered, relevant + file.relevant));
+            (relevant > 0).then(|| covered as f64 * 100.0 / relevant as f64)
+        })
+    }
+}
+
+/// `lines` holds a hit count per line, or `null` (and `"ignored"`) for
+/// lines SimpleCov doesn't count.
+fn line_coverage(lines: &[serde_json::Value]) -> FileCoverage {
+    let counts: Vec<u64> = lines.iter().filter_map(serde_json::Value::as_u64).collect();
+    FileCoverage {
+        covered: counts.iter().filter(|hits| **hits > 0).count(),
+        relevant: counts.len(),
+    }
+}
+
+fn delta(before: Option<f64>, after: f64) -> String {
+    match before {
+        Some(before) => format!("{:+.2}", after - before),
+        None => "new".to_string(),
+    }
+}
+
+/// The `Coverage` section for a run: the total and the files whose
+/// coverage changed since `before`, or the least covered files when there
+/// is nothing to compare with. Paths are shown relative to `root`.
+pub fn coverage_report(before: &CoverageSnapshot, after: &CoverageSnapshot, root: &Path, dir: &Path) -> String {
+    if after.is_empty() || after.written_at == before.written_at {
+        return format!(
+            "  No new coverage results in {}; check that the project starts SimpleCov for this environment and writes {} (and {} with the JSON formatter)",
+            dir.display(),
+            LAST_RUN_FILE,
+            COVERAGE_FILE
+        );
+    }
+
+    let mut report = String::new();
+    let compared = !before.is_empty();
+    if let Some(total) = after.total_percent() {
+        let change = match before.total_percent() {
+            Some(before) => format!("{} since the last coverage run", delta(Some(before), total)),
+            None => "no earlier coverage run to compare with".to_string(),
+        };
+        report.push_str(&format!("  Total: {:.2}% of lines ({})\n", total, change));
+    }
+
+    let mut files: Vec<(&String, &FileCoverage)> = after
+        .files
+        .iter()
+        .filter(|(path, file)| !compared || before.files.get(*path).map(FileCoverage::percent) != Some(file.percent()))
+        .collect();
+    if !compared {
+        files.sort_by(|(_, a), (_, b)| a.percent().total_cmp(&b.percent()));
+    }
+    if compared && files.is_empty() && !after.files.is_empty() {
+        report.push_str("  No file's coverage changed\n");
+    }
+
+    let root = format!("{}/", root.display());
+    for (path, file) in files.iter().take(MAX_REPORTED_FILES) {
+        let shown = path.strip_prefix(&root).unwrap_or(path);
+        let change = match compared {
+            true => format!(", {}", delta(before.files.get(*path).map(FileCoverage::percent), file.percent())),
+            false => String::new(),
+        };
+        report.push_str(&format!(
+            "  {}: {:.2}% ({}/{} lines{})\n",
+            shown,
+            file.percent(),
+            file.covered,
+            file.relevant,
+            change
+        ));
+    }
+    if files.len() > MAX_REPORTED_FILES {
+        report.push_str(&format!("  ... and {} more files\n", files.len() - MAX_REPORTED_FILES));
+    }
+    report.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(total: Option<f64>, files: &[(&str, usize, usize)], written: u64) -> CoverageSnapshot {
+        CoverageSnapshot {
+            total,
+            files: files
+                .iter()
+                .map(|(path, covered, relevant)| (path.to_string(), FileCoverage { covered: *covered, relevant: *relevant }))
+                .collect(),
+            written_at: vec![Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(written))],
+        }
+    }
+
+    #[test]
+    fn test_read_simplecov_results() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(dir.join(".last_run.json"), r#"{"result": {"line": 62.5}}"#).unwrap();
+        std::fs::write(
+            dir.join("coverage.json"),
+            r#"{"meta": {}, "coverage": {"/app/models/user.rb": {"lines": [null, 1, 0, 3, "ignored"]}}}"#,
+        )
+        .unwrap();
+
+        let read = CoverageSnapshot::read(dir);
+        assert_eq!(read.total, Some(62.5));
+        assert_eq!(read.files["/app/models/user.rb"], FileCoverage { covered: 2, relevant: 3 });
+        assert!(CoverageSnapshot::read(&dir.join("missing")).is_empty());
+    }
+
+    #[test]
+    fn test_report_deltas() {
+        let before = snapshot(Some(50.0), &[("/app/a.rb", 1, 4), ("/app/b.rb", 2, 2)], 1);
+        let after = snapshot(Some(62.5), &[("/app/a.rb", 3, 4), ("/app/b.rb", 2, 2), ("/app/c.rb", 0, 2)], 2);
+
+        assert_eq!(
+            coverage_report(&before, &after, Path::new("/app"), Path::new("coverage")),
+            "  Total: 62.50% of lines (+12.50 since the last coverage run)\n  a.rb: 75.00% (3/4 lines, +50.00)\n  c.rb: 0.00% (0/2 lines, new)"
+        );
+    }
+
+    #[test]
+    fn test_report_without_earlier_run() {
+        let after = snapshot(None, &[("/app/a.rb", 3, 4), ("/app/c.rb", 1, 2)], 2);
+
+        assert_eq!(
+            coverage_report(&CoverageSnapshot::default(), &after, Path::new("/app"), Path::new("coverage")),
+            "  Total: 66.67% of lines (no earlier coverage run to compare with)\n  c.rb: 50.00% (1/2 lines)\n  a.rb: 75.00% (3/4 lines)"
+        );
+    }
+
+    #[test]
+    fn test_report_stale_results() {
+        let results = snapshot(Some(50.0), &[], 1);
+        assert!(
+            coverage_report(&results, &results, Path::new("/app"), Path::new("coverage"))
+                .starts_with("  No new coverage results in coverage;")
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+/// The environment variables a request's `env` may set, from `--allow-env`.
+/// Each entry is a name such as `COVERAGE`, or a prefix ending in `*` such
+/// as `FEATURE_*`. Requests can't set anything while it is empty.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnvAllowlist {
+    patterns: Vec<String>,
+}
+
+impl EnvAllowlist {
+    pub fn new(patterns: Vec<String>) -> Self {
+        Self { patterns }
+    }
+
+    fn allows(&self, key: &str) -> bool {
+        self.patterns.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => key.starts_with(prefix),
+            None => key == pattern,
+        })
+    }
+
+    /// The variables to set for a request, or why one of them isn't allowed.
+    pub fn check(&self, env: &BTreeMap<String, String>) -> Result<Vec<(String, String)>, String> {
+        if !env.is_empty() && self.patterns.is_empty() {
+            return Err("env isn't enabled on this server; start it with --allow-env naming the variables requests may set".to_string());
+        }
+        for (key, value) in env {
+            let valid_name = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid_name {
+                return Err(format!("Invalid environment variable name '{}'", key));
+            }
+            if value.contains('\0') {
+                return Err(format!("Environment variable {} contains a null byte", key));
+            }
+            if !self.allows(key) {
+                return Err(format!(
+                    "Environment variable {} is not allowed; --allow-env permits {}",
+                    key,
+                    self.patterns.join(", ")
+                ));
+            }
+        }
+
+        Ok(env.iter().map(|(key, value)| (key.clone(), value.clone())).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_allows_names_and_prefixes() {
+        let allowlist = EnvAllowlist::new(vec!["COVERAGE".to_string(), "FEATURE_*".to_string()]);
+
+        assert_eq!(
+            allowlist.check(&env(&[("FEATURE_CHECKOUT", "on"), ("COVERAGE", "1")])).unwrap(),
+            [("COVERAGE".to_string(), "1".to_string()), ("FEATURE_CHECKOUT".to_string(), "on".to_string())]
+        );
+        assert_eq!(
+            allowlist.check(&env(&[("DATABASE_URL", "postgres://")])).unwrap_err(),
+            "Environment variable DATABASE_URL is not allowed; --allow-env permits COVERAGE, FEATURE_*"
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_variables() {
+        let allowlist = EnvAllowlist::new(vec!["*".to_string()]);
+
+        assert_eq!(
+            allowlist.check(&env(&[("1ST", "x")])).unwrap_err(),
+            "Invalid environment variable name '1ST'"
+        );
+        assert!(allowlist.check(&env(&[("A=B", "x")])).is_err());
+        assert!(allowlist.check(&env(&[("COVERAGE", "1\0")])).is_err());
+        assert!(allowlist.check(&env(&[("ANYTHING", "x")])).is_ok());
+    }
+
+    #[test]
+    fn test_empty_allowlist() {
+        let allowlist = EnvAllowlist::default();
+
+        assert_eq!(allowlist.check(&BTreeMap::new()).unwrap(), []);
+        assert!(
+            allowlist
+                .check(&env(&[("COVERAGE", "1")]))
+                .unwrap_err()
+                .starts_with("env isn't enabled on this server")
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::script_file::ScriptFile;
+
+/// Keeps the status file next to the script, in the session's private
+/// directory.
+const STATUS_SCRIPT: &str =
+    "RSpec.configure { |config| config.example_status_persistence_file_path = File.join(__dir__, 'example_status.txt') }\n";
+const STATUS_FILE: &str = "example_status.txt";
+
+/// RSpec options for `rerun_failures`, on top of the status file.
+const RERUN_ARGS: &[&str] = &["--only-failures", "--order", "defined"];
+
+/// The targets of the last `run_rspec` call and the directory it ran in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LastRun {
+    pub files: Vec<String>,
+    pub workdir: Option<String>,
+    pub project: Option<String>,
+}
+
+/// A session's RSpec example status file
+/// (`example_status_persistence_file_path`), set through a script loaded
+/// with `--require`. Every run records its examples' statuses there, so
+/// `rerun_failures` can run `--only-failures`. The script is written once,
+/// on the first run, and both files are removed when the last clone is
+/// dropped.
+#[derive(Debug, Clone)]
+pub struct ExampleStatus {
+    files: Arc<StatusFiles>,
+}
+
+#[derive(Debug)]
+struct StatusFiles {
+    script: OnceLock<ScriptFile>,
+    last_run: Mutex<Option<LastRun>>,
+}
+
+impl ExampleStatus {
+    pub fn new() -> Self {
+        Self {
+            files: Arc::new(StatusFiles {
+                script: OnceLock::new(),
+                last_run: Mutex::new(None),
+            }),
+        }
+    }
+
+    fn script(&self) -> Result<&ScriptFile, String> {
+        if let Some(script) = self.files.script.get() {
+            return Ok(script);
+        }
+        let script = ScriptFile::create("example_status.rb", STATUS_SCRIPT)
+            .map_err(|e| format!("Failed to write example status script: {}", e))?;
+        Ok(self.files.script.get_or_init(|| script))
+    }
+
+    /// The `--require` arguments that load the script.
+    pub fn prepare(&self) -> Result<Vec<String>, String> {
+        Ok(self.script()?.require_args())
+    }
+
+    /// `prepare`'s arguments plus the options that run only the examples the
+    /// status file lists as failed, in file order.
+    pub fn rerun_args(&self) -> Result<Vec<String>, String> {
+        let mut args = self.prepare()?;
+        args.extend(RERUN_ARGS.iter().map(|arg| arg.to_string()));
+        Ok(args)
+    }
+
+    pub fn record(&self, last_run: LastRun) {
+        *self.files.last_run.lock().unwrap() = Some(last_run);
+    }
+
+    pub fn last_run(&self) -> Option<LastRun> {
+        self.files.last_run.lock().unwrap().clone()
+    }
+
+    /// IDs (`./spec/models/user_spec.rb[1:2]`) of the examples under
+    /// `target`, a file or directory, that failed when they last ran. Empty
+    /// until a run has written the file.
+    pub fn failed_examples(&self, target: &str) -> Vec<String> {
+        let target = target.trim_start_matches("./").trim_end_matches('/');
+        let contents = self
+            .files
+            .script
+            .get()
+            .and_then(|script| std::fs::read_to_string(script.dir().join(STATUS_FILE)).ok())
+            .unwrap_or_default();
+        contents
+            .lines()
+            .filter_map(|line| {
+                let mut columns = line.split('|').map(str::trim);
+                let id = columns.next()?;
+                let file = id.trim_start_matches("./").split('[').next()?;
+                let under = file == target || file.starts_with(&format!("{}/", target));
+                (under && columns.next()? == "failed").then(|| id.to_string())
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    pub fn status_path(&self) -> std::path::PathBuf {
+        self.script().unwrap().dir().join(STATUS_FILE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const STATUS_CONTENTS: &str = "\
+example_id                          | status  | run_time        |
+----------------------------------- | ------- | --------------- |
+./spec/models/user_spec.rb[1:1]     | passed  | 0.00128 seconds |
+./spec/models/user_spec.rb[1:2]     | failed  | 0.01207 seconds |
+./spec/models/user_spec.rb[1:3]     | pending | 0.00011 seconds |
+./spec/models/order_spec.rb[1:1:1]  | failed  | 0.00342 seconds |
+";
+
+    #[test]
+    fn test_prepare_writes_script() {
+        let status = ExampleStatus::new();
+        let args = status.prepare().unwrap();
+        assert_eq!(args[0], "--require");
+
+        assert_eq!(std::fs::read_to_string(&args[1]).unwrap(), STATUS_SCRIPT);
+        assert_eq!(status.prepare().unwrap(), args);
+        assert_eq!(PathBuf::from(&args[1]).with_file_name(STATUS_FILE), status.status_path());
+    }
+
+    #[test]
+    fn test_rerun_args() {
+        let args = ExampleStatus::new().rerun_args().unwrap();
+        assert_eq!(args[2..], ["--only-failures", "--order", "defined"]);
+    }
+
+    #[test]
+    fn test_failed_examples() {
+        let status = ExampleStatus::new();
+        assert!(status.failed_examples("spec").is_empty());
+
+        std::fs::write(status.status_path(), STATUS_CONTENTS).unwrap();
+        assert_eq!(
+            status.failed_examples("spec/models/"),
+            vec!["./spec/models/user_spec.rb[1:2]", "./spec/models/order_spec.rb[1:1:1]"]
+        );
+        assert_eq!(status.failed_examples("./spec/models/user_spec.rb"), vec!["./spec/models/user_spec.rb[1:2]"]);
+        assert!(status.failed_examples("spec/models/user").is_empty());
+    }
+
+    #[test]
+    fn test_files_removed_on_drop() {
+        let status = ExampleStatus::new();
+        let script = PathBuf::from(&status.prepare().unwrap()[1]);
+        std::fs::write(status.status_path(), STATUS_CONTENTS).unwrap();
+        let path = status.status_path();
+
+        let clone = status.clone();
+        drop(status);
+        assert!(script.exists());
+        drop(clone);
+        assert!(!script.exists());
+        assert!(!path.exists());
+    }
+}
@@ -1,11 +1,33 @@
+use crate::spec_files::glob_match;
+
+/// Largest line number accepted; no spec file is anywhere near this long.
+const MAX_LINE_NUMBER: i32 = 1_000_000;
+
+/// Characters a shell gives meaning to. Remote and container runs go
+/// through one, and no spec path needs them.
+const SHELL_METACHARACTERS: &[char] = &[
+    ';', '|', '&', '$', '`', '<', '>', '(', ')', '{', '}', '[', ']', '*', '?', '!', '~', '#', '\\', '\'', '"',
+];
+
 #[derive(Debug)]
 pub struct ParsedFilePath {
     pub file_path: String,
     pub line_numbers: Vec<i32>,
+    /// Scoped RSpec example ids such as `1:2:1`, run as `file[1:2:1]`.
+    pub example_ids: Vec<String>,
 }
 
 impl ParsedFilePath {
-    pub fn from_args(file_path: &str, line_numbers: Vec<i32>) -> Result<Self, String> {
+    /// Line numbers must be between 1 and 1,000,000. They are sorted and
+    /// deduplicated, so the same targets always give the same argument.
+    ///
+    /// `file_path` may carry targets the way RSpec prints them,
+    /// `spec/models/user_spec.rb:42` or `spec/models/user_spec.rb[1:2]`;
+    /// its lines are merged with `line_numbers`.
+    pub fn from_args(file_path: &str, mut line_numbers: Vec<i32>) -> Result<Self, String> {
+        let (file_path, suffix_lines, example_ids) = Self::split_targets(Self::normalize(file_path))?;
+        line_numbers.extend(suffix_lines);
+
         if file_path.is_empty() {
             return Err("Empty file path".to_string());
         }
@@ -13,6 +35,10 @@ impl ParsedFilePath {
         // Validate file path format
         Self::validate_file_path(file_path)?;
 
+        if Self::is_directory_path(file_path) && !line_numbers.is_empty() {
+            return Err("Line numbers can't be used with a directory".to_string());
+        }
+
         // Validate line numbers
         for line_num in &line_numbers {
             if *line_num <= 0 {
@@ -21,15 +47,110 @@ impl ParsedFilePath {
                     line_num
                 ));
             }
+            if *line_num > MAX_LINE_NUMBER {
+                return Err(format!(
+                    "Line numbers must be at most {}, got: {}",
+                    MAX_LINE_NUMBER, line_num
+                ));
+            }
         }
+        line_numbers.sort_unstable();
+        line_numbers.dedup();
 
-        Ok(ParsedFilePath {
+        let parsed = ParsedFilePath {
             file_path: file_path.to_string(),
             line_numbers,
-        })
+            example_ids: Vec::new(),
+        };
+        match example_ids {
+            Some(ids) => parsed.with_example_ids(&[ids.to_string()]),
+            None => Ok(parsed),
+        }
+    }
+
+    /// Splits trailing `:LINE`s or a bracketed `[ids]` off a path.
+    fn split_targets(path: &str) -> Result<(&str, Vec<i32>, Option<&str>), String> {
+        if let Some((file, ids)) = path.strip_suffix(']').and_then(|rest| rest.rsplit_once('[')) {
+            return Ok((file, Vec::new(), Some(ids)));
+        }
+
+        let mut file = path;
+        let mut line_numbers = Vec::new();
+        while let Some((rest, line)) = file.rsplit_once(':') {
+            if line.is_empty() || !line.chars().all(|c| c.is_ascii_digit()) {
+                break;
+            }
+            let line_num = line.parse().map_err(|_| format!("Line numbers must be at most {}, got: {}", MAX_LINE_NUMBER, line))?;
+            line_numbers.push(line_num);
+            file = rest;
+        }
+        Ok((file, line_numbers, None))
+    }
+
+    /// Parses the targets of a single run. Line numbers need exactly one
+    /// path; with several, errors name the path they are about.
+    pub fn from_paths(paths: &[String], line_numbers: Vec<i32>) -> Result<Vec<Self>, String> {
+        match paths {
+            [] => Err("At least one file path is required".to_string()),
+            [path] => Ok(vec![Self::from_args(path, line_numbers)?]),
+            _ if !line_numbers.is_empty() => {
+                Err("Line numbers can only be used with a single file".to_string())
+            }
+            _ => paths
+                .iter()
+                .map(|path| Self::from_args(path, Vec::new()).map_err(|e| format!("{}: {}", path.trim(), e)))
+                .collect(),
+        }
+    }
+
+    /// Targets examples by RSpec id instead of line, which survives edits
+    /// that move lines. Ids are the scope (`1:2:1` or `[1:2:1]`) or a full
+    /// id for this file (`./spec/models/user_spec.rb[1:2:1]`).
+    pub fn with_example_ids(mut self, ids: &[String]) -> Result<Self, String> {
+        if ids.is_empty() {
+            return Ok(self);
+        }
+        if self.is_directory() {
+            return Err("Example ids can't be used with a directory".to_string());
+        }
+        if !self.line_numbers.is_empty() {
+            return Err("Example ids can't be combined with line numbers".to_string());
+        }
+
+        for id in ids.iter().map(|id| id.trim()) {
+            let scopes = match id.split_once('[') {
+                Some((file, scopes)) if !file.is_empty() => {
+                    let file = file.strip_prefix("./").unwrap_or(file);
+                    if file != self.file_path.strip_prefix("./").unwrap_or(&self.file_path) {
+                        return Err(format!("Example id '{}' is not in {}", id, self.file_path));
+                    }
+                    scopes.strip_suffix(']').unwrap_or(scopes)
+                }
+                _ => id.trim_start_matches('[').trim_end_matches(']'),
+            };
+
+            for scope in scopes.split(',') {
+                let valid = !scope.is_empty()
+                    && scope.split(':').all(|index| !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()));
+                if !valid {
+                    return Err(format!(
+                        "Invalid example id '{}'; use an RSpec id such as '1:2:1' or './spec/models/user_spec.rb[1:2:1]'",
+                        id
+                    ));
+                }
+                if !self.example_ids.iter().any(|existing| existing == scope) {
+                    self.example_ids.push(scope.to_string());
+                }
+            }
+        }
+
+        Ok(self)
     }
 
     pub fn as_arg(&self) -> String {
+        if !self.example_ids.is_empty() {
+            return format!("{}[{}]", self.file_path, self.example_ids.join(","));
+        }
         if self.line_numbers.is_empty() {
             self.file_path.clone()
         } else {
@@ -45,11 +166,77 @@ impl ParsedFilePath {
         }
     }
 
+    /// Makes an absolute path relative to the run's directory (`workdir`, or
+    /// the server's own), rejecting one that points outside it.
+    pub fn relative_to(mut self, workdir: Option<&std::path::Path>) -> Result<Self, String> {
+        self.file_path = crate::workdir::relative_spec_path(workdir, &self.file_path)?;
+        Ok(self)
+    }
+
+    /// Checks the target against globs such as `spec/**` (see
+    /// [`glob_match`]); with none, every path is allowed. A directory is
+    /// allowed when everything under it is.
+    pub fn allowed_by(&self, patterns: &[String]) -> Result<(), String> {
+        let mut path = self.file_path.strip_prefix("./").unwrap_or(&self.file_path).to_string();
+        if self.is_directory() && !path.ends_with('/') {
+            path.push('/');
+        }
+        match patterns.is_empty() || patterns.iter().any(|pattern| glob_match(pattern.trim_start_matches("./"), &path)) {
+            true => Ok(()),
+            false => Err(format!("Path {} isn't matched by --allowed-path ({})", self.file_path, patterns.join(", "))),
+        }
+    }
+
+    /// Whether this targets a directory of specs rather than a single file.
+    pub fn is_directory(&self) -> bool {
+        Self::is_directory_path(&self.file_path)
+    }
+
+    /// The rule that let this path through validation.
+    pub fn allowing_rule(&self) -> &'static str {
+        if self.is_directory() {
+            "spec directory (no extension or a trailing '/')"
+        } else {
+            "spec file (*_spec.rb)"
+        }
+    }
+
+    /// A trailing `/` or a last component without an extension, e.g.
+    /// `spec/models/` or `spec/models`.
+    fn is_directory_path(path: &str) -> bool {
+        path.ends_with('/') || !path.rsplit('/').next().unwrap_or(path).contains('.')
+    }
+
+    /// Strips stray whitespace and a matching pair of surrounding quotes,
+    /// which LLMs sometimes add around paths.
+    fn normalize(path: &str) -> &str {
+        let trimmed = path.trim();
+
+        for quote in ['"', '\'', '`'] {
+            if let Some(unquoted) = trimmed
+                .strip_prefix(quote)
+                .and_then(|p| p.strip_suffix(quote))
+            {
+                return unquoted.trim();
+            }
+        }
+
+        trimmed
+    }
+
     fn validate_file_path(path: &str) -> Result<(), String> {
         // Block dangerous characters first
-        if path.contains('\0') || path.contains('\n') {
+        if path.chars().any(char::is_control) {
             return Err("Invalid characters in file path".to_string());
         }
+        if let Some(c) = path.chars().find(|c| SHELL_METACHARACTERS.contains(c)) {
+            return Err(format!("Shell metacharacter '{}' not allowed in file path", c));
+        }
+
+        // RSpec would read the value as an option, e.g. `--require=evil.rb`
+        if path.starts_with('-') {
+            return Err("File paths can't start with '-'".to_string());
+        }
 
         // Prevent path traversal
         if path.contains("../") {
@@ -59,6 +246,10 @@ impl ParsedFilePath {
         // Remove optional "./" prefix for validation
         let clean_path = path.strip_prefix("./").unwrap_or(path);
 
+        if Self::is_directory_path(clean_path) {
+            return Ok(());
+        }
+
         // Must end with _spec.rb and have content before it
         if !clean_path.ends_with("_spec.rb") {
             return Err("File must be an RSpec test file (*_spec.rb)".to_string());
@@ -72,6 +263,106 @@ impl ParsedFilePath {
     }
 }
 
+/// Where the specs for an implementation file conventionally live, e.g.
+/// `app/models/user.rb` in `spec/models/user_spec.rb`.
+#[derive(Debug, PartialEq)]
+pub struct SpecMapping {
+    pub source: String,
+    /// Spec paths to look for, most likely first.
+    pub candidates: Vec<String>,
+    /// The constant the file defines, such as `Foo::Bar` for
+    /// `lib/foo/bar.rb`, which its spec usually describes.
+    pub constant: Option<String>,
+}
+
+impl SpecMapping {
+    /// Maps `source`, relative to the project root, to spec paths under
+    /// `spec_dir`. Files under `app/` keep their path below `app/<kind>`,
+    /// files under `lib/` may be specced with or without the `lib/`, and
+    /// controllers may instead have request specs. A spec file maps to
+    /// itself.
+    pub fn for_source(source: &str, spec_dir: &str) -> Result<Self, String> {
+        let source = ParsedFilePath::normalize(source);
+        let source = source.strip_prefix("./").unwrap_or(source);
+        if source.is_empty() {
+            return Err("Empty file path".to_string());
+        }
+        if source.contains('\0') || source.contains('\n') {
+            return Err("Invalid characters in file path".to_string());
+        }
+        if source.starts_with('/') || source.split('/').any(|part| part == "..") {
+            return Err("Path traversal not allowed".to_string());
+        }
+        if source.ends_with("_spec.rb") {
+            return Ok(Self { source: source.to_string(), candidates: vec![source.to_string()], constant: None });
+        }
+
+        let spec = |path: &str| format!("{}/{}_spec.rb", spec_dir, path);
+        let parts: Vec<&str> = source.split('/').collect();
+        let (candidates, constant_path) = match parts.as_slice() {
+            // rspec-rails names view specs after the whole template name
+            ["app", "views", rest @ ..] if !rest.is_empty() => (vec![spec(&format!("views/{}", rest.join("/")))], None),
+            _ if !source.ends_with(".rb") => {
+                return Err(format!("{} is not a Ruby file; pass the .rb file whose spec you want", source));
+            }
+            ["app", "controllers", rest @ ..] if !rest.is_empty() => {
+                let path = rest.join("/");
+                let path = path.trim_end_matches(".rb");
+                let mut candidates = vec![spec(&format!("controllers/{}", path))];
+                if let Some(resource) = path.strip_suffix("_controller") {
+                    candidates.push(spec(&format!("requests/{}", resource)));
+                }
+                (candidates, Some(path.to_string()))
+            }
+            ["app", kind, rest @ ..] if !rest.is_empty() => {
+                let path = rest.join("/");
+                let path = path.trim_end_matches(".rb");
+                // Concerns are autoloaded without their directory's namespace
+                let constant = path.strip_prefix("concerns/").unwrap_or(path);
+                (vec![spec(&format!("{}/{}", kind, path))], Some(constant.to_string()))
+            }
+            ["lib", rest @ ..] if !rest.is_empty() => {
+                let path = rest.join("/");
+                let path = path.trim_end_matches(".rb");
+                (vec![spec(path), spec(&format!("lib/{}", path))], Some(path.to_string()))
+            }
+            _ => {
+                let path = source.trim_end_matches(".rb");
+                (vec![spec(path)], parts.last().map(|name| name.trim_end_matches(".rb").to_string()))
+            }
+        };
+
+        Ok(Self {
+            source: source.to_string(),
+            candidates,
+            constant: constant_path.as_deref().map(constant_name),
+        })
+    }
+
+    /// The spec file name to search for when no candidate exists.
+    pub fn spec_file_name(&self) -> Option<&str> {
+        self.candidates.first().and_then(|candidate| candidate.rsplit('/').next())
+    }
+}
+
+/// `admin/user_account` as `Admin::UserAccount`.
+fn constant_name(path: &str) -> String {
+    path.split('/')
+        .map(|part| {
+            part.split('_')
+                .map(|word| {
+                    let mut chars = word.chars();
+                    chars
+                        .next()
+                        .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                        .unwrap_or_default()
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,6 +413,91 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_from_args_rejects_negative_among_valid() {
+        let result = ParsedFilePath::from_args("spec/models/user_spec.rb", vec![37, -3, 87]);
+        assert_eq!(
+            result.unwrap_err(),
+            "Line numbers must be positive integers, got: -3"
+        );
+    }
+
+    #[test]
+    fn test_from_args_with_huge_line_number() {
+        let result = ParsedFilePath::from_args("spec/models/user_spec.rb", vec![i32::MAX]);
+        assert_eq!(
+            result.unwrap_err(),
+            "Line numbers must be at most 1000000, got: 2147483647"
+        );
+        assert!(ParsedFilePath::from_args("spec/models/user_spec.rb", vec![1_000_000]).is_ok());
+    }
+
+    #[test]
+    fn test_from_args_dedupes_and_sorts_line_numbers() {
+        let parsed = ParsedFilePath::from_args("spec/models/user_spec.rb", vec![87, 37, 87, 5, 37]).unwrap();
+        assert_eq!(parsed.line_numbers, vec![5, 37, 87]);
+        assert_eq!(parsed.as_arg(), "spec/models/user_spec.rb:5:37:87");
+    }
+
+    #[test]
+    fn test_with_example_ids() {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let parsed = ParsedFilePath::from_args("spec/models/user_spec.rb", vec![])
+            .unwrap()
+            .with_example_ids(&ids(&["1:2:1", "[1:3]", "./spec/models/user_spec.rb[1:4,1:2:1]"]))
+            .unwrap();
+        assert_eq!(parsed.example_ids, ["1:2:1", "1:3", "1:4"]);
+        assert_eq!(parsed.as_arg(), "spec/models/user_spec.rb[1:2:1,1:3,1:4]");
+
+        let unchanged = ParsedFilePath::from_args("spec/models/user_spec.rb", vec![3]).unwrap().with_example_ids(&[]).unwrap();
+        assert_eq!(unchanged.as_arg(), "spec/models/user_spec.rb:3");
+    }
+
+    #[test]
+    fn test_with_invalid_example_ids() {
+        let with_ids = |file: &str, lines: Vec<i32>, id: &str| {
+            ParsedFilePath::from_args(file, lines).unwrap().with_example_ids(&[id.to_string()]).unwrap_err()
+        };
+
+        assert_eq!(with_ids("spec/models", vec![], "1:1"), "Example ids can't be used with a directory");
+        assert_eq!(
+            with_ids("spec/models/user_spec.rb", vec![4], "1:1"),
+            "Example ids can't be combined with line numbers"
+        );
+        assert_eq!(
+            with_ids("spec/models/user_spec.rb", vec![], "./spec/models/order_spec.rb[1:1]"),
+            "Example id './spec/models/order_spec.rb[1:1]' is not in spec/models/user_spec.rb"
+        );
+        for id in ["", "1::2", "a:1", "1:2]x", "1 2"] {
+            assert!(with_ids("spec/models/user_spec.rb", vec![], id).starts_with("Invalid example id"), "{}", id);
+        }
+    }
+
+    #[test]
+    fn test_from_args_with_targets_in_path() {
+        let parsed = ParsedFilePath::from_args("./spec/models/user_spec.rb:42:7", vec![7, 3]).unwrap();
+        assert_eq!(parsed.file_path, "./spec/models/user_spec.rb");
+        assert_eq!(parsed.line_numbers, vec![3, 7, 42]);
+
+        let parsed = ParsedFilePath::from_args(" spec/models/user_spec.rb[1:2,1:3] ", vec![]).unwrap();
+        assert_eq!(parsed.as_arg(), "spec/models/user_spec.rb[1:2,1:3]");
+        let parsed = parsed.with_example_ids(&["1:4".to_string()]).unwrap();
+        assert_eq!(parsed.example_ids, ["1:2", "1:3", "1:4"]);
+    }
+
+    #[test]
+    fn test_from_args_with_invalid_targets_in_path() {
+        let err = |path: &str, lines: Vec<i32>| ParsedFilePath::from_args(path, lines).unwrap_err();
+
+        assert_eq!(err("spec/models/user_spec.rb:0", vec![]), "Line numbers must be positive integers, got: 0");
+        assert_eq!(err("spec/models/user_spec.rb:99999999999", vec![]), "Line numbers must be at most 1000000, got: 99999999999");
+        assert_eq!(err("spec/models:3", vec![]), "Line numbers can't be used with a directory");
+        assert_eq!(err("spec/models/user_spec.rb[1:2]", vec![4]), "Example ids can't be combined with line numbers");
+        assert!(err("spec/models/user_spec.rb[a]", vec![]).starts_with("Invalid example id"));
+        assert_eq!(err("spec/models/user_spec.rb:x", vec![]), "File must be an RSpec test file (*_spec.rb)");
+        assert_eq!(err(":3", vec![]), "Empty file path");
+    }
+
     #[test]
     fn test_from_args_empty_file_path() {
         let result = ParsedFilePath::from_args("", vec![]);
@@ -129,6 +505,42 @@ mod tests {
         assert_eq!(result.unwrap_err(), "Empty file path");
     }
 
+    #[test]
+    fn test_from_args_trims_whitespace() {
+        let parsed = ParsedFilePath::from_args("  spec/models/user_spec.rb \n", vec![]).unwrap();
+        assert_eq!(parsed.file_path, "spec/models/user_spec.rb");
+    }
+
+    #[test]
+    fn test_from_args_strips_quotes() {
+        let cases = vec![
+            "'spec/models/user_spec.rb'",
+            "\"spec/models/user_spec.rb\"",
+            "`spec/models/user_spec.rb`",
+            " ' spec/models/user_spec.rb ' ",
+        ];
+
+        for case in cases {
+            let parsed = ParsedFilePath::from_args(case, vec![]).unwrap();
+            assert_eq!(parsed.file_path, "spec/models/user_spec.rb", "input: {:?}", case);
+        }
+    }
+
+    #[test]
+    fn test_from_args_rejects_unmatched_quotes() {
+        let result = ParsedFilePath::from_args("'spec/models/user_spec.rb", vec![]);
+        assert_eq!(result.unwrap_err(), "Shell metacharacter ''' not allowed in file path");
+    }
+
+    #[test]
+    fn test_from_args_rejects_invalid_after_normalization() {
+        let result = ParsedFilePath::from_args(" '' ", vec![]);
+        assert_eq!(result.unwrap_err(), "Empty file path");
+
+        let result = ParsedFilePath::from_args("\"../spec/user_spec.rb\"", vec![]);
+        assert_eq!(result.unwrap_err(), "Path traversal not allowed");
+    }
+
     #[test]
     fn test_validate_rspec_file_extension() {
         let result = ParsedFilePath::from_args("spec/models/user.rb", vec![]);
@@ -165,6 +577,88 @@ mod tests {
         let result = ParsedFilePath::from_args("spec/user_spec.rb\0", vec![]);
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), "Invalid characters in file path");
+        assert_eq!(ParsedFilePath::from_args("spec/a\rb_spec.rb", vec![]).unwrap_err(), "Invalid characters in file path");
+    }
+
+    #[test]
+    fn test_validate_rejects_smuggled_arguments() {
+        for (path, error) in [
+            ("--require=./evil", "File paths can't start with '-'"),
+            ("-rspec/support/evil", "File paths can't start with '-'"),
+            ("spec/a_spec.rb;rm -rf .", "Shell metacharacter ';' not allowed in file path"),
+            ("spec/$(whoami)_spec.rb", "Shell metacharacter '$' not allowed in file path"),
+            ("spec/`id`_spec.rb", "Shell metacharacter '`' not allowed in file path"),
+            ("spec/a_spec.rb|sh", "Shell metacharacter '|' not allowed in file path"),
+            ("spec/*_spec.rb", "Shell metacharacter '*' not allowed in file path"),
+            ("~/spec", "Shell metacharacter '~' not allowed in file path"),
+        ] {
+            assert_eq!(ParsedFilePath::from_args(path, vec![]).unwrap_err(), error, "{}", path);
+        }
+        assert!(ParsedFilePath::from_args("spec/user-account_spec.rb", vec![]).is_ok());
+    }
+
+    #[test]
+    fn test_allowed_by() {
+        let patterns = vec!["spec/models/**".to_string(), "spec/requests/*_spec.rb".to_string()];
+        for allowed in ["spec/models/user_spec.rb", "./spec/models/admin/", "spec/models", "spec/requests/api_spec.rb"] {
+            assert!(ParsedFilePath::from_args(allowed, vec![]).unwrap().allowed_by(&patterns).is_ok(), "{}", allowed);
+        }
+        for blocked in ["spec/features/login_spec.rb", "spec/requests/v2/api_spec.rb", "spec", "lib/tasks/evil_spec.rb"] {
+            assert!(ParsedFilePath::from_args(blocked, vec![]).unwrap().allowed_by(&patterns).is_err(), "{}", blocked);
+        }
+        assert_eq!(
+            ParsedFilePath::from_args("spec", vec![]).unwrap().allowed_by(&patterns).unwrap_err(),
+            "Path spec isn't matched by --allowed-path (spec/models/**, spec/requests/*_spec.rb)"
+        );
+        assert!(ParsedFilePath::from_args("lib/a_spec.rb", vec![]).unwrap().allowed_by(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_directory_targets() {
+        for dir in ["spec/models", "spec/models/", "./spec", "spec"] {
+            let parsed = ParsedFilePath::from_args(dir, vec![]).unwrap();
+            assert!(parsed.is_directory(), "{} should be a directory", dir);
+            assert_eq!(parsed.as_arg(), dir);
+        }
+        assert!(!ParsedFilePath::from_args("spec/user_spec.rb", vec![]).unwrap().is_directory());
+
+        assert_eq!(
+            ParsedFilePath::from_args("spec/models/", vec![3]).unwrap_err(),
+            "Line numbers can't be used with a directory"
+        );
+        assert_eq!(
+            ParsedFilePath::from_args("spec/../../etc", vec![]).unwrap_err(),
+            "Path traversal not allowed"
+        );
+    }
+
+    #[test]
+    fn test_from_paths() {
+        let paths = vec!["spec/models".to_string(), "spec/requests/api_spec.rb".to_string()];
+        let parsed = ParsedFilePath::from_paths(&paths, vec![]).unwrap();
+        assert_eq!(
+            parsed.iter().map(ParsedFilePath::as_arg).collect::<Vec<_>>(),
+            vec!["spec/models", "spec/requests/api_spec.rb"]
+        );
+
+        let single = ParsedFilePath::from_paths(&paths[1..], vec![12]).unwrap();
+        assert_eq!(single[0].as_arg(), "spec/requests/api_spec.rb:12");
+    }
+
+    #[test]
+    fn test_from_paths_rejects_line_numbers_with_several_files() {
+        let paths = vec!["spec/a_spec.rb".to_string(), "spec/b_spec.rb".to_string()];
+        assert_eq!(
+            ParsedFilePath::from_paths(&paths, vec![3]).unwrap_err(),
+            "Line numbers can only be used with a single file"
+        );
+        assert_eq!(ParsedFilePath::from_paths(&[], vec![]).unwrap_err(), "At least one file path is required");
+
+        let paths = vec!["spec/a_spec.rb".to_string(), "spec/b.rb".to_string()];
+        assert_eq!(
+            ParsedFilePath::from_paths(&paths, vec![]).unwrap_err(),
+            "spec/b.rb: File must be an RSpec test file (*_spec.rb)"
+        );
     }
 
     #[test]
@@ -185,4 +679,41 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_spec_mapping_for_app_and_lib() {
+        let mapping = SpecMapping::for_source("./app/models/admin/user_account.rb", "spec").unwrap();
+        assert_eq!(mapping.candidates, ["spec/models/admin/user_account_spec.rb"]);
+        assert_eq!(mapping.constant.as_deref(), Some("Admin::UserAccount"));
+        assert_eq!(mapping.spec_file_name(), Some("user_account_spec.rb"));
+
+        let mapping = SpecMapping::for_source("lib/foo/bar.rb", "spec").unwrap();
+        assert_eq!(mapping.candidates, ["spec/foo/bar_spec.rb", "spec/lib/foo/bar_spec.rb"]);
+        assert_eq!(mapping.constant.as_deref(), Some("Foo::Bar"));
+
+        let mapping = SpecMapping::for_source("app/controllers/api/users_controller.rb", "test").unwrap();
+        assert_eq!(
+            mapping.candidates,
+            ["test/controllers/api/users_controller_spec.rb", "test/requests/api/users_spec.rb"]
+        );
+        assert_eq!(mapping.constant.as_deref(), Some("Api::UsersController"));
+
+        let mapping = SpecMapping::for_source("app/models/concerns/archivable.rb", "spec").unwrap();
+        assert_eq!(mapping.constant.as_deref(), Some("Archivable"));
+    }
+
+    #[test]
+    fn test_spec_mapping_other_files() {
+        let mapping = SpecMapping::for_source("app/views/users/index.html.erb", "spec").unwrap();
+        assert_eq!(mapping.candidates, ["spec/views/users/index.html.erb_spec.rb"]);
+        assert_eq!(mapping.constant, None);
+
+        let mapping = SpecMapping::for_source("spec/models/user_spec.rb", "spec").unwrap();
+        assert_eq!(mapping.candidates, ["spec/models/user_spec.rb"]);
+
+        assert_eq!(SpecMapping::for_source("config/initializers/money.rb", "spec").unwrap().candidates, ["spec/config/initializers/money_spec.rb"]);
+        assert!(SpecMapping::for_source("app/assets/logo.png", "spec").unwrap_err().contains("not a Ruby file"));
+        assert_eq!(SpecMapping::for_source("lib/../secret.rb", "spec").unwrap_err(), "Path traversal not allowed");
+        assert_eq!(SpecMapping::for_source("  ", "spec").unwrap_err(), "Empty file path");
+    }
 }
@@ -0,0 +1,193 @@
+use std::path::Path;
+
+use rmcp::model::{GetPromptResult, Prompt, PromptArgument, PromptMessage, PromptMessageRole};
+
+use crate::rspec_output::FailureReport;
+use crate::rspec_result::RspecReport;
+use crate::run_history::{RecordedFailure, RunRecord};
+use crate::source_context::SourceContext;
+
+pub const FIX_FAILING_SPEC: &str = "fix_failing_spec";
+
+pub fn fix_failing_spec_prompt() -> Prompt {
+    let run_id = PromptArgument {
+        name: "run_id".to_string(),
+        title: None,
+        description: Some("Run id from list_runs; the latest run when omitted".to_string()),
+        required: Some(false),
+    };
+    Prompt::new(
+        FIX_FAILING_SPEC,
+        Some("Fix the failures of the latest run, given their messages, the code around them and the command that reruns them"),
+        Some(vec![run_id]),
+    )
+}
+
+/// `failures` as the history keeps them. Examples in the JSON report get
+/// its source context, the failing spec line and where the error was
+/// raised; the rest only the code around their location.
+pub fn recorded_failures(failures: &[FailureReport], report_source: &str, root: Option<&Path>) -> Vec<RecordedFailure> {
+    if failures.is_empty() {
+        return Vec::new();
+    }
+    let mut source = SourceContext::new(root.unwrap_or(Path::new(".")));
+    let mut examples = RspecReport::from_partial_output(report_source).map_or_else(Vec::new, |report| report.examples);
+    source.annotate(&mut examples);
+
+    failures
+        .iter()
+        .map(|failure| {
+            let annotated = examples
+                .iter()
+                .find(|example| example.status == "failed" && example.full_description == failure.description)
+                .map(|example| example.source_context.clone())
+                .filter(|snippets| !snippets.is_empty());
+            let source_context = annotated.unwrap_or_else(|| {
+                failure.location.as_deref().and_then(|location| source.around(location)).into_iter().collect()
+            });
+            RecordedFailure {
+                description: failure.description.clone(),
+                location: failure.location.clone(),
+                message: failure.message.clone(),
+                source_context,
+            }
+        })
+        .collect()
+}
+
+/// The prompt for fixing `run`'s failures, or why it has none.
+pub fn fix_failing_spec(run: &RunRecord) -> Result<GetPromptResult, String> {
+    if run.failures.is_empty() {
+        return Err(format!(
+            "Run {} has no failures to fix; its status is {}. Pass the run_id of a failing run from list_runs",
+            run.id, run.status
+        ));
+    }
+
+    let mut text = format!("Fix the failing specs from run {}.\n\nRun: `{}`\nStatus: {}\n", run.id, run.command, run.status);
+    if let Some(summary) = &run.summary {
+        text.push_str(&format!("Summary: {}\n", summary));
+    }
+    text.push_str(&format!(
+        "\nFor each failure, work out from its message and the code whether the spec or the code under test is wrong, and fix that. Then rerun the failures to check the fix:\n\n    {}\n",
+        run.rerun_command.as_deref().unwrap_or(&run.command)
+    ));
+
+    for (number, failure) in run.failures.iter().enumerate() {
+        text.push_str(&format!("\n## {}. {}\n\n", number + 1, failure.description));
+        if let Some(location) = &failure.location {
+            text.push_str(&format!("Location: {}\n\n", location));
+        }
+        if !failure.message.is_empty() {
+            text.push_str(&format!("```\n{}\n```\n", failure.message.trim_end()));
+        }
+        for snippet in &failure.source_context {
+            text.push_str(&format!("\n`{}:{}`:\n\n```ruby\n{}\n```\n", snippet.file, snippet.line, snippet.code));
+        }
+    }
+
+    Ok(GetPromptResult {
+        description: Some(format!("Fix the failures of run {}", run.id)),
+        messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source_context::SourceSnippet;
+
+    fn run(failures: Vec<RecordedFailure>) -> RunRecord {
+        RunRecord {
+            id: 3,
+            targets: "spec/models/user_spec.rb".to_string(),
+            command: "bundle exec rspec spec/models/user_spec.rb".to_string(),
+            status: "failed".to_string(),
+            exit_code: 1,
+            duration_secs: 0.5,
+            finished_at: 0,
+            summary: Some("2 examples, 1 failure".to_string()),
+            result: String::new(),
+            junit: None,
+            failures,
+            rerun_command: Some("bundle exec rspec spec/models/user_spec.rb:4".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_fix_failing_spec() {
+        let failure = RecordedFailure {
+            description: "User validates email".to_string(),
+            location: Some("spec/models/user_spec.rb:4".to_string()),
+            message: "expected true\n     got false\n".to_string(),
+            source_context: vec![SourceSnippet {
+                file: "spec/models/user_spec.rb".to_string(),
+                line: 4,
+                code: "> 4 |     expect(user).to be_valid".to_string(),
+            }],
+        };
+        let prompt = fix_failing_spec(&run(vec![failure])).unwrap();
+
+        assert_eq!(prompt.description.as_deref(), Some("Fix the failures of run 3"));
+        let PromptMessage { role, content } = &prompt.messages[0];
+        assert_eq!(*role, PromptMessageRole::User);
+        let rmcp::model::PromptMessageContent::Text { text } = content else { panic!("not text: {:?}", content) };
+        assert_eq!(
+            text,
+            "Fix the failing specs from run 3.
+
+Run: `bundle exec rspec spec/models/user_spec.rb`
+Status: failed
+Summary: 2 examples, 1 failure
+
+For each failure, work out from its message and the code whether the spec or the code under test is wrong, and fix that. Then rerun the failures to check the fix:
+
+    bundle exec rspec spec/models/user_spec.rb:4
+
+## 1. User validates email
+
+Location: spec/models/user_spec.rb:4
+
+```
+expected true
+     got false
+```
+
+`spec/models/user_spec.rb:4`:
+
+```ruby
+> 4 |     expect(user).to be_valid
+```
+"
+        );
+
+        let passed = RunRecord { status: "passed".to_string(), ..run(vec![]) };
+        assert!(fix_failing_spec(&passed).unwrap_err().starts_with("Run 3 has no failures to fix; its status is passed"));
+    }
+
+    #[test]
+    fn test_recorded_failures_without_report() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("spec/models")).unwrap();
+        std::fs::write(root.join("spec/models/user_spec.rb"), "describe User do\n  it { expect(1).to eq(2) }\nend\n").unwrap();
+        let failure = FailureReport {
+            description: "User saves".to_string(),
+            location: Some("spec/models/user_spec.rb:2".to_string()),
+            message: "expected: 2\ngot: 1".to_string(),
+            sub_failures: vec![],
+        };
+
+        let recorded = recorded_failures(&[failure], "not a JSON report", Some(root));
+        assert_eq!(recorded[0].description, "User saves");
+        assert_eq!(
+            recorded[0].source_context,
+            [SourceSnippet {
+                file: "spec/models/user_spec.rb".to_string(),
+                line: 2,
+                code: "  1 | describe User do\n> 2 |   it { expect(1).to eq(2) }\n  3 | end".to_string(),
+            }]
+        );
+        assert!(recorded_failures(&[], "", None).is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::process::Command;
+
+/// How long a single `git blame` may take before it is abandoned.
+pub const GIT_BLAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The last commit to touch a line, from `git blame --porcelain`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineBlame {
+    pub commit: String,
+    pub author: String,
+}
+
+impl LineBlame {
+    fn parse(porcelain: &str) -> Option<Self> {
+        let mut lines = porcelain.lines();
+        let commit = lines.next()?.split_whitespace().next()?;
+        let author = lines.find_map(|line| line.strip_prefix("author "))?;
+
+        Some(Self {
+            commit: commit.chars().take(8).collect(),
+            author: author.to_string(),
+        })
+    }
+}
+
+/// Blames a `path:line` location inside `workdir`. Returns `None` when the
+/// file is outside `workdir`, isn't tracked by git, or git doesn't answer
+/// within `timeout`.
+pub async fn blame_location(workdir: &Path, location: &str, timeout: Duration) -> Option<LineBlame> {
+    let (file, line) = location.rsplit_once(':')?;
+    let line: u32 = line.parse().ok()?;
+
+    let workdir = workdir.canonicalize().ok()?;
+    if !workdir.join(file).canonicalize().ok()?.starts_with(&workdir) {
+        return None;
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.arg("blame")
+        .arg("--porcelain")
+        .arg("-L")
+        .arg(format!("{},{}", line, line))
+        .arg("--")
+        .arg(file)
+        .current_dir(&workdir)
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+
+    let output = tokio::time::timeout(timeout, cmd.output()).await.ok()?.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    LineBlame::parse(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// One line per blamed location, e.g. `  spec/a_spec.rb:12 1a2b3c4d Jane Doe`.
+pub fn format_blames(blames: &[(String, LineBlame)]) -> String {
+    blames
+        .iter()
+        .map(|(location, blame)| format!("  {} {} {}", location, blame.commit, blame.author))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A throwaway git repository with one committed spec file.
+#[cfg(test)]
+pub fn test_repository() -> tempfile::TempDir {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    std::fs::create_dir_all(dir.join("spec")).unwrap();
+    std::fs::write(dir.join("spec/user_spec.rb"), "describe User do\n  it { expect(1).to eq 2 }\nend\n").unwrap();
+
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    };
+    git(&["init", "-q"]);
+    git(&["add", "."]);
+    git(&[
+        "-c",
+        "user.name=Jane Doe",
+        "-c",
+        "user.email=jane@example.com",
+        "commit",
+        "-q",
+        "-m",
+        "Add user spec",
+    ]);
+    tmp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_porcelain() {
+        let porcelain = "\
+1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b 2 2 1
+author Jane Doe
+author-mail <jane@example.com>
+\t  it { expect(1).to eq 2 }
+";
+        assert_eq!(
+            LineBlame::parse(porcelain),
+            Some(LineBlame {
+                commit: "1a2b3c4d".to_string(),
+                author: "Jane Doe".to_string(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blame_location_in_repository() {
+        let tmp = test_repository();
+        let repo = tmp.path();
+
+        let blame = blame_location(repo, "spec/user_spec.rb:2", GIT_BLAME_TIMEOUT).await.unwrap();
+        assert_eq!(blame.author, "Jane Doe");
+        assert_eq!(blame.commit.len(), 8);
+        assert!(blame_location(repo, "spec/user_spec.rb:99", GIT_BLAME_TIMEOUT).await.is_none());
+
+    }
+
+    #[tokio::test]
+    async fn test_blame_outside_repository() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(dir.join("user_spec.rb"), "describe User do\nend\n").unwrap();
+
+        assert!(blame_location(dir, "user_spec.rb:1", GIT_BLAME_TIMEOUT).await.is_none());
+        assert!(blame_location(dir, "../user_spec.rb:1", GIT_BLAME_TIMEOUT).await.is_none());
+
+    }
+}
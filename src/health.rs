@@ -0,0 +1,96 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use axum::Json;
+use axum::http::StatusCode;
+
+/// What `/healthz` reports: the build, how long the server has been up,
+/// and whether the program runs start with can be found.
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    started: Instant,
+    /// The program at the start of each run's command, e.g. `bundle`,
+    /// or `ssh` for remote runs.
+    program: String,
+    /// Where a relative `program` such as `bin/rspec` is looked up.
+    workdir: Option<PathBuf>,
+}
+
+impl HealthCheck {
+    pub fn new(program: String, workdir: Option<PathBuf>) -> Self {
+        Self { started: Instant::now(), program, workdir }
+    }
+
+    /// `503 Service Unavailable` when the program can't be found, since
+    /// every run would fail.
+    pub fn report(&self) -> (StatusCode, Json<serde_json::Value>) {
+        let resolves = self.resolves();
+        let status = match resolves {
+            true => StatusCode::OK,
+            false => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        let body = serde_json::json!({
+            "status": if resolves { "ok" } else { "unavailable" },
+            "version": env!("CARGO_PKG_VERSION"),
+            "uptime_seconds": self.started.elapsed().as_secs(),
+            "rspec_command": self.program,
+            "command_resolves": resolves,
+        });
+        (status, Json(body))
+    }
+
+    fn resolves(&self) -> bool {
+        if self.program.is_empty() {
+            return false;
+        }
+        if self.program.contains('/') {
+            let path = Path::new(&self.program);
+            return match (path.is_relative(), &self.workdir) {
+                (true, Some(workdir)) => is_executable(&workdir.join(path)),
+                _ => is_executable(path),
+            };
+        }
+        std::env::var_os("PATH")
+            .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| is_executable(&dir.join(&self.program))))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolves_program() {
+        assert!(HealthCheck::new("sh".to_string(), None).resolves());
+        assert!(HealthCheck::new("/bin/sh".to_string(), None).resolves());
+        assert!(HealthCheck::new("./sh".to_string(), Some(PathBuf::from("/bin"))).resolves());
+        assert!(!HealthCheck::new("mcp-rspec-no-such-command".to_string(), None).resolves());
+        assert!(!HealthCheck::new("bin/rspec".to_string(), Some(std::env::temp_dir())).resolves());
+        assert!(!HealthCheck::new(String::new(), None).resolves());
+    }
+
+    #[test]
+    fn test_report() {
+        let (status, Json(body)) = HealthCheck::new("sh".to_string(), None).report();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ok");
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(body["command_resolves"], true);
+
+        let (status, Json(body)) = HealthCheck::new("mcp-rspec-no-such-command".to_string(), None).report();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["rspec_command"], "mcp-rspec-no-such-command");
+    }
+}
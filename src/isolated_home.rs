@@ -0,0 +1,134 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Numbers per-run directories; process-wide so they never collide.
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// How often `--isolated-home` hands out a fresh directory.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum HomeIsolation {
+    /// One directory shared by every run, created at startup
+    Server,
+    /// A new directory for each run
+    Run,
+}
+
+/// Temporary `HOME`/`GEM_HOME`/`BUNDLE_PATH` so runs neither use nor pollute
+/// the user's global gem state.
+#[derive(Debug, Clone)]
+pub struct IsolatedHome {
+    mode: HomeIsolation,
+    keep: bool,
+    root: PathBuf,
+}
+
+impl IsolatedHome {
+    pub fn new(mode: HomeIsolation, keep: bool) -> Result<Self, String> {
+        let root = std::env::temp_dir().join(format!("mcp-rspec-home-{}", std::process::id()));
+        std::fs::create_dir_all(&root)
+            .map_err(|e| format!("Failed to create isolated home {}: {}", root.display(), e))?;
+
+        Ok(Self {
+            mode,
+            keep,
+            root,
+        })
+    }
+
+    /// The home directory for the next run. Per-run directories are removed
+    /// when the returned guard is dropped, unless `--keep-isolated-home`.
+    pub fn prepare(&self) -> Result<HomeDir, String> {
+        let path = match self.mode {
+            HomeIsolation::Server => self.root.clone(),
+            HomeIsolation::Run => {
+                let run = RUNS.fetch_add(1, Ordering::SeqCst);
+                self.root.join(format!("run-{}", run))
+            }
+        };
+
+        std::fs::create_dir_all(&path)
+            .map_err(|e| format!("Failed to create isolated home {}: {}", path.display(), e))?;
+
+        Ok(HomeDir {
+            remove: self.mode == HomeIsolation::Run && !self.keep,
+            path,
+        })
+    }
+
+    /// Removes the whole isolated home tree at shutdown, unless kept.
+    pub fn cleanup(&self) {
+        if !self.keep {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+}
+
+pub struct HomeDir {
+    path: PathBuf,
+    remove: bool,
+}
+
+impl HomeDir {
+    pub fn env(&self) -> Vec<(String, String)> {
+        let path = |sub: &str| self.path.join(sub).to_string_lossy().to_string();
+        vec![
+            ("HOME".to_string(), self.path.to_string_lossy().to_string()),
+            ("GEM_HOME".to_string(), path("gems")),
+            ("BUNDLE_PATH".to_string(), path("bundle")),
+        ]
+    }
+}
+
+impl Drop for HomeDir {
+    fn drop(&mut self) {
+        if self.remove {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_run_homes_are_fresh_and_removed() {
+        let homes = IsolatedHome::new(HomeIsolation::Run, false).unwrap();
+
+        let first = homes.prepare().unwrap();
+        let second = homes.prepare().unwrap();
+        assert_ne!(first.path, second.path);
+        assert!(first.path.is_dir());
+
+        let path = first.path.clone();
+        drop(first);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_server_home_is_shared() {
+        let homes = IsolatedHome::new(HomeIsolation::Server, true).unwrap();
+
+        let first = homes.prepare().unwrap();
+        let path = first.path.clone();
+        drop(first);
+        assert!(path.is_dir());
+        assert_eq!(homes.prepare().unwrap().path, path);
+    }
+
+    #[test]
+    fn test_env() {
+        let home = HomeDir {
+            path: PathBuf::from("/tmp/mcp-rspec-home-1"),
+            remove: false,
+        };
+        assert_eq!(
+            home.env(),
+            vec![
+                ("HOME".to_string(), "/tmp/mcp-rspec-home-1".to_string()),
+                ("GEM_HOME".to_string(), "/tmp/mcp-rspec-home-1/gems".to_string()),
+                ("BUNDLE_PATH".to_string(), "/tmp/mcp-rspec-home-1/bundle".to_string()),
+            ]
+        );
+    }
+}
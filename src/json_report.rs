@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Distinguishes report files of concurrent runs.
+static REPORTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Formatter writing to stdout while the JSON report goes to a file.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, schemars::JsonSchema, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum HumanFormatter {
+    Progress,
+    Documentation,
+}
+
+impl HumanFormatter {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HumanFormatter::Progress => "progress",
+            HumanFormatter::Documentation => "documentation",
+        }
+    }
+}
+
+/// Temp file RSpec's JSON formatter writes to alongside a human formatter,
+/// removed when dropped.
+#[derive(Debug)]
+pub struct JsonReportFile {
+    path: PathBuf,
+}
+
+impl JsonReportFile {
+    pub fn new() -> Self {
+        let n = REPORTS.fetch_add(1, Ordering::SeqCst);
+        let path = std::env::temp_dir().join(format!("mcp-rspec-report-{}-{}.json", std::process::id(), n));
+        Self { path }
+    }
+
+    /// `-f <human> -f json -o <file>`; `-o` applies to the formatter just
+    /// before it, so only the JSON goes to the file. `human` is `None` when
+    /// a stdout formatter is already configured.
+    pub fn formatter_args(&self, human: Option<HumanFormatter>) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(human) = human {
+            args.extend(["-f".to_string(), human.as_str().to_string()]);
+        }
+        args.extend([
+            "-f".to_string(),
+            "json".to_string(),
+            "-o".to_string(),
+            self.path.to_string_lossy().to_string(),
+        ]);
+        args
+    }
+
+    /// The report, if RSpec got far enough to write one.
+    pub fn read(&self) -> Option<String> {
+        std::fs::read_to_string(&self.path).ok().filter(|json| !json.trim().is_empty())
+    }
+}
+
+impl Drop for JsonReportFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatter_args() {
+        let report = JsonReportFile::new();
+        let path = report.path.to_string_lossy().to_string();
+
+        assert_eq!(
+            report.formatter_args(Some(HumanFormatter::Documentation)),
+            vec!["-f", "documentation", "-f", "json", "-o", path.as_str()]
+        );
+        assert_eq!(
+            report.formatter_args(Some(HumanFormatter::Progress)),
+            vec!["-f", "progress", "-f", "json", "-o", path.as_str()]
+        );
+        assert_eq!(report.formatter_args(None), vec!["-f", "json", "-o", path.as_str()]);
+    }
+
+    #[test]
+    fn test_truncated_report_file_is_recovered() {
+        let report = JsonReportFile::new();
+        std::fs::write(
+            &report.path,
+            r#"{"version":"3.12.2","examples":[{"full_description":"User saves","status":"passed"},{"full_desc"#,
+        )
+        .unwrap();
+
+        let recovered = crate::rspec_result::RspecReport::from_partial_output(&report.read().unwrap()).unwrap();
+        assert!(recovered.partial);
+        assert_eq!(recovered.examples[0].full_description, "User saves");
+    }
+
+    #[test]
+    fn test_read_and_cleanup() {
+        let report = JsonReportFile::new();
+        assert_eq!(report.read(), None);
+
+        std::fs::write(&report.path, "{\"version\":\"3.12.2\",\"examples\":[]}").unwrap();
+        assert_eq!(report.read().as_deref(), Some("{\"version\":\"3.12.2\",\"examples\":[]}"));
+
+        let path = report.path.clone();
+        drop(report);
+        assert!(!path.exists());
+    }
+}
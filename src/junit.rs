@@ -0,0 +1,133 @@
+use crate::rspec_result::{ExampleReport, RspecReport};
+
+/// A run's report as JUnit XML, laid out like rspec_junit_formatter's so CI
+/// dashboards read it the same way: one `rspec` suite, a test case per
+/// example with its spec file as the class name, and a failure, error or
+/// skipped element for examples that didn't pass.
+pub fn junit_xml(report: &RspecReport) -> String {
+    let count = |status: &str| report.examples.iter().filter(|example| example.status == status).count();
+    let summary = report.summary.as_ref();
+    let time = summary
+        .and_then(|summary| summary.duration)
+        .unwrap_or_else(|| report.examples.iter().filter_map(|example| example.run_time).sum());
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"rspec\" tests=\"{}\" skipped=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.6}\">\n",
+        report.examples.len(),
+        count("pending"),
+        count("failed"),
+        summary.map_or(0, |summary| summary.errors_outside_of_examples_count),
+        time
+    ));
+    if let Some(seed) = report.seed {
+        xml.push_str(&format!("  <properties>\n    <property name=\"seed\" value=\"{}\"/>\n  </properties>\n", seed));
+    }
+    for example in &report.examples {
+        xml.push_str(&test_case(example));
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+fn test_case(example: &ExampleReport) -> String {
+    let file = example.file_path.strip_prefix("./").unwrap_or(&example.file_path);
+    let classname = file.trim_end_matches(".rb").replace('/', ".");
+    let name = match example.full_description.is_empty() {
+        true => &example.description,
+        false => &example.full_description,
+    };
+    let attributes = format!(
+        "classname=\"{}\" name=\"{}\" file=\"{}\" time=\"{:.6}\"",
+        escape(&classname),
+        escape(name),
+        escape(&example.file_path),
+        example.run_time.unwrap_or(0.0)
+    );
+
+    match example.status.as_str() {
+        "failed" => {
+            let exception = example.exception.clone().unwrap_or_default();
+            let mut body = exception.message.clone();
+            for frame in &exception.backtrace {
+                body.push('\n');
+                body.push_str(frame);
+            }
+            format!(
+                "  <testcase {}>\n    <failure message=\"{}\" type=\"{}\">{}</failure>\n  </testcase>\n",
+                attributes,
+                escape(&exception.message),
+                escape(&exception.class),
+                escape(&body)
+            )
+        }
+        "pending" => format!(
+            "  <testcase {}>\n    <skipped message=\"{}\"/>\n  </testcase>\n",
+            attributes,
+            escape(example.pending_message.as_deref().unwrap_or_default())
+        ),
+        _ => format!("  <testcase {}/>\n", attributes),
+    }
+}
+
+/// `text` fit for an XML attribute or element, without the control
+/// characters XML 1.0 doesn't allow, such as the ANSI escapes in colored
+/// failure messages.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' | '\t' => escaped.push(c),
+            '\r' => escaped.push_str("&#13;"),
+            c if (c as u32) < 0x20 => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_junit_xml() {
+        let report = RspecReport::from_output(
+            r#"{"version":"3.13.0","seed":42,"examples":[
+{"id":"./spec/models/user_spec.rb[1:1]","description":"saves","full_description":"User saves","status":"passed","file_path":"./spec/models/user_spec.rb","line_number":4,"run_time":0.01},
+{"id":"./spec/models/user_spec.rb[1:2]","description":"validates","full_description":"User validates <email>","status":"failed","file_path":"./spec/models/user_spec.rb","line_number":8,"run_time":0.02,"exception":{"class":"RSpec::Expectations::ExpectationNotMetError","message":"expected \"a\" & got \"b\"","backtrace":["./spec/models/user_spec.rb:9:in `block (2 levels)'"]}},
+{"id":"./spec/models/user_spec.rb[1:3]","description":"later","full_description":"User later","status":"pending","pending_message":"Not yet implemented","file_path":"./spec/models/user_spec.rb","line_number":12,"run_time":0.0}
+],"summary":{"duration":0.5,"example_count":3,"failure_count":1,"pending_count":1,"errors_outside_of_examples_count":0},"summary_line":"3 examples, 1 failure, 1 pending"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            junit_xml(&report),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuite name="rspec" tests="3" skipped="1" failures="1" errors="0" time="0.500000">
+  <properties>
+    <property name="seed" value="42"/>
+  </properties>
+  <testcase classname="spec.models.user_spec" name="User saves" file="./spec/models/user_spec.rb" time="0.010000"/>
+  <testcase classname="spec.models.user_spec" name="User validates &lt;email&gt;" file="./spec/models/user_spec.rb" time="0.020000">
+    <failure message="expected &quot;a&quot; &amp; got &quot;b&quot;" type="RSpec::Expectations::ExpectationNotMetError">expected &quot;a&quot; &amp; got &quot;b&quot;
+./spec/models/user_spec.rb:9:in `block (2 levels)&apos;</failure>
+  </testcase>
+  <testcase classname="spec.models.user_spec" name="User later" file="./spec/models/user_spec.rb" time="0.000000">
+    <skipped message="Not yet implemented"/>
+  </testcase>
+</testsuite>
+"#
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("\u{1b}[31mred\u{1b}[0m\r\n"), "[31mred[0m&#13;\n");
+    }
+}
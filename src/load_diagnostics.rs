@@ -0,0 +1,130 @@
+use crate::script_file::ScriptFile;
+
+const LOAD_PATH_BEGIN: &str = "MCP_RSPEC_LOAD_PATH_BEGIN";
+const LOAD_PATH_END: &str = "MCP_RSPEC_LOAD_PATH_END";
+const FEATURES_BEGIN: &str = "MCP_RSPEC_LOADED_FEATURES_BEGIN";
+const FEATURES_END: &str = "MCP_RSPEC_LOADED_FEATURES_END";
+
+/// Required via `--require` for the diagnostic run. Prints the load path and
+/// the project files that were required before RSpec gave up.
+const DIAGNOSTIC_SCRIPT: &str = r#"at_exit do
+  root = Dir.pwd
+  $stderr.puts "MCP_RSPEC_LOAD_PATH_BEGIN"
+  $stderr.puts $LOAD_PATH
+  $stderr.puts "MCP_RSPEC_LOAD_PATH_END"
+  $stderr.puts "MCP_RSPEC_LOADED_FEATURES_BEGIN"
+  $stderr.puts $LOADED_FEATURES.select { |f| f.start_with?(root) }
+  $stderr.puts "MCP_RSPEC_LOADED_FEATURES_END"
+end
+"#;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadDiagnostics {
+    pub load_path: Vec<String>,
+    pub required_files: Vec<String>,
+}
+
+impl LoadDiagnostics {
+    /// Writes the diagnostic script for one run and returns it with the
+    /// extra RSpec arguments for a dry run that loads it. The script is
+    /// removed when dropped, so it must be kept until the run finishes.
+    pub fn prepare() -> Result<(ScriptFile, Vec<String>), String> {
+        let script = ScriptFile::create("load_diagnostics.rb", DIAGNOSTIC_SCRIPT)
+            .map_err(|e| format!("Failed to write load diagnostics script: {}", e))?;
+        let args = [vec!["--dry-run".to_string()], script.require_args()].concat();
+        Ok((script, args))
+    }
+
+    pub fn parse(output: &str) -> Option<Self> {
+        let load_path = Self::section(output, LOAD_PATH_BEGIN, LOAD_PATH_END)?;
+        let required_files = Self::section(output, FEATURES_BEGIN, FEATURES_END).unwrap_or_default();
+
+        Some(Self {
+            load_path,
+            required_files,
+        })
+    }
+
+    fn section(output: &str, begin: &str, end: &str) -> Option<Vec<String>> {
+        let mut lines = output.lines().skip_while(|l| l.trim() != begin);
+        lines.next()?;
+
+        Some(
+            lines
+                .take_while(|l| l.trim() != end)
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty())
+                .collect(),
+        )
+    }
+
+    pub fn render(&self) -> String {
+        let list = |items: &[String]| {
+            if items.is_empty() {
+                "  (none)".to_string()
+            } else {
+                items
+                    .iter()
+                    .map(|item| format!("  {}", item))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        };
+
+        format!(
+            "$LOAD_PATH:\n{}\nRequired project files:\n{}",
+            list(&self.load_path),
+            list(&self.required_files)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIAGNOSTIC_OUTPUT: &str = "\
+An error occurred while loading ./spec/models/user_spec.rb.
+MCP_RSPEC_LOAD_PATH_BEGIN
+/app/spec
+/app/lib
+MCP_RSPEC_LOAD_PATH_END
+MCP_RSPEC_LOADED_FEATURES_BEGIN
+/app/config/boot.rb
+MCP_RSPEC_LOADED_FEATURES_END
+";
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let diagnostics = LoadDiagnostics::parse(DIAGNOSTIC_OUTPUT).unwrap();
+        assert_eq!(diagnostics.load_path, vec!["/app/spec", "/app/lib"]);
+        assert_eq!(diagnostics.required_files, vec!["/app/config/boot.rb"]);
+    }
+
+    #[test]
+    fn test_parse_without_markers() {
+        assert!(LoadDiagnostics::parse("No examples found.").is_none());
+    }
+
+    #[test]
+    fn test_render() {
+        let diagnostics = LoadDiagnostics {
+            load_path: vec!["/app/spec".to_string()],
+            required_files: vec![],
+        };
+        assert_eq!(
+            diagnostics.render(),
+            "$LOAD_PATH:\n  /app/spec\nRequired project files:\n  (none)"
+        );
+    }
+
+    #[test]
+    fn test_prepare_writes_script() {
+        let (script, args) = LoadDiagnostics::prepare().unwrap();
+        assert_eq!(args[..2], ["--dry-run".to_string(), "--require".to_string()]);
+        assert_eq!(std::fs::read_to_string(&args[2]).unwrap(), DIAGNOSTIC_SCRIPT);
+
+        drop(script);
+        assert!(!std::path::Path::new(&args[2]).exists());
+    }
+}
@@ -1,92 +1,1033 @@
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser};
 use rmcp::transport::sse_server::{SseServer, SseServerConfig};
+use rmcp::transport::streamable_http_server::session::local::LocalSessionManager;
+use rmcp::transport::streamable_http_server::{StreamableHttpServerConfig, StreamableHttpService};
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use rmcp::ServiceExt;
 use tracing_subscriber::{
+    fmt::writer::BoxMakeWriter,
     layer::SubscriberExt,
     util::SubscriberInitExt,
     {self},
 };
 
+mod arg_order;
+mod auth;
+mod backpressure;
+mod baseline;
+mod bisect;
 mod rspec_runner;
 mod command_runner;
+mod config_file;
+mod connection_limit;
+mod container_runner;
+mod coverage;
+mod env_allowlist;
+mod example_status;
 mod file_path_parser;
+mod fix_prompt;
+mod git_blame;
+mod health;
+mod isolated_home;
+mod json_report;
+mod junit;
+mod load_diagnostics;
+mod parallel;
+mod preset;
+mod process_runner;
+mod process_tracker;
+mod projects;
+mod response_budget;
+mod rspec_output;
+mod rspec_result;
+mod rspec_version;
 mod rspec_server;
+mod run_defaults;
+mod run_history;
+mod run_metrics;
+mod run_outputs;
+mod sandbox;
+mod script_file;
+mod shutdown;
+mod source_context;
+mod spec_files;
+mod spec_markers;
+mod spec_scaffold;
+mod spring;
+mod ssh_runner;
+mod startup;
+mod tls;
+mod transport;
+mod watcher;
+mod webhook;
+mod workdir;
 
 #[cfg(test)]
 mod mock_runner;
 
+use crate::arg_order::{ArgOrder, DEFAULT_ARG_ORDER};
+use crate::auth::{AuthPolicy, AuthScope, SharedAuthPolicy, require_token};
+use crate::backpressure::{Backpressure, InFlightLimit};
+use crate::baseline::Baseline;
+use crate::command_runner::{CommandRunner, RunContext};
+use crate::config_file::{ConfigFile, DEFAULT_CONFIG_FILE, watch_config_file};
+use crate::connection_limit::{ConnectionLimit, limit_connections};
+use crate::env_allowlist::EnvAllowlist;
+use crate::coverage::CoverageConfig;
+use crate::container_runner::{ContainerExec, ContainerRunner, ExecMode, PathMapping};
+use crate::health::HealthCheck;
+use crate::isolated_home::{HomeIsolation, IsolatedHome};
+use crate::json_report::HumanFormatter;
+use crate::parallel::{ParallelConfig, ParallelStrategy};
+use crate::preset::{Preset, RunSettings, parse_env_file, parse_env_var};
+use crate::process_runner::ProcessCommandRunner;
+use crate::process_tracker::ProcessTracker;
+use crate::projects::Projects;
+use crate::run_history::RunHistory;
+use crate::run_metrics::RunMetrics;
+use crate::rspec_result::StdoutFormat;
 use crate::rspec_runner::RspecRunner;
-use crate::rspec_server::RspecServer;
+use crate::rspec_server::{OutputStyle, RspecServer, SuiteLimits};
+use crate::rspec_version::VersionCache;
+use crate::run_defaults::{DEFAULT_MAX_REPORTED_FAILURES, RunDefaults};
+use crate::sandbox::SandboxPrefix;
+use crate::shutdown::ShutdownMode;
+use crate::spec_files::SpecListing;
+use crate::spring::SpringMode;
+use crate::ssh_runner::{SshProcessRunner, SshTarget};
+use crate::transport::{RetryPolicy, Transport, serve_with_retry};
+use crate::webhook::FailureWebhook;
+use crate::watcher::{WatchConfig, WatchSubscribers, spawn_watcher};
+use crate::workdir::WorkdirPolicy;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(name = "mcp-rspec")]
-#[command(about = "Configurable RSpec runner MCP server over HTTP with SSE")]
+#[command(about = "Configurable RSpec runner MCP server over HTTP (SSE or Streamable HTTP) or stdio")]
 #[command(version)]
 struct Cli {
+    /// TOML file of settings, reloaded when it changes or on SIGHUP; ./mcp-rspec.toml is read if it exists
+    #[arg(long, env = "MCP_RSPEC_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// sse to listen on --hostname and --port, or stdio for clients that launch the server
+    #[arg(long, env = "MCP_RSPEC_TRANSPORT", value_enum, default_value_t = Transport::Sse)]
+    transport: Transport,
+
     #[arg(short = 'H', long, env = "MCP_RSPEC_HOSTNAME", default_value = "127.0.0.1")]
     hostname: String,
 
     #[arg(short, long, env = "MCP_RSPEC_PORT", default_value = "30301")]
     port: u16,
 
+    /// PEM certificate chain to serve HTTPS with; needs --tls-key
+    #[arg(long, env = "MCP_RSPEC_TLS_CERT", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long, env = "MCP_RSPEC_TLS_KEY", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
     #[arg(short = 'c', long, env = "RSPEC_RUNNER_CMD", default_value = "bundle exec rspec")]
     rspec_cmd: String,
+
+    /// Run through Spring's preloaded app (`bin/spring rspec`), falling back to --rspec-cmd when Spring fails
+    #[arg(long, env = "MCP_RSPEC_SPRING", value_enum, default_value_t = SpringMode::Off)]
+    spring: SpringMode,
+
+    /// Maximum runs executing at once, 0 for no limit; see --backpressure for what happens beyond it
+    #[arg(
+        long,
+        visible_aliases = ["max-concurrency", "max-concurrent-runs"],
+        env = "MCP_RSPEC_MAX_IN_FLIGHT",
+        default_value = "1"
+    )]
+    max_in_flight: usize,
+
+    /// What a run does when --max-in-flight runs are already executing
+    #[arg(long, env = "MCP_RSPEC_BACKPRESSURE", value_enum, default_value_t = Backpressure::Queue)]
+    backpressure: Backpressure,
+
+    /// Runs allowed to wait for a slot with --backpressure queue-with-limit
+    #[arg(long, env = "MCP_RSPEC_MAX_QUEUED", default_value = "8")]
+    max_queued: usize,
+
+    /// Seconds a queued run waits for a slot before failing; no limit when unset
+    #[arg(long, env = "MCP_RSPEC_QUEUE_TIMEOUT")]
+    queue_timeout: Option<u64>,
+
+    /// Times binding or serving the SSE transport is retried, with backoff, before exiting
+    #[arg(long, env = "MCP_RSPEC_TRANSPORT_RETRIES", default_value = "5")]
+    transport_retries: u32,
+
+    /// Maximum simultaneous SSE clients; further connections get 503
+    #[arg(long, env = "MCP_RSPEC_MAX_CONNECTIONS")]
+    max_connections: Option<usize>,
+
+    #[arg(long, env = "MCP_RSPEC_ARG_ORDER", default_value = DEFAULT_ARG_ORDER)]
+    arg_order: ArgOrder,
+
+    /// Directory runs happen in when a request doesn't name one
+    #[arg(long, visible_aliases = ["workdir", "project-root"], env = "MCP_RSPEC_DEFAULT_WORKDIR")]
+    default_workdir: Option<PathBuf>,
+
+    /// Additional directory requests may choose as their workdir; may be repeated
+    #[arg(long = "allow-workdir", value_name = "DIR")]
+    allowed_workdirs: Vec<PathBuf>,
+
+    /// Glob, relative to the workdir, that spec paths must match, such as `spec/**` (repeatable)
+    #[arg(long = "allowed-path", env = "MCP_RSPEC_ALLOWED_PATHS", value_name = "GLOB", value_delimiter = ',')]
+    allowed_paths: Vec<String>,
+
+    /// Give runs a temporary HOME, GEM_HOME and BUNDLE_PATH, shared by the server or fresh per run
+    #[arg(long, env = "MCP_RSPEC_ISOLATED_HOME", value_enum)]
+    isolated_home: Option<HomeIsolation>,
+
+    /// Keep isolated home directories instead of removing them
+    #[arg(long, env = "MCP_RSPEC_KEEP_ISOLATED_HOME")]
+    keep_isolated_home: bool,
+
+    /// Command every run is wrapped in, e.g. "firejail --net=none"
+    #[arg(long, env = "MCP_RSPEC_SANDBOX_PREFIX")]
+    sandbox_prefix: Option<SandboxPrefix>,
+
+    /// Run RSpec locally, or inside a container with docker compose exec or docker exec
+    #[arg(long, env = "MCP_RSPEC_EXEC_MODE", value_enum, default_value_t = ExecMode::Local)]
+    exec_mode: ExecMode,
+
+    /// Compose service to run RSpec in with --exec-mode docker-compose, e.g. "web"
+    #[arg(long, env = "MCP_RSPEC_SERVICE")]
+    service: Option<String>,
+
+    /// Container name or id to run RSpec in with --exec-mode docker
+    #[arg(long, env = "MCP_RSPEC_CONTAINER")]
+    container: Option<String>,
+
+    /// HOST:CONTAINER directory mount used to translate paths, e.g. "/home/me/app:/app"; repeatable
+    #[arg(long = "container-mount", env = "MCP_RSPEC_CONTAINER_MOUNTS", value_delimiter = ',')]
+    container_mounts: Vec<PathMapping>,
+
+    /// Host to run RSpec on with --exec-mode ssh, "host" or "user@host"
+    #[arg(long, env = "MCP_RSPEC_SSH_HOST")]
+    ssh_host: Option<String>,
+
+    /// User to log in as on --ssh-host
+    #[arg(long, env = "MCP_RSPEC_SSH_USER")]
+    ssh_user: Option<String>,
+
+    /// Port of the SSH server on --ssh-host
+    #[arg(long, env = "MCP_RSPEC_SSH_PORT")]
+    ssh_port: Option<u16>,
+
+    /// Private key to log in with, passed to ssh -i
+    #[arg(long, env = "MCP_RSPEC_SSH_KEY")]
+    ssh_key: Option<PathBuf>,
+
+    /// Project root on --ssh-host that the local project root corresponds to
+    #[arg(long, env = "MCP_RSPEC_SSH_ROOT")]
+    ssh_root: Option<String>,
+
+    /// Shell command run once before serving, e.g. "bundle install"
+    #[arg(long, env = "MCP_RSPEC_STARTUP_COMMAND")]
+    startup_command: Option<String>,
+
+    /// Exit if the startup command fails; with false only a warning is logged
+    #[arg(
+        long,
+        env = "MCP_RSPEC_STARTUP_STRICT",
+        default_value_t = true,
+        num_args = 0..=1,
+        default_missing_value = "true",
+        action = clap::ArgAction::Set
+    )]
+    startup_strict: bool,
+
+    /// Seconds the startup command may take
+    #[arg(long, env = "MCP_RSPEC_STARTUP_TIMEOUT_SECS", default_value = "600")]
+    startup_timeout_secs: u64,
+
+    #[arg(long, env = "MCP_RSPEC_ALLOW_ADMIN_TOOLS")]
+    allow_admin_tools: bool,
+
+    /// Formatter runs write to stdout: progress, or json to return results parsed from RSpec's JSON report
+    #[arg(long, env = "MCP_RSPEC_FORMAT", value_enum, default_value_t = StdoutFormat::Progress)]
+    format: StdoutFormat,
+
+    /// Run with the documentation formatter and notify clients as each example completes
+    #[arg(long, env = "MCP_RSPEC_STREAM_EXAMPLES")]
+    stream_examples: bool,
+
+    /// Number of slowest files to report for multi-file runs with JSON output (0 disables)
+    #[arg(long, env = "MCP_RSPEC_SLOWEST_FILES", default_value = "5")]
+    slowest_files: usize,
+
+    /// Bearer token required for tool calls
+    #[arg(long, env = "MCP_RSPEC_AUTH_TOKEN")]
+    auth_token: Option<String>,
+
+    /// Which tools require the auth token
+    #[arg(long, env = "MCP_RSPEC_AUTH_SCOPE", value_enum, default_value_t = AuthScope::All)]
+    auth_scope: AuthScope,
+
+    /// On load errors, do an extra dry run reporting $LOAD_PATH and required files
+    #[arg(long, env = "MCP_RSPEC_DIAGNOSE_LOAD_ERRORS")]
+    diagnose_load_errors: bool,
+
+    /// Maximum number of parsed failures to include in a result
+    #[arg(long, env = "MCP_RSPEC_MAX_REPORTED_FAILURES", default_value_t = DEFAULT_MAX_REPORTED_FAILURES)]
+    max_reported_failures: usize,
+
+    /// Kill runs after this many seconds unless the request sets timeout_seconds
+    #[arg(long, visible_alias = "timeout-secs", env = "MCP_RSPEC_TIMEOUT", value_parser = clap::value_parser!(u64).range(1..))]
+    timeout: Option<u64>,
+
+    /// output_style for requests that don't set one
+    #[arg(long, env = "MCP_RSPEC_DEFAULT_OUTPUT_STYLE", value_enum)]
+    default_output_style: Option<OutputStyle>,
+
+    /// group_examples for requests that don't set it
+    #[arg(long, env = "MCP_RSPEC_DEFAULT_GROUP_EXAMPLES")]
+    default_group_examples: Option<bool>,
+
+    /// retries for requests that don't set them
+    #[arg(long, env = "MCP_RSPEC_DEFAULT_RETRIES")]
+    default_retries: Option<u32>,
+
+    /// verbose for requests that don't set it
+    #[arg(long, env = "MCP_RSPEC_DEFAULT_VERBOSE")]
+    default_verbose: Option<bool>,
+
+    /// human_formatter for requests that don't set one
+    #[arg(long, env = "MCP_RSPEC_DEFAULT_HUMAN_FORMATTER", value_enum)]
+    default_human_formatter: Option<HumanFormatter>,
+
+    /// Defaults for a common stack; explicit --require and --env take precedence
+    #[arg(long, env = "MCP_RSPEC_PRESET", value_enum)]
+    preset: Option<Preset>,
+
+    /// File passed to RSpec with --require for every run
+    #[arg(long, env = "MCP_RSPEC_REQUIRE")]
+    require: Option<String>,
+
+    /// Environment variable (KEY=VALUE) set for every run; may be repeated, or comma-separated in MCP_RSPEC_ENV
+    #[arg(long = "env", env = "MCP_RSPEC_ENV", value_name = "KEY=VALUE", value_parser = parse_env_var, value_delimiter = ',')]
+    env_vars: Vec<(String, String)>,
+
+    /// File of KEY=VALUE lines set for every run, before --env
+    #[arg(long, env = "MCP_RSPEC_ENV_FILE")]
+    env_file: Option<PathBuf>,
+
+    /// Environment variable a request's env may set, or a prefix ending in *; repeatable
+    #[arg(long = "allow-env", env = "MCP_RSPEC_ALLOW_ENV", value_name = "NAME", value_delimiter = ',')]
+    allowed_env: Vec<String>,
+
+    /// Start runs from an empty environment rather than the server's; PATH is kept unless --env sets it
+    #[arg(long, env = "MCP_RSPEC_ENV_CLEAR")]
+    env_clear: bool,
+
+    /// Convert \r\n line endings in captured output to \n before parsing
+    #[arg(
+        long,
+        env = "MCP_RSPEC_NORMALIZE_NEWLINES",
+        default_value_t = true,
+        num_args = 0..=1,
+        default_missing_value = "true",
+        action = clap::ArgAction::Set
+    )]
+    normalize_newlines: bool,
+
+    /// Truncate the stdout and stderr included in results to this many bytes together, keeping both ends; 0 disables
+    #[arg(long, env = "MCP_RSPEC_MAX_OUTPUT_BYTES", default_value = "100000")]
+    max_output_bytes: usize,
+
+    /// Run directory targets one spec file at a time, killing files that run longer than this
+    #[arg(long, env = "MCP_RSPEC_PER_FILE_TIMEOUT_SECS")]
+    per_file_timeout_secs: Option<u64>,
+
+    /// File of known failures (`path description` per line) left out of results; update_baseline rewrites it
+    #[arg(long, env = "MCP_RSPEC_BASELINE_FAILURES")]
+    baseline_failures: Option<PathBuf>,
+
+    /// Record each session's example statuses and register rerun_failures, which reruns the last run's failures
+    #[arg(long, env = "MCP_RSPEC_RERUN_FAILURES")]
+    rerun_failures: bool,
+
+    /// Directory, relative to the workdir, that list_spec_files searches
+    #[arg(long, env = "MCP_RSPEC_SPEC_DIR", default_value = "spec")]
+    spec_dir: String,
+
+    /// Gitignore-style pattern of files or directories list_spec_files skips (repeatable)
+    #[arg(long = "spec-exclude", env = "MCP_RSPEC_SPEC_EXCLUDE", value_name = "PATTERN", value_delimiter = ',')]
+    spec_excludes: Vec<String>,
+
+    /// Kill run_rspec_suite runs after this many seconds, instead of --timeout
+    #[arg(long, env = "MCP_RSPEC_SUITE_TIMEOUT_SECS")]
+    suite_timeout_secs: Option<u64>,
+
+    /// Refuse run_rspec_suite runs with more examples than this, counted with a dry run first
+    #[arg(long, env = "MCP_RSPEC_SUITE_MAX_EXAMPLES")]
+    suite_max_examples: Option<u32>,
+
+    /// Split run_rspec_suite runs across processes with parallel_tests or turbo_tests
+    #[arg(long, env = "MCP_RSPEC_PARALLEL", value_enum)]
+    parallel: Option<ParallelStrategy>,
+
+    /// Command for --parallel; defaults to `bundle exec parallel_rspec` or `bundle exec turbo_tests`
+    #[arg(long, env = "MCP_RSPEC_PARALLEL_CMD")]
+    parallel_cmd: Option<String>,
+
+    /// Processes for --parallel runs that don't ask for a number; the tool's default (one per CPU) otherwise
+    #[arg(long, env = "MCP_RSPEC_PARALLEL_PROCESSES", value_parser = clap::value_parser!(u32).range(1..=64))]
+    parallel_processes: Option<u32>,
+
+    /// JSON file of named projects, each with a root and optionally its own RSpec command, that requests pick with `project`
+    #[arg(long, env = "MCP_RSPEC_PROJECTS_FILE")]
+    projects_file: Option<PathBuf>,
+
+    /// Recent runs kept for list_runs and get_run; 0 disables both tools
+    #[arg(long, env = "MCP_RSPEC_RUN_HISTORY", default_value = "20")]
+    run_history: usize,
+
+    /// JSON file the run history is saved to and reloaded from, so it outlives restarts
+    #[arg(long, env = "MCP_RSPEC_RUN_HISTORY_FILE")]
+    run_history_file: Option<PathBuf>,
+
+    /// Keep each run_rspec run's results as JUnit XML, returned by get_run_report
+    #[arg(long, env = "MCP_RSPEC_JUNIT_REPORTS")]
+    junit_reports: bool,
+
+    /// Keep run_rspec results within this many bytes by dropping the output, then the failures
+    #[arg(long, env = "MCP_RSPEC_RESPONSE_BUDGET_BYTES")]
+    response_budget_bytes: Option<usize>,
+
+    /// Name the last commit and author of each failing line, via git blame
+    #[arg(long, env = "MCP_RSPEC_ENABLE_GIT_BLAME")]
+    enable_git_blame: bool,
+
+    /// Offer run_rspec_with_coverage, reporting SimpleCov results
+    #[arg(long, env = "MCP_RSPEC_ENABLE_COVERAGE")]
+    enable_coverage: bool,
+
+    /// Variable that turns SimpleCov on for coverage runs
+    #[arg(long, env = "MCP_RSPEC_COVERAGE_ENV", value_name = "KEY=VALUE", value_parser = parse_env_var, value_delimiter = ',', default_value = "COVERAGE=1")]
+    coverage_env: Vec<(String, String)>,
+
+    /// SimpleCov's output directory, relative to the run's working directory
+    #[arg(long, env = "MCP_RSPEC_COVERAGE_DIR", default_value = "coverage")]
+    coverage_dir: PathBuf,
+
+    /// Offer bisect_failure, which runs rspec --bisect on order-dependent failures
+    #[arg(long, env = "MCP_RSPEC_ENABLE_BISECT")]
+    enable_bisect: bool,
+
+    /// Kill bisect_failure runs after this many seconds, unless a request sets its own timeout
+    #[arg(long, env = "MCP_RSPEC_BISECT_TIMEOUT_SECS", default_value_t = 600)]
+    bisect_timeout_secs: u64,
+
+    /// Offer create_spec, which writes skeleton spec files into the project
+    #[arg(long, env = "MCP_RSPEC_ENABLE_CREATE_SPEC")]
+    enable_create_spec: bool,
+
+    /// Watch the project for changed app and spec files, run their specs and send the results to clients as logging notifications
+    #[arg(long, env = "MCP_RSPEC_WATCH")]
+    watch: bool,
+
+    /// What happens to runs in progress on Ctrl+C or SIGTERM
+    #[arg(long, env = "MCP_RSPEC_SHUTDOWN_MODE", value_enum, default_value_t = ShutdownMode::Wait)]
+    shutdown_mode: ShutdownMode,
+
+    /// How long shutdown waits for runs to finish, or to exit after SIGTERM with --shutdown-mode terminate, before killing them
+    #[arg(long, env = "MCP_RSPEC_SHUTDOWN_GRACE_SECS", default_value_t = 30)]
+    shutdown_grace_secs: u64,
+
+    /// How long --watch waits after the last change before running specs
+    #[arg(long, env = "MCP_RSPEC_WATCH_DEBOUNCE_MS", default_value_t = 300)]
+    watch_debounce_ms: u64,
+
+    /// Include the environment variables applied to each run in its result, with secrets redacted
+    #[arg(long, env = "MCP_RSPEC_REPORT_ENV")]
+    report_env: bool,
+
+    /// Flag results of runs that didn't pass as tool errors
+    #[arg(
+        long,
+        env = "MCP_RSPEC_ERROR_ON_FAILURE",
+        default_value_t = true,
+        num_args = 0..=1,
+        default_missing_value = "true",
+        action = clap::ArgAction::Set
+    )]
+    error_on_failure: bool,
+
+    /// Kill a run once its stdout and stderr together exceed this many bytes
+    #[arg(long, env = "MCP_RSPEC_ABORT_ON_OUTPUT_BYTES")]
+    abort_on_output_bytes: Option<usize>,
+
+    /// http:// URL that receives a JSON POST whenever a run fails
+    #[arg(long, env = "MCP_RSPEC_FAILURE_WEBHOOK")]
+    failure_webhook: Option<String>,
+
+    /// Secret used to sign failure webhook payloads (HMAC-SHA256)
+    #[arg(long, env = "MCP_RSPEC_FAILURE_WEBHOOK_SECRET")]
+    failure_webhook_secret: Option<String>,
+}
+
+/// Where the Streamable HTTP transport serves MCP.
+const MCP_PATH: &str = "/mcp";
+
+/// Adds the `--max-connections` limit on `stream_path` and, when the token
+/// guards the transport, the auth check to an HTTP transport's routes.
+fn guard_http(
+    mut router: axum::Router,
+    stream_path: &str,
+    max_connections: Option<usize>,
+    auth: SharedAuthPolicy,
+) -> axum::Router {
+    if let Some(max) = max_connections {
+        let limit = ConnectionLimit::new(stream_path, max);
+        router = router.layer(axum::middleware::from_fn_with_state(limit, limit_connections));
+    }
+    // Outermost, so rejected clients don't take a connection slot
+    router.layer(axum::middleware::from_fn_with_state(auth, require_token))
+}
+
+/// Fills in the settings `file` has, except those set with a flag or
+/// environment variable, which are named in `explicit`.
+fn apply_config_file(cli: &mut Cli, file: &ConfigFile, explicit: &[&str]) {
+    let from_file = |key: &str| !explicit.contains(&key);
+    if let Some(rspec_cmd) = &file.rspec_cmd && from_file("rspec_cmd") {
+        cli.rspec_cmd = rspec_cmd.clone();
+    }
+    if let Some(default_workdir) = &file.default_workdir && from_file("default_workdir") {
+        cli.default_workdir = Some(default_workdir.clone());
+    }
+    if let Some(allowed_workdirs) = &file.allowed_workdirs && from_file("allowed_workdirs") {
+        cli.allowed_workdirs = allowed_workdirs.clone();
+    }
+    if let Some(projects_file) = &file.projects_file && from_file("projects_file") {
+        cli.projects_file = Some(projects_file.clone());
+    }
+    if let Some(bytes) = file.abort_on_output_bytes && from_file("abort_on_output_bytes") {
+        cli.abort_on_output_bytes = Some(bytes);
+    }
+    if let Some(timeout) = file.timeout && from_file("timeout") {
+        cli.timeout = Some(timeout);
+    }
+    if let Some(timeout) = file.suite_timeout_secs && from_file("suite_timeout_secs") {
+        cli.suite_timeout_secs = Some(timeout);
+    }
+    if let Some(allowed_env) = &file.allowed_env && from_file("allowed_env") {
+        cli.allowed_env = allowed_env.clone();
+    }
+    if let Some(token) = &file.auth_token && from_file("auth_token") {
+        cli.auth_token = Some(token.clone());
+    }
+    if let Some(scope) = file.auth_scope && from_file("auth_scope") {
+        cli.auth_scope = scope;
+    }
+    if let Some(bytes) = file.max_output_bytes && from_file("max_output_bytes") {
+        cli.max_output_bytes = bytes;
+    }
+    if let Some(bytes) = file.response_budget_bytes && from_file("response_budget_bytes") {
+        cli.response_budget_bytes = Some(bytes);
+    }
+    if let Some(max) = file.max_reported_failures && from_file("max_reported_failures") {
+        cli.max_reported_failures = max;
+    }
+}
+
+/// The settings a config file reload changes, taken by each session as it
+/// starts; sessions already open keep theirs.
+#[derive(Clone)]
+struct SessionSettings {
+    env_allowlist: EnvAllowlist,
+    run_defaults: RunDefaults,
+    max_output_bytes: Option<usize>,
+    response_budget: Option<usize>,
+    suite_limits: SuiteLimits,
+}
+
+impl SessionSettings {
+    fn from_cli(cli: &Cli) -> Self {
+        Self {
+            env_allowlist: EnvAllowlist::new(cli.allowed_env.clone()),
+            run_defaults: RunDefaults {
+                max_reported_failures: Some(cli.max_reported_failures),
+                output_style: cli.default_output_style,
+                group_examples: cli.default_group_examples,
+                retries: cli.default_retries,
+                verbose: cli.default_verbose,
+                human_formatter: cli.default_human_formatter,
+                timeout_seconds: cli.timeout,
+            },
+            max_output_bytes: Some(cli.max_output_bytes).filter(|limit| *limit > 0),
+            response_budget: cli.response_budget_bytes,
+            suite_limits: SuiteLimits {
+                timeout: cli.suite_timeout_secs.map(Duration::from_secs),
+                max_examples: cli.suite_max_examples,
+            },
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let explicit: Vec<&str> = ConfigFile::KEYS
+        .iter()
+        .copied()
+        .filter(|key| matches!(matches.value_source(key), Some(ValueSource::CommandLine | ValueSource::EnvVariable)))
+        .collect();
+    let config_path = cli
+        .config
+        .clone()
+        .or_else(|| Path::new(DEFAULT_CONFIG_FILE).is_file().then(|| PathBuf::from(DEFAULT_CONFIG_FILE)));
+    let config_file = config_path
+        .as_deref()
+        .map(ConfigFile::load)
+        .transpose()
+        .map_err(anyhow::Error::msg)?
+        .unwrap_or_default();
+    let flags = cli.clone();
+    apply_config_file(&mut cli, &config_file, &explicit);
+    let session_settings = Arc::new(RwLock::new(SessionSettings::from_cli(&cli)));
+    let transport_auth: SharedAuthPolicy =
+        Arc::new(RwLock::new(AuthPolicy::new(cli.auth_token.clone(), cli.auth_scope)));
 
     if cli.rspec_cmd.trim().is_empty() {
         anyhow::bail!("rspec_cmd cannot be empty")
     }
 
-    let bind_address: SocketAddr = format!("{}:{}", cli.hostname, cli.port).parse()?;
+    let workdirs =
+        WorkdirPolicy::new(cli.default_workdir.clone(), cli.allowed_workdirs.clone()).map_err(anyhow::Error::msg)?;
+    let baseline = cli
+        .baseline_failures
+        .clone()
+        .map(Baseline::load)
+        .transpose()
+        .map_err(anyhow::Error::msg)?;
+    let projects = cli
+        .projects_file
+        .as_deref()
+        .map(Projects::load)
+        .transpose()
+        .map_err(anyhow::Error::msg)?
+        .unwrap_or_default();
+    let history = RunHistory::new(cli.run_history, cli.run_history_file).map_err(anyhow::Error::msg)?;
+
+    let isolated_home = cli
+        .isolated_home
+        .map(|mode| IsolatedHome::new(mode, cli.keep_isolated_home))
+        .transpose()
+        .map_err(anyhow::Error::msg)?;
 
+    let failure_webhook = cli
+        .failure_webhook
+        .as_deref()
+        .map(|url| FailureWebhook::new(url, cli.failure_webhook_secret.clone()))
+        .transpose()
+        .map_err(anyhow::Error::msg)?;
+
+    if cli.transport == Transport::Stdio && cli.auth_token.is_some() {
+        anyhow::bail!("--auth-token needs an HTTP transport; stdio has no request headers to carry the token")
+    }
+    if cli.transport == Transport::Stdio && cli.tls_cert.is_some() {
+        anyhow::bail!("--tls-cert needs an HTTP transport; stdio is not served over the network")
+    }
+    let tls = match (&cli.tls_cert, &cli.tls_key) {
+        (Some(cert), Some(key)) => Some(tls::load_acceptor(cert, key).map_err(anyhow::Error::msg)?),
+        _ => None,
+    };
+    let scheme = if tls.is_some() { "https" } else { "http" };
+
+    // Over stdio, stdout carries the protocol
+    let log_writer = match cli.transport {
+        Transport::Sse | Transport::StreamableHttp => BoxMakeWriter::new(std::io::stdout),
+        Transport::Stdio => BoxMakeWriter::new(std::io::stderr),
+    };
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "debug".to_string().into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_writer(log_writer))
         .init();
 
-    tracing::info!("Starting MCP RSpec server on {}", bind_address);
+    let mut env_vars = match &cli.env_file {
+        Some(path) => parse_env_file(path).map_err(anyhow::Error::msg)?,
+        None => Vec::new(),
+    };
+    env_vars.extend(cli.env_vars);
+    let settings = RunSettings::resolve(cli.preset, cli.require, env_vars);
+    if cli.format == StdoutFormat::Json && cli.stream_examples {
+        anyhow::bail!("--format json can't be used with --stream-examples, which needs the documentation formatter")
+    }
+    let mut format_args = settings.rspec_args();
+    format_args.extend(cli.format.rspec_args());
+    if cli.stream_examples {
+        format_args.extend(["-f".to_string(), "documentation".to_string()]);
+    }
 
-    let config = SseServerConfig {
-        bind: bind_address,
-        sse_path: "/sse".to_string(),
-        post_path: "/message".to_string(),
-        ct: tokio_util::sync::CancellationToken::new(),
-        sse_keep_alive: None,
+    let exec = ContainerExec::from_mode(cli.exec_mode, cli.service, cli.container).map_err(anyhow::Error::msg)?;
+    let ssh = match (cli.exec_mode, cli.ssh_host) {
+        (ExecMode::Ssh, Some(host)) => Some(SshTarget {
+            host,
+            user: cli.ssh_user,
+            port: cli.ssh_port,
+            identity_file: cli.ssh_key,
+            remote_root: cli.ssh_root,
+            local_root: workdirs.default_workdir().map(PathBuf::from),
+        }),
+        (ExecMode::Ssh, None) => anyhow::bail!("--exec-mode ssh needs --ssh-host, e.g. --ssh-host deploy@ci-box"),
+        _ => None,
+    };
+    if cli.spring != SpringMode::Off && cli.exec_mode != ExecMode::Local {
+        anyhow::bail!("--spring needs --exec-mode local; Spring is detected and run in the local workdir")
+    }
+    // Both load a script from the temp directory with --require
+    let temp_dir = std::env::temp_dir();
+    for (_, flag) in [(cli.rerun_failures, "--rerun-failures"), (cli.diagnose_load_errors, "--diagnose-load-errors")]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+    {
+        if ssh.is_some() {
+            anyhow::bail!("{} can't be used with --exec-mode ssh; its script is written locally, not on the remote host", flag)
+        }
+        if exec.is_some() && !cli.container_mounts.iter().any(|mount| mount.covers(&temp_dir)) {
+            anyhow::bail!(
+                "{} needs the temp directory mounted into the container, e.g. --container-mount {}:{}",
+                flag,
+                temp_dir.display(),
+                temp_dir.display()
+            )
+        }
+    }
+    if ssh.is_some() && isolated_home.is_some() {
+        anyhow::bail!("--isolated-home can't be used with --exec-mode ssh; the home directory would be created locally")
+    }
+    let process_tracker = ProcessTracker::new();
+    let run_metrics = RunMetrics::new();
+    let runner = RspecRunner::new(cli.rspec_cmd.clone())
+        .with_arg_order(cli.arg_order)
+        .with_process_tracker(process_tracker.clone())
+        .with_run_metrics(run_metrics.clone())
+        .with_format_args(format_args)
+        .with_env(settings.env.clone())
+        .with_env_clear(cli.env_clear)
+        .with_abort_on_output_bytes(cli.abort_on_output_bytes)
+        .with_sandbox_prefix(cli.sandbox_prefix.unwrap_or_default())
+        .with_workdir(workdirs.default_workdir().map(PathBuf::from))
+        .with_isolated_home(isolated_home.clone())
+        .with_spring(cli.spring)
+        .with_in_flight_limit(
+            (cli.max_in_flight > 0).then(|| {
+                InFlightLimit::new(cli.max_in_flight, cli.backpressure, cli.max_queued)
+                    .with_queue_timeout(cli.queue_timeout.map(Duration::from_secs))
+            }),
+        );
+    let remote = ssh.is_some();
+    let spring_runner = (cli.spring != SpringMode::Off).then(|| runner.clone());
+    let runner = match ssh {
+        Some(target) => {
+            tracing::info!("Running RSpec on {} over SSH", target.host);
+            runner.with_process_runner(Arc::new(SshProcessRunner::new(
+                target,
+                ProcessCommandRunner::new(process_tracker.clone()),
+            )))
+        }
+        None => runner,
+    };
+    let runner: Arc<dyn CommandRunner> = match exec {
+        Some(exec) => {
+            tracing::info!("Running RSpec in {:?}", exec);
+            Arc::new(
+                ContainerRunner::new(runner, exec, &cli.rspec_cmd)
+                    .with_mounts(cli.container_mounts)
+                    .with_env(settings.env.clone())
+                    .with_workdir(workdirs.default_workdir().map(PathBuf::from)),
+            )
+        }
+        None => Arc::new(runner),
     };
 
-    let (sse_server, router) = SseServer::new(config);
+    if let Some(command) = &cli.startup_command {
+        tracing::info!("Running startup command: {}", command);
+        let timeout = Duration::from_secs(cli.startup_timeout_secs);
+        match startup::run_startup_command(command, workdirs.default_workdir(), timeout).await {
+            Ok(output) => {
+                for line in output.lines() {
+                    tracing::info!(target: "startup", "{}", line);
+                }
+                tracing::info!("Startup command finished");
+            }
+            Err(e) if cli.startup_strict => anyhow::bail!(e),
+            Err(e) => tracing::warn!("{}", e),
+        }
+    }
 
-    let listener = tokio::net::TcpListener::bind(sse_server.config.bind).await?;
+    if let Some(spring_runner) = spring_runner {
+        tokio::spawn(async move {
+            match spring_runner.warm_up_spring().await {
+                Ok(()) => tracing::info!("Spring is warm; runs use bin/spring rspec"),
+                Err(reason) => tracing::warn!("Spring wasn't warmed up ({}); runs fall back to --rspec-cmd when it fails", reason),
+            }
+        });
+    }
 
-    let ct = sse_server.config.ct.child_token();
+    let versions = VersionCache::new();
+    match versions.get_or_detect(&runner).await {
+        Some(version) => tracing::info!("Detected RSpec {}", version),
+        None => tracing::warn!("Could not detect RSpec version; flag support will not be checked"),
+    }
 
-    let server = axum::serve(listener, router).with_graceful_shutdown(async move {
-        ct.cancelled().await;
-        tracing::info!("sse server cancelled");
+    let allow_admin_tools = cli.allow_admin_tools;
+    let stream_examples = cli.stream_examples;
+    let slowest_files = cli.slowest_files;
+    let auth = transport_auth.clone();
+    let diagnose_load_errors = cli.diagnose_load_errors;
+    let error_patterns = settings.error_patterns;
+    let normalize_newlines = cli.normalize_newlines;
+    let error_on_failure = cli.error_on_failure;
+    let git_blame = cli.enable_git_blame;
+    let coverage = cli.enable_coverage.then_some(CoverageConfig {
+        env: cli.coverage_env,
+        dir: cli.coverage_dir,
+    });
+    let create_spec = cli.enable_create_spec;
+    let junit_reports = cli.junit_reports;
+    let bisect_timeout = cli.enable_bisect.then_some(Duration::from_secs(cli.bisect_timeout_secs.max(1)));
+    let per_file_timeout = cli.per_file_timeout_secs.map(Duration::from_secs);
+    let json_format = cli.format == StdoutFormat::Json;
+    let rerun_failures = cli.rerun_failures;
+    let spec_listing = SpecListing::new(cli.spec_dir, cli.spec_excludes);
+    let allowed_paths = cli.allowed_paths;
+    let parallel = cli.parallel.map(|strategy| ParallelConfig {
+        strategy,
+        command: cli
+            .parallel_cmd
+            .as_deref()
+            .unwrap_or(strategy.default_command())
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+        processes: cli.parallel_processes,
     });
+    let watch_subscribers = cli.watch.then(WatchSubscribers::default);
+    // Held for the server's lifetime; watching stops when it drops
+    let _watcher = match &watch_subscribers {
+        Some(subscribers) => {
+            let config = WatchConfig {
+                root: match workdirs.default_workdir() {
+                    Some(root) => PathBuf::from(root),
+                    None => std::env::current_dir()?,
+                },
+                spec_dir: spec_listing.spec_dir().to_string(),
+                debounce: Duration::from_millis(cli.watch_debounce_ms),
+            };
+            tracing::info!("Watching {} for changes", config.root.display());
+            Some(spawn_watcher(runner.clone(), config, subscribers.clone())?)
+        }
+        None => None,
+    };
+    let reported_env = cli.report_env.then(|| settings.env.clone());
+    let metrics_tracker = process_tracker.clone();
+    let shutdown_tracker = process_tracker.clone();
+    let stop_runs = || {
+        shutdown::stop_runs(&shutdown_tracker, cli.shutdown_mode, Duration::from_secs(cli.shutdown_grace_secs))
+    };
+    let metrics = move || async move { format!("{}{}", metrics_tracker.render_metrics(), run_metrics.render_metrics()) };
+    let program = match remote {
+        true => "ssh".to_string(),
+        false => runner.command_line(&[], &RunContext::default()).into_iter().next().unwrap_or_default(),
+    };
+    let health = HealthCheck::new(program, workdirs.default_workdir().map(PathBuf::from));
+    let healthz = move || async move { health.report() };
+    // Held for the server's lifetime; reloading stops when it drops
+    let _config_watcher = match config_path {
+        Some(path) => {
+            tracing::info!("Reading settings from {}", path.display());
+            let (settings, auth, reloaded) = (session_settings.clone(), transport_auth.clone(), path.clone());
+            // Each reload is compared with the last, so a change is only warned about once
+            let last_loaded = std::sync::Mutex::new(config_file);
+            let reload = move || match ConfigFile::load(&reloaded) {
+                Ok(file) => {
+                    let mut last = last_loaded.lock().unwrap();
+                    if last.needs_restart(&file) {
+                        tracing::warn!(
+                            "{} changed settings that apply after a restart: rspec_cmd, default_workdir, allowed_workdirs, projects_file or abort_on_output_bytes",
+                            reloaded.display()
+                        );
+                    }
+                    let mut cli = flags.clone();
+                    apply_config_file(&mut cli, &file, &explicit);
+                    *settings.write().unwrap() = SessionSettings::from_cli(&cli);
+                    *auth.write().unwrap() = AuthPolicy::new(cli.auth_token.clone(), cli.auth_scope);
+                    tracing::info!("Reloaded {}", reloaded.display());
+                    *last = file;
+                }
+                Err(e) => tracing::warn!("{}; keeping the current settings", e),
+            };
+            Some(watch_config_file(&path, reload)?)
+        }
+        None => None,
+    };
+    let make_server = move || {
+        let session = session_settings.read().unwrap().clone();
+        RspecServer::new(runner.clone())
+            .with_admin_tools(allow_admin_tools)
+            .with_process_tracker(process_tracker.clone())
+            .with_version_cache(versions.clone())
+            .with_example_streaming(stream_examples)
+            .with_slowest_files(slowest_files)
+            .with_auth_policy(auth.read().unwrap().clone())
+            .with_load_diagnostics(diagnose_load_errors)
+            .with_run_defaults(session.run_defaults)
+            .with_error_patterns(error_patterns.clone())
+            .with_failure_webhook(failure_webhook.clone())
+            .with_workdir_policy(workdirs.clone())
+            .with_env_allowlist(session.env_allowlist)
+            .with_max_output_bytes(session.max_output_bytes)
+            .with_newline_normalization(normalize_newlines)
+            .with_error_on_failure(error_on_failure)
+            .with_env_report(reported_env.clone())
+            .with_git_blame(git_blame)
+            .with_coverage(coverage.clone())
+            .with_bisect(bisect_timeout)
+            .with_spec_scaffolding(create_spec)
+            .with_parallel(parallel.clone())
+            .with_watch_subscribers(watch_subscribers.clone())
+            .with_per_file_timeout(per_file_timeout)
+            .with_baseline(baseline.clone())
+            .with_response_budget(session.response_budget)
+            .with_json_format(json_format)
+            .with_rerun_failures(rerun_failures)
+            .with_spec_listing(spec_listing.clone())
+            .with_allowed_paths(allowed_paths.clone())
+            .with_suite_limits(session.suite_limits)
+            .with_projects(projects.clone())
+            .with_run_history(history.clone())
+            .with_junit_reports(junit_reports)
+    };
 
-    tokio::spawn(async move {
-        if let Err(e) = server.await {
-            tracing::error!(error = %e, "sse server shutdown with error");
+    let served = match cli.transport {
+        Transport::Sse => {
+            let bind_address: SocketAddr = format!("{}:{}", cli.hostname, cli.port).parse()?;
+            tracing::info!("Starting MCP RSpec server on {}", bind_address);
+
+            let config = SseServerConfig {
+                bind: bind_address,
+                sse_path: "/sse".to_string(),
+                post_path: "/message".to_string(),
+                ct: tokio_util::sync::CancellationToken::new(),
+                sse_keep_alive: None,
+            };
+
+            let (sse_server, router) = SseServer::new(config);
+            let router = guard_http(router, &sse_server.config.sse_path, cli.max_connections, transport_auth);
+
+            let router = router
+                .route("/metrics", axum::routing::get(metrics))
+                .route("/healthz", axum::routing::get(healthz));
+
+            let ct = sse_server.config.ct.child_token();
+            let retry_policy = RetryPolicy::new(cli.transport_retries);
+            let mut server = tokio::spawn(serve_with_retry(sse_server.config.bind, router, ct, retry_policy, tls));
+            let ct = sse_server.with_service(make_server);
+
+            tracing::info!("MCP RSpec server is running!");
+            tracing::info!("SSE endpoint: {}://{}/sse", scheme, bind_address);
+            tracing::info!("Message endpoint: {}://{}/message", scheme, bind_address);
+            tracing::info!("Metrics endpoint: {}://{}/metrics", scheme, bind_address);
+            tracing::info!("Health endpoint: {}://{}/healthz", scheme, bind_address);
+            tracing::info!("Press Ctrl+C to stop");
+
+            let served = tokio::select! {
+                signal = shutdown::signal() => {
+                    stop_runs().await;
+                    signal.map_err(anyhow::Error::from)
+                }
+                served = &mut server => match served {
+                    Ok(Ok(())) => Ok(()),
+                    Ok(Err(e)) => Err(anyhow::anyhow!("SSE transport failed after {} retries: {}", cli.transport_retries, e)),
+                    Err(e) => Err(e.into()),
+                },
+            };
+            ct.cancel();
+            served
         }
-    });
+        Transport::StreamableHttp => {
+            let bind_address: SocketAddr = format!("{}:{}", cli.hostname, cli.port).parse()?;
+            tracing::info!("Starting MCP RSpec server on {}", bind_address);
+
+            let service = StreamableHttpService::new(
+                move || Ok(make_server()),
+                LocalSessionManager::default().into(),
+                StreamableHttpServerConfig::default(),
+            );
+            let router = axum::Router::new().nest_service(MCP_PATH, service);
+            let router = guard_http(router, MCP_PATH, cli.max_connections, transport_auth)
+                .route("/metrics", axum::routing::get(metrics))
+                .route("/healthz", axum::routing::get(healthz));
 
-    let runner = RspecRunner::new(cli.rspec_cmd);
-    let ct = sse_server.with_service(move || RspecServer::new(runner.clone()));
+            let ct = tokio_util::sync::CancellationToken::new();
+            let retry_policy = RetryPolicy::new(cli.transport_retries);
+            let mut server = tokio::spawn(serve_with_retry(bind_address, router, ct.child_token(), retry_policy, tls));
 
-    tracing::info!("MCP RSpec server is running!");
-    tracing::info!("SSE endpoint: http://{}/sse", bind_address);
-    tracing::info!("Message endpoint: http://{}/message", bind_address);
-    tracing::info!("Press Ctrl+C to stop");
+            tracing::info!("MCP RSpec server is running!");
+            tracing::info!("MCP endpoint: {}://{}{}", scheme, bind_address, MCP_PATH);
+            tracing::info!("Metrics endpoint: {}://{}/metrics", scheme, bind_address);
+            tracing::info!("Health endpoint: {}://{}/healthz", scheme, bind_address);
+            tracing::info!("Press Ctrl+C to stop");
 
-    tokio::signal::ctrl_c().await?;
-    ct.cancel();
-    Ok(())
+            let served = tokio::select! {
+                signal = shutdown::signal() => {
+                    stop_runs().await;
+                    signal.map_err(anyhow::Error::from)
+                }
+                served = &mut server => match served {
+                    Ok(Ok(())) => Ok(()),
+                    Ok(Err(e)) => Err(anyhow::anyhow!("HTTP transport failed after {} retries: {}", cli.transport_retries, e)),
+                    Err(e) => Err(e.into()),
+                },
+            };
+            ct.cancel();
+            served
+        }
+        Transport::Stdio => {
+            tracing::info!("MCP RSpec server is running on stdio");
+            let service = make_server().serve(rmcp::transport::stdio()).await?;
+            tokio::select! {
+                signal = shutdown::signal() => {
+                    stop_runs().await;
+                    signal.map_err(anyhow::Error::from)
+                }
+                quit = service.waiting() => quit.map(|_| ()).map_err(anyhow::Error::from),
+            }
+        }
+    };
+    if let Some(isolated_home) = isolated_home {
+        isolated_home.cleanup();
+    }
+    served
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bool_flags_with_optional_values() {
+        let cli = Cli::try_parse_from(["mcp-rspec"]).unwrap();
+        assert!(cli.error_on_failure && cli.normalize_newlines && cli.startup_strict);
+
+        let cli = Cli::try_parse_from(["mcp-rspec", "--error-on-failure", "--normalize-newlines", "--startup-strict"]).unwrap();
+        assert!(cli.error_on_failure && cli.normalize_newlines && cli.startup_strict);
+
+        let cli = Cli::try_parse_from(["mcp-rspec", "--error-on-failure", "false", "--normalize-newlines=false"]).unwrap();
+        assert!(!cli.error_on_failure && !cli.normalize_newlines);
+    }
 }
@@ -1,11 +1,26 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
 use async_trait::async_trait;
-use crate::command_runner::{CommandResult, CommandRunner};
+use crate::command_runner::{CommandResult, CommandRunner, RunContext};
+use crate::rspec_version::RspecVersion;
+
+/// A recorded run: the command line and the context it was given.
+type Call = (Vec<String>, RunContext);
 
 #[derive(Clone)]
 pub struct MockRunner {
     exit_code: i32,
     stdout: String,
     stderr: String,
+    calls: Arc<Mutex<Vec<Call>>>,
+    version: Option<RspecVersion>,
+    abort_reason: Option<String>,
+    signal: Option<i32>,
+    peak_memory_mb: Option<f64>,
+    delays: HashMap<String, Duration>,
+    sequence: Arc<Mutex<VecDeque<(i32, String, String)>>>,
 }
 
 impl MockRunner {
@@ -14,6 +29,13 @@ impl MockRunner {
             exit_code: 0,
             stdout: "mock test output".to_string(),
             stderr: String::new(),
+            calls: Arc::default(),
+            version: None,
+            abort_reason: None,
+            signal: None,
+            peak_memory_mb: None,
+            delays: HashMap::new(),
+            sequence: Arc::default(),
         }
     }
 
@@ -23,17 +45,135 @@ impl MockRunner {
             exit_code,
             stdout,
             stderr,
+            calls: Arc::default(),
+            version: None,
+            abort_reason: None,
+            signal: None,
+            peak_memory_mb: None,
+            delays: HashMap::new(),
+            sequence: Arc::default(),
         }
     }
+
+    pub fn with_version(mut self, version: RspecVersion) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn with_abort_reason(mut self, reason: &str) -> Self {
+        self.abort_reason = Some(reason.to_string());
+        self
+    }
+
+    /// Every run reports being terminated by `signal`, with exit code -1.
+    pub fn with_signal(mut self, signal: i32) -> Self {
+        self.exit_code = -1;
+        self.signal = Some(signal);
+        self
+    }
+
+    /// Every run reports `mb` as its peak memory.
+    pub fn with_peak_memory(mut self, mb: f64) -> Self {
+        self.peak_memory_mb = Some(mb);
+        self
+    }
+
+    /// Runs including `path` take `delay`. A shorter `RunContext::timeout` ends
+    /// the run early with a timed-out result, like a killed process.
+    pub fn with_delay(mut self, path: &str, delay: Duration) -> Self {
+        self.delays.insert(path.to_string(), delay);
+        self
+    }
+
+    /// Results returned by successive runs, in order. Once used up, runs
+    /// fall back to the configured result.
+    pub fn with_sequence(self, results: Vec<(i32, String, String)>) -> Self {
+        *self.sequence.lock().unwrap() = results.into();
+        self
+    }
+
+    pub fn calls(&self) -> usize {
+        self.calls.lock().unwrap().len()
+    }
+
+    /// The command line of every run so far, in order.
+    pub fn recorded_commands(&self) -> Vec<Vec<String>> {
+        self.calls.lock().unwrap().iter().map(|(argv, _)| argv.clone()).collect()
+    }
+
+    /// The context of every run so far, in order.
+    pub fn recorded_contexts(&self) -> Vec<RunContext> {
+        self.calls.lock().unwrap().iter().map(|(_, context)| context.clone()).collect()
+    }
 }
 
 #[async_trait]
 impl CommandRunner for MockRunner {
-    async fn run(&self, _path: &str) -> Result<CommandResult, String> {
+    async fn run_paths(&self, paths: &[String], context: &RunContext) -> Result<CommandResult, String> {
+        self.calls
+            .lock()
+            .unwrap()
+            // Without the notices sender, which would keep its channel open
+            .push((self.command_line(paths, context), RunContext { notices: None, ..context.clone() }));
+        if let Some(delay) = paths.iter().find_map(|path| self.delays.get(path)) {
+            let killed = |reason: &str, timed_out| CommandResult {
+                exit_code: -1,
+                stdout: String::new(),
+                stderr: String::new(),
+                abort_reason: Some(reason.to_string()),
+                signal: Some(9),
+                timed_out,
+                cancelled: !timed_out,
+                peak_memory_mb: None,
+                launcher: None,
+            };
+            let cancelled = async {
+                match &context.cancel {
+                    Some(cancel) => cancel.cancelled().await,
+                    None => std::future::pending().await,
+                }
+            };
+            let timeout = context.timeout.filter(|timeout| timeout < delay);
+            tokio::select! {
+                _ = tokio::time::sleep(timeout.unwrap_or(*delay)) => {
+                    if timeout.is_some() {
+                        return Ok(killed("Timed out", true));
+                    }
+                }
+                _ = cancelled => return Ok(killed("Cancelled", false)),
+            }
+        }
+        let (exit_code, stdout, stderr) = self
+            .sequence
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| (self.exit_code, self.stdout.clone(), self.stderr.clone()));
         Ok(CommandResult {
-            exit_code: self.exit_code,
-            stdout: self.stdout.clone(),
-            stderr: self.stderr.clone(),
+            exit_code,
+            stdout,
+            stderr,
+            abort_reason: self.abort_reason.clone(),
+            signal: self.signal,
+            timed_out: false,
+            cancelled: false,
+            peak_memory_mb: self.peak_memory_mb,
+            launcher: None,
         })
     }
+
+    fn command_line(&self, paths: &[String], context: &RunContext) -> Vec<String> {
+        context
+            .command
+            .clone()
+            .unwrap_or_else(|| vec!["rspec".to_string()])
+            .into_iter()
+            .chain(context.extra_args.iter().cloned())
+            .chain(paths.iter().cloned())
+            .collect()
+    }
+
+    async fn detect_version(&self) -> Option<RspecVersion> {
+        self.version
+    }
 }
@@ -0,0 +1,172 @@
+use crate::command_runner::shell_join;
+use crate::rspec_result::{ReportSummary, RspecReport};
+
+/// A tool that splits a run across several RSpec processes.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ParallelStrategy {
+    /// `parallel_rspec` from the parallel_tests gem
+    ParallelTests,
+    /// `turbo_tests`
+    TurboTests,
+}
+
+impl ParallelStrategy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ParallelTests => "parallel_tests",
+            Self::TurboTests => "turbo_tests",
+        }
+    }
+
+    pub fn default_command(&self) -> &'static str {
+        match self {
+            Self::ParallelTests => "bundle exec parallel_rspec",
+            Self::TurboTests => "bundle exec turbo_tests",
+        }
+    }
+}
+
+/// How `--parallel` runs suites: the tool, its command, and the processes
+/// when a request doesn't ask for a number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParallelConfig {
+    pub strategy: ParallelStrategy,
+    pub command: Vec<String>,
+    /// The tool picks (one per CPU) when unset.
+    pub processes: Option<u32>,
+}
+
+/// A run split across processes, taken by the runner in place of a plain
+/// RSpec invocation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParallelRun {
+    pub strategy: ParallelStrategy,
+    pub processes: Option<u32>,
+}
+
+impl ParallelRun {
+    /// `command` running `paths` across processes, with the RSpec `options`
+    /// handed to each of them. parallel_tests takes them as a single
+    /// `--test-options` string and prints each process's output once it
+    /// finishes, so their JSON reports don't interleave; turbo_tests takes
+    /// them directly and writes one report.
+    pub fn argv(&self, command: &[String], options: &[String], paths: &[String]) -> Vec<String> {
+        let mut argv = command.to_vec();
+        if let Some(processes) = self.processes {
+            argv.extend(["-n".to_string(), processes.to_string()]);
+        }
+        match self.strategy {
+            ParallelStrategy::ParallelTests => {
+                argv.push("--serialize-stdout".to_string());
+                if !options.is_empty() {
+                    argv.extend(["--test-options".to_string(), shell_join(options)]);
+                }
+            }
+            ParallelStrategy::TurboTests => argv.extend(options.iter().cloned()),
+        }
+        argv.extend(paths.iter().cloned());
+        argv
+    }
+}
+
+/// Every JSON report in `output`, one per process, combined into one, and
+/// how many there were. The processes ran side by side, so the duration is
+/// the longest of theirs.
+pub fn combine_reports(output: &str) -> Option<(RspecReport, usize)> {
+    let reports: Vec<RspecReport> = output
+        .match_indices("{\"version\"")
+        .filter_map(|(start, _)| {
+            let mut stream = serde_json::Deserializer::from_str(&output[start..]).into_iter::<RspecReport>();
+            stream.next()?.ok()
+        })
+        .collect();
+    let count = reports.len();
+
+    let mut reports = reports.into_iter();
+    let mut combined = reports.next()?;
+    for report in reports {
+        combined.examples.extend(report.examples);
+        combined.seed = combined.seed.or(report.seed);
+        combined.summary = match (combined.summary, report.summary) {
+            (Some(a), Some(b)) => Some(ReportSummary {
+                duration: match (a.duration, b.duration) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    (a, b) => a.or(b),
+                },
+                example_count: a.example_count + b.example_count,
+                failure_count: a.failure_count + b.failure_count,
+                pending_count: a.pending_count + b.pending_count,
+                errors_outside_of_examples_count: a.errors_outside_of_examples_count
+                    + b.errors_outside_of_examples_count,
+            }),
+            (a, b) => a.or(b),
+        };
+    }
+    if count > 1 {
+        combined.summary_line = combined.summary.as_ref().map(summary_line);
+    }
+    Some((combined, count))
+}
+
+/// `12 examples, 1 failure, 2 pending`, as RSpec words it.
+fn summary_line(summary: &ReportSummary) -> String {
+    let plural = |count: u32, word: &str| format!("{} {}{}", count, word, if count == 1 { "" } else { "s" });
+    let mut line = format!(
+        "{}, {}",
+        plural(summary.example_count, "example"),
+        plural(summary.failure_count, "failure")
+    );
+    if summary.pending_count > 0 {
+        line.push_str(&format!(", {} pending", summary.pending_count));
+    }
+    if summary.errors_outside_of_examples_count > 0 {
+        line.push_str(&format!(
+            ", {} occurred outside of examples",
+            plural(summary.errors_outside_of_examples_count, "error")
+        ));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_argv() {
+        let run = ParallelRun { strategy: ParallelStrategy::ParallelTests, processes: Some(4) };
+        assert_eq!(
+            run.argv(&strings(&["bundle", "exec", "parallel_rspec"]), &strings(&["-f", "json", "--tag", "~js"]), &strings(&["spec"])),
+            strings(&["bundle", "exec", "parallel_rspec", "-n", "4", "--serialize-stdout", "--test-options", "-f json --tag '~js'", "spec"])
+        );
+
+        let run = ParallelRun { strategy: ParallelStrategy::TurboTests, processes: None };
+        assert_eq!(
+            run.argv(&strings(&["turbo_tests"]), &strings(&["-f", "json"]), &strings(&["spec/models"])),
+            strings(&["turbo_tests", "-f", "json", "spec/models"])
+        );
+    }
+
+    #[test]
+    fn test_combine_reports() {
+        let output = r#"{"version":"3.13.0","seed":42,"examples":[{"id":"./spec/a_spec.rb[1:1]","status":"failed","file_path":"./spec/a_spec.rb"}],"summary":{"duration":2.5,"example_count":1,"failure_count":1,"pending_count":0,"errors_outside_of_examples_count":0},"summary_line":"1 example, 1 failure"}
+{"version":"3.13.0","seed":42,"examples":[{"id":"./spec/b_spec.rb[1:1]","status":"passed"},{"id":"./spec/b_spec.rb[1:2]","status":"pending"}],"summary":{"duration":4.0,"example_count":2,"failure_count":0,"pending_count":1,"errors_outside_of_examples_count":0},"summary_line":"2 examples, 0 failures, 1 pending"}
+
+3 examples, 1 failure, 1 pending
+
+Took 5 seconds
+"#;
+        let (report, count) = combine_reports(output).unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(report.examples.len(), 3);
+        assert_eq!(report.seed, Some(42));
+        assert_eq!(report.summary.as_ref().unwrap().duration, Some(4.0));
+        assert_eq!(report.summary_line.as_deref(), Some("3 examples, 1 failure, 1 pending"));
+        assert!(combine_reports("3 examples, 0 failures").is_none());
+    }
+}
@@ -0,0 +1,243 @@
+use std::path::Path;
+
+/// Built-in defaults for common stacks, selected with `--preset`.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Preset {
+    /// `--require rails_helper`, `RAILS_ENV=test` and database error detection
+    Rails,
+    /// `--require spec_helper`
+    Plain,
+}
+
+/// Database errors that usually mean the test database isn't running or
+/// migrated, rather than a problem with the spec itself.
+const RAILS_ERROR_PATTERNS: &[&str] = &[
+    "ActiveRecord::ConnectionNotEstablished",
+    "ActiveRecord::NoDatabaseError",
+    "ActiveRecord::PendingMigrationError",
+    "PG::ConnectionBad",
+    "Mysql2::Error::ConnectionError",
+    "SQLite3::CantOpenException",
+];
+
+/// Settings for every run after applying a preset and explicit flags.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunSettings {
+    pub require: Option<String>,
+    pub env: Vec<(String, String)>,
+    pub error_patterns: Vec<String>,
+}
+
+impl RunSettings {
+    /// Starts from the preset's defaults; an explicit `require` replaces the
+    /// preset's, and explicit env vars replace preset values with the same key.
+    pub fn resolve(preset: Option<Preset>, require: Option<String>, env: Vec<(String, String)>) -> Self {
+        let mut settings = preset.map(Preset::settings).unwrap_or_default();
+
+        if require.is_some() {
+            settings.require = require;
+        }
+
+        for (key, value) in env {
+            settings.env.retain(|(k, _)| *k != key);
+            settings.env.push((key, value));
+        }
+
+        settings
+    }
+
+    /// RSpec options implied by these settings.
+    pub fn rspec_args(&self) -> Vec<String> {
+        match &self.require {
+            Some(require) => vec!["--require".to_string(), require.clone()],
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Preset {
+    fn settings(self) -> RunSettings {
+        match self {
+            Preset::Rails => RunSettings {
+                require: Some("rails_helper".to_string()),
+                env: vec![("RAILS_ENV".to_string(), "test".to_string())],
+                error_patterns: RAILS_ERROR_PATTERNS.iter().map(|p| p.to_string()).collect(),
+            },
+            Preset::Plain => RunSettings {
+                require: Some("spec_helper".to_string()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Parses a `KEY=VALUE` environment assignment.
+pub fn parse_env_var(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("Expected KEY=VALUE, got '{}'", value)),
+    }
+}
+
+/// Reads a dotenv-style file for `--env-file`: `KEY=VALUE` lines, with
+/// blank lines, `#` comments and an `export ` prefix skipped, and quotes
+/// around a value removed.
+pub fn parse_env_file(path: &Path) -> Result<Vec<(String, String)>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read env file {}: {}", path.display(), e))?;
+    parse_env_lines(&text).map_err(|e| format!("Invalid env file {}: {}", path.display(), e))
+}
+
+fn parse_env_lines(text: &str) -> Result<Vec<(String, String)>, String> {
+    let mut env = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = parse_env_var(line).map_err(|e| format!("line {}: {}", index + 1, e))?;
+        let value = value.trim();
+        let unquoted = ['"', '\''].iter().find_map(|quote| {
+            value
+                .strip_prefix(*quote)
+                .and_then(|value| value.strip_suffix(*quote))
+        });
+        env.push((key.trim().to_string(), unquoted.unwrap_or(value).to_string()));
+    }
+    Ok(env)
+}
+
+/// Key fragments whose values are redacted in environment reports.
+const SENSITIVE_KEY_PARTS: &[&str] = &["SECRET", "TOKEN", "PASSWORD", "PASSWD", "KEY", "CREDENTIAL", "AUTH"];
+
+/// Lists the environment the server applied to a run, one `KEY=VALUE` per
+/// line. Later assignments win, and sensitive values are redacted.
+pub fn format_env_report(env: &[(String, String)]) -> String {
+    let mut applied: Vec<(&str, &str)> = Vec::new();
+    for (key, value) in env {
+        match applied.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => applied.push((key, value)),
+        }
+    }
+
+    applied
+        .iter()
+        .map(|(key, value)| {
+            let upper = key.to_ascii_uppercase();
+            if SENSITIVE_KEY_PARTS.iter().any(|part| upper.contains(part)) {
+                format!("  {}=[REDACTED]", key)
+            } else {
+                format!("  {}={}", key, value)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The first configured error pattern found in the output, if any.
+pub fn detect_error_pattern<'a>(patterns: &'a [String], output: &str) -> Option<&'a str> {
+    patterns
+        .iter()
+        .find(|pattern| output.contains(pattern.as_str()))
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_env_report() {
+        let env = vec![
+            ("RAILS_ENV".to_string(), "test".to_string()),
+            ("API_TOKEN".to_string(), "abc123".to_string()),
+            ("RAILS_LOG_LEVEL".to_string(), "info".to_string()),
+            ("rails_master_key".to_string(), "shh".to_string()),
+            ("RAILS_LOG_LEVEL".to_string(), "debug".to_string()),
+        ];
+        assert_eq!(
+            format_env_report(&env),
+            "  RAILS_ENV=test\n  API_TOKEN=[REDACTED]\n  RAILS_LOG_LEVEL=debug\n  rails_master_key=[REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_rails_preset() {
+        let settings = RunSettings::resolve(Some(Preset::Rails), None, vec![]);
+
+        assert_eq!(settings.require.as_deref(), Some("rails_helper"));
+        assert_eq!(settings.env, vec![("RAILS_ENV".to_string(), "test".to_string())]);
+        assert!(settings.error_patterns.contains(&"PG::ConnectionBad".to_string()));
+        assert_eq!(settings.rspec_args(), vec!["--require", "rails_helper"]);
+    }
+
+    #[test]
+    fn test_plain_preset() {
+        let settings = RunSettings::resolve(Some(Preset::Plain), None, vec![]);
+
+        assert_eq!(settings.require.as_deref(), Some("spec_helper"));
+        assert!(settings.env.is_empty());
+        assert!(settings.error_patterns.is_empty());
+    }
+
+    #[test]
+    fn test_no_preset() {
+        let settings = RunSettings::resolve(None, None, vec![]);
+        assert_eq!(settings, RunSettings::default());
+        assert!(settings.rspec_args().is_empty());
+    }
+
+    #[test]
+    fn test_explicit_flags_override_preset() {
+        let settings = RunSettings::resolve(
+            Some(Preset::Rails),
+            Some("spec_helper".to_string()),
+            vec![
+                ("RAILS_ENV".to_string(), "ci".to_string()),
+                ("DISABLE_SPRING".to_string(), "1".to_string()),
+            ],
+        );
+
+        assert_eq!(settings.require.as_deref(), Some("spec_helper"));
+        assert_eq!(
+            settings.env,
+            vec![
+                ("RAILS_ENV".to_string(), "ci".to_string()),
+                ("DISABLE_SPRING".to_string(), "1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_env_var() {
+        assert_eq!(parse_env_var("RAILS_ENV=test").unwrap(), ("RAILS_ENV".to_string(), "test".to_string()));
+        assert_eq!(parse_env_var("EMPTY=").unwrap(), ("EMPTY".to_string(), String::new()));
+        assert!(parse_env_var("RAILS_ENV").is_err());
+        assert!(parse_env_var("=test").is_err());
+    }
+
+    #[test]
+    fn test_parse_env_lines() {
+        let text = "# test settings\nRAILS_ENV=test\n\nexport DATABASE_URL=\"postgres://localhost/app_test\"\nGREETING='hi there'\n";
+        assert_eq!(
+            parse_env_lines(text).unwrap(),
+            [
+                ("RAILS_ENV".to_string(), "test".to_string()),
+                ("DATABASE_URL".to_string(), "postgres://localhost/app_test".to_string()),
+                ("GREETING".to_string(), "hi there".to_string()),
+            ]
+        );
+        assert_eq!(parse_env_lines("A=1\nB\n").unwrap_err(), "line 2: Expected KEY=VALUE, got 'B'");
+    }
+
+    #[test]
+    fn test_detect_error_pattern() {
+        let settings = RunSettings::resolve(Some(Preset::Rails), None, vec![]);
+        let output = "An error occurred while loading ./spec/models/user_spec.rb.\nPG::ConnectionBad: could not connect to server";
+
+        assert_eq!(detect_error_pattern(&settings.error_patterns, output), Some("PG::ConnectionBad"));
+        assert_eq!(detect_error_pattern(&settings.error_patterns, "1 example, 1 failure"), None);
+    }
+}
@@ -0,0 +1,500 @@
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use crate::command_runner::CommandResult;
+use crate::process_tracker::ProcessTracker;
+
+/// How often a running process's peak memory is read.
+const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A fully resolved process to start: what `RspecRunner` hands to its
+/// `ProcessRunner` once paths, options and environment are worked out.
+#[derive(Debug, Clone, Default)]
+pub struct CommandSpec {
+    /// Program and arguments; never empty.
+    pub argv: Vec<String>,
+    /// Directory to run in; the server's working directory when unset.
+    pub workdir: Option<PathBuf>,
+    /// Applied in order, so later entries win.
+    pub env: Vec<(String, String)>,
+    /// Start from an empty environment, keeping only `PATH` unless `env`
+    /// sets it.
+    pub env_clear: bool,
+    /// Kill the process once it has run this long.
+    pub timeout: Option<Duration>,
+    /// Kill the process when this is cancelled.
+    pub cancel: Option<CancellationToken>,
+    /// Kill the process once stdout and stderr together exceed this many
+    /// bytes.
+    pub output_limit: Option<usize>,
+}
+
+/// Starts processes and collects their output.
+#[async_trait]
+pub trait ProcessRunner: Send + Sync {
+    /// Runs `spec` to completion, sending each stdout line to `lines` as it
+    /// is produced when given.
+    async fn spawn(&self, spec: &CommandSpec, lines: Option<UnboundedSender<String>>) -> Result<CommandResult, String>;
+}
+
+#[cfg(unix)]
+fn exit_signal(status: &std::process::ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &std::process::ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Kills the child and, on Unix, everything in its process group, so
+/// processes RSpec or a wrapper script started die with it instead of
+/// holding the output pipes open.
+#[cfg(unix)]
+fn kill_process_group(child: &mut tokio::process::Child) -> std::io::Result<()> {
+    if let Some(pid) = child.id() {
+        // SAFETY: kill(2) takes no pointers; the child leads its own group
+        unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+    }
+    child.start_kill()
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(child: &mut tokio::process::Child) -> std::io::Result<()> {
+    child.start_kill()
+}
+
+/// Kills the run's process group if the run is dropped before the child is
+/// reaped, e.g. when a client disconnect aborts the request's task;
+/// `kill_on_drop` alone only reaches the direct child.
+struct ProcessGroupGuard(Option<u32>);
+
+impl ProcessGroupGuard {
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.0 {
+            tracing::warn!(pid, "run dropped before the rspec process exited; killing its process group");
+            // SAFETY: kill(2) takes no pointers; the child leads its own group
+            unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+        }
+    }
+}
+
+/// The process's peak resident memory so far (`VmHWM`), in megabytes.
+/// It is gone once the process exits, so runs sample it while they wait.
+#[cfg(target_os = "linux")]
+fn peak_memory_mb(pid: u32) -> Option<f64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let kb: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb as f64 / 1024.0)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn peak_memory_mb(_pid: u32) -> Option<f64> {
+    None
+}
+
+/// Why `program` couldn't be started, with what to check.
+fn spawn_error(program: &str, error: std::io::Error) -> String {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => format!(
+            "Could not start `{}`: command not found. Check that it is installed and on the server's PATH (e.g. that `bundle` or `rspec` resolves), or set --rspec-cmd",
+            program
+        ),
+        std::io::ErrorKind::PermissionDenied => {
+            format!("Could not start `{}`: permission denied. Check that it is executable", program)
+        }
+        _ => format!("Could not start `{}`: {}", program, error),
+    }
+}
+
+/// Runs each spec as a real child process in its own process group,
+/// counted by the process tracker.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessCommandRunner {
+    process_tracker: ProcessTracker,
+}
+
+impl ProcessCommandRunner {
+    pub fn new(process_tracker: ProcessTracker) -> Self {
+        Self { process_tracker }
+    }
+}
+
+#[async_trait]
+impl ProcessRunner for ProcessCommandRunner {
+    async fn spawn(&self, spec: &CommandSpec, lines: Option<UnboundedSender<String>>) -> Result<CommandResult, String> {
+        let argv = &spec.argv;
+        let mut cmd = Command::new(&argv[0]);
+        if let Some(workdir) = &spec.workdir {
+            cmd.current_dir(workdir);
+        }
+        if spec.env_clear {
+            cmd.env_clear();
+            if let Some(path) = std::env::var_os("PATH") {
+                cmd.env("PATH", path);
+            }
+        }
+
+        // Its own process group, so a kill reaches the processes it starts
+        #[cfg(unix)]
+        cmd.process_group(0);
+        cmd.args(&argv[1..])
+            .envs(spec.env.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let mut child = cmd.spawn().map_err(|e| spawn_error(&argv[0], e))?;
+        let pid = child.id();
+        let ticket = self.process_tracker.spawned(pid);
+        let group = ProcessGroupGuard(pid);
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+
+        let limit = OutputLimit::new(spec.output_limit);
+        let mut timed_out = false;
+        let mut was_cancelled = false;
+        let mut peak_memory: Option<f64> = None;
+        let wait = async {
+            let deadline = async {
+                match spec.timeout {
+                    Some(timeout) => tokio::time::sleep(timeout).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::pin!(deadline);
+            let cancelled = async {
+                match &spec.cancel {
+                    Some(cancel) => cancel.cancelled().await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::pin!(cancelled);
+            let mut sampler = tokio::time::interval(MEMORY_SAMPLE_INTERVAL);
+            loop {
+                tokio::select! {
+                    status = child.wait() => break status,
+                    _ = limit.exceeded.cancelled() => {
+                        kill_process_group(&mut child)?;
+                        break child.wait().await;
+                    }
+                    _ = &mut deadline => {
+                        timed_out = true;
+                        kill_process_group(&mut child)?;
+                        break child.wait().await;
+                    }
+                    _ = &mut cancelled => {
+                        was_cancelled = true;
+                        kill_process_group(&mut child)?;
+                        break child.wait().await;
+                    }
+                    _ = sampler.tick() => {
+                        if let Some(mb) = pid.and_then(peak_memory_mb) {
+                            peak_memory = Some(peak_memory.map_or(mb, |peak| peak.max(mb)));
+                        }
+                    }
+                }
+            }
+        };
+
+        let (stdout, stderr, status) = tokio::join!(
+            read_lines(stdout, lines, &limit),
+            read_all(stderr, &limit),
+            wait
+        );
+        ticket.reaped();
+        group.disarm();
+
+        match (stdout, stderr, status) {
+            (Ok(stdout), Ok(stderr), Ok(status)) => Ok(CommandResult {
+                exit_code: status.code().unwrap_or(-1),
+                stdout,
+                stderr,
+                abort_reason: match (timed_out, spec.timeout) {
+                    (true, Some(timeout)) => Some(format!(
+                        "Timed out after {} seconds; the process was killed and the output is partial",
+                        timeout.as_secs_f64()
+                    )),
+                    _ if was_cancelled => {
+                        Some("Cancelled by the client; the process was killed and the output is partial".to_string())
+                    }
+                    _ => limit.abort_reason(),
+                },
+                signal: exit_signal(&status),
+                timed_out,
+                cancelled: was_cancelled,
+                peak_memory_mb: peak_memory,
+                launcher: None,
+            }),
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+                Err(format!("Command execution failed: {}", e))
+            }
+        }
+    }
+}
+
+/// Byte budget shared by the stdout and stderr readers. Unlike truncating
+/// the reported output, going over it stops the process.
+struct OutputLimit {
+    max: Option<usize>,
+    used: AtomicUsize,
+    exceeded: CancellationToken,
+}
+
+impl OutputLimit {
+    fn new(max: Option<usize>) -> Self {
+        Self {
+            max,
+            used: AtomicUsize::new(0),
+            exceeded: CancellationToken::new(),
+        }
+    }
+
+    /// Counts `bytes` of output, returning false once the budget is spent.
+    fn record(&self, bytes: usize) -> bool {
+        let Some(max) = self.max else {
+            return true;
+        };
+
+        let used = self.used.fetch_add(bytes, Ordering::SeqCst) + bytes;
+        if used > max {
+            self.exceeded.cancel();
+        }
+        used <= max
+    }
+
+    fn abort_reason(&self) -> Option<String> {
+        let max = self.max.filter(|_| self.exceeded.is_cancelled())?;
+        Some(format!(
+            "Output exceeded {} bytes (--abort-on-output-bytes); the process was killed and the output is partial",
+            max
+        ))
+    }
+}
+
+/// Reads a pipe to the end, forwarding each complete line as it arrives.
+async fn read_lines(
+    pipe: impl AsyncRead + Unpin,
+    lines: Option<UnboundedSender<String>>,
+    limit: &OutputLimit,
+) -> std::io::Result<String> {
+    let mut reader = BufReader::new(pipe);
+    let mut output = Vec::new();
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        if limit.exceeded.is_cancelled() || reader.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        if !limit.record(line.len()) {
+            break;
+        }
+        output.extend_from_slice(&line);
+
+        if let Some(lines) = &lines {
+            let text = String::from_utf8_lossy(&line);
+            let _ = lines.send(text.trim_end_matches(['\r', '\n']).to_string());
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&output).to_string())
+}
+
+async fn read_all(mut pipe: impl AsyncRead + Unpin, limit: &OutputLimit) -> std::io::Result<String> {
+    let mut output = Vec::new();
+    let mut buf = [0u8; 8192];
+
+    loop {
+        let n = pipe.read(&mut buf).await?;
+        if n == 0 || !limit.record(n) {
+            break;
+        }
+        output.extend_from_slice(&buf[..n]);
+    }
+
+    Ok(String::from_utf8_lossy(&output).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(argv: &[&str]) -> CommandSpec {
+        CommandSpec {
+            argv: argv.iter().map(|arg| arg.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spawn_with_env_clear() {
+        let runner = ProcessCommandRunner::default();
+        let mut spec = CommandSpec {
+            env: vec![("RAILS_ENV".to_string(), "test".to_string())],
+            env_clear: true,
+            ..spec(&["sh", "-c", "env"])
+        };
+
+        let result = runner.spawn(&spec, None).await.unwrap();
+        let mut keys: Vec<_> = result
+            .stdout
+            .lines()
+            .filter_map(|line| line.split_once('=').map(|(key, _)| key))
+            .filter(|key| !["PWD", "SHLVL", "_", "OLDPWD"].contains(key))
+            .collect();
+        keys.sort();
+        assert_eq!(keys, ["PATH", "RAILS_ENV"]);
+
+        spec.env.push(("PATH".to_string(), "/usr/bin:/bin".to_string()));
+        let result = runner.spawn(&spec, None).await.unwrap();
+        assert!(result.stdout.lines().any(|line| line == "PATH=/usr/bin:/bin"));
+    }
+
+    #[tokio::test]
+    async fn test_spawn_times_out() {
+        let runner = ProcessCommandRunner::default();
+        let timeout = Some(Duration::from_millis(100));
+
+        let result = runner.spawn(&CommandSpec { timeout, ..spec(&["sleep", "5"]) }, None).await.unwrap();
+        assert!(result.timed_out);
+        assert_eq!(
+            result.abort_reason.as_deref(),
+            Some("Timed out after 0.1 seconds; the process was killed and the output is partial")
+        );
+
+        let result = runner.spawn(&CommandSpec { timeout, ..spec(&["sleep", "0"]) }, None).await.unwrap();
+        assert!(!result.timed_out);
+        assert_eq!(result.abort_reason, None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_dropped_run_kills_process_group() {
+        let runner = ProcessCommandRunner::default();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let run = tokio::spawn(async move { runner.spawn(&spec(&["sh", "-c", "sleep 5 & echo $!; wait"]), Some(tx)).await });
+
+        // The shell's background `sleep`, which `kill_on_drop` doesn't reach
+        let pid: u32 = rx.recv().await.unwrap().parse().unwrap();
+        run.abort();
+        let _ = run.await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+        let state = stat.rsplit(')').next().unwrap_or_default().trim_start().chars().next();
+        assert!(matches!(state, None | Some('Z') | Some('X')), "sleep {} still running: {}", pid, stat);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_process_group() {
+        let runner = ProcessCommandRunner::default();
+        let spec = CommandSpec {
+            timeout: Some(Duration::from_millis(200)),
+            ..spec(&["sh", "-c", "echo started; sleep 5; echo finished"])
+        };
+
+        // `sleep` is the shell's child and holds stdout open until killed
+        let started = std::time::Instant::now();
+        let result = runner.spawn(&spec, None).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert!(result.timed_out);
+        assert_eq!(result.stdout, "started\n");
+        assert_eq!(runner.process_tracker.stats().active, 0);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_missing_command() {
+        let runner = ProcessCommandRunner::default();
+
+        let err = runner.spawn(&spec(&["mcp-rspec-no-such-command", "exec", "rspec"]), None).await.unwrap_err();
+        assert!(err.starts_with("Could not start `mcp-rspec-no-such-command`: command not found. "));
+    }
+
+    #[tokio::test]
+    async fn test_spawn_reports_signal() {
+        let runner = ProcessCommandRunner::default();
+
+        let result = runner.spawn(&spec(&["sh", "-c", "kill -9 $$"]), None).await.unwrap();
+        assert_eq!(result.signal, Some(9));
+        assert_eq!(result.exit_code, -1);
+
+        let result = runner.spawn(&spec(&["sh", "-c", "exit 3"]), None).await.unwrap();
+        assert_eq!(result.signal, None);
+        assert_eq!(result.exit_code, 3);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_reports_peak_memory() {
+        let runner = ProcessCommandRunner::default();
+
+        let result = runner.spawn(&spec(&["sleep", "0.2"]), None).await.unwrap();
+        if cfg!(target_os = "linux") {
+            assert!(result.peak_memory_mb.is_some_and(|mb| mb > 0.0));
+        } else {
+            assert_eq!(result.peak_memory_mb, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spawn_aborts_on_output_bytes() {
+        // `yes` streams its argument forever, like a runaway `puts` loop
+        let runner = ProcessCommandRunner::default();
+        let spec = CommandSpec { output_limit: Some(4096), ..spec(&["yes", "spec/models/user_spec.rb"]) };
+
+        let result = runner.spawn(&spec, None).await.unwrap();
+        assert!(result.stdout.len() <= 4096);
+        assert!(result.stdout.starts_with("spec/models/user_spec.rb\n"));
+        assert_eq!(
+            result.abort_reason.as_deref(),
+            Some("Output exceeded 4096 bytes (--abort-on-output-bytes); the process was killed and the output is partial")
+        );
+        assert_eq!(runner.process_tracker.stats().active, 0);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_within_output_limit() {
+        let runner = ProcessCommandRunner::default();
+        let spec = CommandSpec { output_limit: Some(4096), ..spec(&["echo", "spec/models/user_spec.rb"]) };
+
+        let result = runner.spawn(&spec, None).await.unwrap();
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(result.abort_reason, None);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_forwards_stdout_lines() {
+        let runner = ProcessCommandRunner::default();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let result = runner.spawn(&spec(&["echo", "spec/models/user_spec.rb"]), Some(tx)).await.unwrap();
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(result.stdout, "spec/models/user_spec.rb\n");
+        assert_eq!(rx.recv().await, Some("spec/models/user_spec.rb".to_string()));
+        assert_eq!(rx.recv().await, None);
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+/// Counts spawned RSpec child processes so leaks show up in metrics.
+///
+/// A process counts as active from spawn until it has been waited on. A
+/// [`SpawnTicket`] dropped without being reaped (e.g. the request future was
+/// abandoned mid-run) leaves the count raised, which is exactly the leak this
+/// is meant to surface.
+///
+/// It also keeps the pids of the processes still running, each leading its
+/// own process group, so shutdown can wait for or signal them.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessTracker {
+    inner: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    spawned: AtomicU64,
+    reaped: AtomicU64,
+    running: Mutex<HashSet<u32>>,
+    exited: Notify,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcessStats {
+    pub spawned: u64,
+    pub reaped: u64,
+    pub active: u64,
+}
+
+impl ProcessTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawned(&self, pid: Option<u32>) -> SpawnTicket {
+        self.inner.spawned.fetch_add(1, Ordering::SeqCst);
+        if let Some(pid) = pid {
+            self.inner.running.lock().unwrap().insert(pid);
+        }
+        let stats = self.stats();
+        tracing::debug!(?pid, active = stats.active, "spawned rspec process");
+
+        SpawnTicket {
+            tracker: self.clone(),
+            pid,
+            reaped: false,
+        }
+    }
+
+    pub fn stats(&self) -> ProcessStats {
+        let spawned = self.inner.spawned.load(Ordering::SeqCst);
+        let reaped = self.inner.reaped.load(Ordering::SeqCst);
+
+        ProcessStats {
+            spawned,
+            reaped,
+            active: spawned.saturating_sub(reaped),
+        }
+    }
+
+    /// Processes that haven't exited yet.
+    pub fn running(&self) -> usize {
+        self.inner.running.lock().unwrap().len()
+    }
+
+    /// Waits up to `timeout` for every running process to exit, returning
+    /// whether they all did.
+    pub async fn wait_for_exit(&self, timeout: Duration) -> bool {
+        let exited = async {
+            loop {
+                let notified = self.inner.exited.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                if self.running() == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        };
+        tokio::time::timeout(timeout, exited).await.is_ok()
+    }
+
+    /// Sends `signal` to the process group of every running process.
+    #[cfg(unix)]
+    pub fn signal_all(&self, signal: libc::c_int) {
+        for pid in self.inner.running.lock().unwrap().iter() {
+            // SAFETY: kill(2) takes no pointers; each process leads its own group
+            unsafe { libc::kill(-(*pid as libc::pid_t), signal) };
+        }
+    }
+
+    pub fn render_metrics(&self) -> String {
+        let stats = self.stats();
+        format!(
+            "# HELP mcp_rspec_child_processes_active RSpec child processes spawned but not yet reaped.\n\
+             # TYPE mcp_rspec_child_processes_active gauge\n\
+             mcp_rspec_child_processes_active {}\n\
+             # HELP mcp_rspec_child_processes_spawned_total RSpec child processes spawned.\n\
+             # TYPE mcp_rspec_child_processes_spawned_total counter\n\
+             mcp_rspec_child_processes_spawned_total {}\n\
+             # HELP mcp_rspec_child_processes_reaped_total RSpec child processes reaped.\n\
+             # TYPE mcp_rspec_child_processes_reaped_total counter\n\
+             mcp_rspec_child_processes_reaped_total {}\n",
+            stats.active, stats.spawned, stats.reaped
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct SpawnTicket {
+    tracker: ProcessTracker,
+    pid: Option<u32>,
+    reaped: bool,
+}
+
+impl SpawnTicket {
+    pub fn reaped(mut self) {
+        self.reaped = true;
+        self.tracker.inner.reaped.fetch_add(1, Ordering::SeqCst);
+        let stats = self.tracker.stats();
+        tracing::debug!(pid = ?self.pid, active = stats.active, "reaped rspec process");
+    }
+}
+
+impl Drop for SpawnTicket {
+    fn drop(&mut self) {
+        // An unreaped process's group is killed as its run is dropped
+        if let Some(pid) = self.pid {
+            self.tracker.inner.running.lock().unwrap().remove(&pid);
+        }
+        self.tracker.inner.exited.notify_waiters();
+        if !self.reaped {
+            let stats = self.tracker.stats();
+            tracing::warn!(
+                pid = ?self.pid,
+                active = stats.active,
+                "rspec process was not reaped and may have leaked"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawn_and_reap() {
+        let tracker = ProcessTracker::new();
+
+        let first = tracker.spawned(Some(100));
+        let second = tracker.spawned(Some(101));
+        assert_eq!(tracker.stats().active, 2);
+
+        first.reaped();
+        assert_eq!(
+            tracker.stats(),
+            ProcessStats {
+                spawned: 2,
+                reaped: 1,
+                active: 1,
+            }
+        );
+
+        second.reaped();
+        assert_eq!(tracker.stats().active, 0);
+    }
+
+    #[test]
+    fn test_dropped_ticket_counts_as_leaked() {
+        let tracker = ProcessTracker::new();
+
+        drop(tracker.spawned(Some(100)));
+        tracker.spawned(None).reaped();
+
+        assert_eq!(
+            tracker.stats(),
+            ProcessStats {
+                spawned: 2,
+                reaped: 1,
+                active: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_clones_share_counters() {
+        let tracker = ProcessTracker::new();
+        let clone = tracker.clone();
+
+        let ticket = clone.spawned(None);
+        assert_eq!(tracker.stats().active, 1);
+
+        ticket.reaped();
+        assert_eq!(tracker.stats().active, 0);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_exit() {
+        let tracker = ProcessTracker::new();
+        assert!(tracker.wait_for_exit(Duration::ZERO).await);
+
+        let ticket = tracker.spawned(Some(100));
+        assert_eq!(tracker.running(), 1);
+        assert!(!tracker.wait_for_exit(Duration::from_millis(10)).await);
+
+        let waiting = tokio::spawn({
+            let tracker = tracker.clone();
+            async move { tracker.wait_for_exit(Duration::from_secs(5)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        ticket.reaped();
+        assert!(waiting.await.unwrap());
+        assert_eq!(tracker.running(), 0);
+    }
+
+    #[test]
+    fn test_render_metrics() {
+        let tracker = ProcessTracker::new();
+        drop(tracker.spawned(None));
+
+        let metrics = tracker.render_metrics();
+        assert!(metrics.contains("mcp_rspec_child_processes_active 1\n"));
+        assert!(metrics.contains("mcp_rspec_child_processes_spawned_total 1\n"));
+        assert!(metrics.contains("mcp_rspec_child_processes_reaped_total 0\n"));
+    }
+}
@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::workdir::WorkdirPolicy;
+
+/// A project from `--projects-file`. Requests naming it run inside its root,
+/// with its own RSpec command when it sets one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    pub name: String,
+    pub workdirs: WorkdirPolicy,
+    pub command: Option<Vec<String>>,
+}
+
+impl Project {
+    pub fn root(&self) -> &Path {
+        self.workdirs.default_workdir().expect("project roots are always set")
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectsFile {
+    projects: Vec<ProjectEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectEntry {
+    name: String,
+    root: PathBuf,
+    #[serde(default)]
+    command: Option<String>,
+}
+
+/// The projects a request may pick with its `project` argument.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Projects {
+    projects: Vec<Project>,
+}
+
+impl Projects {
+    /// Reads a JSON file of the form
+    /// `{"projects": [{"name": "billing", "root": "../billing", "command": "bin/rspec"}]}`.
+    /// Relative roots are resolved against the file's directory, and must
+    /// exist.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read projects file {}: {}", path.display(), e))?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        Self::parse(&text, base).map_err(|e| format!("Invalid projects file {}: {}", path.display(), e))
+    }
+
+    fn parse(text: &str, base: &Path) -> Result<Self, String> {
+        let file: ProjectsFile = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let mut projects: Vec<Project> = Vec::new();
+
+        for entry in file.projects {
+            let name = entry.name.trim().to_string();
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)) {
+                return Err(format!("project name '{}' must be letters, digits, '-', '_' or '.'", entry.name));
+            }
+            if projects.iter().any(|project| project.name == name) {
+                return Err(format!("project '{}' is declared twice", name));
+            }
+            let command = match entry.command {
+                Some(command) if command.trim().is_empty() => {
+                    return Err(format!("project '{}' has an empty command", name));
+                }
+                command => command.map(|command| command.split_whitespace().map(str::to_string).collect()),
+            };
+            let workdirs = WorkdirPolicy::new(Some(base.join(entry.root)), Vec::new())
+                .map_err(|e| format!("project '{}': {}", name, e))?;
+
+            projects.push(Project { name, workdirs, command });
+        }
+
+        Ok(Self { projects })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.projects.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Project> {
+        self.projects.iter()
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Project, String> {
+        let name = name.trim();
+        self.projects.iter().find(|project| project.name == name).ok_or_else(|| match self.is_empty() {
+            true => format!("Unknown project '{}'; no projects are configured (--projects-file)", name),
+            false => format!(
+                "Unknown project '{}'; known projects: {}",
+                name,
+                self.projects.iter().map(|project| project.name.as_str()).collect::<Vec<_>>().join(", ")
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).canonicalize().unwrap()
+    }
+
+    #[test]
+    fn test_parse_projects() {
+        let text = r#"{"projects": [
+            {"name": "billing", "root": "src", "command": "bin/rspec --no-color"},
+            {"name": "storefront", "root": "."}
+        ]}"#;
+        let projects = Projects::parse(text, &project_dir()).unwrap();
+
+        let billing = projects.get("billing").unwrap();
+        assert_eq!(billing.root(), project_dir().join("src"));
+        assert_eq!(billing.command, Some(vec!["bin/rspec".to_string(), "--no-color".to_string()]));
+        assert_eq!(projects.get(" storefront ").unwrap().command, None);
+        assert_eq!(
+            projects.get("admin").unwrap_err(),
+            "Unknown project 'admin'; known projects: billing, storefront"
+        );
+    }
+
+    #[test]
+    fn test_invalid_projects_rejected() {
+        let parse = |text: &str| Projects::parse(text, &project_dir()).unwrap_err();
+
+        assert_eq!(
+            parse(r#"{"projects": [{"name": "a", "root": "."}, {"name": "a", "root": "src"}]}"#),
+            "project 'a' is declared twice"
+        );
+        assert_eq!(
+            parse(r#"{"projects": [{"name": "a b", "root": "."}]}"#),
+            "project name 'a b' must be letters, digits, '-', '_' or '.'"
+        );
+        assert_eq!(
+            parse(r#"{"projects": [{"name": "a", "root": ".", "command": " "}]}"#),
+            "project 'a' has an empty command"
+        );
+        assert!(parse(r#"{"projects": [{"name": "a", "root": "no-such-dir"}]}"#).starts_with("project 'a': Working directory"));
+        assert!(parse(r#"{"projects": [{"name": "a", "root": ".", "cmd": "rspec"}]}"#).contains("unknown field"));
+    }
+
+    #[test]
+    fn test_no_projects() {
+        assert_eq!(
+            Projects::default().get("billing").unwrap_err(),
+            "Unknown project 'billing'; no projects are configured (--projects-file)"
+        );
+    }
+}
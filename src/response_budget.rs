@@ -0,0 +1,71 @@
+/// How much of a run's result is kept to stay within
+/// `--response-budget-bytes`, from most to least detailed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DetailLevel {
+    /// Everything, including the captured output
+    Full,
+    /// The status lines and parsed failures
+    FailuresOnly,
+    /// Only the status lines
+    SummaryOnly,
+}
+
+impl DetailLevel {
+    const ALL: [DetailLevel; 3] = [DetailLevel::Full, DetailLevel::FailuresOnly, DetailLevel::SummaryOnly];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DetailLevel::Full => "full",
+            DetailLevel::FailuresOnly => "failures_only",
+            DetailLevel::SummaryOnly => "summary_only",
+        }
+    }
+}
+
+/// Renders the most detailed level whose text, serialized as a JSON
+/// string, fits in `budget` bytes. Summary-only is used if nothing fits.
+pub fn fit_to_budget(budget: usize, mut render: impl FnMut(DetailLevel) -> String) -> String {
+    let mut text = String::new();
+    for level in DetailLevel::ALL {
+        text = render(level);
+        if serialized_len(&text) <= budget {
+            break;
+        }
+    }
+    text
+}
+
+fn serialized_len(text: &str) -> usize {
+    serde_json::to_string(text).map_or(text.len(), |json| json.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(level: DetailLevel) -> String {
+        match level {
+            DetailLevel::Full => "x".repeat(100),
+            DetailLevel::FailuresOnly => "x".repeat(50),
+            DetailLevel::SummaryOnly => "x".repeat(20),
+        }
+    }
+
+    #[test]
+    fn test_keeps_full_detail_within_budget() {
+        assert_eq!(fit_to_budget(102, render).len(), 100);
+    }
+
+    #[test]
+    fn test_steps_down_until_it_fits() {
+        assert_eq!(fit_to_budget(101, render).len(), 50);
+        assert_eq!(fit_to_budget(40, render).len(), 20);
+        assert_eq!(fit_to_budget(1, render).len(), 20);
+    }
+
+    #[test]
+    fn test_counts_json_escaping() {
+        let text = "\n".repeat(10);
+        assert_eq!(serialized_len(&text), 22);
+    }
+}
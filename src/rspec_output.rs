@@ -0,0 +1,1757 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::command_runner::CommandResult;
+
+const LOAD_ERROR_PREFIX: &str = "An error occurred while loading ";
+
+/// Top-level lines that end the example listing in documentation output.
+const DOCUMENTATION_TRAILERS: &[&str] = &["Failures:", "Pending:", "Finished in ", "Failed examples:"];
+
+/// Top-level informational lines that can precede the example listing.
+const DOCUMENTATION_HEADERS: &[&str] = &["Run options:", "Randomized with seed", "No examples found"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunStatus {
+    Passed,
+    Failed,
+    SpecSyntaxError {
+        file: String,
+        line: Option<u32>,
+        message: String,
+    },
+    LoadError {
+        file: String,
+        message: String,
+    },
+    /// Killed by a signal, e.g. a crash (SIGSEGV) or the OOM killer (SIGKILL).
+    Terminated {
+        signal: i32,
+    },
+    /// Exited non-zero with nothing on stdout but an error on stderr: the
+    /// command (`bundle`, a binstub) failed before RSpec ran, e.g. a missing
+    /// Gemfile or uninstalled gems. `reason` is the first stderr line.
+    NotStarted {
+        exit_code: i32,
+        reason: String,
+    },
+}
+
+/// Longest stderr line kept as a [`RunStatus::NotStarted`] reason, in
+/// characters.
+const NOT_STARTED_REASON_MAX_CHARS: usize = 200;
+
+impl RunStatus {
+    pub fn classify(result: &CommandResult) -> Self {
+        if let Some(signal) = result.signal {
+            return RunStatus::Terminated { signal };
+        }
+
+        if let Some(status) = Self::detect_load_error(&result.stdout)
+            .or_else(|| Self::detect_load_error(&result.stderr))
+        {
+            return status;
+        }
+
+        let reason = result.stderr.lines().map(str::trim).find(|line| !line.is_empty());
+        if result.exit_code != 0
+            && result.stdout.trim().is_empty()
+            && result.abort_reason.is_none()
+            && let Some(reason) = reason
+        {
+            return RunStatus::NotStarted {
+                exit_code: result.exit_code,
+                reason: reason.chars().take(NOT_STARTED_REASON_MAX_CHARS).collect(),
+            };
+        }
+
+        if result.exit_code == 0 {
+            RunStatus::Passed
+        } else {
+            RunStatus::Failed
+        }
+    }
+
+    /// Short snake_case name, e.g. for listing the outcome of each attempt.
+    pub fn label(&self) -> &'static str {
+        match self {
+            RunStatus::Passed => "passed",
+            RunStatus::Failed => "failed",
+            RunStatus::SpecSyntaxError { .. } => "spec_syntax_error",
+            RunStatus::LoadError { .. } => "load_error",
+            RunStatus::Terminated { .. } => "terminated",
+            RunStatus::NotStarted { .. } => "not_started",
+        }
+    }
+
+    /// What the run means for the caller. RSpec exits 1 when examples fail,
+    /// so any other non-zero code is RSpec itself crashing or refusing to run.
+    pub fn outcome(&self, exit_code: i32) -> String {
+        match self {
+            RunStatus::Passed => "passed".to_string(),
+            RunStatus::Failed if exit_code == 1 => "tests failed".to_string(),
+            RunStatus::Failed => format!(
+                "RSpec crashed or could not run (exit code {}); these are not test failures",
+                exit_code
+            ),
+            RunStatus::SpecSyntaxError { .. } | RunStatus::LoadError { .. } => {
+                "RSpec could not load the specs; no examples ran".to_string()
+            }
+            RunStatus::Terminated { signal } => {
+                format!("RSpec was killed by signal {} before finishing; results are incomplete", signal)
+            }
+            RunStatus::NotStarted { exit_code, .. } => format!(
+                "The RSpec command failed before any specs ran (exit code {}); this is a setup problem, not a test failure",
+                exit_code
+            ),
+        }
+    }
+
+    pub fn is_load_failure(&self) -> bool {
+        matches!(self, RunStatus::SpecSyntaxError { .. } | RunStatus::LoadError { .. })
+    }
+
+    /// Looks for an exception raised while RSpec was loading a spec file,
+    /// reporting `SyntaxError`s separately from other load failures.
+    ///
+    /// Exceptions raised inside examples are reported under numbered
+    /// `Failure/Error:` entries instead, so they never match here.
+    fn detect_load_error(output: &str) -> Option<Self> {
+        let mut lines = output.lines().peekable();
+
+        while let Some(line) = lines.next() {
+            let Some(loaded) = line.trim().strip_prefix(LOAD_ERROR_PREFIX) else {
+                continue;
+            };
+            let file = loaded.trim_end_matches('.').to_string();
+
+            let mut exception = None;
+            let mut first_detail = None;
+
+            while let Some(line) = lines.next_if(|l| !l.trim().starts_with(LOAD_ERROR_PREFIX)) {
+                let trimmed = line.trim();
+
+                let Some(class) = &exception else {
+                    exception = Self::parse_exception_class(trimmed);
+                    continue;
+                };
+
+                if trimmed.is_empty() || trimmed.starts_with("-->") {
+                    continue;
+                }
+
+                if class == "SyntaxError"
+                    && let Some((line_number, message)) = Self::parse_location(trimmed)
+                {
+                    return Some(RunStatus::SpecSyntaxError {
+                        file,
+                        line: Some(line_number),
+                        message,
+                    });
+                }
+
+                if trimmed.starts_with('#') {
+                    break;
+                }
+
+                first_detail.get_or_insert_with(|| trimmed.to_string());
+            }
+
+            match exception.as_deref() {
+                Some("SyntaxError") => {
+                    return Some(RunStatus::SpecSyntaxError {
+                        file,
+                        line: None,
+                        message: first_detail.unwrap_or_else(|| "syntax error".to_string()),
+                    });
+                }
+                Some(class) => {
+                    let message = match first_detail {
+                        Some(detail) => format!("{}: {}", class, detail),
+                        None => class.to_string(),
+                    };
+                    return Some(RunStatus::LoadError { file, message });
+                }
+                None => {}
+            }
+        }
+
+        None
+    }
+
+    /// Matches the `NameError:` style line RSpec prints above an exception's
+    /// message.
+    fn parse_exception_class(line: &str) -> Option<String> {
+        let class = line.strip_suffix(':')?;
+        let valid = class.starts_with(|c: char| c.is_ascii_uppercase())
+            && class.chars().all(|c| c.is_ascii_alphanumeric() || c == ':' || c == '_');
+
+        valid.then(|| class.to_string())
+    }
+
+    /// Parses `path/to/file.rb:12: message` into the line number and message.
+    fn parse_location(line: &str) -> Option<(u32, String)> {
+        let (path, rest) = line.split_once(".rb:")?;
+        if path.is_empty() {
+            return None;
+        }
+
+        let (line_number, message) = rest.split_once(':')?;
+        let line_number = line_number.parse().ok()?;
+
+        Some((line_number, message.trim().to_string()))
+    }
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunStatus::Passed => write!(f, "Passed"),
+            RunStatus::Failed => write!(f, "Failed"),
+            RunStatus::SpecSyntaxError {
+                file,
+                line,
+                message,
+            } => match line {
+                Some(line) => write!(f, "SpecSyntaxError in {}:{}: {}", file, line, message),
+                None => write!(f, "SpecSyntaxError in {}: {}", file, message),
+            },
+            RunStatus::LoadError { file, message } => {
+                write!(f, "LoadError in {}: {}", file, message)
+            }
+            RunStatus::Terminated { signal } => write!(f, "Terminated by signal {}", signal),
+            RunStatus::NotStarted { exit_code, reason } => {
+                write!(f, "NotStarted (exit code {}): {}", exit_code, reason)
+            }
+        }
+    }
+}
+
+/// Counts and timings from the end of RSpec's human-readable output.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunSummary {
+    pub examples: u32,
+    pub failures: u32,
+    pub pending: u32,
+    pub errors_outside_examples: u32,
+    /// Seconds spent running examples, from the `Finished in` line.
+    pub run_time: Option<f64>,
+    /// Seconds spent loading spec files, from `(files took ... to load)`.
+    pub load_time: Option<f64>,
+    /// Seed from `Randomized with seed N`, when examples ran in random order.
+    pub seed: Option<u64>,
+}
+
+impl RunSummary {
+    /// Parses the `Finished in ...` and `N examples, M failures` lines.
+    /// Returns `None` if neither is present.
+    pub fn parse(output: &str) -> Option<Self> {
+        let mut summary = RunSummary::default();
+        let mut found = false;
+
+        for line in output.lines().map(str::trim) {
+            if let Some(rest) = line.strip_prefix("Finished in ") {
+                let (run, load) = match rest.split_once(" (files took ") {
+                    Some((run, load)) => (run, Some(load.trim_end_matches(" to load)"))),
+                    None => (rest, None),
+                };
+                summary.run_time = parse_duration(run);
+                summary.load_time = load.and_then(parse_duration);
+                found = true;
+            } else if let Some(seed) = line.strip_prefix("Randomized with seed ") {
+                summary.seed = seed.trim().parse().ok();
+            } else if Self::parse_counts(line, &mut summary) {
+                found = true;
+            }
+        }
+
+        found.then_some(summary)
+    }
+
+    fn parse_counts(line: &str, summary: &mut RunSummary) -> bool {
+        let mut counts = RunSummary::default();
+        let mut parts = line.split(", ");
+
+        let Some((examples, "example" | "examples")) = parts.next().and_then(|p| p.split_once(' ')) else {
+            return false;
+        };
+        let Ok(examples) = examples.parse() else {
+            return false;
+        };
+        counts.examples = examples;
+
+        for part in parts {
+            let Some((count, label)) = part.split_once(' ') else {
+                continue;
+            };
+            let Ok(count) = count.parse() else {
+                continue;
+            };
+
+            if label.starts_with("failure") {
+                counts.failures = count;
+            } else if label == "pending" {
+                counts.pending = count;
+            } else if label.starts_with("error") {
+                counts.errors_outside_examples = count;
+            }
+        }
+
+        summary.examples = counts.examples;
+        summary.failures = counts.failures;
+        summary.pending = counts.pending;
+        summary.errors_outside_examples = counts.errors_outside_examples;
+        true
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} example{}, {} failure{}",
+            self.examples,
+            if self.examples == 1 { "" } else { "s" },
+            self.failures,
+            if self.failures == 1 { "" } else { "s" }
+        )?;
+
+        if self.pending > 0 {
+            write!(f, ", {} pending", self.pending)?;
+        }
+        if self.errors_outside_examples > 0 {
+            write!(f, ", {} error(s) outside of examples", self.errors_outside_examples)?;
+        }
+
+        match (self.run_time, self.load_time) {
+            (Some(run), Some(load)) => write!(f, " (run_time: {}s, load_time: {}s)", run, load),
+            (Some(run), None) => write!(f, " (run_time: {}s)", run),
+            (None, Some(load)) => write!(f, " (load_time: {}s)", load),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Effective filters and seed from the header RSpec prints before running,
+/// e.g. `Run options: include {:focus=>true}` and `Randomized with seed 123`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunOptions {
+    pub include: Option<String>,
+    pub exclude: Option<String>,
+    pub seed: Option<u64>,
+}
+
+impl RunOptions {
+    /// Returns `None` when the output has no header, as with the JSON
+    /// formatter.
+    pub fn parse(output: &str) -> Option<Self> {
+        let mut options = RunOptions::default();
+        let mut found = false;
+        let mut lines = output.lines().map(str::trim);
+
+        while let Some(line) = lines.next() {
+            if let Some(rest) = line.strip_prefix("Run options:") {
+                found = true;
+                let rest = rest.trim();
+                if !rest.is_empty() {
+                    options.filter(rest);
+                    continue;
+                }
+                // With both filters each goes on its own indented line
+                for line in lines.by_ref() {
+                    if !options.filter(line) {
+                        break;
+                    }
+                }
+            } else if let Some(seed) = line.strip_prefix("Randomized with seed ")
+                && options.seed.is_none()
+            {
+                options.seed = seed.trim().parse().ok();
+                found |= options.seed.is_some();
+            }
+        }
+
+        found.then_some(options)
+    }
+
+    fn filter(&mut self, line: &str) -> bool {
+        if let Some(include) = line.strip_prefix("include ") {
+            self.include = Some(include.to_string());
+        } else if let Some(exclude) = line.strip_prefix("exclude ") {
+            self.exclude = Some(exclude.to_string());
+        } else {
+            return false;
+        }
+        true
+    }
+}
+
+impl fmt::Display for RunOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(include) = &self.include {
+            parts.push(format!("include {}", include));
+        }
+        if let Some(exclude) = &self.exclude {
+            parts.push(format!("exclude {}", exclude));
+        }
+        if let Some(seed) = self.seed {
+            parts.push(format!("seed {}", seed));
+        }
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+/// Longest line [`compact_status`] returns, in characters.
+const COMPACT_STATUS_MAX_CHARS: usize = 60;
+
+/// A single plain-text line for editor status bars, e.g. `✓ 42 passed` or
+/// `✗ 3 failed / 42 (seed 12345)`.
+pub fn compact_status(status: &RunStatus, summary: Option<&RunSummary>) -> String {
+    let line = match (status, summary) {
+        (RunStatus::SpecSyntaxError { file, line: Some(line), .. }, _) => {
+            format!("✗ syntax error in {}:{}", file, line)
+        }
+        (RunStatus::SpecSyntaxError { file, line: None, .. }, _) => format!("✗ syntax error in {}", file),
+        (RunStatus::LoadError { file, .. }, _) => format!("✗ load error in {}", file),
+        (RunStatus::Terminated { signal }, _) => format!("✗ terminated by signal {}", signal),
+        (RunStatus::NotStarted { exit_code, .. }, _) => format!("✗ not started (exit code {})", exit_code),
+        (RunStatus::Passed, Some(summary)) => {
+            let passed = summary.examples.saturating_sub(summary.failures + summary.pending);
+            match summary.pending {
+                0 => format!("✓ {} passed", passed),
+                pending => format!("✓ {} passed, {} pending", passed, pending),
+            }
+        }
+        (RunStatus::Passed, None) => "✓ passed".to_string(),
+        (RunStatus::Failed, Some(summary)) => {
+            let mut line = if summary.failures == 0 && summary.errors_outside_examples > 0 {
+                format!("✗ {} error(s) outside examples", summary.errors_outside_examples)
+            } else {
+                format!("✗ {} failed / {}", summary.failures, summary.examples)
+            };
+            if let Some(seed) = summary.seed {
+                line.push_str(&format!(" (seed {})", seed));
+            }
+            line
+        }
+        (RunStatus::Failed, None) => "✗ failed".to_string(),
+    };
+
+    let line: String = line.chars().filter(|c| !c.is_control()).collect();
+    if line.chars().count() > COMPACT_STATUS_MAX_CHARS {
+        let mut truncated: String = line.chars().take(COMPACT_STATUS_MAX_CHARS - 1).collect();
+        truncated.push('…');
+        truncated
+    } else {
+        line
+    }
+}
+
+/// Longest failure description quoted by [`plain_summary`], in characters.
+const PLAIN_SUMMARY_MAX_DESCRIPTION: usize = 80;
+
+/// One or two sentences for people who don't read test output, e.g. `All 42
+/// tests passed.` or `3 of 42 tests failed in user_spec.rb; the first was
+/// "User saves a record".`
+pub fn plain_summary(
+    target: &str,
+    status: &RunStatus,
+    summary: Option<&RunSummary>,
+    failures: &[FailureReport],
+) -> String {
+    let name = |path: &str| {
+        let path = path.split(':').next().unwrap_or(path);
+        path.rsplit('/').find(|part| !part.is_empty()).unwrap_or(path).to_string()
+    };
+    let tests = |count: u32| if count == 1 { "test" } else { "tests" };
+
+    match (status, summary) {
+        (RunStatus::Passed, Some(summary)) if summary.examples == 0 => {
+            format!("No tests were found in {}.", name(target))
+        }
+        (RunStatus::Passed, Some(summary)) => {
+            let ran = summary.examples.saturating_sub(summary.pending);
+            let mut text = match ran {
+                1 => "The test passed.".to_string(),
+                ran => format!("All {} tests passed.", ran),
+            };
+            if summary.pending > 0 {
+                text.push_str(&format!(" {} more {} on hold.", summary.pending, match summary.pending {
+                    1 => "is",
+                    _ => "are",
+                }));
+            }
+            text
+        }
+        (RunStatus::Passed, None) => "The tests passed.".to_string(),
+        (RunStatus::Failed, Some(summary)) if summary.failures > 0 => {
+            let mut text = format!(
+                "{} of {} {} failed in {}",
+                summary.failures,
+                summary.examples,
+                tests(summary.examples),
+                name(target)
+            );
+            if let Some(first) = failures.first() {
+                let mut description: String = first.description.chars().filter(|c| !c.is_control()).collect();
+                if description.chars().count() > PLAIN_SUMMARY_MAX_DESCRIPTION {
+                    description = description.chars().take(PLAIN_SUMMARY_MAX_DESCRIPTION - 1).collect();
+                    description.push('…');
+                }
+                let first = if summary.failures == 1 { "it" } else { "the first" };
+                text.push_str(&format!("; {} was \"{}\"", first, description));
+            }
+            text.push('.');
+            text
+        }
+        (RunStatus::Failed, _) => format!("The tests in {} failed.", name(target)),
+        (RunStatus::SpecSyntaxError { file, .. }, _) => format!(
+            "The tests in {} couldn't run because the file has a typing mistake.",
+            name(file)
+        ),
+        (RunStatus::LoadError { file, .. }, _) => format!(
+            "The tests in {} couldn't start because something they depend on failed to load.",
+            name(file)
+        ),
+        (RunStatus::Terminated { .. }, _) => format!(
+            "The tests in {} were stopped unexpectedly before they finished.",
+            name(target)
+        ),
+        (RunStatus::NotStarted { .. }, _) => format!(
+            "The tests in {} couldn't start because the test tool isn't set up correctly.",
+            name(target)
+        ),
+    }
+}
+
+/// One entry from the `Failures:` section of RSpec's output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailureReport {
+    pub description: String,
+    /// `path:line` of the first spec-file backtrace line, if any.
+    pub location: Option<String>,
+    pub message: String,
+    /// Individual expectations from an `aggregate_failures` block.
+    pub sub_failures: Vec<SubFailure>,
+}
+
+/// One `N.M)` entry under an aggregated failure.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubFailure {
+    pub number: String,
+    pub location: Option<String>,
+    pub message: String,
+}
+
+impl FailureReport {
+    /// Ruby's `SystemStackError` ("stack level too deep"), usually from
+    /// infinite recursion.
+    pub fn is_stack_overflow(&self) -> bool {
+        self.message.contains("SystemStackError")
+    }
+
+    /// Parses the numbered entries of the `Failures:` section, in the order
+    /// RSpec lists them (run order).
+    pub fn parse_all(output: &str) -> Vec<Self> {
+        let mut failures = Vec::new();
+        let mut current: Option<(String, Vec<&str>)> = None;
+
+        let section = output
+            .lines()
+            .skip_while(|line| line.trim() != "Failures:")
+            .skip(1)
+            .take_while(|line| {
+                let line = line.trim();
+                !line.starts_with("Finished in ") && line != "Failed examples:"
+            });
+
+        for line in section {
+            if let Some(description) = Self::numbered_heading(line) {
+                failures.extend(current.take().map(|(d, body)| Self::from_body(d, &body)));
+                current = Some((description.to_string(), Vec::new()));
+            } else if let Some((_, body)) = current.as_mut() {
+                body.push(line.trim());
+            }
+        }
+        failures.extend(current.map(|(d, body)| Self::from_body(d, &body)));
+
+        failures
+    }
+
+    fn numbered_heading(line: &str) -> Option<&str> {
+        let line = line.trim_start();
+        let (number, description) = line.split_once(") ")?;
+        (!number.is_empty() && number.chars().all(|c| c.is_ascii_digit())).then_some(description.trim())
+    }
+
+    /// Splits `1.2) Failure/Error: ...` into its number and the rest.
+    fn sub_heading(line: &str) -> Option<(&str, &str)> {
+        let (number, rest) = line.split_once(") ")?;
+        let (major, minor) = number.split_once('.')?;
+        let numeric = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+        (numeric(major) && numeric(minor)).then_some((number, rest))
+    }
+
+    fn from_body(description: String, body: &[&str]) -> Self {
+        let split = body
+            .iter()
+            .position(|line| Self::sub_heading(line).is_some())
+            .unwrap_or(body.len());
+        let (own, aggregated) = body.split_at(split);
+
+        let mut sub_failures = Vec::new();
+        let mut current: Option<(&str, Vec<&str>)> = None;
+        for line in aggregated {
+            if let Some((number, rest)) = Self::sub_heading(line) {
+                sub_failures.extend(current.take().map(|(n, lines)| SubFailure::from_lines(n, &lines)));
+                current = Some((number, vec![rest]));
+            } else if let Some((_, lines)) = current.as_mut() {
+                lines.push(line);
+            }
+        }
+        sub_failures.extend(current.map(|(n, lines)| SubFailure::from_lines(n, &lines)));
+
+        Self {
+            description,
+            location: spec_location(own),
+            message: message_lines(own),
+            sub_failures,
+        }
+    }
+}
+
+impl SubFailure {
+    fn from_lines(number: &str, lines: &[&str]) -> Self {
+        Self {
+            number: number.to_string(),
+            location: spec_location(lines),
+            message: message_lines(lines),
+        }
+    }
+}
+
+/// `path:line` of the first spec-file backtrace line.
+fn spec_location(lines: &[&str]) -> Option<String> {
+    lines.iter().find_map(|line| {
+        let path = line.strip_prefix("# ")?.trim_start_matches("./");
+        let end = path.find("_spec.rb:")? + "_spec.rb:".len();
+        let digits = path[end..].chars().take_while(char::is_ascii_digit).count();
+        (digits > 0).then(|| path[..end + digits].to_string())
+    })
+}
+
+/// Non-empty lines other than backtrace lines.
+fn message_lines(lines: &[&str]) -> String {
+    lines
+        .iter()
+        .filter(|line| !line.is_empty() && !line.starts_with("# "))
+        .copied()
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Renders the first `limit` failures, noting how many were left out.
+pub fn format_failures(failures: &[FailureReport], limit: usize) -> String {
+    let mut lines = Vec::new();
+
+    for (index, failure) in failures.iter().take(limit).enumerate() {
+        let kind = if failure.is_stack_overflow() { " [StackOverflow]" } else { "" };
+        match &failure.location {
+            Some(location) => {
+                lines.push(format!("  {}) {} ({}){}", index + 1, failure.description, location, kind))
+            }
+            None => lines.push(format!("  {}) {}{}", index + 1, failure.description, kind)),
+        }
+        lines.extend(failure.message.lines().map(|line| format!("     {}", line)));
+
+        for sub in &failure.sub_failures {
+            let mut message = sub.message.lines();
+            let first = message.next().unwrap_or_default();
+            match &sub.location {
+                Some(location) => lines.push(format!("     {}) {} ({})", sub.number, first, location)),
+                None => lines.push(format!("     {}) {}", sub.number, first)),
+            }
+            lines.extend(message.map(|line| format!("          {}", line)));
+        }
+    }
+
+    let omitted = failures.len().saturating_sub(limit);
+    if omitted > 0 {
+        lines.push(format!(
+            "  ... {} more failure{} omitted",
+            omitted,
+            if omitted == 1 { "" } else { "s" }
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Locations to rerun every failure, from the `Failed examples:` section
+/// (`rspec ./spec/x_spec.rb:12 # ...`), falling back to the parsed failure
+/// locations. Deduplicated, in the order RSpec listed them.
+pub fn rerun_locations(output: &str, failures: &[FailureReport]) -> Vec<String> {
+    let listed = output
+        .lines()
+        .skip_while(|line| line.trim() != "Failed examples:")
+        .skip(1)
+        .filter_map(|line| {
+            let location = line.trim().strip_prefix("rspec ")?;
+            let location = location.split(" # ").next()?.trim().trim_matches('\'');
+            Some(location.trim_start_matches("./").to_string())
+        });
+
+    let mut locations: Vec<String> = Vec::new();
+    let candidates: Vec<String> = match listed.collect::<Vec<_>>() {
+        listed if !listed.is_empty() => listed,
+        _ => failures.iter().filter_map(|f| f.location.clone()).collect(),
+    };
+    for location in candidates {
+        if !locations.contains(&location) {
+            locations.push(location);
+        }
+    }
+    locations
+}
+
+/// What rerunning a failing example showed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetryVerdict {
+    /// Failed on every retry
+    Consistent,
+    /// Passed on this retry, counting from 1
+    Flaky(usize),
+    /// A retry ended with this status label before the example passed
+    Unconfirmed(&'static str),
+}
+
+impl RetryVerdict {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RetryVerdict::Consistent => "consistent",
+            RetryVerdict::Flaky(_) => "flaky",
+            RetryVerdict::Unconfirmed(_) => "unconfirmed",
+        }
+    }
+}
+
+impl fmt::Display for RetryVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryVerdict::Consistent => write!(f, "consistent"),
+            RetryVerdict::Flaky(retry) => write!(f, "flaky (passed on retry {})", retry),
+            RetryVerdict::Unconfirmed(status) => write!(f, "unconfirmed (a retry ended as {})", status),
+        }
+    }
+}
+
+/// One line per retried location, e.g. `  spec/a_spec.rb:12: consistent`.
+pub fn format_retries(verdicts: &[(String, RetryVerdict)]) -> String {
+    verdicts
+        .iter()
+        .map(|(location, verdict)| format!("  {}: {}", location, verdict))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Converts `\r\n` line endings (Windows hosts, WSL) to `\n`.
+pub fn normalize_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
+
+/// Longest run of backtrace frames [`collapse_repeated_frames`] looks for.
+const MAX_FRAME_CYCLE: usize = 4;
+
+/// Repetitions a cycle needs before it is collapsed.
+const MIN_FRAME_REPETITIONS: usize = 3;
+
+/// Collapses backtrace frames (`# ...` lines) repeating in a cycle of up to
+/// four, as in a `SystemStackError` backtrace, into one copy of the cycle
+/// and a count of the repetitions left out.
+pub fn collapse_repeated_frames(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let is_frame = |line: &str| line.trim_start().starts_with("# ");
+    let mut collapsed = Vec::new();
+    let mut i = 0;
+
+    'lines: while i < lines.len() {
+        for cycle in 1..=MAX_FRAME_CYCLE {
+            let Some(frames) = lines.get(i..i + cycle) else {
+                break;
+            };
+            if !frames.iter().all(|line| is_frame(line)) {
+                break;
+            }
+
+            let repetitions = lines[i..]
+                .chunks_exact(cycle)
+                .take_while(|chunk| *chunk == frames)
+                .count();
+            if repetitions >= MIN_FRAME_REPETITIONS {
+                let indent = &frames[0][..frames[0].len() - frames[0].trim_start().len()];
+                collapsed.extend(frames.iter().map(|line| line.to_string()));
+                collapsed.push(format!(
+                    "{}# ... {} more repetitions of the {} frame{} above",
+                    indent,
+                    repetitions - 1,
+                    cycle,
+                    if cycle == 1 { "" } else { "s" }
+                ));
+                i += repetitions * cycle;
+                continue 'lines;
+            }
+        }
+
+        collapsed.push(lines[i].to_string());
+        i += 1;
+    }
+
+    let mut collapsed = collapsed.join("\n");
+    if text.ends_with('\n') {
+        collapsed.push('\n');
+    }
+    collapsed
+}
+
+/// Keeps the start and end of `text` within `limit` bytes, cutting the
+/// middle on character boundaries and noting how much was cut. The end gets
+/// two thirds of the room, since RSpec prints failures and the summary last.
+pub fn truncate_output(text: &str, limit: usize) -> String {
+    if text.len() <= limit {
+        return text.to_string();
+    }
+
+    let mut head = limit / 3;
+    while !text.is_char_boundary(head) {
+        head -= 1;
+    }
+    let mut tail = text.len() - (limit - limit / 3);
+    while !text.is_char_boundary(tail) {
+        tail += 1;
+    }
+
+    format!(
+        "{}\n... [{} bytes truncated] ...\n{}",
+        &text[..head],
+        tail - head,
+        &text[tail..]
+    )
+}
+
+/// Splits a combined `limit` into byte limits for stdout and stderr. Each
+/// gets half, and room one side doesn't need goes to the other.
+pub fn split_output_limit(stdout_len: usize, stderr_len: usize, limit: usize) -> (usize, usize) {
+    let stderr_limit = stderr_len.min(limit / 2).max(limit.saturating_sub(stdout_len));
+    (limit.saturating_sub(stderr_limit), stderr_limit)
+}
+
+/// Parses RSpec's formatted durations, e.g. `0.05 seconds`,
+/// `1 minute 5.3 seconds` or `2 minutes 1 second`, into seconds. Accepts a
+/// decimal comma for locales that use one.
+fn parse_duration(text: &str) -> Option<f64> {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    if tokens.is_empty() || !tokens.len().is_multiple_of(2) {
+        return None;
+    }
+
+    tokens.chunks(2).try_fold(0.0, |total, pair| {
+        let value: f64 = pair[0].replace(',', ".").parse().ok()?;
+        let multiplier = match pair[1].trim_end_matches('s') {
+            "second" => 1.0,
+            "minute" => 60.0,
+            "hour" => 3600.0,
+            _ => return None,
+        };
+        Some(total + value * multiplier)
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExampleStatus {
+    Passed,
+    Failed,
+    Pending,
+}
+
+/// A completed example seen while streaming documentation-formatted output.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExampleEvent {
+    pub index: usize,
+    pub id: String,
+    pub description: String,
+    pub status: ExampleStatus,
+}
+
+/// Incrementally parses `--format documentation` output into example events.
+///
+/// Groups and passing examples look alike in documentation output, so a
+/// plain line is only known to be an example once the next line turns out
+/// not to be nested under it. Failed and pending examples carry a marker and
+/// are reported straight away.
+#[derive(Debug, Default)]
+pub struct DocumentationParser {
+    groups: Vec<(usize, String)>,
+    unresolved: Option<(usize, String)>,
+    completed: usize,
+    finished: bool,
+}
+
+impl DocumentationParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, line: &str) -> Vec<ExampleEvent> {
+        let mut events = Vec::new();
+
+        let text = line.trim();
+        if self.finished || text.is_empty() {
+            return events;
+        }
+
+        let indent = line.len() - line.trim_start().len();
+        if indent == 0 {
+            if DOCUMENTATION_TRAILERS.iter().any(|t| text.starts_with(t)) {
+                events.extend(self.finish());
+                return events;
+            }
+            if DOCUMENTATION_HEADERS.iter().any(|h| text.starts_with(h)) {
+                return events;
+            }
+        }
+
+        if let Some((previous_indent, previous)) = self.unresolved.take() {
+            if indent > previous_indent {
+                self.close_groups(previous_indent);
+                self.groups.push((previous_indent, previous));
+            } else {
+                events.push(self.complete(previous_indent, &previous, ExampleStatus::Passed));
+            }
+        }
+
+        match Self::strip_marker(text) {
+            Some((description, status)) => {
+                events.push(self.complete(indent, description, status));
+            }
+            None => self.unresolved = Some((indent, text.to_string())),
+        }
+
+        events
+    }
+
+    /// Resolves a trailing line once the stream ends.
+    pub fn finish(&mut self) -> Option<ExampleEvent> {
+        self.finished = true;
+        let (indent, description) = self.unresolved.take()?;
+        Some(self.complete(indent, &description, ExampleStatus::Passed))
+    }
+
+    fn complete(&mut self, indent: usize, description: &str, status: ExampleStatus) -> ExampleEvent {
+        self.close_groups(indent);
+        self.completed += 1;
+
+        let id = self
+            .groups
+            .iter()
+            .map(|(_, group)| group.as_str())
+            .chain(std::iter::once(description))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        ExampleEvent {
+            index: self.completed,
+            id,
+            description: description.to_string(),
+            status,
+        }
+    }
+
+    fn close_groups(&mut self, indent: usize) {
+        while self.groups.last().is_some_and(|(group_indent, _)| *group_indent >= indent) {
+            self.groups.pop();
+        }
+    }
+
+    fn strip_marker(text: &str) -> Option<(&str, ExampleStatus)> {
+        if let Some(index) = text.rfind(" (FAILED - ")
+            && text.ends_with(')')
+        {
+            return Some((&text[..index], ExampleStatus::Failed));
+        }
+
+        if let Some(index) = text.rfind(" (PENDING: ")
+            && text.ends_with(')')
+        {
+            return Some((&text[..index], ExampleStatus::Pending));
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(exit_code: i32, stdout: &str) -> CommandResult {
+        CommandResult {
+            exit_code,
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            abort_reason: None,
+            signal: None,
+            timed_out: false,
+            cancelled: false,
+            peak_memory_mb: None,
+            launcher: None,
+        }
+    }
+
+    #[test]
+    fn test_classify_signal() {
+        let killed = CommandResult {
+            signal: Some(9),
+            ..result(-1, "An error occurred while loading ./spec/models/user_spec.rb.\n")
+        };
+        assert_eq!(RunStatus::classify(&killed), RunStatus::Terminated { signal: 9 });
+        assert_eq!(RunStatus::Terminated { signal: 9 }.to_string(), "Terminated by signal 9");
+        assert_eq!(RunStatus::Terminated { signal: 9 }.label(), "terminated");
+        assert_eq!(RunStatus::classify(&result(-1, "")), RunStatus::Failed);
+    }
+
+    #[test]
+    fn test_classify_not_started() {
+        let bundler = CommandResult {
+            stderr: "\nCould not locate Gemfile or .bundle/ directory\n".to_string(),
+            ..result(10, "")
+        };
+        let status = RunStatus::classify(&bundler);
+        assert_eq!(
+            status,
+            RunStatus::NotStarted { exit_code: 10, reason: "Could not locate Gemfile or .bundle/ directory".to_string() }
+        );
+        assert_eq!(status.to_string(), "NotStarted (exit code 10): Could not locate Gemfile or .bundle/ directory");
+        assert_eq!(status.label(), "not_started");
+        assert_eq!(
+            status.outcome(10),
+            "The RSpec command failed before any specs ran (exit code 10); this is a setup problem, not a test failure"
+        );
+
+        // RSpec's own failures always print to stdout
+        let failed = CommandResult { stderr: "warning: deprecated\n".to_string(), ..result(1, "1 example, 1 failure") };
+        assert_eq!(RunStatus::classify(&failed), RunStatus::Failed);
+        let timed_out = CommandResult {
+            stderr: "bundler: command not found: rspec\n".to_string(),
+            abort_reason: Some("Timed out".to_string()),
+            ..result(127, "")
+        };
+        assert_eq!(RunStatus::classify(&timed_out), RunStatus::Failed);
+    }
+
+    #[test]
+    fn test_classify_passed() {
+        let status = RunStatus::classify(&result(0, "3 examples, 0 failures"));
+        assert_eq!(status, RunStatus::Passed);
+    }
+
+    #[test]
+    fn test_classify_failed() {
+        let status = RunStatus::classify(&result(1, "3 examples, 1 failure"));
+        assert_eq!(status, RunStatus::Failed);
+    }
+
+    #[test]
+    fn test_classify_syntax_error() {
+        let output = r#"
+An error occurred while loading ./spec/models/user_spec.rb.
+Failure/Error: __send__(method, file)
+
+SyntaxError:
+  /app/spec/models/user_spec.rb:12: syntax error, unexpected end-of-input, expecting `end'
+# /usr/local/bundle/gems/rspec-core-3.12.2/lib/rspec/core/configuration.rb:2138:in `load'
+No examples found.
+
+Finished in 0.00003 seconds (files took 0.10612 seconds to load)
+0 examples, 0 failures, 1 error occurred outside of examples
+"#;
+
+        let status = RunStatus::classify(&result(1, output));
+        assert_eq!(
+            status,
+            RunStatus::SpecSyntaxError {
+                file: "./spec/models/user_spec.rb".to_string(),
+                line: Some(12),
+                message: "syntax error, unexpected end-of-input, expecting `end'".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_classify_syntax_error_prism_format() {
+        let output = r#"
+An error occurred while loading ./spec/models/user_spec.rb.
+Failure/Error: __send__(method, file)
+
+SyntaxError:
+  --> /app/spec/models/user_spec.rb
+  /app/spec/models/user_spec.rb:7: syntax errors found (SyntaxError)
+"#;
+
+        let status = RunStatus::classify(&result(1, output));
+        assert_eq!(
+            status,
+            RunStatus::SpecSyntaxError {
+                file: "./spec/models/user_spec.rb".to_string(),
+                line: Some(7),
+                message: "syntax errors found (SyntaxError)".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_classify_load_error_other_than_syntax() {
+        let output = r#"
+An error occurred while loading ./spec/models/user_spec.rb.
+Failure/Error: require 'missing'
+
+LoadError:
+  cannot load such file -- missing
+"#;
+
+        let status = RunStatus::classify(&result(1, output));
+        assert_eq!(
+            status,
+            RunStatus::LoadError {
+                file: "./spec/models/user_spec.rb".to_string(),
+                message: "LoadError: cannot load such file -- missing".to_string(),
+            }
+        );
+        assert!(status.is_load_failure());
+    }
+
+    #[test]
+    fn test_outcome() {
+        assert_eq!(RunStatus::Passed.outcome(0), "passed");
+        assert_eq!(RunStatus::Failed.outcome(1), "tests failed");
+        assert_eq!(
+            RunStatus::Failed.outcome(127),
+            "RSpec crashed or could not run (exit code 127); these are not test failures"
+        );
+        let load_error = RunStatus::LoadError {
+            file: "./spec/models/user_spec.rb".to_string(),
+            message: "LoadError: cannot load such file -- missing".to_string(),
+        };
+        assert_eq!(load_error.outcome(1), "RSpec could not load the specs; no examples ran");
+        assert_eq!(
+            RunStatus::Terminated { signal: 9 }.outcome(-1),
+            "RSpec was killed by signal 9 before finishing; results are incomplete"
+        );
+    }
+
+    #[test]
+    fn test_classify_runtime_exception_in_example() {
+        let output = r#"
+Failures:
+
+  1) User saves
+     Failure/Error: eval("def broken")
+
+     SyntaxError:
+       (eval):1: syntax error, unexpected end-of-input
+     # ./spec/models/user_spec.rb:5:in `block (2 levels) in <top (required)>'
+
+1 example, 1 failure
+"#;
+
+        let status = RunStatus::classify(&result(1, output));
+        assert_eq!(status, RunStatus::Failed);
+    }
+
+    fn parse_documentation(output: &str) -> Vec<ExampleEvent> {
+        let mut parser = DocumentationParser::new();
+        let mut events: Vec<ExampleEvent> = output.lines().flat_map(|line| parser.push(line)).collect();
+        events.extend(parser.finish());
+        events
+    }
+
+    #[test]
+    fn test_run_options_with_filters_and_seed() {
+        let output = "\
+Run options:
+  include {:focus=>true, :locations=>{\"./spec/models/user_spec.rb\"=>[12]}}
+  exclude {:slow=>true}
+
+Randomized with seed 4242
+..
+
+Finished in 0.1 seconds (files took 0.2 seconds to load)
+2 examples, 0 failures
+
+Randomized with seed 4242
+";
+        let options = RunOptions::parse(output).unwrap();
+        assert_eq!(
+            options,
+            RunOptions {
+                include: Some("{:focus=>true, :locations=>{\"./spec/models/user_spec.rb\"=>[12]}}".to_string()),
+                exclude: Some("{:slow=>true}".to_string()),
+                seed: Some(4242),
+            }
+        );
+        assert_eq!(
+            options.to_string(),
+            "include {:focus=>true, :locations=>{\"./spec/models/user_spec.rb\"=>[12]}}; exclude {:slow=>true}; seed 4242"
+        );
+    }
+
+    #[test]
+    fn test_run_options_single_line() {
+        let options = RunOptions::parse("Run options: include {:type=>\"model\"}\n.\n").unwrap();
+        assert_eq!(options.include.as_deref(), Some("{:type=>\"model\"}"));
+        assert_eq!(options.exclude, None);
+        assert_eq!(options.seed, None);
+    }
+
+    #[test]
+    fn test_run_options_absent() {
+        assert_eq!(RunOptions::parse("{\"version\":\"3.12.2\",\"examples\":[]}"), None);
+    }
+
+    #[test]
+    fn test_documentation_stream() {
+        let output = r#"
+Randomized with seed 12345
+
+User
+  #save
+    persists the record
+    rejects a blank email (FAILED - 1)
+  #name
+    joins first and last name
+    handles unicode (PENDING: Not yet implemented)
+
+Order
+  totals line items
+
+Pending: (Failures listed here are expected and do not affect your suite's status)
+
+  1) User#name handles unicode
+     # Not yet implemented
+
+Failures:
+
+  1) User#save rejects a blank email
+     Failure/Error: expect(user).not_to be_valid
+
+Finished in 0.05 seconds (files took 1.2 seconds to load)
+5 examples, 1 failure, 1 pending
+"#;
+
+        let events = parse_documentation(output);
+        let summary: Vec<(usize, &str, ExampleStatus)> = events
+            .iter()
+            .map(|e| (e.index, e.id.as_str(), e.status))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (1, "User #save persists the record", ExampleStatus::Passed),
+                (2, "User #save rejects a blank email", ExampleStatus::Failed),
+                (3, "User #name joins first and last name", ExampleStatus::Passed),
+                (4, "User #name handles unicode", ExampleStatus::Pending),
+                (5, "Order totals line items", ExampleStatus::Passed),
+            ]
+        );
+        assert_eq!(events[1].description, "rejects a blank email");
+    }
+
+    #[test]
+    fn test_documentation_stream_reports_failures_immediately() {
+        let mut parser = DocumentationParser::new();
+
+        assert!(parser.push("User").is_empty());
+        assert!(parser.push("  saves").is_empty());
+
+        let events = parser.push("  rejects blanks (FAILED - 1)");
+        let statuses: Vec<ExampleStatus> = events.iter().map(|e| e.status).collect();
+        assert_eq!(statuses, vec![ExampleStatus::Passed, ExampleStatus::Failed]);
+    }
+
+    #[test]
+    fn test_documentation_stream_without_trailer() {
+        let events = parse_documentation("User
+  saves
+");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id, "User saves");
+    }
+
+    #[test]
+    fn test_summary_seconds() {
+        let output = "..F\n\nFinished in 0.05123 seconds (files took 1.2 seconds to load)\n3 examples, 1 failure, 1 pending\n";
+        let summary = RunSummary::parse(output).unwrap();
+        assert_eq!(
+            summary,
+            RunSummary {
+                examples: 3,
+                failures: 1,
+                pending: 1,
+                errors_outside_examples: 0,
+                run_time: Some(0.05123),
+                load_time: Some(1.2),
+                seed: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_summary_minutes() {
+        let summary = RunSummary::parse(
+            "Finished in 1 minute 5.3 seconds (files took 2 minutes 1 second to load)",
+        )
+        .unwrap();
+        assert_eq!(summary.run_time, Some(65.3));
+        assert_eq!(summary.load_time, Some(121.0));
+
+        let summary = RunSummary::parse("Finished in 12 minutes 3 seconds (files took 0.5 seconds to load)").unwrap();
+        assert_eq!(summary.run_time, Some(723.0));
+        assert_eq!(summary.load_time, Some(0.5));
+    }
+
+    #[test]
+    fn test_summary_format_variations() {
+        let summary = RunSummary::parse("Finished in 0.00489 seconds").unwrap();
+        assert_eq!(summary.run_time, Some(0.00489));
+        assert_eq!(summary.load_time, None);
+
+        let summary = RunSummary::parse("Finished in 1,5 seconds (files took 0,25 seconds to load)").unwrap();
+        assert_eq!(summary.run_time, Some(1.5));
+        assert_eq!(summary.load_time, Some(0.25));
+
+        let summary = RunSummary::parse("Finished in 1 second (files took 1 second to load)").unwrap();
+        assert_eq!(summary.run_time, Some(1.0));
+
+        let summary = RunSummary::parse("Finished in soon").unwrap();
+        assert_eq!(summary.run_time, None);
+    }
+
+    #[test]
+    fn test_summary_with_errors_outside_examples() {
+        let summary = RunSummary::parse("0 examples, 0 failures, 1 error occurred outside of examples").unwrap();
+        assert_eq!(summary.examples, 0);
+        assert_eq!(summary.errors_outside_examples, 1);
+        assert_eq!(summary.run_time, None);
+    }
+
+    #[test]
+    fn test_summary_missing() {
+        assert!(RunSummary::parse("mock test output").is_none());
+        assert!(RunSummary::parse("3 apples, 1 failure").is_none());
+    }
+
+    fn failures_output(count: usize) -> String {
+        let mut output = String::from("F\n\nFailures:\n\n");
+        for i in 1..=count {
+            output.push_str(&format!(
+                "  {i}) Widget {i} works\n     Failure/Error: expect({i}).to eq(0)\n\n       expected: 0\n            got: {i}\n     # ./spec/models/widget_spec.rb:{i}:in `block (2 levels)'\n\n"
+            ));
+        }
+        output.push_str("Finished in 0.1 seconds (files took 0.2 seconds to load)\n");
+        output
+    }
+
+    #[test]
+    fn test_parse_failures() {
+        let failures = FailureReport::parse_all(&failures_output(2));
+
+        assert_eq!(failures.len(), 2);
+        assert_eq!(
+            failures[0],
+            FailureReport {
+                description: "Widget 1 works".to_string(),
+                location: Some("spec/models/widget_spec.rb:1".to_string()),
+                message: "Failure/Error: expect(1).to eq(0)\nexpected: 0\ngot: 1".to_string(),
+                sub_failures: vec![],
+            }
+        );
+        assert_eq!(failures[1].description, "Widget 2 works");
+    }
+
+    const AGGREGATE_FAILURES_OUTPUT: &str = "\
+Failures:
+
+  1) User validates the profile
+     Got 2 failures from failure aggregation block \"profile\".
+     # ./spec/models/user_spec.rb:5:in `block (2 levels) in <top (required)>'
+
+     1.1) Failure/Error: expect(user.name).to eq(\"Ann\")
+
+            expected: \"Ann\"
+                 got: nil
+
+            (compared using ==)
+          # ./spec/models/user_spec.rb:6:in `block (3 levels) in <top (required)>'
+
+     1.2) Failure/Error: expect(user).to be_valid
+            expected `#<User id: nil>.valid?` to be truthy, got false
+          # ./spec/models/user_spec.rb:7:in `block (3 levels) in <top (required)>'
+
+  2) User saves
+     Failure/Error: expect(user.save).to be(true)
+     # ./spec/models/user_spec.rb:12:in `block (2 levels) in <top (required)>'
+
+Finished in 0.1 seconds (files took 0.2 seconds to load)
+";
+
+    #[test]
+    fn test_parse_aggregate_failures() {
+        let failures = FailureReport::parse_all(AGGREGATE_FAILURES_OUTPUT);
+        assert_eq!(failures.len(), 2);
+
+        let aggregated = &failures[0];
+        assert_eq!(aggregated.description, "User validates the profile");
+        assert_eq!(aggregated.location.as_deref(), Some("spec/models/user_spec.rb:5"));
+        assert_eq!(aggregated.message, "Got 2 failures from failure aggregation block \"profile\".");
+        assert_eq!(
+            aggregated.sub_failures,
+            vec![
+                SubFailure {
+                    number: "1.1".to_string(),
+                    location: Some("spec/models/user_spec.rb:6".to_string()),
+                    message: "Failure/Error: expect(user.name).to eq(\"Ann\")\nexpected: \"Ann\"\ngot: nil\n(compared using ==)".to_string(),
+                },
+                SubFailure {
+                    number: "1.2".to_string(),
+                    location: Some("spec/models/user_spec.rb:7".to_string()),
+                    message: "Failure/Error: expect(user).to be_valid\nexpected `#<User id: nil>.valid?` to be truthy, got false".to_string(),
+                },
+            ]
+        );
+
+        assert!(failures[1].sub_failures.is_empty());
+        assert_eq!(failures[1].location.as_deref(), Some("spec/models/user_spec.rb:12"));
+    }
+
+    #[test]
+    fn test_format_aggregate_failures() {
+        let failures = FailureReport::parse_all(AGGREGATE_FAILURES_OUTPUT);
+        let text = format_failures(&failures[..1], 5);
+
+        assert_eq!(
+            text,
+            "  1) User validates the profile (spec/models/user_spec.rb:5)\n     Got 2 failures from failure aggregation block \"profile\".\n     1.1) Failure/Error: expect(user.name).to eq(\"Ann\") (spec/models/user_spec.rb:6)\n          expected: \"Ann\"\n          got: nil\n          (compared using ==)\n     1.2) Failure/Error: expect(user).to be_valid (spec/models/user_spec.rb:7)\n          expected `#<User id: nil>.valid?` to be truthy, got false"
+        );
+    }
+
+    #[test]
+    fn test_parse_failures_without_section() {
+        assert!(FailureReport::parse_all("...\n\n3 examples, 0 failures").is_empty());
+    }
+
+    #[test]
+    fn test_format_failures_truncates() {
+        let failures = FailureReport::parse_all(&failures_output(120));
+        assert_eq!(failures.len(), 120);
+
+        let text = format_failures(&failures, 3);
+        assert!(text.starts_with("  1) Widget 1 works (spec/models/widget_spec.rb:1)\n     Failure/Error"));
+        assert!(text.contains("  3) Widget 3 works"));
+        assert!(!text.contains("Widget 4 works"));
+        assert!(text.ends_with("  ... 117 more failures omitted"));
+    }
+
+    #[test]
+    fn test_format_failures_within_limit() {
+        let failures = FailureReport::parse_all(&failures_output(2));
+        let text = format_failures(&failures, 5);
+        assert!(!text.contains("omitted"));
+        assert!(text.contains("  2) Widget 2 works"));
+    }
+
+    #[test]
+    fn test_summary_seed() {
+        let output = "Randomized with seed 12345\n.F\n\nFinished in 0.1 seconds\n2 examples, 1 failure\n\nRandomized with seed 12345\n";
+        assert_eq!(RunSummary::parse(output).unwrap().seed, Some(12345));
+    }
+
+    #[test]
+    fn test_compact_status() {
+        let summary = |examples, failures, pending, seed| RunSummary {
+            examples,
+            failures,
+            pending,
+            seed,
+            ..Default::default()
+        };
+
+        let cases = [
+            (RunStatus::Passed, Some(summary(42, 0, 0, None)), "✓ 42 passed"),
+            (RunStatus::Passed, Some(summary(42, 0, 2, Some(7))), "✓ 40 passed, 2 pending"),
+            (RunStatus::Passed, None, "✓ passed"),
+            (RunStatus::Failed, Some(summary(42, 3, 0, Some(12345))), "✗ 3 failed / 42 (seed 12345)"),
+            (RunStatus::Failed, Some(summary(42, 3, 0, None)), "✗ 3 failed / 42"),
+            (RunStatus::Failed, None, "✗ failed"),
+            (
+                RunStatus::Failed,
+                Some(RunSummary {
+                    errors_outside_examples: 1,
+                    ..Default::default()
+                }),
+                "✗ 1 error(s) outside examples",
+            ),
+            (
+                RunStatus::SpecSyntaxError {
+                    file: "spec/models/user_spec.rb".to_string(),
+                    line: Some(12),
+                    message: "syntax error, unexpected end-of-input".to_string(),
+                },
+                None,
+                "✗ syntax error in spec/models/user_spec.rb:12",
+            ),
+            (
+                RunStatus::LoadError {
+                    file: "spec/models/user_spec.rb".to_string(),
+                    message: "NameError: uninitialized constant User".to_string(),
+                },
+                None,
+                "✗ load error in spec/models/user_spec.rb",
+            ),
+        ];
+
+        for (status, summary, expected) in cases {
+            assert_eq!(compact_status(&status, summary.as_ref()), expected);
+        }
+    }
+
+    #[test]
+    fn test_plain_summary() {
+        let summary = |examples, failures, pending| RunSummary {
+            examples,
+            failures,
+            pending,
+            ..Default::default()
+        };
+        let failures = FailureReport::parse_all(&failures_output(3));
+        let target = "spec/models/user_spec.rb:12";
+
+        let cases = [
+            (RunStatus::Passed, Some(summary(42, 0, 0)), &[][..], "All 42 tests passed."),
+            (RunStatus::Passed, Some(summary(1, 0, 0)), &[][..], "The test passed."),
+            (
+                RunStatus::Passed,
+                Some(summary(42, 0, 2)),
+                &[][..],
+                "All 40 tests passed. 2 more are on hold.",
+            ),
+            (RunStatus::Passed, Some(summary(0, 0, 0)), &[][..], "No tests were found in user_spec.rb."),
+            (RunStatus::Passed, None, &[][..], "The tests passed."),
+            (
+                RunStatus::Failed,
+                Some(summary(42, 3, 0)),
+                &failures[..],
+                "3 of 42 tests failed in user_spec.rb; the first was \"Widget 1 works\".",
+            ),
+            (
+                RunStatus::Failed,
+                Some(summary(1, 1, 0)),
+                &failures[..1],
+                "1 of 1 test failed in user_spec.rb; it was \"Widget 1 works\".",
+            ),
+            (RunStatus::Failed, None, &[][..], "The tests in user_spec.rb failed."),
+            (
+                RunStatus::SpecSyntaxError {
+                    file: "spec/models/user_spec.rb".to_string(),
+                    line: Some(12),
+                    message: "syntax error, unexpected end-of-input".to_string(),
+                },
+                None,
+                &[][..],
+                "The tests in user_spec.rb couldn't run because the file has a typing mistake.",
+            ),
+            (
+                RunStatus::LoadError {
+                    file: "spec/models/user_spec.rb".to_string(),
+                    message: "NameError: uninitialized constant User".to_string(),
+                },
+                None,
+                &[][..],
+                "The tests in user_spec.rb couldn't start because something they depend on failed to load.",
+            ),
+            (
+                RunStatus::Terminated { signal: 9 },
+                None,
+                &[][..],
+                "The tests in user_spec.rb were stopped unexpectedly before they finished.",
+            ),
+        ];
+
+        for (status, summary, failures, expected) in cases {
+            assert_eq!(plain_summary(target, &status, summary.as_ref(), failures), expected);
+        }
+        assert_eq!(
+            plain_summary("spec/models/", &RunStatus::Failed, None, &[]),
+            "The tests in models failed."
+        );
+    }
+
+    #[test]
+    fn test_stack_overflow_failure() {
+        let mut output = String::from("\
+F
+
+Failures:
+
+  1) User#name returns the full name
+     Failure/Error: full_name
+
+     SystemStackError:
+       stack level too deep
+");
+        for _ in 0..500 {
+            output.push_str("     # ./app/models/user.rb:5:in `full_name'\n");
+            output.push_str("     # ./app/models/user.rb:9:in `name'\n");
+        }
+        output.push_str("     # ./spec/models/user_spec.rb:3:in `block (2 levels) in <top (required)>'\n\n");
+        output.push_str("1 example, 1 failure\n");
+
+        let failures = FailureReport::parse_all(&output);
+        assert!(failures[0].is_stack_overflow());
+        assert_eq!(
+            format_failures(&failures, 5).lines().next().unwrap(),
+            "  1) User#name returns the full name (spec/models/user_spec.rb:3) [StackOverflow]"
+        );
+
+        let collapsed = collapse_repeated_frames(&output);
+        assert!(collapsed.contains(
+            "\
+     # ./app/models/user.rb:5:in `full_name'
+     # ./app/models/user.rb:9:in `name'
+     # ... 499 more repetitions of the 2 frames above
+     # ./spec/models/user_spec.rb:3:in `block (2 levels) in <top (required)>'
+"
+        ));
+        assert_eq!(collapsed.lines().count(), 15);
+        assert!(collapsed.ends_with("1 example, 1 failure\n"));
+    }
+
+    #[test]
+    fn test_collapse_leaves_short_repeats_alone() {
+        let text = "# ./a.rb:1\n# ./a.rb:1\nsame\nsame\nsame\n";
+        assert_eq!(collapse_repeated_frames(text), text);
+    }
+
+    #[test]
+    fn test_compact_status_is_bounded_and_plain() {
+        let status = RunStatus::LoadError {
+            file: format!("spec/{}/\x1b[31muser_spec.rb", "nested/".repeat(20)),
+            message: String::new(),
+        };
+
+        let line = compact_status(&status, None);
+        assert_eq!(line.chars().count(), COMPACT_STATUS_MAX_CHARS);
+        assert!(line.ends_with('…'));
+        assert!(!line.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_rerun_locations_from_failed_examples() {
+        let output = "\
+Failed examples:
+
+rspec ./spec/models/user_spec.rb:12 # User saves
+rspec ./spec/models/user_spec.rb:40 # User validates
+rspec './spec/models/order_spec.rb[1:2]' # Order totals
+rspec ./spec/requests/auth_spec.rb:5 # Auth logs in
+rspec ./spec/models/user_spec.rb:12 # User saves again
+";
+        assert_eq!(
+            rerun_locations(output, &[]),
+            vec![
+                "spec/models/user_spec.rb:12",
+                "spec/models/user_spec.rb:40",
+                "spec/models/order_spec.rb[1:2]",
+                "spec/requests/auth_spec.rb:5",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rerun_locations_fall_back_to_failures() {
+        let failures = FailureReport::parse_all(&failures_output(2));
+        assert_eq!(
+            rerun_locations("", &failures),
+            vec!["spec/models/widget_spec.rb:1", "spec/models/widget_spec.rb:2"]
+        );
+    }
+
+    #[test]
+    fn test_normalize_newlines() {
+        assert_eq!(normalize_newlines("a\r\nb\r\n"), "a\nb\n");
+        assert_eq!(normalize_newlines("a\nb"), "a\nb");
+    }
+
+    #[test]
+    fn test_truncate_output() {
+        assert_eq!(truncate_output("short", 10), "short");
+        assert_eq!(
+            truncate_output("0123456789abcdefghij", 9),
+            "012\n... [11 bytes truncated] ...\nefghij"
+        );
+        assert_eq!(truncate_output("✓✓✓✓", 7), "\n... [9 bytes truncated] ...\n✓");
+    }
+
+    #[test]
+    fn test_truncate_output_keeps_head_and_tail() {
+        let output = format!(
+            "Randomized with seed 1234\n{}\n\nFailures:\n\n  1) User fails\n\n3000 examples, 1 failure\n",
+            "é".repeat(50_000)
+        );
+        let truncated = truncate_output(&output, 3_000);
+        assert!(truncated.len() <= 3_000 + 40);
+        assert!(truncated.starts_with("Randomized with seed 1234\n"));
+        assert!(truncated.ends_with("Failures:\n\n  1) User fails\n\n3000 examples, 1 failure\n"));
+        assert!(truncated.contains(" bytes truncated] ...\n"));
+    }
+
+    #[test]
+    fn test_split_output_limit() {
+        assert_eq!(split_output_limit(1_000, 1_000, 100), (50, 50));
+        assert_eq!(split_output_limit(1_000, 10, 100), (90, 10));
+        assert_eq!(split_output_limit(10, 1_000, 100), (10, 90));
+        assert_eq!(split_output_limit(30, 40, 100), (30, 70));
+    }
+
+    #[test]
+    fn test_summary_display() {
+        let summary = RunSummary {
+            examples: 1,
+            failures: 0,
+            pending: 2,
+            errors_outside_examples: 0,
+            run_time: Some(0.5),
+            load_time: Some(1.25),
+            seed: None,
+        };
+        assert_eq!(
+            summary.to_string(),
+            "1 example, 0 failures, 2 pending (run_time: 0.5s, load_time: 1.25s)"
+        );
+    }
+
+    #[test]
+    fn test_display_syntax_error() {
+        let status = RunStatus::SpecSyntaxError {
+            file: "./spec/models/user_spec.rb".to_string(),
+            line: Some(12),
+            message: "syntax error".to_string(),
+        };
+        assert_eq!(
+            status.to_string(),
+            "SpecSyntaxError in ./spec/models/user_spec.rb:12: syntax error"
+        );
+    }
+}
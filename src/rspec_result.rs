@@ -0,0 +1,905 @@
+use serde::{Deserialize, Serialize};
+
+use crate::rspec_output::{FailureReport, RunSummary};
+use crate::source_context::SourceSnippet;
+
+/// Formatter runs write to stdout, from `--format`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum StdoutFormat {
+    /// RSpec's own default (usually `progress`, or whatever `.rspec` sets)
+    #[default]
+    Progress,
+    /// `-f json`, rendered as a list of examples in results
+    Json,
+}
+
+impl StdoutFormat {
+    pub fn rspec_args(&self) -> Vec<String> {
+        match self {
+            StdoutFormat::Progress => Vec::new(),
+            StdoutFormat::Json => vec!["-f".to_string(), "json".to_string()],
+        }
+    }
+}
+
+/// Subset of the report written by RSpec's JSON formatter (`-f json`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct RspecReport {
+    #[serde(default)]
+    pub examples: Vec<ExampleReport>,
+    pub summary: Option<ReportSummary>,
+    pub summary_line: Option<String>,
+    pub seed: Option<u64>,
+    /// Written with `--profile`.
+    pub profile: Option<ProfileReport>,
+    /// Recovered from a truncated report, e.g. after RSpec crashed mid-run.
+    #[serde(skip)]
+    pub partial: bool,
+}
+
+/// The slowest examples and groups, from `--profile N`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ProfileReport {
+    #[serde(default)]
+    pub examples: Vec<ExampleReport>,
+    /// Seconds the slowest examples took together.
+    pub slowest: Option<f64>,
+    /// Seconds all examples took.
+    pub total: Option<f64>,
+    #[serde(default)]
+    pub groups: Vec<GroupProfile>,
+}
+
+/// One of the slowest top-level groups.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct GroupProfile {
+    #[serde(default)]
+    pub description: String,
+    /// `path:line` of the group, as RSpec gives it.
+    #[serde(default)]
+    pub location: String,
+    #[serde(default)]
+    pub count: u32,
+    #[serde(default)]
+    pub total_time: f64,
+    #[serde(default)]
+    pub average: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExampleReport {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub full_description: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub pending_message: Option<String>,
+    #[serde(default)]
+    pub file_path: String,
+    pub line_number: Option<u32>,
+    pub run_time: Option<f64>,
+    pub exception: Option<ExampleException>,
+    /// Source around the failure, added after the run; RSpec doesn't write it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_context: Vec<SourceSnippet>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ExampleException {
+    #[serde(default)]
+    pub class: String,
+    #[serde(default)]
+    pub message: String,
+    /// RSpec writes `null` when the exception has none.
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub backtrace: Vec<String>,
+}
+
+fn null_as_empty<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(Option::<Vec<String>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReportSummary {
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub example_count: u32,
+    #[serde(default)]
+    pub failure_count: u32,
+    #[serde(default)]
+    pub pending_count: u32,
+    #[serde(default)]
+    pub errors_outside_of_examples_count: u32,
+}
+
+impl From<&RunSummary> for ReportSummary {
+    fn from(summary: &RunSummary) -> Self {
+        Self {
+            duration: summary.run_time,
+            example_count: summary.examples,
+            failure_count: summary.failures,
+            pending_count: summary.pending,
+            errors_outside_of_examples_count: summary.errors_outside_examples,
+        }
+    }
+}
+
+impl ExampleReport {
+    /// `path:line` without the leading `./`, or just the path.
+    pub fn location(&self) -> String {
+        let file_path = self.file_path.strip_prefix("./").unwrap_or(&self.file_path);
+        match self.line_number {
+            Some(line) => format!("{}:{}", file_path, line),
+            None => file_path.to_string(),
+        }
+    }
+}
+
+/// Examples under one top-level `describe` block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExampleGroup {
+    pub name: String,
+    pub file_path: String,
+    pub passed: usize,
+    pub failed: usize,
+    pub pending: usize,
+    pub examples: Vec<ExampleReport>,
+}
+
+impl ExampleGroup {
+    /// Longest whole-word prefix shared by the examples' group
+    /// descriptions (full description minus the example's own).
+    fn derive_name(examples: &[ExampleReport]) -> String {
+        let context = |example: &ExampleReport| -> Vec<String> {
+            example
+                .full_description
+                .strip_suffix(&example.description)
+                .unwrap_or(&example.full_description)
+                .split_whitespace()
+                .map(str::to_string)
+                .collect()
+        };
+
+        let mut words = context(&examples[0]);
+        for example in &examples[1..] {
+            let shared = words
+                .iter()
+                .zip(context(example))
+                .take_while(|(a, b)| **a == *b)
+                .count();
+            words.truncate(shared);
+        }
+        words.join(" ")
+    }
+}
+
+/// A file, `describe`/`context` block or example in
+/// [`RspecReport::example_tree`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExampleNode {
+    pub kind: NodeKind,
+    /// RSpec's id, e.g. `./spec/models/user_spec.rb[1:2]`; the path for files.
+    pub id: String,
+    pub description: String,
+    /// Examples only; the report has no lines for blocks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_number: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ExampleNode>,
+    /// Leading words shared by the full descriptions of the examples below.
+    #[serde(skip)]
+    shared_words: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    File,
+    Group,
+    Example,
+}
+
+impl ExampleNode {
+    fn new(kind: NodeKind, id: String) -> Self {
+        Self {
+            kind,
+            id,
+            description: String::new(),
+            line_number: None,
+            children: Vec::new(),
+            shared_words: None,
+        }
+    }
+
+    /// The child with `id`, added if it isn't there yet.
+    fn child(nodes: &mut Vec<ExampleNode>, kind: NodeKind, id: String) -> &mut ExampleNode {
+        let index = match nodes.iter().position(|node| node.id == id) {
+            Some(index) => index,
+            None => {
+                nodes.push(ExampleNode::new(kind, id));
+                nodes.len() - 1
+            }
+        };
+        &mut nodes[index]
+    }
+
+    fn share(&mut self, words: &[String]) {
+        match &mut self.shared_words {
+            Some(shared) => {
+                let common = shared.iter().zip(words).take_while(|(a, b)| a == b).count();
+                shared.truncate(common);
+            }
+            None => self.shared_words = Some(words.to_vec()),
+        }
+    }
+
+    /// Names blocks by the shared words their parent's name doesn't cover.
+    fn name_groups(nodes: &mut [ExampleNode], parent_words: usize) {
+        for node in nodes.iter_mut().filter(|node| node.kind == NodeKind::Group) {
+            let words = node.shared_words.take().unwrap_or_default();
+            node.description = words[parent_words.min(words.len())..].join(" ");
+            Self::name_groups(&mut node.children, words.len());
+        }
+    }
+
+    /// The `[1:2]` part of the id.
+    fn scope(&self) -> &str {
+        self.id.rfind('[').map_or("", |start| &self.id[start..])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileTiming {
+    pub file_path: String,
+    pub examples: usize,
+    pub run_time: f64,
+}
+
+impl RspecReport {
+    /// Finds a JSON report in command output, skipping anything printed
+    /// before it (e.g. `puts` from spec files or bundler warnings).
+    pub fn from_output(output: &str) -> Option<Self> {
+        let start = output.find("{\"version\"").or_else(|| output.find('{'))?;
+        let mut stream = serde_json::Deserializer::from_str(&output[start..]).into_iter::<Self>();
+        stream.next()?.ok()
+    }
+
+    /// Like [`RspecReport::from_output`], but when the report is cut off
+    /// recovers the examples that were written completely, marking the
+    /// result `partial`.
+    pub fn from_partial_output(output: &str) -> Option<Self> {
+        if let Some(report) = Self::from_output(output) {
+            return Some(report);
+        }
+
+        let key = "\"examples\":";
+        let mut rest = output[output.find(key)? + key.len()..].trim_start().strip_prefix('[')?;
+        let mut examples = Vec::new();
+        loop {
+            rest = rest.trim_start().trim_start_matches(',').trim_start();
+            let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<ExampleReport>();
+            match stream.next() {
+                Some(Ok(example)) => {
+                    examples.push(example);
+                    rest = &rest[stream.byte_offset()..];
+                }
+                _ => break,
+            }
+        }
+
+        Some(Self {
+            examples,
+            summary: None,
+            summary_line: None,
+            seed: None,
+            profile: None,
+            partial: true,
+        })
+    }
+
+    /// The report's counts in the shape parsed from text output.
+    pub fn run_summary(&self) -> Option<RunSummary> {
+        let summary = self.summary.as_ref()?;
+        Some(RunSummary {
+            examples: summary.example_count,
+            failures: summary.failure_count,
+            pending: summary.pending_count,
+            errors_outside_examples: summary.errors_outside_of_examples_count,
+            run_time: summary.duration,
+            load_time: None,
+            seed: self.seed,
+        })
+    }
+
+    /// The report as a tool result's `structuredContent`: the run's
+    /// `status` label, the summary with the seed, and every example, failed
+    /// ones first, with its exception and backtrace.
+    pub fn structured_content(&self, status: &str) -> serde_json::Value {
+        let mut examples: Vec<&ExampleReport> = self.examples.iter().collect();
+        examples.sort_by_key(|example| example.status != "failed");
+
+        serde_json::json!({
+            "status": status,
+            "partial": self.partial,
+            "summary": self.summary,
+            "summary_line": self.summary_line,
+            "seed": self.seed,
+            "examples": examples,
+        })
+    }
+
+    /// Failed examples as the failures parsed from text output would be.
+    pub fn failures(&self) -> Vec<FailureReport> {
+        self.examples
+            .iter()
+            .filter(|example| example.status == "failed")
+            .map(|example| FailureReport {
+                description: example.full_description.clone(),
+                location: Some(example.location()),
+                message: match &example.exception {
+                    Some(exception) => format!("{}:\n{}", exception.class, exception.message.trim_end()),
+                    None => String::new(),
+                },
+                sub_failures: Vec::new(),
+            })
+            .collect()
+    }
+
+    /// Per-file total run time, slowest first, limited to `limit` files.
+    ///
+    /// Examples without timing count as zero; files with no recorded time at
+    /// all are left out since there is nothing to rank them by.
+    pub fn slowest_files(&self, limit: usize) -> Vec<FileTiming> {
+        let mut files: Vec<FileTiming> = Vec::new();
+
+        for example in &self.examples {
+            let file_path = example.file_path.strip_prefix("./").unwrap_or(&example.file_path);
+            let run_time = example.run_time.unwrap_or(0.0);
+
+            match files.iter_mut().find(|f| f.file_path == file_path) {
+                Some(file) => {
+                    file.examples += 1;
+                    file.run_time += run_time;
+                }
+                None => files.push(FileTiming {
+                    file_path: file_path.to_string(),
+                    examples: 1,
+                    run_time,
+                }),
+            }
+        }
+
+        files.retain(|f| f.run_time > 0.0);
+        files.sort_by(|a, b| b.run_time.total_cmp(&a.run_time));
+        files.truncate(limit);
+        files
+    }
+
+    /// Examples grouped by top-level `describe` block, in run order. The
+    /// JSON report has no group metadata, so the block is taken from the
+    /// first scoped index of the example id (`./spec/x_spec.rb[1:...]`).
+    pub fn groups(&self) -> Vec<ExampleGroup> {
+        let mut keys: Vec<&str> = Vec::new();
+        let mut groups: Vec<ExampleGroup> = Vec::new();
+
+        for example in &self.examples {
+            let key = match example.id.split_once(':') {
+                Some((key, _)) => key,
+                None => example.id.strip_suffix(']').unwrap_or(&example.file_path),
+            };
+
+            let index = match keys.iter().position(|k| *k == key) {
+                Some(index) => index,
+                None => {
+                    keys.push(key);
+                    groups.push(ExampleGroup {
+                        name: String::new(),
+                        file_path: example.file_path.strip_prefix("./").unwrap_or(&example.file_path).to_string(),
+                        passed: 0,
+                        failed: 0,
+                        pending: 0,
+                        examples: Vec::new(),
+                    });
+                    groups.len() - 1
+                }
+            };
+
+            let group = &mut groups[index];
+            match example.status.as_str() {
+                "passed" => group.passed += 1,
+                "failed" => group.failed += 1,
+                "pending" => group.pending += 1,
+                _ => {}
+            }
+            group.examples.push(example.clone());
+        }
+
+        for group in &mut groups {
+            group.name = ExampleGroup::derive_name(&group.examples);
+        }
+        groups
+    }
+
+    /// The examples as a tree of files, `describe`/`context` blocks and
+    /// examples, in run order. Blocks come from the scoped indexes in the
+    /// example ids (`./spec/x_spec.rb[1:2:1]`). The report has no block
+    /// metadata, so a block's description is the words its examples' full
+    /// descriptions share beyond its parent's.
+    pub fn example_tree(&self) -> Vec<ExampleNode> {
+        let mut files: Vec<ExampleNode> = Vec::new();
+
+        for example in &self.examples {
+            let (file, scopes) = match example.id.strip_suffix(']').and_then(|id| id.split_once('[')) {
+                Some((file, scopes)) => (file.to_string(), scopes.split(':').collect::<Vec<_>>()),
+                None => (example.file_path.clone(), Vec::new()),
+            };
+            let words: Vec<String> = example
+                .full_description
+                .strip_suffix(&example.description)
+                .unwrap_or(&example.full_description)
+                .split_whitespace()
+                .map(str::to_string)
+                .collect();
+
+            let mut node = ExampleNode::child(&mut files, NodeKind::File, file.clone());
+            for depth in 1..scopes.len() {
+                let id = format!("{}[{}]", file, scopes[..depth].join(":"));
+                node = ExampleNode::child(&mut node.children, NodeKind::Group, id);
+                node.share(&words);
+            }
+            node.children.push(ExampleNode {
+                description: example.description.clone(),
+                line_number: example.line_number,
+                ..ExampleNode::new(NodeKind::Example, example.id.clone())
+            });
+        }
+
+        for file in &mut files {
+            file.description = file.id.strip_prefix("./").unwrap_or(&file.id).to_string();
+            ExampleNode::name_groups(&mut file.children, 0);
+        }
+        files
+    }
+
+    pub fn file_count(&self) -> usize {
+        let mut files: Vec<&str> = self.examples.iter().map(|e| e.file_path.as_str()).collect();
+        files.sort_unstable();
+        files.dedup();
+        files.len()
+    }
+}
+
+pub fn format_slowest_files(files: &[FileTiming]) -> String {
+    files
+        .iter()
+        .map(|f| {
+            format!(
+                "  {:.4} seconds {} ({} example{})",
+                f.run_time,
+                f.file_path,
+                f.examples,
+                if f.examples == 1 { "" } else { "s" }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The `Slowest Examples` and `Slowest Groups` sections for a profile, as
+/// RSpec prints them with `--profile`.
+pub fn format_profile(profile: &ProfileReport) -> String {
+    let mut text = String::from("Slowest Examples");
+    if let (Some(slowest), Some(total)) = (profile.slowest, profile.total)
+        && total > 0.0
+    {
+        text.push_str(&format!(
+            " ({:.4} seconds, {:.1}% of {:.4} seconds)",
+            slowest,
+            slowest * 100.0 / total,
+            total
+        ));
+    }
+    text.push(':');
+    for example in &profile.examples {
+        text.push_str(&format!(
+            "\n  {:.4} seconds {} {}",
+            example.run_time.unwrap_or_default(),
+            example.location(),
+            example.full_description
+        ));
+    }
+
+    if !profile.groups.is_empty() {
+        text.push_str("\n\nSlowest Groups:");
+        for group in &profile.groups {
+            text.push_str(&format!(
+                "\n  {:.4} seconds average ({:.4} seconds / {} example{}) {} {}",
+                group.average,
+                group.total_time,
+                group.count,
+                if group.count == 1 { "" } else { "s" },
+                group.location.strip_prefix("./").unwrap_or(&group.location),
+                group.description
+            ));
+        }
+    }
+    text
+}
+
+/// The summary line, then one `[status] path:line full description` line
+/// per example, failed ones first.
+pub fn format_report(report: &RspecReport) -> String {
+    let rank = |example: &&ExampleReport| match example.status.as_str() {
+        "failed" => 0,
+        "pending" => 1,
+        _ => 2,
+    };
+    let mut examples: Vec<&ExampleReport> = report.examples.iter().collect();
+    examples.sort_by_key(rank);
+
+    let mut lines: Vec<String> = report.summary_line.iter().cloned().collect();
+    lines.extend(
+        examples
+            .iter()
+            .map(|example| format!("  [{}] {} {}", example.status, example.location(), example.full_description)),
+    );
+    lines.join("\n")
+}
+
+/// One `[status] full description` line per example.
+pub fn format_examples(examples: &[ExampleReport]) -> String {
+    examples
+        .iter()
+        .map(|example| format!("  [{}] {}", example.status, example.full_description))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// One line per file, block and example, indented by depth. Blocks and
+/// examples end with the `[scope]` of their id.
+pub fn format_example_tree(nodes: &[ExampleNode]) -> String {
+    fn push_lines(lines: &mut Vec<String>, nodes: &[ExampleNode], depth: usize) {
+        for node in nodes {
+            let indent = "  ".repeat(depth);
+            lines.push(match (node.kind, node.line_number) {
+                (NodeKind::File, _) => node.description.clone(),
+                (NodeKind::Group, _) if node.description.is_empty() => format!("{}{}", indent, node.scope()),
+                (NodeKind::Group, _) => format!("{}{} {}", indent, node.description, node.scope()),
+                (NodeKind::Example, Some(line)) => format!("{}- {} (line {}) {}", indent, node.description, line, node.scope()),
+                (NodeKind::Example, None) => format!("{}- {} {}", indent, node.description, node.scope()),
+            });
+            push_lines(lines, &node.children, depth + 1);
+        }
+    }
+
+    let mut lines = Vec::new();
+    push_lines(&mut lines, nodes, 0);
+    lines.join("\n")
+}
+
+pub fn format_groups(groups: &[ExampleGroup]) -> String {
+    let mut lines = Vec::new();
+
+    for group in groups {
+        let mut counts = format!("{} passed, {} failed", group.passed, group.failed);
+        if group.pending > 0 {
+            counts.push_str(&format!(", {} pending", group.pending));
+        }
+        lines.push(format!("  {} ({}): {}", group.name, group.file_path, counts));
+
+        for example in &group.examples {
+            let description = example
+                .full_description
+                .strip_prefix(&group.name)
+                .map(str::trim)
+                .filter(|d| !d.is_empty())
+                .unwrap_or(&example.description);
+            lines.push(format!("    [{}] {}", example.status, description));
+        }
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MULTI_FILE_REPORT: &str = r#"{
+        "version": "3.12.2",
+        "examples": [
+            {"id": "./spec/models/user_spec.rb[1:1]", "file_path": "./spec/models/user_spec.rb", "run_time": 0.5},
+            {"id": "./spec/models/user_spec.rb[1:2]", "file_path": "./spec/models/user_spec.rb", "run_time": 0.25},
+            {"id": "./spec/models/order_spec.rb[1:1]", "file_path": "./spec/models/order_spec.rb", "run_time": 1.5},
+            {"id": "./spec/models/tag_spec.rb[1:1]", "file_path": "./spec/models/tag_spec.rb", "run_time": 0.1},
+            {"id": "./spec/models/tag_spec.rb[1:2]", "file_path": "./spec/models/tag_spec.rb"},
+            {"id": "./spec/models/noop_spec.rb[1:1]", "file_path": "./spec/models/noop_spec.rb", "run_time": 0.0}
+        ],
+        "summary": {"duration": 2.4, "example_count": 6, "failure_count": 0}
+    }"#;
+
+    #[test]
+    fn test_slowest_files() {
+        let report = RspecReport::from_output(MULTI_FILE_REPORT).unwrap();
+        let files = report.slowest_files(5);
+
+        assert_eq!(
+            files,
+            vec![
+                FileTiming {
+                    file_path: "spec/models/order_spec.rb".to_string(),
+                    examples: 1,
+                    run_time: 1.5,
+                },
+                FileTiming {
+                    file_path: "spec/models/user_spec.rb".to_string(),
+                    examples: 2,
+                    run_time: 0.75,
+                },
+                FileTiming {
+                    file_path: "spec/models/tag_spec.rb".to_string(),
+                    examples: 2,
+                    run_time: 0.1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slowest_files_limit() {
+        let report = RspecReport::from_output(MULTI_FILE_REPORT).unwrap();
+        let files = report.slowest_files(1);
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].file_path, "spec/models/order_spec.rb");
+    }
+
+    #[test]
+    fn test_file_count() {
+        let report = RspecReport::from_output(MULTI_FILE_REPORT).unwrap();
+        assert_eq!(report.file_count(), 4);
+    }
+
+    #[test]
+    fn test_from_output_skips_leading_noise() {
+        let output = format!("Debug: connecting\n{}\n", MULTI_FILE_REPORT);
+        let report = RspecReport::from_output(&output).unwrap();
+        assert_eq!(report.examples.len(), 6);
+    }
+
+    #[test]
+    fn test_from_output_without_json() {
+        assert!(RspecReport::from_output("..F\n\n3 examples, 1 failure").is_none());
+    }
+
+    const MULTI_GROUP_REPORT: &str = r#"{
+        "version": "3.12.2",
+        "examples": [
+            {"id": "./spec/models/user_spec.rb[1:1:1]", "description": "saves", "full_description": "User #save saves", "status": "passed", "file_path": "./spec/models/user_spec.rb"},
+            {"id": "./spec/models/user_spec.rb[1:2]", "description": "has a name", "full_description": "User has a name", "status": "passed", "file_path": "./spec/models/user_spec.rb"},
+            {"id": "./spec/models/user_spec.rb[2:1]", "description": "is pending", "full_description": "User::Profile is pending", "status": "pending", "file_path": "./spec/models/user_spec.rb"},
+            {"id": "./spec/requests/auth_spec.rb[1:1]", "description": "logs in", "full_description": "Auth login flow logs in", "status": "failed", "file_path": "./spec/requests/auth_spec.rb"},
+            {"id": "./spec/requests/auth_spec.rb[1:2]", "description": "logs out", "full_description": "Auth login flow logs out", "status": "failed", "file_path": "./spec/requests/auth_spec.rb"}
+        ]
+    }"#;
+
+    const FAILING_REPORT: &str = r#"{
+        "version": "3.12.2",
+        "seed": 4242,
+        "examples": [
+            {"id": "./spec/models/user_spec.rb[1:1]", "description": "saves", "full_description": "User saves", "status": "passed", "file_path": "./spec/models/user_spec.rb", "line_number": 4},
+            {"id": "./spec/models/user_spec.rb[1:2]", "description": "archives", "full_description": "User archives", "status": "pending", "file_path": "./spec/models/user_spec.rb", "line_number": 8},
+            {"id": "./spec/models/user_spec.rb[1:3]", "description": "rejects blanks", "full_description": "User rejects blanks", "status": "failed", "file_path": "./spec/models/user_spec.rb", "line_number": 12,
+             "exception": {"class": "RSpec::Expectations::ExpectationNotMetError", "message": "expected true\n     got false\n", "backtrace": ["./spec/models/user_spec.rb:13:in `block (2 levels) in <top (required)>'"]}}
+        ],
+        "summary": {"duration": 0.25, "example_count": 3, "failure_count": 1, "pending_count": 1, "errors_outside_of_examples_count": 0},
+        "summary_line": "3 examples, 1 failure, 1 pending"
+    }"#;
+
+    #[test]
+    fn test_format_report_lists_failures_first() {
+        let report = RspecReport::from_output(FAILING_REPORT).unwrap();
+        assert_eq!(
+            format_report(&report),
+            "3 examples, 1 failure, 1 pending
+  [failed] spec/models/user_spec.rb:12 User rejects blanks
+  [pending] spec/models/user_spec.rb:8 User archives
+  [passed] spec/models/user_spec.rb:4 User saves"
+        );
+    }
+
+    #[test]
+    fn test_report_summary_and_failures() {
+        let report = RspecReport::from_output(FAILING_REPORT).unwrap();
+        let summary = report.run_summary().unwrap();
+        assert_eq!((summary.examples, summary.failures, summary.pending), (3, 1, 1));
+        assert_eq!(summary.run_time, Some(0.25));
+        assert_eq!(summary.seed, Some(4242));
+
+        assert_eq!(
+            report.failures(),
+            vec![FailureReport {
+                description: "User rejects blanks".to_string(),
+                location: Some("spec/models/user_spec.rb:12".to_string()),
+                message: "RSpec::Expectations::ExpectationNotMetError:\nexpected true\n     got false".to_string(),
+                sub_failures: Vec::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_structured_content() {
+        let report = RspecReport::from_output(FAILING_REPORT).unwrap();
+        let content = report.structured_content("failed");
+
+        assert_eq!(content["status"], "failed");
+        assert_eq!(content["partial"], false);
+        assert_eq!(content["seed"], 4242);
+        assert_eq!(content["summary"]["example_count"], 3);
+        assert_eq!(content["summary"]["duration"], 0.25);
+        assert_eq!(content["summary_line"], "3 examples, 1 failure, 1 pending");
+        assert_eq!(
+            content["examples"][0],
+            serde_json::json!({
+                "id": "./spec/models/user_spec.rb[1:3]",
+                "description": "rejects blanks",
+                "full_description": "User rejects blanks",
+                "status": "failed",
+                "pending_message": null,
+                "file_path": "./spec/models/user_spec.rb",
+                "line_number": 12,
+                "run_time": null,
+                "exception": {
+                    "class": "RSpec::Expectations::ExpectationNotMetError",
+                    "message": "expected true\n     got false\n",
+                    "backtrace": ["./spec/models/user_spec.rb:13:in `block (2 levels) in <top (required)>'"]
+                }
+            })
+        );
+        assert_eq!(content["examples"][1]["status"], "passed");
+        assert_eq!(content["examples"][2]["status"], "pending");
+    }
+
+    #[test]
+    fn test_exception_with_null_backtrace() {
+        let exception: ExampleException = serde_json::from_str(r#"{"class": "RuntimeError", "message": "boom", "backtrace": null}"#).unwrap();
+        assert!(exception.backtrace.is_empty());
+    }
+
+    #[test]
+    fn test_from_partial_output_recovers_completed_examples() {
+        let truncated = r#"{"version":"3.12.2","examples":[{"id":"./spec/models/user_spec.rb[1:1]","description":"saves","full_description":"User saves","status":"passed","file_path":"./spec/models/user_spec.rb","run_time":0.01},
+{"id":"./spec/models/user_spec.rb[1:2]","description":"validates","full_description":"User validates","status":"failed","file_path":"./spec/models/user_spec.rb","run_time":0.02},{"id":"./spec/models/user_spec.rb[1:3]","descrip"#;
+
+        let report = RspecReport::from_partial_output(truncated).unwrap();
+        assert!(report.partial);
+        assert_eq!(report.examples.len(), 2);
+        assert_eq!(
+            format_examples(&report.examples),
+            "  [passed] User saves\n  [failed] User validates"
+        );
+    }
+
+    #[test]
+    fn test_from_partial_output_with_complete_report() {
+        let complete = r#"{"version":"3.12.2","examples":[{"description":"saves","full_description":"User saves","status":"passed"}]}"#;
+        let report = RspecReport::from_partial_output(complete).unwrap();
+        assert!(!report.partial);
+        assert_eq!(report.examples.len(), 1);
+
+        assert!(RspecReport::from_partial_output("Finished in 0.1 seconds").is_none());
+    }
+
+    #[test]
+    fn test_groups() {
+        let report = RspecReport::from_output(MULTI_GROUP_REPORT).unwrap();
+        let groups = report.groups();
+
+        let summary: Vec<_> = groups
+            .iter()
+            .map(|g| (g.name.as_str(), g.file_path.as_str(), g.passed, g.failed, g.pending, g.examples.len()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("User", "spec/models/user_spec.rb", 2, 0, 0, 2),
+                ("User::Profile", "spec/models/user_spec.rb", 0, 0, 1, 1),
+                ("Auth login flow", "spec/requests/auth_spec.rb", 0, 2, 0, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_groups() {
+        let report = RspecReport::from_output(MULTI_GROUP_REPORT).unwrap();
+
+        assert_eq!(
+            format_groups(&report.groups()),
+            "  User (spec/models/user_spec.rb): 2 passed, 0 failed\n    [passed] #save saves\n    [passed] has a name\n  User::Profile (spec/models/user_spec.rb): 0 passed, 0 failed, 1 pending\n    [pending] is pending\n  Auth login flow (spec/requests/auth_spec.rb): 0 passed, 2 failed\n    [failed] logs in\n    [failed] logs out"
+        );
+    }
+
+    const NESTED_REPORT: &str = r#"{
+        "examples": [
+            {"id": "./spec/models/user_spec.rb[1:1:1]", "description": "returns the full name", "full_description": "User #name returns the full name", "file_path": "./spec/models/user_spec.rb", "line_number": 5},
+            {"id": "./spec/models/user_spec.rb[1:1:2:1]", "description": "is blank", "full_description": "User #name without a last name is blank", "file_path": "./spec/models/user_spec.rb", "line_number": 10},
+            {"id": "./spec/models/user_spec.rb[1:2]", "description": "is valid", "full_description": "User is valid", "file_path": "./spec/models/user_spec.rb", "line_number": 15},
+            {"id": "./spec/models/tag_spec.rb[1:1]", "description": "example at ./spec/models/tag_spec.rb:3", "full_description": "Tag example at ./spec/models/tag_spec.rb:3", "file_path": "./spec/models/tag_spec.rb", "line_number": 3}
+        ]
+    }"#;
+
+    #[test]
+    fn test_example_tree() {
+        let report = RspecReport::from_output(NESTED_REPORT).unwrap();
+        let tree = report.example_tree();
+
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].kind, NodeKind::File);
+        let user = &tree[0].children[0];
+        assert_eq!((user.id.as_str(), user.description.as_str()), ("./spec/models/user_spec.rb[1]", "User"));
+        let name = &user.children[0];
+        assert_eq!((name.id.as_str(), name.description.as_str()), ("./spec/models/user_spec.rb[1:1]", "#name"));
+        assert_eq!(name.children[1].description, "without a last name");
+        assert_eq!(user.children[1].kind, NodeKind::Example);
+        assert_eq!(user.children[1].line_number, Some(15));
+    }
+
+    #[test]
+    fn test_format_example_tree() {
+        let report = RspecReport::from_output(NESTED_REPORT).unwrap();
+
+        assert_eq!(
+            format_example_tree(&report.example_tree()),
+            "spec/models/user_spec.rb\n  User [1]\n    #name [1:1]\n      - returns the full name (line 5) [1:1:1]\n      without a last name [1:1:2]\n        - is blank (line 10) [1:1:2:1]\n    - is valid (line 15) [1:2]\nspec/models/tag_spec.rb\n  Tag [1]\n    - example at ./spec/models/tag_spec.rb:3 (line 3) [1:1]"
+        );
+    }
+
+    #[test]
+    fn test_flat_examples_kept() {
+        let report = RspecReport::from_output(MULTI_GROUP_REPORT).unwrap();
+        assert_eq!(report.examples.len(), 5);
+        assert_eq!(report.examples[3].full_description, "Auth login flow logs in");
+    }
+
+    #[test]
+    fn test_format_slowest_files() {
+        let files = vec![
+            FileTiming {
+                file_path: "spec/models/order_spec.rb".to_string(),
+                examples: 1,
+                run_time: 1.5,
+            },
+            FileTiming {
+                file_path: "spec/models/user_spec.rb".to_string(),
+                examples: 2,
+                run_time: 0.75,
+            },
+        ];
+
+        assert_eq!(
+            format_slowest_files(&files),
+            "  1.5000 seconds spec/models/order_spec.rb (1 example)\n  0.7500 seconds spec/models/user_spec.rb (2 examples)"
+        );
+    }
+
+    #[test]
+    fn test_profile() {
+        let output = r#"{"version":"3.13.0","examples":[],"profile":{
+            "examples":[{"id":"./spec/models/user_spec.rb[1:1]","description":"saves","full_description":"User saves","status":"passed","file_path":"./spec/models/user_spec.rb","line_number":3,"run_time":0.75}],
+            "slowest":0.75,"total":1.5,
+            "groups":[{"total_time":1.25,"count":2,"description":"User","average":0.625,"location":"./spec/models/user_spec.rb:1"}]
+        }}"#;
+        let profile = RspecReport::from_output(output).unwrap().profile.unwrap();
+
+        assert_eq!(profile.groups[0].count, 2);
+        assert_eq!(
+            format_profile(&profile),
+            "Slowest Examples (0.7500 seconds, 50.0% of 1.5000 seconds):\n  0.7500 seconds spec/models/user_spec.rb:3 User saves\n\nSlowest Groups:\n  0.6250 seconds average (1.2500 seconds / 2 examples) spec/models/user_spec.rb:1 User"
+        );
+        assert!(RspecReport::from_output(r#"{"examples":[]}"#).unwrap().profile.is_none());
+    }
+}
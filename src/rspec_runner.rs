@@ -1,56 +1,712 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
 use async_trait::async_trait;
-use tokio::process::Command;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::arg_order::ArgOrder;
+use crate::backpressure::InFlightLimit;
+use crate::command_runner::{CommandResult, CommandRunner, RunContext, shell_join};
+use crate::isolated_home::IsolatedHome;
+use crate::process_runner::{CommandSpec, ProcessCommandRunner, ProcessRunner};
+use crate::process_tracker::ProcessTracker;
+use crate::rspec_version::RspecVersion;
+use crate::run_metrics::RunMetrics;
+use crate::sandbox::SandboxPrefix;
+use crate::spring::{self, SPRING_COMMAND, SpringMode};
+
+const VERSION_DETECTION_TIMEOUT: Duration = Duration::from_secs(60);
 
-use crate::command_runner::{CommandResult, CommandRunner};
+/// How long warming Spring up may take; a large Rails app can take a while
+/// to boot.
+const SPRING_WARM_UP_TIMEOUT: Duration = Duration::from_secs(300);
 
+/// Turns paths and a run's context into a command and hands it to a
+/// `ProcessRunner`, a real child process unless a test swaps it out.
 #[derive(Clone)]
 pub struct RspecRunner {
     cmd: String,
     args: Vec<String>,
+    format_args: Vec<String>,
+    env: Vec<(String, String)>,
+    env_clear: bool,
+    arg_order: ArgOrder,
+    process: Arc<dyn ProcessRunner>,
+    abort_on_output_bytes: Option<usize>,
+    sandbox_prefix: SandboxPrefix,
+    workdir: Option<PathBuf>,
+    isolated_home: Option<IsolatedHome>,
+    in_flight: Option<InFlightLimit>,
+    run_metrics: Option<RunMetrics>,
+    spring: SpringMode,
 }
 
 impl RspecRunner {
+    /// A blank `command` doesn't panic here; runs fail with an error instead.
     pub fn new(command: String) -> Self {
         let parts: Vec<String> = command
             .split_whitespace()
             .map(|s| s.to_string())
             .collect();
 
-        let cmd = parts.first()
-            .expect("Command cannot be empty")
-            .to_string();
+        let cmd = parts.first().cloned().unwrap_or_default();
 
         let args = parts.get(1..).unwrap_or(&[]).to_vec();
 
-        Self { cmd, args }
+        Self {
+            cmd,
+            args,
+            format_args: Vec::new(),
+            env: Vec::new(),
+            env_clear: false,
+            arg_order: ArgOrder::default(),
+            process: Arc::new(ProcessCommandRunner::default()),
+            abort_on_output_bytes: None,
+            sandbox_prefix: SandboxPrefix::default(),
+            workdir: None,
+            isolated_home: None,
+            in_flight: None,
+            run_metrics: None,
+            spring: SpringMode::Off,
+        }
+    }
+
+    pub fn with_process_tracker(mut self, process_tracker: ProcessTracker) -> Self {
+        self.process = Arc::new(ProcessCommandRunner::new(process_tracker));
+        self
+    }
+
+    /// Start processes with `process` instead of as local children, e.g.
+    /// over SSH.
+    pub fn with_process_runner(mut self, process: Arc<dyn ProcessRunner>) -> Self {
+        self.process = process;
+        self
+    }
+
+    pub fn with_arg_order(mut self, arg_order: ArgOrder) -> Self {
+        self.arg_order = arg_order;
+        self
+    }
+
+    pub fn with_format_args(mut self, format_args: Vec<String>) -> Self {
+        self.format_args = format_args;
+        self
+    }
+
+    /// Environment variables set for every RSpec process.
+    pub fn with_env(mut self, env: Vec<(String, String)>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Start runs from an empty environment instead of the server's, keeping
+    /// only `PATH` unless `with_env` sets it.
+    pub fn with_env_clear(mut self, enabled: bool) -> Self {
+        self.env_clear = enabled;
+        self
+    }
+
+    /// Kill the process once stdout and stderr together exceed `limit` bytes.
+    pub fn with_abort_on_output_bytes(mut self, limit: Option<usize>) -> Self {
+        self.abort_on_output_bytes = limit;
+        self
+    }
+
+    pub fn with_sandbox_prefix(mut self, sandbox_prefix: SandboxPrefix) -> Self {
+        self.sandbox_prefix = sandbox_prefix;
+        self
+    }
+
+    /// Directory used for version detection and for runs whose context
+    /// doesn't name one.
+    pub fn with_workdir(mut self, workdir: Option<PathBuf>) -> Self {
+        self.workdir = workdir;
+        self
+    }
+
+    pub fn with_isolated_home(mut self, isolated_home: Option<IsolatedHome>) -> Self {
+        self.isolated_home = isolated_home;
+        self
+    }
+
+    /// Cap concurrent runs; shared by every clone of the runner.
+    pub fn with_in_flight_limit(mut self, in_flight: Option<InFlightLimit>) -> Self {
+        self.in_flight = in_flight;
+        self
+    }
+
+    /// Count runs and their durations in `run_metrics`, for `/metrics`.
+    pub fn with_run_metrics(mut self, run_metrics: RunMetrics) -> Self {
+        self.run_metrics = Some(run_metrics);
+        self
+    }
+
+    /// Run through Spring where `spring` says to, falling back to the
+    /// configured command when Spring fails.
+    pub fn with_spring(mut self, spring: SpringMode) -> Self {
+        self.spring = spring;
+        self
+    }
+
+    /// Boots the app in Spring for the default workdir with `bin/spring
+    /// rspec --version`, so the first run doesn't pay for it.
+    pub async fn warm_up_spring(&self) -> Result<(), String> {
+        let root = self.workdir.clone().unwrap_or_else(|| PathBuf::from("."));
+        self.spring.choose(&root, &self.env, !self.env_clear)?;
+        let base: Vec<String> = SPRING_COMMAND.iter().map(|part| part.to_string()).collect();
+        let spec = CommandSpec {
+            argv: self.sandbox_prefix.wrap([base, vec!["--version".to_string()]].concat()),
+            workdir: self.workdir.clone(),
+            env: self.env.clone(),
+            env_clear: self.env_clear,
+            timeout: Some(SPRING_WARM_UP_TIMEOUT),
+            ..Default::default()
+        };
+        let result = self.process.spawn(&spec, None).await?;
+        if result.exit_code == 0 {
+            return Ok(());
+        }
+        Err(spring::failure(&result).unwrap_or_else(|| format!("bin/spring rspec --version exited with {}", result.exit_code)))
+    }
+
+    fn build_argv(&self, paths: &[String], extra_args: &[String]) -> Vec<String> {
+        let base: Vec<String> = std::iter::once(self.cmd.clone())
+            .chain(self.args.iter().cloned())
+            .collect();
+        self.assemble_argv(&base, paths, extra_args)
+    }
+
+    /// `build_argv` for a run, with the context's command in place of the
+    /// runner's when it sets one.
+    fn context_argv(&self, paths: &[String], context: &RunContext) -> Vec<String> {
+        if let (Some(parallel), Some(command)) = (&context.parallel, &context.command) {
+            let options: Vec<String> = self.format_args.iter().chain(&context.extra_args).cloned().collect();
+            return self.sandbox_prefix.wrap(parallel.argv(command, &options, paths));
+        }
+        match &context.command {
+            Some(command) => self.assemble_argv(command, paths, &context.extra_args),
+            None => self.build_argv(paths, &context.extra_args),
+        }
+    }
+
+    fn assemble_argv(&self, base: &[String], paths: &[String], extra_args: &[String]) -> Vec<String> {
+        let options: Vec<String> = self
+            .format_args
+            .iter()
+            .chain(extra_args)
+            .cloned()
+            .collect();
+
+        let argv = self
+            .arg_order
+            .assemble(base, &options, paths);
+        self.sandbox_prefix.wrap(argv)
+    }
+
+    async fn execute(
+        &self,
+        paths: &[String],
+        context: &RunContext,
+        lines: Option<UnboundedSender<String>>,
+    ) -> Result<CommandResult, String> {
+        // Held until the run finishes
+        let cancelled = async {
+            match &context.cancel {
+                Some(cancel) => cancel.cancelled().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(cancelled);
+        let _slot = match &self.in_flight {
+            Some(in_flight) => tokio::select! {
+                slot = in_flight.acquire(|position| {
+                    if let Some(notices) = &context.notices {
+                        let _ = notices.send(format!("Waiting for a run slot: position {} in the queue", position));
+                    }
+                }) => Some(slot?),
+                _ = &mut cancelled => return Err("Cancelled while waiting for a run slot".to_string()),
+            },
+            None => None,
+        };
+        let run = self.run_metrics.as_ref().map(RunMetrics::started);
+        let result = self.spawn(paths, context, lines).await;
+        if let Some(run) = run {
+            let passed = matches!(&result, Ok(r) if r.exit_code == 0 && !r.timed_out && !r.cancelled);
+            run.finished(passed);
+        }
+        result
+    }
+
+    async fn spawn(
+        &self,
+        paths: &[String],
+        context: &RunContext,
+        lines: Option<UnboundedSender<String>>,
+    ) -> Result<CommandResult, String> {
+        if self.cmd.is_empty() {
+            return Err("The RSpec command is empty; set --rspec-cmd, e.g. \"bundle exec rspec\"".to_string());
+        }
+        // Held until the process exits so a per-run home outlives it
+        let home = self.isolated_home.as_ref().map(IsolatedHome::prepare).transpose()?;
+        let spec = CommandSpec {
+            argv: self.context_argv(paths, context),
+            workdir: context.workdir.clone().or_else(|| self.workdir.clone()),
+            env: home
+                .iter()
+                .flat_map(|home| home.env())
+                .chain(self.env.iter().cloned())
+                .chain(context.env.iter().cloned())
+                .collect(),
+            env_clear: self.env_clear,
+            timeout: context.timeout,
+            cancel: context.cancel.clone(),
+            output_limit: self.abort_on_output_bytes,
+        };
+
+        if self.spring == SpringMode::Off || context.command.is_some() || context.parallel.is_some() {
+            return self.process.spawn(&spec, lines).await;
+        }
+        let configured = shell_join(&[vec![self.cmd.clone()], self.args.clone()].concat());
+        let root = spec.workdir.clone().unwrap_or_else(|| PathBuf::from("."));
+        if let Err(reason) = self.spring.choose(&root, &spec.env, !self.env_clear) {
+            let result = self.process.spawn(&spec, lines).await?;
+            let launcher = format!("{} (not Spring: {})", configured, reason);
+            return Ok(CommandResult { launcher: Some(launcher), ..result });
+        }
+
+        let base: Vec<String> = SPRING_COMMAND.iter().map(|part| part.to_string()).collect();
+        let spring_spec = CommandSpec { argv: self.assemble_argv(&base, paths, &context.extra_args), ..spec.clone() };
+        let reason = match self.process.spawn(&spring_spec, lines.clone()).await {
+            Ok(result) => match spring::failure(&result) {
+                None => return Ok(CommandResult { launcher: Some(format!("Spring ({})", shell_join(&base))), ..result }),
+                Some(reason) => reason,
+            },
+            Err(e) => e,
+        };
+
+        tracing::warn!(reason = %reason, "Spring failed; rerunning without it");
+        if let Some(notices) = &context.notices {
+            let _ = notices.send(format!("Spring failed ({}); rerunning with {}", reason, configured));
+        }
+        // Binstubs such as bin/rspec would otherwise try Spring again
+        let mut spec = spec;
+        spec.env.push(("DISABLE_SPRING".to_string(), "1".to_string()));
+        let result = self.process.spawn(&spec, lines).await?;
+        let launcher = format!("{} (Spring failed: {})", configured, reason);
+        Ok(CommandResult { launcher: Some(launcher), ..result })
     }
 }
 
 #[async_trait]
 impl CommandRunner for RspecRunner {
-    async fn run(&self, file_path: &str) -> Result<CommandResult, String> {
+    async fn run_paths(&self, paths: &[String], context: &RunContext) -> Result<CommandResult, String> {
+        self.execute(paths, context, None).await
+    }
 
-        let mut cmd = Command::new(&self.cmd);
+    async fn run_streaming(
+        &self,
+        paths: &[String],
+        context: &RunContext,
+        lines: UnboundedSender<String>,
+    ) -> Result<CommandResult, String> {
+        self.execute(paths, context, Some(lines)).await
+    }
 
-        for arg in &self.args {
-            cmd.arg(arg);
+    fn command_line(&self, paths: &[String], context: &RunContext) -> Vec<String> {
+        self.context_argv(paths, context)
+    }
+
+    /// Runs the configured command with `--version` so flag support can be
+    /// checked up front. Returns `None` if the version can't be determined.
+    async fn detect_version(&self) -> Option<RspecVersion> {
+        if self.cmd.is_empty() {
+            return None;
+        }
+        let spec = CommandSpec {
+            argv: self.build_argv(&["--version".to_string()], &[]),
+            workdir: self.workdir.clone(),
+            timeout: Some(VERSION_DETECTION_TIMEOUT),
+            ..Default::default()
+        };
+
+        match self.process.spawn(&spec, None).await {
+            Ok(result) if result.timed_out => {
+                tracing::warn!("rspec version detection timed out");
+                None
+            }
+            Ok(result) => RspecVersion::parse(&result.stdout),
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to run rspec version detection");
+                None
+            }
         }
+    }
+}
 
-        cmd.arg(file_path);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tokio_util::sync::CancellationToken;
 
-        match cmd.output().await {
-            Ok(output) => {
-                let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                let exit_code = output.status.code().unwrap_or(-1);
+    use crate::parallel::{ParallelRun, ParallelStrategy};
 
-                Ok(CommandResult {
-                    exit_code,
-                    stdout,
-                    stderr,
-                })
-            }
-            Err(e) => Err(format!("Command execution failed: {}", e)),
+    /// Records each spec instead of starting a process, replying with
+    /// `stdout`.
+    #[derive(Default)]
+    struct RecordingProcess {
+        stdout: String,
+        specs: Mutex<Vec<CommandSpec>>,
+    }
+
+    #[async_trait]
+    impl ProcessRunner for RecordingProcess {
+        async fn spawn(&self, spec: &CommandSpec, _lines: Option<UnboundedSender<String>>) -> Result<CommandResult, String> {
+            self.specs.lock().unwrap().push(spec.clone());
+            Ok(CommandResult {
+                exit_code: 0,
+                stdout: self.stdout.clone(),
+                stderr: String::new(),
+                abort_reason: None,
+                signal: None,
+                timed_out: false,
+                cancelled: false,
+                peak_memory_mb: None,
+                launcher: None,
+            })
         }
     }
+
+    #[test]
+    fn test_build_argv_default_order() {
+        let runner = RspecRunner::new("bundle exec rspec".to_string());
+        assert_eq!(
+            runner.build_argv(&["spec/models/user_spec.rb:12".to_string()], &[]),
+            vec!["bundle", "exec", "rspec", "spec/models/user_spec.rb:12"]
+        );
+    }
+
+    #[test]
+    fn test_build_argv_custom_order() {
+        let runner = RspecRunner::new("docker compose exec web".to_string())
+            .with_arg_order("{base} rspec {path}".parse().unwrap());
+        assert_eq!(
+            runner.build_argv(&["spec/models/user_spec.rb".to_string()], &[]),
+            vec!["docker", "compose", "exec", "web", "rspec", "spec/models/user_spec.rb"]
+        );
+    }
+
+    #[test]
+    fn test_build_argv_sandbox_prefix() {
+        let runner = RspecRunner::new("bundle exec rspec".to_string())
+            .with_sandbox_prefix("firejail --net=none".parse().unwrap());
+        assert_eq!(
+            runner.build_argv(&["spec/models/user_spec.rb:12".to_string()], &["--fail-fast".to_string()]),
+            vec!["firejail", "--net=none", "bundle", "exec", "rspec", "--fail-fast", "spec/models/user_spec.rb:12"]
+        );
+    }
+
+    #[test]
+    fn test_context_command_replaces_runner_command() {
+        let runner = RspecRunner::new("bundle exec rspec".to_string()).with_format_args(vec!["-f".to_string(), "p".to_string()]);
+        let context = RunContext {
+            command: Some(vec!["bin/rspec".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            runner.command_line(&["spec/a_spec.rb".to_string()], &context),
+            vec!["bin/rspec", "-f", "p", "spec/a_spec.rb"]
+        );
+    }
+
+    #[test]
+    fn test_parallel_run_wraps_rspec_options() {
+        let runner = RspecRunner::new("bundle exec rspec".to_string()).with_format_args(vec!["-f".to_string(), "p".to_string()]);
+        let context = RunContext {
+            extra_args: vec!["--tag".to_string(), "fast".to_string()],
+            command: Some(vec!["parallel_rspec".to_string()]),
+            parallel: Some(ParallelRun { strategy: ParallelStrategy::ParallelTests, processes: Some(2) }),
+            ..Default::default()
+        };
+        assert_eq!(
+            runner.command_line(&["spec".to_string()], &context),
+            vec!["parallel_rspec", "-n", "2", "--serialize-stdout", "--test-options", "-f p --tag fast", "spec"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_builds_command_spec() {
+        let process = Arc::new(RecordingProcess::default());
+        let runner = RspecRunner::new("bundle exec rspec".to_string())
+            .with_process_runner(process.clone())
+            .with_format_args(vec!["-f".to_string(), "json".to_string()])
+            .with_env(vec![("RAILS_ENV".to_string(), "test".to_string()), ("LOG".to_string(), "info".to_string())])
+            .with_env_clear(true)
+            .with_abort_on_output_bytes(Some(4096))
+            .with_workdir(Some(PathBuf::from("/srv/app")));
+        let context = RunContext {
+            extra_args: vec!["--fail-fast".to_string()],
+            env: vec![("LOG".to_string(), "debug".to_string())],
+            timeout: Some(Duration::from_secs(30)),
+            ..Default::default()
+        };
+
+        runner.run("spec/models/user_spec.rb:12", &context).await.unwrap();
+        let spec = process.specs.lock().unwrap().pop().unwrap();
+        assert_eq!(spec.argv, ["bundle", "exec", "rspec", "-f", "json", "--fail-fast", "spec/models/user_spec.rb:12"]);
+        assert_eq!(spec.workdir, Some(PathBuf::from("/srv/app")));
+        assert_eq!(
+            spec.env,
+            [("RAILS_ENV", "test"), ("LOG", "info"), ("LOG", "debug")].map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+        assert!(spec.env_clear);
+        assert_eq!(spec.timeout, Some(Duration::from_secs(30)));
+        assert_eq!(spec.output_limit, Some(4096));
+
+        let context = RunContext { workdir: Some(PathBuf::from("/srv/billing")), ..Default::default() };
+        runner.run("spec", &context).await.unwrap();
+        assert_eq!(process.specs.lock().unwrap()[0].workdir, Some(PathBuf::from("/srv/billing")));
+    }
+
+    #[tokio::test]
+    async fn test_detect_version() {
+        let process = Arc::new(RecordingProcess { stdout: "RSpec 3.13\n  - rspec-core 3.13.0\n".to_string(), ..Default::default() });
+        let runner = RspecRunner::new("bundle exec rspec".to_string())
+            .with_process_runner(process.clone())
+            .with_env(vec![("RAILS_ENV".to_string(), "test".to_string())])
+            .with_workdir(Some(PathBuf::from("/srv/app")));
+
+        assert_eq!(runner.detect_version().await, Some(RspecVersion::new(3, 13, 0)));
+        let spec = process.specs.lock().unwrap().pop().unwrap();
+        assert_eq!(spec.argv, ["bundle", "exec", "rspec", "--version"]);
+        assert_eq!(spec.workdir, Some(PathBuf::from("/srv/app")));
+        assert!(spec.env.is_empty());
+        assert_eq!(spec.timeout, Some(VERSION_DETECTION_TIMEOUT));
+    }
+
+    #[tokio::test]
+    async fn test_run_sets_env() {
+        let runner = RspecRunner::new("sh -c env".to_string())
+            .with_env(vec![("RAILS_ENV".to_string(), "test".to_string())]);
+
+        let result = runner.run("spec/models/user_spec.rb", &RunContext::default()).await.unwrap();
+        assert!(result.stdout.lines().any(|line| line == "RAILS_ENV=test"));
+    }
+
+    #[tokio::test]
+    async fn test_run_context_env_overrides_runner_env() {
+        let runner = RspecRunner::new("sh -c env".to_string())
+            .with_env(vec![("RAILS_LOG_LEVEL".to_string(), "info".to_string())]);
+        let context = RunContext {
+            env: vec![("RAILS_LOG_LEVEL".to_string(), "debug".to_string())],
+            ..Default::default()
+        };
+
+        let result = runner.run("spec/models/user_spec.rb", &context).await.unwrap();
+        assert!(result.stdout.lines().any(|line| line == "RAILS_LOG_LEVEL=debug"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rejects_over_in_flight_limit() {
+        use crate::backpressure::{Backpressure, InFlightLimit};
+
+        let runner = RspecRunner::new("sleep".to_string())
+            .with_in_flight_limit(Some(InFlightLimit::new(1, Backpressure::Reject, 0)));
+
+        let first = tokio::spawn({
+            let runner = runner.clone();
+            async move { runner.run("0.3", &RunContext::default()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let error = runner.run("0", &RunContext::default()).await.unwrap_err();
+        assert!(error.contains("--max-in-flight"), "{}", error);
+        assert_eq!(first.await.unwrap().unwrap().exit_code, 0);
+        assert!(runner.run("0", &RunContext::default()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_run_queues_over_in_flight_limit() {
+        use crate::backpressure::{Backpressure, InFlightLimit};
+
+        let runner = RspecRunner::new("sleep".to_string())
+            .with_in_flight_limit(Some(InFlightLimit::new(1, Backpressure::Queue, 0)));
+
+        let first = tokio::spawn({
+            let runner = runner.clone();
+            async move { runner.run("0.2", &RunContext::default()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let context = RunContext { notices: Some(tx), ..Default::default() };
+        assert_eq!(runner.run("0", &context).await.unwrap().exit_code, 0);
+        assert!(first.is_finished());
+        drop(context);
+        assert_eq!(rx.recv().await.as_deref(), Some("Waiting for a run slot: position 1 in the queue"));
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_run_counted_in_metrics() {
+        let metrics = RunMetrics::new();
+        let runner = RspecRunner::new("sh -c".to_string()).with_run_metrics(metrics.clone());
+
+        runner.run("exit 0", &RunContext::default()).await.unwrap();
+        runner.run("exit 1", &RunContext::default()).await.unwrap();
+
+        let text = metrics.render_metrics();
+        assert!(text.contains("mcp_rspec_runs_total 2\n"));
+        assert!(text.contains("mcp_rspec_run_failures_total 1\n"));
+        assert!(text.contains("mcp_rspec_runs_active 0\n"));
+    }
+
+    #[tokio::test]
+    async fn test_run_cancelled() {
+        use crate::backpressure::{Backpressure, InFlightLimit};
+
+        let tracker = ProcessTracker::new();
+        let runner = RspecRunner::new("sh -c".to_string())
+            .with_process_tracker(tracker.clone())
+            .with_in_flight_limit(Some(InFlightLimit::new(1, Backpressure::Queue, 0)));
+        let cancel = CancellationToken::new();
+        let context = RunContext { cancel: Some(cancel.clone()), ..Default::default() };
+
+        let running = tokio::spawn({
+            let (runner, context) = (runner.clone(), context.clone());
+            async move { runner.run("echo started; exec sleep 5", &context).await }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let queued = tokio::spawn({
+            let (runner, context) = (runner.clone(), context.clone());
+            async move { runner.run("echo queued", &context).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancel.cancel();
+
+        let result = tokio::time::timeout(Duration::from_secs(2), running).await.unwrap().unwrap().unwrap();
+        assert!(result.cancelled);
+        assert_eq!(result.signal, Some(9));
+        assert_eq!(result.stdout, "started\n");
+        assert_eq!(
+            result.abort_reason.as_deref(),
+            Some("Cancelled by the client; the process was killed and the output is partial")
+        );
+        assert_eq!(queued.await.unwrap().unwrap_err(), "Cancelled while waiting for a run slot");
+        assert_eq!(tracker.stats().active, 0);
+
+        // The slot was released
+        let result = runner.run("echo again", &RunContext::default()).await.unwrap();
+        assert!(!result.cancelled);
+        assert_eq!(result.stdout, "again\n");
+    }
+
+    #[tokio::test]
+    async fn test_run_with_blank_command() {
+        let runner = RspecRunner::new(" \t ".to_string());
+
+        let err = runner.run("spec/models/user_spec.rb", &RunContext::default()).await.unwrap_err();
+        assert_eq!(err, "The RSpec command is empty; set --rspec-cmd, e.g. \"bundle exec rspec\"");
+        assert_eq!(runner.detect_version().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_run_sets_isolated_home() {
+        use crate::isolated_home::HomeIsolation;
+
+        let homes = IsolatedHome::new(HomeIsolation::Run, false).unwrap();
+        let runner = RspecRunner::new("sh -c env".to_string()).with_isolated_home(Some(homes));
+
+        let result = runner.run("spec/models/user_spec.rb", &RunContext::default()).await.unwrap();
+        let value = |key: &str| {
+            result
+                .stdout
+                .lines()
+                .find_map(|line| line.strip_prefix(&format!("{}=", key)))
+                .map(str::to_string)
+        };
+
+        let home = value("HOME").unwrap();
+        assert!(home.contains("mcp-rspec-home-"));
+        assert_eq!(value("GEM_HOME"), Some(format!("{}/gems", home)));
+        assert_eq!(value("BUNDLE_PATH"), Some(format!("{}/bundle", home)));
+        assert!(!std::path::Path::new(&home).exists());
+    }
+
+    /// Fails every run through `bin/spring` like an unreachable Spring
+    /// server.
+    #[derive(Default)]
+    struct BrokenSpring {
+        specs: Mutex<Vec<CommandSpec>>,
+    }
+
+    #[async_trait]
+    impl ProcessRunner for BrokenSpring {
+        async fn spawn(&self, spec: &CommandSpec, _lines: Option<UnboundedSender<String>>) -> Result<CommandResult, String> {
+            self.specs.lock().unwrap().push(spec.clone());
+            let spring = spec.argv[0] == SPRING_COMMAND[0];
+            Ok(CommandResult {
+                exit_code: if spring { 1 } else { 0 },
+                stdout: if spring { String::new() } else { "1 example, 0 failures".to_string() },
+                stderr: if spring { "Error connecting to Spring server".to_string() } else { String::new() },
+                abort_reason: None,
+                signal: None,
+                timed_out: false,
+                cancelled: false,
+                peak_memory_mb: None,
+                launcher: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_through_spring() {
+        let process = Arc::new(RecordingProcess::default());
+        let runner = RspecRunner::new("bundle exec rspec".to_string())
+            .with_process_runner(process.clone())
+            .with_env_clear(true)
+            .with_spring(SpringMode::On);
+        let result = runner.run("spec/models/user_spec.rb", &RunContext::default()).await.unwrap();
+        assert_eq!(process.specs.lock().unwrap()[0].argv, ["bin/spring", "rspec", "spec/models/user_spec.rb"]);
+        assert_eq!(result.launcher.as_deref(), Some("Spring (bin/spring rspec)"));
+
+        let context = RunContext { env: vec![("DISABLE_SPRING".to_string(), "1".to_string())], ..Default::default() };
+        let result = runner.run("spec", &context).await.unwrap();
+        assert_eq!(process.specs.lock().unwrap()[1].argv, ["bundle", "exec", "rspec", "spec"]);
+        assert_eq!(result.launcher.as_deref(), Some("bundle exec rspec (not Spring: DISABLE_SPRING is set)"));
+    }
+
+    #[tokio::test]
+    async fn test_run_falls_back_when_spring_fails() {
+        let process = Arc::new(BrokenSpring::default());
+        let runner = RspecRunner::new("bin/rspec".to_string())
+            .with_process_runner(process.clone())
+            .with_env_clear(true)
+            .with_spring(SpringMode::On);
+        let (notices, mut received) = tokio::sync::mpsc::unbounded_channel();
+        let context = RunContext { notices: Some(notices), ..Default::default() };
+
+        let result = runner.run("spec", &context).await.unwrap();
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(result.launcher.as_deref(), Some("bin/rspec (Spring failed: Error connecting to Spring server)"));
+        let specs = process.specs.lock().unwrap();
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[1].argv, ["bin/rspec", "spec"]);
+        assert!(specs[1].env.contains(&("DISABLE_SPRING".to_string(), "1".to_string())));
+        assert!(received.try_recv().unwrap().starts_with("Spring failed (Error connecting to Spring server)"));
+    }
+
+    #[tokio::test]
+    async fn test_run_in_workdir() {
+        let runner = RspecRunner::new("sh -c pwd".to_string());
+        let workdir = std::env::temp_dir().canonicalize().unwrap();
+        let context = RunContext {
+            workdir: Some(workdir.clone()),
+            ..Default::default()
+        };
+
+        let result = runner.run("spec/models/user_spec.rb", &context).await.unwrap();
+        assert_eq!(result.stdout.trim(), workdir.to_string_lossy());
+    }
 }
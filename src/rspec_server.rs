@@ -1,129 +1,5539 @@
 use rmcp::{
-    ErrorData as McpError, RoleServer, ServerHandler,
-    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
+    ErrorData as McpError, Peer, RoleServer, ServerHandler,
+    handler::server::{router::tool::ToolRouter, tool::ToolCallContext, wrapper::Parameters},
     model::*,
     schemars,
-    service::RequestContext,
-    tool, tool_handler, tool_router,
+    service::{NotificationContext, RequestContext},
+    tool, tool_router,
 };
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio_util::sync::CancellationToken;
 
-use crate::command_runner::{CommandResult, CommandRunner};
-use crate::file_path_parser::ParsedFilePath;
+use crate::auth::AuthPolicy;
+use crate::baseline::Baseline;
+use crate::bisect::{BisectOutcome, is_progress_line};
+use crate::coverage::{CoverageConfig, CoverageSnapshot, coverage_report};
+use crate::env_allowlist::EnvAllowlist;
+use crate::example_status::{ExampleStatus, LastRun};
+use crate::command_runner::{CommandResult, CommandRunner, RunContext, shell_join};
+use crate::file_path_parser::{ParsedFilePath, SpecMapping};
+use crate::git_blame::{GIT_BLAME_TIMEOUT, blame_location, format_blames};
+use crate::json_report::{HumanFormatter, JsonReportFile};
+use crate::fix_prompt::{FIX_FAILING_SPEC, fix_failing_spec, fix_failing_spec_prompt, recorded_failures};
+use crate::junit::junit_xml;
+use crate::load_diagnostics::LoadDiagnostics;
+use crate::preset::{detect_error_pattern, format_env_report};
+use crate::process_tracker::ProcessTracker;
+use crate::projects::Projects;
+use crate::response_budget::{DetailLevel, fit_to_budget};
+use crate::run_history::{self, RunHistory, RunRecord};
+use crate::run_outputs::{OutputStream, RunOutputs};
+use crate::rspec_output::{
+    DocumentationParser, ExampleEvent, collapse_repeated_frames, FailureReport, RunOptions, RunStatus, RunSummary, compact_status,
+    format_failures, format_retries, normalize_newlines, plain_summary, rerun_locations, split_output_limit, truncate_output,
+    RetryVerdict,
+};
+use crate::rspec_result::{
+    ReportSummary, RspecReport, format_example_tree, format_examples, format_groups, format_profile, format_report, format_slowest_files,
+};
+use crate::parallel::{ParallelConfig, ParallelRun, combine_reports};
+use crate::watcher::{LogLevel, WatchSubscribers};
+use crate::rspec_version::VersionCache;
+use crate::run_defaults::{DEFAULT_MAX_REPORTED_FAILURES, RunDefaults, SessionDefaults, apply_defaults};
+use crate::spec_files::{MAX_LISTED_FILES, SPEC_URI_PREFIX, SpecListing};
+use crate::spec_scaffold::{SpecHelper, create, skeleton};
+use crate::spec_markers::{MarkerCache, MarkerCounts};
+use crate::source_context::SourceContext;
+use crate::webhook::{FailurePayload, FailureWebhook};
+use crate::workdir::{WorkdirPolicy, check_spec_directory, spec_files_in};
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct RspecServerArgs {
+    #[schemars(
+        description = "RSpec test file path (must be relative to project root and end with '_spec.rb'), or a directory of specs such as 'spec/models'. A file may carry targets as RSpec prints them, 'spec/models/user_spec.rb:42' or 'spec/models/user_spec.rb[1:2]'. Pass an array to run several files and directories in one run",
+        example = "spec/models/user_spec.rb"
+    )]
+    pub file: SpecPaths,
+
+    #[schemars(
+        description = "Optional line numbers (1 to 1000000) to target specific tests; duplicates are dropped and the rest sorted. Line numbers drift as files change, so prefer 'example' unless they were just read from the file or a failure",
+        example = "[37, 87]"
+    )]
+    pub line_numbers: Option<Vec<i32>>,
+
+    #[schemars(
+        description = "Run only examples whose full description contains this text (RSpec's -e), e.g. from a failure's description. Combines with line_numbers",
+        example = "rejects blank names"
+    )]
+    pub example: Option<String>,
+
+    #[schemars(
+        description = "RSpec example or group ids to run in the single file given, as list_examples reports them: the scope ('1:2:1') or the full id ('./spec/models/user_spec.rb[1:2:1]'). Unlike line numbers, ids don't drift when lines move. Can't be combined with line_numbers",
+        example = "[\"1:2:1\", \"1:3\"]"
+    )]
+    pub example_ids: Option<Vec<String>>,
+
+    #[schemars(
+        description = "Validate the arguments and return the command that would run, without running it"
+    )]
+    pub dry_run_command: Option<bool>,
+
+    #[schemars(
+        description = "Maximum number of parsed failures to include, overriding the server's --max-reported-failures"
+    )]
+    pub max_reported_failures: Option<usize>,
+
+    #[schemars(
+        description = "Result format: 'full' (default), 'compact' for a single status-bar line such as '✓ 42 passed', or 'plain_summary' for a jargon-free sentence to show end users"
+    )]
+    pub output_style: Option<OutputStyle>,
+
+    #[schemars(
+        description = "With JSON output, also report examples grouped by top-level describe block with per-group pass/fail counts"
+    )]
+    pub group_examples: Option<bool>,
+
+    #[schemars(
+        description = "Re-run the failing examples up to this many times (max 5), each time only those still failing, and report each failure as consistent or flaky (passed on retry k). When the failures can't be located, the whole run is retried instead. Every attempt's outcome is reported"
+    )]
+    pub retries: Option<u32>,
+
+    #[schemars(
+        description = "Directory to run in, relative to the server's default workdir. Must be within the allowed directories"
+    )]
+    pub workdir: Option<String>,
+
+    #[schemars(
+        description = "Project to run in, by name from list_projects; its root and RSpec command replace the server's, and workdir is relative to its root"
+    )]
+    pub project: Option<String>,
+
+    #[schemars(
+        description = "For this run only, add --backtrace and set RAILS_LOG_LEVEL=debug to help debug a specific failure"
+    )]
+    pub verbose: Option<bool>,
+
+    #[schemars(
+        description = "Run with this formatter on stdout ('progress' or 'documentation') while the JSON report is written to a temp file and used for example groups and slowest files"
+    )]
+    pub human_formatter: Option<HumanFormatter>,
+
+    #[schemars(
+        description = "Kill the run after this many seconds, overriding the server's --timeout. The result then says it timed out and includes the output captured so far"
+    )]
+    pub timeout_seconds: Option<u64>,
+
+    #[schemars(
+        description = "Example order: 'random' or 'defined' (file order). Defaults to the project's configured order, or 'random' when a seed is given"
+    )]
+    pub order: Option<ExampleOrder>,
+
+    #[schemars(
+        description = "Seed for random order, e.g. from a failing run's 'Randomized with seed N', to reproduce an order-dependent failure",
+        example = "4242"
+    )]
+    pub seed: Option<u32>,
+
+    #[schemars(
+        description = "Run only examples with these RSpec tags (--tag), e.g. 'fast' or 'type:model'; prefix a tag with '~' to skip it instead, e.g. '~js'",
+        example = "[\"fast\", \"~js\"]"
+    )]
+    pub tags: Option<Vec<String>>,
+
+    #[schemars(
+        description = "Environment variables for this run only, e.g. {\"COVERAGE\": \"1\"}; they win over the server's. Only names the server's --allow-env permits are accepted",
+        example = "{\"COVERAGE\": \"1\"}"
+    )]
+    pub env: Option<BTreeMap<String, String>>,
+}
+
+/// The `file` argument: one path, as it has always been, or several.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+#[serde(untagged)]
+pub enum SpecPaths {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl SpecPaths {
+    pub fn as_slice(&self) -> &[String] {
+        match self {
+            SpecPaths::One(path) => std::slice::from_ref(path),
+            SpecPaths::Many(paths) => paths,
+        }
+    }
+}
+
+impl Default for SpecPaths {
+    fn default() -> Self {
+        SpecPaths::One(String::new())
+    }
+}
+
+impl From<&str> for SpecPaths {
+    fn from(path: &str) -> Self {
+        SpecPaths::One(path.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, schemars::JsonSchema, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum OutputStyle {
+    #[default]
+    Full,
+    Compact,
+    PlainSummary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExampleOrder {
+    Random,
+    Defined,
+}
+
+/// RSpec's `--order` for the `order` and `seed` arguments; a seed implies
+/// random order.
+fn order_args(order: Option<ExampleOrder>, seed: Option<u32>) -> Result<Vec<String>, String> {
+    let order = match (order, seed) {
+        (None, None) => return Ok(Vec::new()),
+        (Some(ExampleOrder::Defined), Some(_)) => {
+            return Err("seed can only be used with order 'random'".to_string());
+        }
+        (Some(ExampleOrder::Defined), None) => "defined".to_string(),
+        (_, Some(seed)) => format!("random:{}", seed),
+        (Some(ExampleOrder::Random), None) => "random".to_string(),
+    };
+    Ok(vec!["--order".to_string(), order])
+}
+
+/// RSpec's `--tag` options for the `tags` argument. Tags are names with an
+/// optional `:value` and a leading `~` for exclusion, e.g. `type:model`.
+fn tag_args(tags: &[String]) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for tag in tags {
+        let name = tag.strip_prefix('~').unwrap_or(tag);
+        let valid = !name.is_empty()
+            && !name.starts_with([':', '-'])
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ':' | '.'));
+        if !valid {
+            return Err(format!(
+                "Invalid tag '{}'; use a name with an optional ':value', e.g. 'fast', 'type:model' or '~js'",
+                tag
+            ));
+        }
+        args.extend(["--tag".to_string(), tag.clone()]);
+    }
+    Ok(args)
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct ListExamplesArgs {
+    #[schemars(
+        description = "RSpec test file or directory of specs, relative to the project root",
+        example = "spec/models/user_spec.rb"
+    )]
+    pub file: String,
+
+    #[schemars(
+        description = "Directory to run in, relative to the server's default workdir. Must be within the allowed directories"
+    )]
+    pub workdir: Option<String>,
+
+    #[schemars(
+        description = "Project to run in, by name from list_projects; its root and RSpec command replace the server's, and workdir is relative to its root"
+    )]
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct ProfileSpecsArgs {
+    #[schemars(
+        description = "RSpec test file or directory of specs to profile, relative to the project root. Defaults to the spec directory",
+        example = "spec/models"
+    )]
+    pub path: Option<String>,
+
+    #[schemars(
+        description = "How many of the slowest examples to report, from 1 to 100. Defaults to 10",
+        example = "10"
+    )]
+    pub count: Option<u32>,
+
+    #[schemars(
+        description = "Profile only examples with these RSpec tags (--tag), e.g. 'type:request'; prefix a tag with '~' to skip it instead",
+        example = "[\"type:request\"]"
+    )]
+    pub tags: Option<Vec<String>>,
+
+    #[schemars(
+        description = "Kill the run after this many seconds, overriding the server's --timeout"
+    )]
+    pub timeout_seconds: Option<u64>,
+
+    #[schemars(
+        description = "Directory to run in, relative to the server's default workdir. Must be within the allowed directories"
+    )]
+    pub workdir: Option<String>,
+
+    #[schemars(
+        description = "Project to run in, by name from list_projects; its root and RSpec command replace the server's, and workdir is relative to its root"
+    )]
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct SpecMarkersArgs {
+    #[schemars(
+        description = "RSpec test file path (must be relative to project root and end with '_spec.rb')",
+        example = "spec/models/user_spec.rb"
+    )]
+    pub file: String,
+
+    #[schemars(
+        description = "Directory to run in, relative to the server's default workdir. Must be within the allowed directories"
+    )]
+    pub workdir: Option<String>,
+
+    #[schemars(
+        description = "Project to run in, by name from list_projects; its root and RSpec command replace the server's, and workdir is relative to its root"
+    )]
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct GetRunOutputArgs {
+    #[schemars(description = "Run id from a truncated result's 'Full Output' line")]
+    pub run_id: u64,
+
+    #[schemars(description = "Which output to read: stdout (default) or stderr")]
+    pub stream: Option<OutputStream>,
+
+    #[schemars(description = "Byte offset to start reading at; 0 by default. Pass a page's 'Next Offset' to continue")]
+    pub offset: Option<usize>,
+
+    #[schemars(description = "Most bytes to return; the server's --max-output-bytes by default")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct ListRunsArgs {
+    #[schemars(description = "Most runs to list, newest first; 10 by default")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct GetRunArgs {
+    #[schemars(description = "Run id from list_runs; the latest run when omitted")]
+    pub run_id: Option<u64>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct ConfigureArgs {
+    #[schemars(description = "Project, by name from list_projects, that this session's runs use when they don't name one")]
+    pub project: Option<String>,
+    #[schemars(description = "Directory this session's runs use when they name neither a workdir nor a project, relative to the project's root or the server's default workdir")]
+    pub workdir: Option<String>,
+    #[schemars(
+        description = "Environment variables for this session's runs, e.g. {\"COVERAGE\": \"1\"}; a run's own env wins. Only names the server's --allow-env permits are accepted"
+    )]
+    pub env: Option<BTreeMap<String, String>>,
+    #[schemars(description = "Formatter on stdout ('progress' or 'documentation') for this session's runs")]
+    pub human_formatter: Option<HumanFormatter>,
+    #[schemars(description = "Result format for this session's runs: 'full', 'compact' or 'plain_summary'")]
+    pub output_style: Option<OutputStyle>,
+    #[schemars(description = "Seconds after which this session's runs are killed, overriding the server's --timeout")]
+    pub timeout_seconds: Option<u64>,
+    #[schemars(description = "Clear this session's defaults first, so only the ones given here remain")]
+    pub reset: Option<bool>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct GetRunReportArgs {
+    #[schemars(description = "Run id from list_runs; the latest run when omitted")]
+    pub run_id: Option<u64>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct UpdateBaselineArgs {
+    #[schemars(
+        description = "RSpec test file or directory to run; its failures replace the baseline entries for the specs under it",
+        example = "spec/models"
+    )]
+    pub file: String,
+
+    #[schemars(
+        description = "Directory to run in, relative to the server's default workdir. Must be within the allowed directories"
+    )]
+    pub workdir: Option<String>,
+
+    #[schemars(
+        description = "Project to run in, by name from list_projects; its root and RSpec command replace the server's, and workdir is relative to its root"
+    )]
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct RunSuiteArgs {
+    #[schemars(
+        description = "Directory of specs to run, relative to the project root. Defaults to the whole suite (the server's --spec-dir)",
+        example = "spec/requests"
+    )]
+    pub directory: Option<String>,
+
+    #[schemars(
+        description = "Directory to run in, relative to the server's default workdir. Must be within the allowed directories"
+    )]
+    pub workdir: Option<String>,
+
+    #[schemars(
+        description = "Project to run in, by name from list_projects; its root and RSpec command replace the server's, and workdir is relative to its root"
+    )]
+    pub project: Option<String>,
+
+    #[schemars(
+        description = "Run only examples with these RSpec tags (--tag), as for run_rspec; '~tag' skips a tag"
+    )]
+    pub tags: Option<Vec<String>>,
+
+    #[schemars(
+        description = "Split the run across this many processes with the server's --parallel tool (parallel_tests or turbo_tests), combining their results. Defaults to --parallel-processes, or one per CPU; 1 runs a single RSpec process",
+        example = "4"
+    )]
+    pub processes: Option<u32>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct FindSpecForArgs {
+    #[schemars(
+        description = "Implementation file relative to the project root, e.g. app/models/user.rb or lib/foo/bar.rb",
+        example = "app/models/user.rb"
+    )]
+    pub file: String,
+
+    #[schemars(
+        description = "When no conventional spec exists, search the spec directory for files with the same name or describing the file's constant. Defaults to false"
+    )]
+    pub search: Option<bool>,
+
+    #[schemars(
+        description = "Directory to look in, relative to the server's default workdir. Must be within the allowed directories"
+    )]
+    pub workdir: Option<String>,
+
+    #[schemars(
+        description = "Project to look in, by name from list_projects; workdir is relative to its root"
+    )]
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct CreateSpecArgs {
+    #[schemars(
+        description = "Implementation file to write a spec for, relative to the project root, e.g. app/models/user.rb or lib/foo/bar.rb",
+        example = "app/models/user.rb"
+    )]
+    pub file: String,
+
+    #[schemars(
+        description = "Directory of the project, relative to the server's default workdir. Must be within the allowed directories"
+    )]
+    pub workdir: Option<String>,
+
+    #[schemars(
+        description = "Project to create the spec in, by name from list_projects; workdir is relative to its root"
+    )]
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct BisectFailureArgs {
+    #[schemars(
+        description = "The failing example, as RSpec prints it: spec/models/user_spec.rb:42 or spec/models/user_spec.rb[1:2]",
+        example = "spec/models/user_spec.rb:42"
+    )]
+    pub example: String,
+
+    #[schemars(description = "Seed of the run the example failed in, from its 'Randomized with seed' line")]
+    pub seed: u64,
+
+    #[schemars(
+        description = "Spec files or directories of the run that failed, which bisect narrows down. Defaults to the spec directory; the example is added when they don't cover it"
+    )]
+    pub files: Option<Vec<String>>,
+
+    #[schemars(description = "Kill the bisect after this many seconds, instead of the server's bisect timeout")]
+    pub timeout_seconds: Option<u64>,
+
+    #[schemars(
+        description = "Directory to run in, relative to the server's default workdir. Must be within the allowed directories"
+    )]
+    pub workdir: Option<String>,
+
+    #[schemars(
+        description = "Project to run in, by name from list_projects; workdir is relative to its root"
+    )]
+    pub project: Option<String>,
+}
+
+/// Guardrails for `run_rspec_suite`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SuiteLimits {
+    /// Kill suite runs after this long, instead of the `--timeout` default.
+    pub timeout: Option<Duration>,
+    /// Refuse to run more examples than this, counted with a dry run first.
+    pub max_examples: Option<u32>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct ListSpecFilesArgs {
+    #[schemars(
+        description = "Only list paths containing this text, or matching it as a glob when it has '*' or '?' ('**' spans directories)",
+        example = "spec/models/**/*_spec.rb"
+    )]
+    pub filter: Option<String>,
+
+    #[schemars(
+        description = "Directory to list in, relative to the server's default workdir. Must be within the allowed directories"
+    )]
+    pub workdir: Option<String>,
+
+    #[schemars(
+        description = "Project to list in, by name from list_projects; its root and RSpec command replace the server's, and workdir is relative to its root"
+    )]
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct CheckAccessArgs {
+    #[schemars(
+        description = "Path to check, as it would be passed to run_rspec",
+        example = "spec/models/user_spec.rb"
+    )]
+    pub path: String,
+
+    #[schemars(description = "Working directory to check, as it would be passed to run_rspec")]
+    pub workdir: Option<String>,
+
+    #[schemars(description = "Project to check in, as it would be passed to run_rspec")]
+    pub project: Option<String>,
+}
+
+/// Tools only registered when the server runs with `--allow-admin-tools`.
+const ADMIN_TOOLS: &[&str] = &["process_stats", "reset_state"];
+
+/// Tools only registered when the server runs with `--baseline-failures`.
+const BASELINE_TOOLS: &[&str] = &["update_baseline"];
+
+/// Tools enabled by `with_max_output_bytes`, when output is truncated.
+const OUTPUT_TOOLS: &[&str] = &["get_run_output"];
+
+/// Tools enabled by `with_run_history`, when the history keeps runs.
+const HISTORY_TOOLS: &[&str] = &["list_runs", "get_run"];
+
+/// Tools enabled by `with_junit_reports`, when the history is kept.
+const JUNIT_TOOLS: &[&str] = &["get_run_report"];
+
+/// Tools only registered when the server runs with `--rerun-failures`.
+const RERUN_TOOLS: &[&str] = &["rerun_failures", "last_failures"];
+
+/// Tools only registered when the server runs with `--projects-file`.
+const PROJECT_TOOLS: &[&str] = &["list_projects"];
+
+/// Tools enabled by `with_coverage`.
+const COVERAGE_TOOLS: &[&str] = &["run_rspec_with_coverage"];
+
+/// Tools enabled by `with_bisect`.
+const BISECT_TOOLS: &[&str] = &["bisect_failure"];
+
+/// Tools enabled by `with_spec_scaffolding`, which write to the project.
+const SCAFFOLD_TOOLS: &[&str] = &["create_spec"];
+
+/// Every list above; none of these tools is registered until the option
+/// enabling it is set. Which tools need the auth token is declared on each
+/// tool instead, by its `read_only_hint`.
+const GATED_TOOLS: &[&[&str]] = &[
+    ADMIN_TOOLS,
+    BASELINE_TOOLS,
+    OUTPUT_TOOLS,
+    HISTORY_TOOLS,
+    JUNIT_TOOLS,
+    RERUN_TOOLS,
+    PROJECT_TOOLS,
+    COVERAGE_TOOLS,
+    BISECT_TOOLS,
+    SCAFFOLD_TOOLS,
+];
+
+/// RSpec options and environment added for a `verbose` run.
+const VERBOSE_ARGS: &[&str] = &["--backtrace"];
+const VERBOSE_ENV: &[(&str, &str)] = &[("RAILS_LOG_LEVEL", "debug")];
+
+/// Most examples `list_examples` returns; the rest are only counted.
+const MAX_LISTED_EXAMPLES: usize = 500;
+
+/// Slowest examples `profile_specs` reports when no count is given, and
+/// the most it reports.
+const DEFAULT_PROFILE_COUNT: u32 = 10;
+const MAX_PROFILE_COUNT: u32 = 100;
+
+/// MIME type of spec file resources.
+const SPEC_MIME_TYPE: &str = "text/x-ruby";
+
+/// Upper bound on the per-request `retries` argument.
+const MAX_RETRIES: u32 = 5;
+
+/// Upper bound on `run_rspec_suite`'s `processes` argument.
+const MAX_PARALLEL_PROCESSES: u32 = 64;
+
+#[derive(Clone)]
+pub struct RspecServer<R: CommandRunner + Clone + 'static> {
+    tool_router: ToolRouter<RspecServer<R>>,
+    runner: R,
+    process_tracker: ProcessTracker,
+    versions: VersionCache,
+    stream_examples: bool,
+    slowest_files: usize,
+    auth: AuthPolicy,
+    diagnose_load_errors: bool,
+    defaults: RunDefaults,
+    error_patterns: Vec<String>,
+    failure_webhook: Option<FailureWebhook>,
+    workdirs: WorkdirPolicy,
+    env_allowlist: EnvAllowlist,
+    max_output_bytes: Option<usize>,
+    run_outputs: RunOutputs,
+    history: RunHistory,
+    markers: MarkerCache,
+    normalize_newlines: bool,
+    error_on_failure: bool,
+    reported_env: Option<Vec<(String, String)>>,
+    git_blame: bool,
+    per_file_timeout: Option<Duration>,
+    baseline: Option<Baseline>,
+    response_budget: Option<usize>,
+    json_format: bool,
+    /// Keep a JUnit XML report of each run in the history.
+    junit_reports: bool,
+    example_status: Option<ExampleStatus>,
+    coverage: Option<CoverageConfig>,
+    /// How long `bisect_failure` may run, when it is enabled.
+    bisect_timeout: Option<Duration>,
+    /// How `run_rspec_suite` splits runs across processes, when it does.
+    parallel: Option<ParallelConfig>,
+    /// Clients told about `--watch` runs, when watching.
+    watch_subscribers: Option<WatchSubscribers>,
+    /// This session's `logging/setLevel` level for watch notifications.
+    log_level: LogLevel,
+    /// Run defaults this session's client set with `configure`.
+    session_defaults: Arc<std::sync::Mutex<SessionDefaults>>,
+    spec_listing: SpecListing,
+    suite_limits: SuiteLimits,
+    projects: Projects,
+    /// Globs such as `spec/**` every target must match, when not empty.
+    allowed_paths: Vec<String>,
+}
+
+#[tool_router]
+impl <R: CommandRunner + Clone + 'static> RspecServer<R> {
+    pub fn new(runner: R) -> Self {
+        let mut tool_router = Self::tool_router();
+        for name in GATED_TOOLS.iter().copied().flatten() {
+            tool_router.remove_route(name);
+        }
+
+        Self {
+            tool_router,
+            runner,
+            process_tracker: ProcessTracker::new(),
+            versions: VersionCache::new(),
+            stream_examples: false,
+            slowest_files: 0,
+            auth: AuthPolicy::default(),
+            diagnose_load_errors: false,
+            defaults: RunDefaults::default(),
+            error_patterns: Vec::new(),
+            failure_webhook: None,
+            workdirs: WorkdirPolicy::default(),
+            env_allowlist: EnvAllowlist::default(),
+            max_output_bytes: None,
+            run_outputs: RunOutputs::default(),
+            history: RunHistory::default(),
+            markers: MarkerCache::new(),
+            normalize_newlines: true,
+            error_on_failure: true,
+            reported_env: None,
+            git_blame: false,
+            per_file_timeout: None,
+            baseline: None,
+            response_budget: None,
+            json_format: false,
+            junit_reports: false,
+            example_status: None,
+            coverage: None,
+            bisect_timeout: None,
+            parallel: None,
+            watch_subscribers: None,
+            log_level: Arc::new(std::sync::Mutex::new(LoggingLevel::Info)),
+            session_defaults: Arc::default(),
+            spec_listing: SpecListing::default(),
+            suite_limits: SuiteLimits::default(),
+            projects: Projects::default(),
+            allowed_paths: Vec::new(),
+        }
+    }
+
+    /// Registers the named tools, which `new` leaves out.
+    fn enable_tools(&mut self, names: &[&str]) {
+        let mut all = Self::tool_router();
+        for name in names {
+            if let Some(route) = all.map.remove(*name) {
+                self.tool_router.add_route(route);
+            }
+        }
+    }
+
+    pub fn with_admin_tools(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.enable_tools(ADMIN_TOOLS);
+        }
+        self
+    }
+
+    pub fn with_process_tracker(mut self, process_tracker: ProcessTracker) -> Self {
+        self.process_tracker = process_tracker;
+        self
+    }
+
+    pub fn with_version_cache(mut self, versions: VersionCache) -> Self {
+        self.versions = versions;
+        self
+    }
+
+    /// Emit an MCP logging notification per completed example. Expects the
+    /// runner to be using the documentation formatter.
+    pub fn with_example_streaming(mut self, enabled: bool) -> Self {
+        self.stream_examples = enabled;
+        self
+    }
+
+    /// Number of slowest files to report when a run's JSON output covers
+    /// more than one file. Zero disables the report.
+    pub fn with_slowest_files(mut self, limit: usize) -> Self {
+        self.slowest_files = limit;
+        self
+    }
+
+    pub fn with_auth_policy(mut self, auth: AuthPolicy) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// On load failures, do an extra dry run that reports `$LOAD_PATH` and
+    /// the project files required before the failure.
+    pub fn with_load_diagnostics(mut self, enabled: bool) -> Self {
+        self.diagnose_load_errors = enabled;
+        self
+    }
+
+    /// Values used for the `run_rspec` arguments a request leaves out.
+    pub fn with_run_defaults(mut self, defaults: RunDefaults) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Errors (e.g. database connection failures) to call out when they
+    /// appear in a run's output.
+    pub fn with_error_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.error_patterns = patterns;
+        self
+    }
+
+    pub fn with_failure_webhook(mut self, webhook: Option<FailureWebhook>) -> Self {
+        self.failure_webhook = webhook;
+        self
+    }
+
+    /// Keep finished runs in `history` and register `list_runs` and
+    /// `get_run` when it keeps any.
+    pub fn with_run_history(mut self, history: RunHistory) -> Self {
+        if history.is_enabled() {
+            self.enable_tools(HISTORY_TOOLS);
+        }
+        self.history = history;
+        self
+    }
+
+    pub fn with_workdir_policy(mut self, workdirs: WorkdirPolicy) -> Self {
+        self.workdirs = workdirs;
+        self
+    }
+
+    /// The variables a request's `env` may set.
+    pub fn with_env_allowlist(mut self, env_allowlist: EnvAllowlist) -> Self {
+        self.env_allowlist = env_allowlist;
+        self
+    }
+
+    /// Truncate the stdout and stderr echoed in results to `limit` bytes
+    /// together, keeping the start and end of each. The run keeps going and
+    /// parsing still sees the full output. Registers `get_run_output`, which
+    /// reads the full output of truncated runs.
+    pub fn with_max_output_bytes(mut self, limit: Option<usize>) -> Self {
+        if limit.is_some() {
+            self.enable_tools(OUTPUT_TOOLS);
+        }
+        self.max_output_bytes = limit;
+        self
+    }
+
+    /// Convert `\r\n` in captured output to `\n` before parsing. On by default.
+    pub fn with_newline_normalization(mut self, enabled: bool) -> Self {
+        self.normalize_newlines = enabled;
+        self
+    }
+
+    /// Flag run results as tool errors (`is_error`) unless the run passed,
+    /// for clients that branch on the call outcome. On by default.
+    pub fn with_error_on_failure(mut self, enabled: bool) -> Self {
+        self.error_on_failure = enabled;
+        self
+    }
+
+    /// Include an `Environment` section listing `env` (the variables the
+    /// runner applies to every run) plus any set for the request. The
+    /// inherited environment is never included.
+    pub fn with_env_report(mut self, env: Option<Vec<(String, String)>>) -> Self {
+        self.reported_env = env;
+        self
+    }
+
+    /// Add a `Blame` section naming the last commit and author of each
+    /// reported failure's line, via `git blame`.
+    pub fn with_git_blame(mut self, enabled: bool) -> Self {
+        self.git_blame = enabled;
+        self
+    }
+
+    /// Run directory targets one spec file at a time, killing any file that
+    /// runs longer than `timeout` and reporting an outcome per file.
+    pub fn with_per_file_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.per_file_timeout = timeout;
+        self
+    }
+
+    /// Leave failures already in `baseline` out of results, and register
+    /// the `update_baseline` tool.
+    pub fn with_baseline(mut self, baseline: Option<Baseline>) -> Self {
+        if baseline.is_some() {
+            self.enable_tools(BASELINE_TOOLS);
+        }
+        self.baseline = baseline;
+        self
+    }
+
+    /// Byte budget for `run_rspec` results. Larger results drop the
+    /// captured output, then the failures, until they fit.
+    pub fn with_response_budget(mut self, budget: Option<usize>) -> Self {
+        self.response_budget = budget;
+        self
+    }
+
+    /// Runs write RSpec's JSON report to stdout (`--format json`). Results
+    /// list its examples and take the summary and failures from it, falling
+    /// back to the raw output when it doesn't parse.
+    pub fn with_json_format(mut self, enabled: bool) -> Self {
+        self.json_format = enabled;
+        self
+    }
+
+    /// Keep each `run_rspec` run's results as JUnit XML for
+    /// `get_run_report`, writing a JSON report alongside stdout when the
+    /// run doesn't already have one. Call after `with_run_history`; without
+    /// a history there is nowhere to keep them.
+    pub fn with_junit_reports(mut self, enabled: bool) -> Self {
+        if enabled && self.history.is_enabled() {
+            self.enable_tools(JUNIT_TOOLS);
+            self.junit_reports = true;
+        }
+        self
+    }
+
+    /// Point RSpec's example status persistence at a file of this session,
+    /// and register `rerun_failures`, which reruns the last `run_rspec`
+    /// target with `--only-failures`, and `last_failures`, which lists the
+    /// examples that would rerun.
+    pub fn with_rerun_failures(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.enable_tools(RERUN_TOOLS);
+            self.example_status = Some(ExampleStatus::new());
+        }
+        self
+    }
+
+    /// Offer `run_rspec_with_coverage`, which runs with `coverage.env` set
+    /// and reports SimpleCov's results from `coverage.dir`.
+    pub fn with_coverage(mut self, coverage: Option<CoverageConfig>) -> Self {
+        if coverage.is_some() {
+            self.enable_tools(COVERAGE_TOOLS);
+        }
+        self.coverage = coverage;
+        self
+    }
+
+    /// Offer `create_spec`, which writes skeleton specs into the project.
+    pub fn with_spec_scaffolding(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.enable_tools(SCAFFOLD_TOOLS);
+        }
+        self
+    }
+
+    /// Offer `bisect_failure`, killing bisects after `timeout` unless a
+    /// request sets its own.
+    pub fn with_bisect(mut self, timeout: Option<Duration>) -> Self {
+        if timeout.is_some() {
+            self.enable_tools(BISECT_TOOLS);
+        }
+        self.bisect_timeout = timeout;
+        self
+    }
+
+    /// Run `run_rspec_suite` across processes with parallel_tests or
+    /// turbo_tests.
+    pub fn with_parallel(mut self, parallel: Option<ParallelConfig>) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Report `--watch` runs to this session's client once it initializes,
+    /// as logging notifications.
+    pub fn with_watch_subscribers(mut self, subscribers: Option<WatchSubscribers>) -> Self {
+        self.watch_subscribers = subscribers;
+        self
+    }
+
+    /// Restricts the paths tools take to those matching one of `patterns`,
+    /// relative to the run's directory.
+    pub fn with_allowed_paths(mut self, patterns: Vec<String>) -> Self {
+        self.allowed_paths = patterns;
+        self
+    }
+
+    /// Where `list_spec_files` looks for specs and what it skips.
+    pub fn with_spec_listing(mut self, listing: SpecListing) -> Self {
+        self.spec_listing = listing;
+        self
+    }
+
+    /// Time and size limits for `run_rspec_suite`.
+    pub fn with_suite_limits(mut self, limits: SuiteLimits) -> Self {
+        self.suite_limits = limits;
+        self
+    }
+
+    /// Projects requests may name with `project`, and the `list_projects`
+    /// tool when there are any.
+    pub fn with_projects(mut self, projects: Projects) -> Self {
+        if !projects.is_empty() {
+            self.enable_tools(PROJECT_TOOLS);
+        }
+        self.projects = projects;
+        self
+    }
+
+    /// Where spec file resources are listed and read: the default workdir,
+    /// or the server's own directory.
+    fn resource_root(&self) -> std::path::PathBuf {
+        self.workdirs
+            .default_workdir()
+            .map_or_else(|| std::path::PathBuf::from("."), std::path::Path::to_path_buf)
+    }
+
+    /// Every spec file `list_spec_files` would list in the default workdir,
+    /// as resources, `MAX_LISTED_FILES` per page.
+    fn spec_resources(&self, cursor: Option<String>) -> Result<ListResourcesResult, McpError> {
+        let start = match cursor {
+            Some(cursor) => cursor
+                .parse::<usize>()
+                .map_err(|_| McpError::invalid_params(format!("Invalid cursor '{}'", cursor), None))?,
+            None => 0,
+        };
+        let files = self
+            .spec_listing
+            .list(&self.resource_root(), None)
+            .map_err(|e| McpError::internal_error(e, None))?;
+
+        let resources = files
+            .iter()
+            .skip(start)
+            .take(MAX_LISTED_FILES)
+            .map(|file| {
+                let mut resource = RawResource::new(format!("{}{}", SPEC_URI_PREFIX, file), file.clone());
+                resource.mime_type = Some(SPEC_MIME_TYPE.to_string());
+                resource.no_annotation()
+            })
+            .collect();
+        let next_cursor = (start + MAX_LISTED_FILES < files.len()).then(|| (start + MAX_LISTED_FILES).to_string());
+
+        Ok(ListResourcesResult { next_cursor, resources })
+    }
+
+    fn read_spec_resource(&self, uri: String) -> Result<ReadResourceResult, McpError> {
+        let path = uri.strip_prefix(SPEC_URI_PREFIX).ok_or_else(|| {
+            McpError::resource_not_found(format!("Unknown resource {}; spec files are {}PATH", uri, SPEC_URI_PREFIX), None)
+        })?;
+        let text = self
+            .spec_listing
+            .read(&self.resource_root(), path)
+            .map_err(|e| McpError::resource_not_found(e, None))?;
+
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::TextResourceContents {
+                uri,
+                mime_type: Some(SPEC_MIME_TYPE.to_string()),
+                text,
+                meta: None,
+            }],
+        })
+    }
+
+    /// The `fix_failing_spec` prompt for the run `request` names, or the
+    /// latest.
+    fn prompt(&self, request: GetPromptRequestParam) -> Result<GetPromptResult, McpError> {
+        if request.name != FIX_FAILING_SPEC || !self.history.is_enabled() {
+            return Err(McpError::invalid_params(format!("Unknown prompt {}", request.name), None));
+        }
+        let run_id = match request.arguments.as_ref().and_then(|arguments| arguments.get("run_id")) {
+            None | Some(serde_json::Value::Null) => None,
+            Some(serde_json::Value::Number(id)) => id.as_u64(),
+            Some(serde_json::Value::String(id)) if id.trim().is_empty() => None,
+            Some(serde_json::Value::String(id)) => Some(id.trim().parse().map_err(|_| {
+                McpError::invalid_params(format!("Invalid parameters: run_id must be a run id, got '{}'", id), None)
+            })?),
+            Some(other) => return Err(McpError::invalid_params(format!("Invalid parameters: run_id must be a run id, got {}", other), None)),
+        };
+        let Some(run) = self.history.get(run_id) else {
+            let message = match run_id {
+                Some(id) => format!("No run {} in the history; it keeps the last {} runs", id, self.history.capacity()),
+                None => "No runs yet; call run_rspec first".to_string(),
+            };
+            return Err(McpError::invalid_params(message, None));
+        };
+
+        fix_failing_spec(&run).map_err(|e| McpError::invalid_params(e, None))
+    }
+
+    /// The directory and RSpec command for a request's `project` and
+    /// `workdir`: the project's root and command when it names one, else
+    /// the server's own.
+    fn run_target(
+        &self,
+        project: Option<&str>,
+        workdir: Option<&str>,
+    ) -> Result<(Option<std::path::PathBuf>, Option<Vec<String>>), String> {
+        match project.map(str::trim).filter(|name| !name.is_empty()) {
+            Some(name) => {
+                let project = self.projects.get(name)?;
+                Ok((project.workdirs.resolve(workdir)?, project.command.clone()))
+            }
+            None => Ok((self.workdirs.resolve(workdir)?, None)),
+        }
+    }
+
+    /// `parsed` relative to the run's directory, if it stays inside it and
+    /// `--allowed-path` allows it.
+    fn resolve_target(&self, parsed: ParsedFilePath, workdir: Option<&std::path::Path>) -> Result<ParsedFilePath, String> {
+        let parsed = parsed.relative_to(workdir)?;
+        parsed.allowed_by(&self.allowed_paths)?;
+        Ok(parsed)
+    }
+
+    /// Names the program that failed to boot RSpec, for `NotStarted` runs.
+    fn not_started_hint(&self, paths: &[String], context: &RunContext) -> String {
+        let command = self.runner.command_line(paths, context);
+        let program = command.first().map_or("rspec", String::as_str);
+        format!(
+            "Hint: `{}` exited before RSpec ran; check that it and rspec are installed and on PATH, and that the project's gems are installed (`bundle install`)\n",
+            program
+        )
+    }
+
+    /// The report on stdout, when runs use `--format json`.
+    fn stdout_report(&self, stdout: &str) -> Option<RspecReport> {
+        self.json_format.then(|| RspecReport::from_output(stdout)).flatten()
+    }
+
+    fn authorize(&self, tool: &str, extensions: &Extensions) -> Result<(), McpError> {
+        let headers = extensions
+            .get::<axum::http::request::Parts>()
+            .map(|parts| &parts.headers);
+
+        self.auth
+            .check(tool, self.is_read_only(tool), headers)
+            .map_err(|e| McpError::invalid_params(e, None))
+    }
+
+    /// Whether `tool` is declared with `read_only_hint = true`, which leaves
+    /// it open under `--auth-scope runs`. Admin tools never are, as they
+    /// reach every session's state.
+    fn is_read_only(&self, tool: &str) -> bool {
+        let hint = self
+            .tool_router
+            .map
+            .get(tool)
+            .and_then(|route| route.attr.annotations.as_ref())
+            .and_then(|annotations| annotations.read_only_hint);
+        hint == Some(true) && !ADMIN_TOOLS.contains(&tool)
+    }
+
+    /// Rejects flags the detected RSpec version doesn't understand. When the
+    /// version is unknown everything is passed through to RSpec.
+    async fn require_flag(&self, flag: &str) -> Result<(), McpError> {
+        match self.versions.get_or_detect(&self.runner).await {
+            Some(version) => version
+                .require(flag)
+                .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None)),
+            None => Ok(()),
+        }
+    }
+
+    #[tool(
+        description = "Run RSpec tests for a specific file with optional line number targeting. Accepts file paths relative to project root, ending in '_spec.rb' with optional array of line numbers. To select examples by description instead, which survives edits that move lines, pass 'example'",
+        annotations(read_only_hint = false)
+    )]
+    async fn run_rspec(
+        &self,
+        Parameters(args): Parameters<RspecServerArgs>,
+        peer: Peer<RoleServer>,
+        meta: Meta,
+        cancel: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let listeners = self.listeners(peer, meta);
+        self.execute_run(args, listeners, RunKind::Plain, Some(cancel)).await
+    }
+
+    /// `execute_run` with only example events, as most tests need.
+    #[cfg(test)]
+    async fn execute_rspec(
+        &self,
+        args: RspecServerArgs,
+        events: Option<UnboundedSender<ExampleEvent>>,
+    ) -> Result<CallToolResult, McpError> {
+        let listeners = RunListeners {
+            examples: events,
+            lines: None,
+        };
+        self.execute_run(args, listeners, RunKind::Plain, None).await
+    }
+
+    /// Example events with `--stream-examples`, and progress notifications
+    /// when the request carries a progress token.
+    fn listeners(&self, peer: Peer<RoleServer>, meta: Meta) -> RunListeners {
+        RunListeners {
+            examples: self.stream_examples.then(|| forward_example_events(peer.clone())),
+            lines: meta.get_progress_token().map(|token| forward_progress(peer, token)),
+        }
+    }
+
+    /// Runs `args`, as `kind` asks. Cancelling `cancel` kills the run, which
+    /// then reports the output it had produced.
+    async fn execute_run(
+        &self,
+        args: RspecServerArgs,
+        listeners: RunListeners,
+        kind: RunKind,
+        cancel: Option<CancellationToken>,
+    ) -> Result<CallToolResult, McpError> {
+        let args = self.session_defaults.lock().unwrap().apply(args);
+        let args = apply_defaults(args, &self.defaults);
+
+        // Parse the file paths and validate format
+        let line_numbers = args.line_numbers.unwrap_or_default();
+        let (workdir, command) = self
+            .run_target(args.project.as_deref(), args.workdir.as_deref())
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        let example_ids = args.example_ids.clone().unwrap_or_default();
+        let parsed_files = match ParsedFilePath::from_paths(args.file.as_slice(), line_numbers).and_then(|parsed| {
+            if parsed.len() > 1 && !example_ids.is_empty() {
+                return Err("Example ids can only be used with a single file".to_string());
+            }
+            parsed
+                .into_iter()
+                .map(|parsed| self.resolve_target(parsed, workdir.as_deref())?.with_example_ids(&example_ids))
+                .collect::<Result<Vec<_>, _>>()
+        }) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                return Err(McpError::invalid_params(
+                    format!("Invalid parameters: {}", e),
+                    None,
+                ));
+            }
+        };
+
+        if parsed_files.iter().any(|parsed| parsed.line_numbers.len() > 1) {
+            self.require_flag("file:LINE:LINE").await?;
+        }
+        if parsed_files.iter().any(|parsed| !parsed.example_ids.is_empty()) {
+            self.require_flag("file[ID]").await?;
+        }
+
+        // Build the RSpec file arguments from parsed components; results
+        // name them all together
+        let rspec_args: Vec<String> = parsed_files.iter().map(ParsedFilePath::as_arg).collect();
+        let rspec_arg = rspec_args.join(" ");
+
+        if args.example.as_deref().is_some_and(|e| e.trim().is_empty() || e.contains('\0')) {
+            return Err(McpError::invalid_params(
+                "Invalid parameters: example must be non-empty text without null bytes",
+                None,
+            ));
+        }
+        if args.timeout_seconds == Some(0) {
+            return Err(McpError::invalid_params(
+                "Invalid parameters: timeout_seconds must be at least 1",
+                None,
+            ));
+        }
+        let mut context = RunContext {
+            workdir,
+            command,
+            timeout: args.timeout_seconds.map(Duration::from_secs),
+            cancel,
+            ..Default::default()
+        };
+        for parsed_file in parsed_files.iter().filter(|parsed| parsed.is_directory()) {
+            check_spec_directory(context.workdir.as_deref(), &parsed_file.file_path)
+                .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        }
+        if let Some(example) = &args.example {
+            context.extra_args.extend(["-e".to_string(), example.clone()]);
+        }
+        context.extra_args.extend(
+            tag_args(args.tags.as_deref().unwrap_or_default())
+                .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?,
+        );
+        context.extra_args.extend(
+            order_args(args.order, args.seed)
+                .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?,
+        );
+        if args.verbose.unwrap_or(false) {
+            context.extra_args.extend(VERBOSE_ARGS.iter().map(|arg| arg.to_string()));
+            context.env.extend(VERBOSE_ENV.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+        }
+        if let Some(env) = &args.env {
+            context.env.extend(
+                self.env_allowlist
+                    .check(env)
+                    .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?,
+            );
+        }
+        if let (RunKind::Coverage, Some(coverage)) = (kind, &self.coverage) {
+            context.env.extend(coverage.env.iter().cloned());
+        }
+        if let Some(example_status) = &self.example_status {
+            let status_args = match kind {
+                RunKind::OnlyFailures => example_status.rerun_args(),
+                RunKind::Plain | RunKind::Coverage => example_status.prepare(),
+            };
+            context.extra_args.extend(status_args.map_err(|e| McpError::internal_error(e, None))?);
+        }
+        // Retries leave the first run's JSON report in place
+        let retry_context = context.clone();
+        // Streaming already puts the documentation formatter on stdout
+        let json_report = match args.human_formatter {
+            Some(_) if self.json_format => {
+                return Err(McpError::invalid_params(
+                    "Invalid parameters: human_formatter can't be used with --format json, which already writes the JSON report to stdout",
+                    None,
+                ));
+            }
+            Some(HumanFormatter::Progress) if self.stream_examples => {
+                return Err(McpError::invalid_params(
+                    "Invalid parameters: human_formatter 'progress' can't be used with --stream-examples, which needs the documentation formatter",
+                    None,
+                ));
+            }
+            Some(human) => {
+                let report = JsonReportFile::new();
+                let human = (!self.stream_examples).then_some(human);
+                context.extra_args.extend(report.formatter_args(human));
+                Some(report)
+            }
+            None if self.junit_reports && !self.json_format => {
+                let report = JsonReportFile::new();
+                let human = (!self.stream_examples).then_some(HumanFormatter::Progress);
+                context.extra_args.extend(report.formatter_args(human));
+                Some(report)
+            }
+            None => None,
+        };
+
+        if args.dry_run_command.unwrap_or(false) {
+            let command = self.display_command(&rspec_args, &context);
+            return Ok(CallToolResult::success(vec![Content::text(command)]));
+        }
+
+        if let Some(example_status) = &self.example_status {
+            example_status.record(LastRun {
+                files: parsed_files.iter().map(|parsed| parsed.file_path.clone()).collect(),
+                workdir: args.workdir.clone(),
+                project: args.project.clone(),
+            });
+        }
+
+        if let Some(timeout) = self.per_file_timeout
+            && let [dir] = parsed_files.as_slice()
+            && dir.is_directory()
+        {
+            let limit = args.max_reported_failures.unwrap_or(DEFAULT_MAX_REPORTED_FAILURES);
+            return self.run_files_individually(&rspec_arg, &context, timeout, limit).await;
+        }
+
+        let started = std::time::Instant::now();
+        let mut result = match listeners.is_empty() {
+            true => self.runner.run_paths(&rspec_args, &context).await,
+            false => self.run_with_listeners(&rspec_args, &context, listeners).await,
+        };
+
+        // Only plain failures are retried; load errors won't fix themselves.
+        // Failures that can be located are rerun on their own, and the
+        // first run is reported; otherwise the last of the whole reruns is
+        let retries = args.retries.unwrap_or(0).min(MAX_RETRIES) as usize;
+        let mut outcomes = Vec::new();
+        let mut verdicts = Vec::new();
+        while let Ok(attempt) = &result {
+            let status = RunStatus::classify(attempt);
+            outcomes.push(status.label());
+            if status != RunStatus::Failed || attempt.cancelled || outcomes.len() > retries {
+                break;
+            }
+            let locations = self.failing_locations(attempt);
+            if !locations.is_empty() {
+                let remaining = retries + 1 - outcomes.len();
+                verdicts = self.retry_failures(locations, &retry_context, remaining, &mut outcomes).await;
+                break;
+            }
+            result = self.runner.run_paths(&rspec_args, &context).await;
+        }
+
+        if self.normalize_newlines {
+            result = result.map(|result| CommandResult {
+                stdout: normalize_newlines(&result.stdout),
+                stderr: normalize_newlines(&result.stderr),
+                ..result
+            });
+        }
+
+        match result {
+            Ok(result) => {
+                let status = RunStatus::classify(&result);
+                let stdout_report = self.stdout_report(&result.stdout);
+                let summary = RunSummary::parse(&result.stdout)
+                    .or_else(|| stdout_report.as_ref()?.run_summary());
+                let run_options = RunOptions::parse(&result.stdout);
+                // What reproduces this run's order, when it was random
+                let seed_used = args
+                    .seed
+                    .map(u64::from)
+                    .or_else(|| run_options.as_ref()?.seed)
+                    .or_else(|| summary.as_ref()?.seed);
+                let json_output = json_report.as_ref().and_then(JsonReportFile::read);
+                let report_source = json_output.as_deref().unwrap_or(&result.stdout);
+                let failures = stdout_report
+                    .as_ref()
+                    .map_or_else(|| FailureReport::parse_all(&result.stdout), RspecReport::failures);
+                let (failures, known_failures) = match &self.baseline {
+                    Some(baseline) => baseline.subtract(failures),
+                    None => (failures, 0),
+                };
+                // Only known failures is a pass; the exit code still shows
+                // in the text
+                let baseline_only = status == RunStatus::Failed && known_failures > 0 && failures.is_empty();
+                let status = match baseline_only {
+                    true => RunStatus::Passed,
+                    false => status,
+                };
+                let rerun_command = Some(rerun_locations(&result.stdout, &failures))
+                    .filter(|locations| !locations.is_empty())
+                    .map(|locations| self.display_command(&locations, &context));
+                let record = RunRecord {
+                    id: self.history.next_id(),
+                    targets: rspec_arg.clone(),
+                    command: self.display_command(&rspec_args, &context),
+                    status: status.label().to_string(),
+                    exit_code: result.exit_code,
+                    duration_secs: started.elapsed().as_secs_f64(),
+                    finished_at: run_history::now(),
+                    summary: summary.as_ref().map(ToString::to_string),
+                    result: String::new(),
+                    junit: match self.junit_reports {
+                        true => RspecReport::from_partial_output(report_source).map(|report| junit_xml(&report)),
+                        false => None,
+                    },
+                    failures: match self.history.is_enabled() {
+                        true => {
+                            let limit = args.max_reported_failures.unwrap_or(DEFAULT_MAX_REPORTED_FAILURES);
+                            recorded_failures(&failures[..failures.len().min(limit)], report_source, context.workdir.as_deref())
+                        }
+                        false => Vec::new(),
+                    },
+                    rerun_command: rerun_command.clone(),
+                };
+
+                if let Some(webhook) = self.failure_webhook.as_ref().filter(|_| status != RunStatus::Passed) {
+                    webhook.notify(FailurePayload {
+                        file: rspec_arg.clone(),
+                        failure_count: summary.as_ref().map_or(0, |s| s.failures),
+                        status: status.to_string(),
+                    });
+                }
+
+                if args.output_style.unwrap_or_default() == OutputStyle::Compact {
+                    let line = compact_status(&status, summary.as_ref());
+                    return Ok(self.record_run(record, self.run_result(&status, line)));
+                }
+
+                if args.output_style.unwrap_or_default() == OutputStyle::PlainSummary {
+                    let text = plain_summary(&rspec_arg, &status, summary.as_ref(), &failures);
+                    return Ok(self.record_run(record, self.run_result(&status, text)));
+                }
+
+                let detected_error = detect_error_pattern(&self.error_patterns, &result.stdout)
+                    .or_else(|| detect_error_pattern(&self.error_patterns, &result.stderr));
+                let slowest_files = self.slowest_files_report(report_source);
+                let mut structured = RspecReport::from_partial_output(report_source);
+                if let Some(report) = structured.as_mut() {
+                    let root = context.workdir.clone().unwrap_or_else(|| std::path::PathBuf::from("."));
+                    SourceContext::new(&root).annotate(&mut report.examples);
+                }
+                let mut structured_content = match &structured {
+                    Some(report) => report.structured_content(status.label()),
+                    // Without a JSON report there are only the counts
+                    None => serde_json::json!({
+                        "status": status.label(),
+                        "summary": summary.as_ref().map(ReportSummary::from),
+                    }),
+                };
+                if let Some(content) = structured_content.as_object_mut() {
+                    content.insert("seed".to_string(), seed_used.into());
+                    content.insert("attempts".to_string(), outcomes.len().into());
+                    content.insert("outcomes".to_string(), outcomes.clone().into());
+                }
+                if let Some(content) = structured_content.as_object_mut().filter(|_| !verdicts.is_empty()) {
+                    let retried = verdicts.iter().map(|(location, verdict)| {
+                        let retry = match verdict {
+                            RetryVerdict::Flaky(retry) => Some(*retry),
+                            _ => None,
+                        };
+                        serde_json::json!({ "location": location, "verdict": verdict.label(), "passed_on_retry": retry })
+                    });
+                    content.insert("retries".to_string(), retried.collect());
+                }
+                let partial_examples = structured.filter(|report| report.partial).map(|report| report.examples);
+                let groups = if args.group_examples.unwrap_or(false) {
+                    RspecReport::from_output(report_source)
+                        .map(|report| report.groups())
+                        .filter(|groups| !groups.is_empty())
+                } else {
+                    None
+                };
+                let failure_limit = args.max_reported_failures.unwrap_or(DEFAULT_MAX_REPORTED_FAILURES);
+                let blames = if self.git_blame {
+                    self.blame_failures(&failures[..failures.len().min(failure_limit)], &context)
+                        .await
+                } else {
+                    None
+                };
+                let load_diagnostics = if self.diagnose_load_errors && status.is_load_failure() {
+                    self.load_diagnostics(&rspec_args, &context).await
+                } else {
+                    None
+                };
+                // Everything above parses the full output; only the text
+                // echoed back is truncated
+                let CommandResult { exit_code, stdout, stderr, abort_reason, signal, cancelled, peak_memory_mb, launcher, .. } = result;
+                let (stdout, stderr) = if failures.iter().any(FailureReport::is_stack_overflow)
+                    || stderr.contains("SystemStackError")
+                {
+                    (collapse_repeated_frames(&stdout), collapse_repeated_frames(&stderr))
+                } else {
+                    (stdout, stderr)
+                };
+                // A report that didn't parse is shown raw
+                let stdout = stdout_report.as_ref().map_or(stdout, format_report);
+                let outcome = match cancelled {
+                    true => "cancelled by the client before finishing; results are incomplete".to_string(),
+                    false if baseline_only => "passed; every failure is a known baseline failure".to_string(),
+                    false => status.outcome(exit_code),
+                };
+                let outcome = match !verdicts.is_empty() && verdicts.iter().all(|(_, v)| matches!(v, RetryVerdict::Flaky(_))) {
+                    true => format!("{}, but every failing example passed on retry (flaky)", outcome),
+                    false => outcome,
+                };
+                let exit_code = match signal {
+                    Some(signal) => format!("none (terminated by signal {})", signal),
+                    None => exit_code.to_string(),
+                };
+                let (stdout, stderr, full_output) = match self.max_output_bytes {
+                    Some(limit) if stdout.len() + stderr.len() > limit => {
+                        let full_output = self.keep_full_output(record.id, &stdout, &stderr);
+                        let (stdout_limit, stderr_limit) = split_output_limit(stdout.len(), stderr.len(), limit);
+                        (truncate_output(&stdout, stdout_limit), truncate_output(&stderr, stderr_limit), full_output)
+                    }
+                    _ => (stdout, stderr, String::new()),
+                };
+                let summary = summary
+                    .map(|summary| format!("Summary: {}\n", summary))
+                    .unwrap_or_default();
+                let run_options = run_options
+                    .map(|options| format!("Run Options: {}\n", options))
+                    .unwrap_or_default();
+                let aborted = abort_reason
+                    .map(|reason| format!("Aborted: {}\n", reason))
+                    .unwrap_or_default();
+                let peak_memory = peak_memory_mb
+                    .map(|mb| format!("Peak Memory: {:.1} MB\n", mb))
+                    .unwrap_or_default();
+                let launcher = launcher
+                    .map(|launcher| format!("Runner: {}\n", launcher))
+                    .unwrap_or_default();
+                let hint = match &status {
+                    RunStatus::NotStarted { .. } => self.not_started_hint(&rspec_args, &context),
+                    _ => String::new(),
+                };
+                let seed = match (args.seed, seed_used) {
+                    (Some(seed), _) => format!("Seed: {} (requested)\n", seed),
+                    (None, Some(seed)) => format!("Seed: {} (random order; pass seed: {} to reproduce it)\n", seed, seed),
+                    (None, None) => String::new(),
+                };
+                let header = format!(
+                    "Test Results for: {}\n{}{}Exit Code: {}\nStatus: {}\nOutcome: {}\n{}Attempts: {} ({})\n{}{}{}{}{}{}",
+                    rspec_arg,
+                    workdir_line(&context),
+                    launcher,
+                    exit_code,
+                    status,
+                    outcome,
+                    hint,
+                    outcomes.len(),
+                    outcomes.join(", "),
+                    aborted,
+                    summary,
+                    run_options,
+                    seed,
+                    peak_memory,
+                    full_output
+                );
+                let mut result_text = format!("\nOutput:\n{}\n\nErrors:\n{}", stdout, stderr);
+
+                if let Some(error) = detected_error {
+                    result_text.push_str(&format!(
+                        "\n\nDetected Error: {} (check that the test database is running and migrated)",
+                        error
+                    ));
+                }
+
+                let mut failures_text = String::new();
+                if !failures.is_empty() {
+                    failures_text.push_str(&format!(
+                        "\n\nFailures ({} of {} shown):\n",
+                        failures.len().min(failure_limit),
+                        failures.len()
+                    ));
+                    failures_text.push_str(&format_failures(&failures, failure_limit));
+                }
+
+                if !verdicts.is_empty() {
+                    failures_text.push_str(&format!(
+                        "\n\nRetries ({} of {} attempts were reruns of the failing examples):\n",
+                        outcomes.len() - 1,
+                        outcomes.len()
+                    ));
+                    failures_text.push_str(&format_retries(&verdicts));
+                }
+
+                if known_failures > 0 {
+                    failures_text.push_str(&format!(
+                        "\n\nBaseline: {} known failure{} not listed{}",
+                        known_failures,
+                        if known_failures == 1 { "" } else { "s" },
+                        if failures.is_empty() { "; no new failures" } else { "" }
+                    ));
+                }
+                result_text.push_str(&failures_text);
+
+                if let Some(blames) = blames {
+                    result_text.push_str("\n\nBlame:\n");
+                    result_text.push_str(&blames);
+                }
+
+                if let Some(examples) = partial_examples {
+                    result_text.push_str(&format!(
+                        "\n\nPartial Results (partial: true, the JSON report was cut off; {} examples completed):\n",
+                        examples.len()
+                    ));
+                    result_text.push_str(&format_examples(&examples));
+                }
+
+                if let Some(rerun_command) = rerun_command {
+                    result_text.push_str("\n\nRerun Command:\n");
+                    result_text.push_str(&rerun_command);
+                }
+
+                if let Some(groups) = groups {
+                    result_text.push_str("\n\nExample Groups:\n");
+                    result_text.push_str(&format_groups(&groups));
+                }
+
+                if let Some(slowest_files) = slowest_files {
+                    result_text.push_str("\n\nSlowest Files:\n");
+                    result_text.push_str(&slowest_files);
+                }
+
+                if let Some(load_diagnostics) = load_diagnostics {
+                    result_text.push_str("\n\nLoad Diagnostics:\n");
+                    result_text.push_str(&load_diagnostics);
+                }
+
+                if let Some(env) = &self.reported_env {
+                    let applied: Vec<_> = env.iter().chain(&context.env).cloned().collect();
+                    result_text.push_str("\n\nEnvironment:\n");
+                    result_text.push_str(&match applied.is_empty() {
+                        true => "  (none)".to_string(),
+                        false => format_env_report(&applied),
+                    });
+                }
+
+                let result_text = match self.response_budget {
+                    Some(budget) => fit_to_budget(budget, |level| {
+                        let detail = format!("Detail: {} (response budget {} bytes)\n", level.label(), budget);
+                        match level {
+                            DetailLevel::Full => format!("{}{}{}", header, detail, result_text),
+                            DetailLevel::FailuresOnly => {
+                                format!("{}{}\n{}", header, detail, failures_text.trim_start()).trim_end().to_string()
+                            }
+                            DetailLevel::SummaryOnly => format!("{}{}", header, detail.trim_end()),
+                        }
+                    }),
+                    None => format!("{}{}", header, result_text),
+                };
+
+                let mut result = self.run_result(&status, result_text);
+                result.structured_content = Some(structured_content);
+                Ok(self.record_run(record, result))
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("Command failed: {}", e),
+                None,
+            )),
+        }
+    }
+
+    /// Locations that rerun each of the run's failures.
+    fn failing_locations(&self, result: &CommandResult) -> Vec<String> {
+        let failures = self
+            .stdout_report(&result.stdout)
+            .map_or_else(|| FailureReport::parse_all(&result.stdout), |report| report.failures());
+        rerun_locations(&result.stdout, &failures)
+    }
+
+    /// Reruns the failing `locations` up to `retries` times, each time only
+    /// those still failing, recording each attempt in `outcomes`.
+    async fn retry_failures(
+        &self,
+        mut locations: Vec<String>,
+        context: &RunContext,
+        retries: usize,
+        outcomes: &mut Vec<&'static str>,
+    ) -> Vec<(String, RetryVerdict)> {
+        let order = locations.clone();
+        let mut verdicts = Vec::new();
+        let mut stopped_by = None;
+        for retry in 1..=retries {
+            let Ok(attempt) = self.runner.run_paths(&locations, context).await else {
+                stopped_by = Some("error");
+                break;
+            };
+            let status = RunStatus::classify(&attempt);
+            outcomes.push(status.label());
+            // A retry that fails without saying where can't tell them apart
+            let still_failing = match status {
+                _ if attempt.cancelled => None,
+                RunStatus::Passed => Some(Vec::new()),
+                RunStatus::Failed => Some(self.failing_locations(&attempt)).filter(|failing| !failing.is_empty()),
+                _ => None,
+            };
+            let Some(still_failing) = still_failing else {
+                stopped_by = Some(status.label());
+                break;
+            };
+
+            let (failing, passed): (Vec<String>, Vec<String>) =
+                locations.into_iter().partition(|location| still_failing.contains(location));
+            verdicts.extend(passed.into_iter().map(|location| (location, RetryVerdict::Flaky(retry))));
+            locations = failing;
+            if locations.is_empty() {
+                break;
+            }
+        }
+
+        let remaining = match stopped_by {
+            Some(status) => RetryVerdict::Unconfirmed(status),
+            None => RetryVerdict::Consistent,
+        };
+        verdicts.extend(locations.into_iter().map(|location| (location, remaining.clone())));
+        verdicts.sort_by_key(|(location, _)| order.iter().position(|listed| listed == location));
+        verdicts
+    }
+
+    /// The `--per-file-timeout-secs` path for directory targets: each spec
+    /// file gets its own process and time budget.
+    async fn run_files_individually(
+        &self,
+        dir: &str,
+        context: &RunContext,
+        timeout: Duration,
+        failure_limit: usize,
+    ) -> Result<CallToolResult, McpError> {
+        let files = spec_files_in(context.workdir.as_deref(), dir)
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        let context = RunContext {
+            timeout: Some(timeout),
+            ..context.clone()
+        };
+
+        let mut outcomes = Vec::new();
+        let mut failures = Vec::new();
+        let (mut passed, mut failed, mut timed_out) = (0, 0, 0);
+        for file in &files {
+            let result = self
+                .runner
+                .run(file, &context)
+                .await
+                .map_err(|e| McpError::internal_error(format!("Command failed: {}", e), None))?;
+            let stdout = match self.normalize_newlines {
+                true => normalize_newlines(&result.stdout),
+                false => result.stdout.clone(),
+            };
+
+            let outcome = if result.cancelled {
+                outcomes.push(format!("  {}: cancelled by the client", file));
+                failed += 1;
+                break;
+            } else if result.timed_out {
+                timed_out += 1;
+                format!("timed out after {} seconds", timeout.as_secs_f64())
+            } else {
+                let status = RunStatus::classify(&result);
+                match status {
+                    RunStatus::Passed => passed += 1,
+                    _ => failed += 1,
+                }
+                let summary = RunSummary::parse(&stdout)
+                    .or_else(|| self.stdout_report(&stdout)?.run_summary());
+                match summary {
+                    Some(summary) => format!("{} ({})", status.label(), summary),
+                    None => status.label().to_string(),
+                }
+            };
+            outcomes.push(format!("  {}: {}", file, outcome));
+            failures.extend(
+                self.stdout_report(&stdout)
+                    .map_or_else(|| FailureReport::parse_all(&stdout), |report| report.failures()),
+            );
+        }
+
+        let status = if failed + timed_out == 0 { RunStatus::Passed } else { RunStatus::Failed };
+        if let Some(webhook) = self.failure_webhook.as_ref().filter(|_| status != RunStatus::Passed) {
+            webhook.notify(FailurePayload {
+                file: dir.to_string(),
+                failure_count: failures.len() as u32,
+                status: status.to_string(),
+            });
+        }
+
+        let mut result_text = format!(
+            "Test Results for: {}\n{}Status: {}\nFiles: {} ({} passed, {} failed, {} timed out)\n\nPer-File Results:\n{}",
+            dir,
+            workdir_line(&context),
+            status,
+            files.len(),
+            passed,
+            failed,
+            timed_out,
+            outcomes.join("\n")
+        );
+        if !failures.is_empty() {
+            result_text.push_str(&format!(
+                "\n\nFailures ({} of {} shown):\n",
+                failures.len().min(failure_limit),
+                failures.len()
+            ));
+            result_text.push_str(&format_failures(&failures, failure_limit));
+        }
+
+        Ok(self.run_result(&status, result_text))
+    }
+
+    /// Adds the run to the history with the text `result` returns.
+    fn record_run(&self, mut record: RunRecord, result: CallToolResult) -> CallToolResult {
+        if self.history.is_enabled() {
+            record.result = result.content.first().and_then(|content| content.as_text()).map(|text| text.text.clone()).unwrap_or_default();
+            self.history.record(record);
+        }
+        result
+    }
+
+    fn run_result(&self, status: &RunStatus, text: String) -> CallToolResult {
+        if self.error_on_failure && *status != RunStatus::Passed {
+            CallToolResult::error(vec![Content::text(text)])
+        } else {
+            CallToolResult::success(vec![Content::text(text)])
+        }
+    }
+
+    /// Blames each failure's location in the run's directory. Locations git
+    /// can't blame are left out; `None` when none could be.
+    async fn blame_failures(&self, failures: &[FailureReport], context: &RunContext) -> Option<String> {
+        let workdir = match &context.workdir {
+            Some(workdir) => workdir.clone(),
+            None => std::env::current_dir().ok()?,
+        };
+
+        let mut blames = Vec::new();
+        for location in failures.iter().filter_map(|failure| failure.location.as_ref()) {
+            if blames.iter().any(|(blamed, _)| blamed == location) {
+                continue;
+            }
+            if let Some(blame) = blame_location(&workdir, location, GIT_BLAME_TIMEOUT).await {
+                blames.push((location.clone(), blame));
+            }
+        }
+
+        (!blames.is_empty()).then(|| format_blames(&blames))
+    }
+
+    /// The command line for `paths` as it can be pasted into a shell,
+    /// including the `cd` when the run has a workdir. Environment values
+    /// are left out since they end up in results and the run history.
+    fn display_command(&self, paths: &[String], context: &RunContext) -> String {
+        let command = shell_join(&self.runner.command_line(paths, context));
+        let command = match context.env.is_empty() {
+            true => command,
+            false => {
+                let env = context.env.iter().map(|(k, _)| format!("{}=[REDACTED]", k));
+                format!("{} {}", env.collect::<Vec<_>>().join(" "), command)
+            }
+        };
+        match &context.workdir {
+            Some(workdir) => {
+                let cd = shell_join(&["cd".to_string(), workdir.to_string_lossy().to_string()]);
+                format!("{} && {}", cd, command)
+            }
+            None => command,
+        }
+    }
+
+    fn slowest_files_report(&self, stdout: &str) -> Option<String> {
+        if self.slowest_files == 0 {
+            return None;
+        }
+
+        let report = RspecReport::from_output(stdout)?;
+        if report.file_count() < 2 {
+            return None;
+        }
+
+        let files = report.slowest_files(self.slowest_files);
+        (!files.is_empty()).then(|| format_slowest_files(&files))
+    }
+
+    async fn load_diagnostics(&self, rspec_args: &[String], context: &RunContext) -> Option<String> {
+        // Held until the diagnostic run finishes
+        let (_script, extra_args) = match LoadDiagnostics::prepare() {
+            Ok(prepared) => prepared,
+            Err(e) => return Some(e),
+        };
+        let context = RunContext {
+            extra_args,
+            ..context.clone()
+        };
+
+        let diagnostics = match self.runner.run_paths(rspec_args, &context).await {
+            Ok(result) => LoadDiagnostics::parse(&result.stderr)
+                .or_else(|| LoadDiagnostics::parse(&result.stdout)),
+            Err(e) => return Some(format!("Diagnostic run failed: {}", e)),
+        };
+
+        Some(match diagnostics {
+            Some(diagnostics) => diagnostics.render(),
+            None => "Diagnostic run produced no load path information".to_string(),
+        })
+    }
+
+    /// Saves output about to be truncated, returning the header line that
+    /// points to it, or nothing if it couldn't be saved.
+    fn keep_full_output(&self, id: u64, stdout: &str, stderr: &str) -> String {
+        match self.run_outputs.save(id, stdout, stderr) {
+            Ok(()) => format!(
+                "Full Output: run {} ({} bytes of stdout, {} of stderr; the output below is truncated, read it all with get_run_output)\n",
+                id,
+                stdout.len(),
+                stderr.len()
+            ),
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to keep truncated output");
+                String::new()
+            }
+        }
+    }
+
+    /// Runs the spec while handing each stdout line to `listeners`, feeding
+    /// the documentation parser for example events. The result still holds
+    /// all the output, including what came before a kill or error.
+    async fn run_with_listeners(
+        &self,
+        rspec_args: &[String],
+        context: &RunContext,
+        listeners: RunListeners,
+    ) -> Result<CommandResult, String> {
+        let (lines_tx, mut lines_rx) = mpsc::unbounded_channel::<String>();
+        // Queue positions go straight to progress, not to the parser
+        let context = RunContext { notices: listeners.lines.clone(), ..context.clone() };
+
+        let forwarder = tokio::spawn(async move {
+            let RunListeners { examples, lines } = listeners;
+            let mut parser = DocumentationParser::new();
+            while let Some(line) = lines_rx.recv().await {
+                if let Some(events) = &examples {
+                    for event in parser.push(&line) {
+                        let _ = events.send(event);
+                    }
+                }
+                if let Some(lines) = &lines {
+                    let _ = lines.send(line);
+                }
+            }
+            if let Some(events) = &examples
+                && let Some(event) = parser.finish()
+            {
+                let _ = events.send(event);
+            }
+        });
+
+        let result = self.runner.run_streaming(rspec_args, &context, lines_tx).await;
+        let _ = forwarder.await;
+        result
+    }
+
+    #[tool(
+        description = "List the *_spec.rb files under the spec directory, relative to the project root, optionally filtered by a substring or glob. Hidden and excluded directories are skipped",
+        annotations(read_only_hint = true)
+    )]
+    async fn list_spec_files(
+        &self,
+        Parameters(args): Parameters<ListSpecFilesArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (workdir, _) = self
+            .run_target(args.project.as_deref(), args.workdir.as_deref())
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        let root = workdir.unwrap_or_else(|| std::path::PathBuf::from("."));
+        let files = self
+            .spec_listing
+            .list(&root, args.filter.as_deref())
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+
+        let mut result_text = format!("Spec Files in: {}\n", self.spec_listing.spec_dir());
+        if let Some(filter) = args.filter.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+            result_text.push_str(&format!("Filter: {}\n", filter));
+        }
+        result_text.push_str(&format!("Count: {}\n", files.len()));
+        for file in files.iter().take(MAX_LISTED_FILES) {
+            result_text.push_str(&format!("\n{}", file));
+        }
+        if files.len() > MAX_LISTED_FILES {
+            result_text.push_str(&format!(
+                "\n... and {} more; pass a filter to narrow the list",
+                files.len() - MAX_LISTED_FILES
+            ));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(result_text)]))
+    }
+
+    #[tool(
+        description = "Find the spec for an implementation file: maps e.g. app/models/user.rb to spec/models/user_spec.rb and lib/foo/bar.rb to spec/foo/bar_spec.rb, and says which of them exist. With search, falls back to spec files of the same name or describing the file's constant",
+        annotations(read_only_hint = true)
+    )]
+    async fn find_spec_for(
+        &self,
+        Parameters(args): Parameters<FindSpecForArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let invalid = |e: String| McpError::invalid_params(format!("Invalid parameters: {}", e), None);
+        let (workdir, _) = self
+            .run_target(args.project.as_deref(), args.workdir.as_deref())
+            .map_err(invalid)?;
+        let source = crate::workdir::relative_spec_path(workdir.as_deref(), args.file.trim()).map_err(invalid)?;
+        let mapping = SpecMapping::for_source(&source, self.spec_listing.spec_dir()).map_err(invalid)?;
+        let root = workdir.unwrap_or_else(|| std::path::PathBuf::from("."));
+
+        let mut result_text = format!("Specs for: {}\n", mapping.source);
+        let mut found = false;
+        for candidate in &mapping.candidates {
+            let exists = root.join(candidate).is_file();
+            found |= exists;
+            result_text.push_str(&format!("\n{} ({})", candidate, if exists { "exists" } else { "missing" }));
+        }
+
+        if !found && args.search.unwrap_or(false) {
+            let matches = self
+                .spec_listing
+                .search(&root, mapping.spec_file_name(), mapping.constant.as_deref())
+                .map_err(invalid)?;
+            let described = mapping.constant.as_deref().map(|constant| format!(" or describing {}", constant)).unwrap_or_default();
+            result_text.push_str(&format!(
+                "\n\nSearch: {} spec files named {}{}",
+                matches.len(),
+                mapping.spec_file_name().unwrap_or_default(),
+                described
+            ));
+            for file in matches.iter().take(MAX_LISTED_FILES) {
+                result_text.push_str(&format!("\n{}", file));
+            }
+        } else if !found {
+            result_text.push_str("\n\nNo spec found; pass search: true to look elsewhere in the spec directory");
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(result_text)]))
+    }
+
+    #[tool(
+        description = "Create a skeleton spec for an implementation file at its conventional path, e.g. spec/models/user_spec.rb for app/models/user.rb, describing the file's constant and requiring rails_helper or spec_helper as the project does. Never overwrites: when the spec already exists, nothing is written",
+        annotations(read_only_hint = false)
+    )]
+    async fn create_spec(&self, Parameters(args): Parameters<CreateSpecArgs>) -> Result<CallToolResult, McpError> {
+        let invalid = |e: String| McpError::invalid_params(format!("Invalid parameters: {}", e), None);
+        let (workdir, _) = self
+            .run_target(args.project.as_deref(), args.workdir.as_deref())
+            .map_err(invalid)?;
+        let source = crate::workdir::relative_spec_path(workdir.as_deref(), args.file.trim()).map_err(invalid)?;
+        let spec_dir = self.spec_listing.spec_dir();
+        let mapping = SpecMapping::for_source(&source, spec_dir).map_err(invalid)?;
+        let root = workdir.unwrap_or_else(|| std::path::PathBuf::from("."));
+        if mapping.source.ends_with("_spec.rb") {
+            return Err(invalid(format!("{} is already a spec file; pass the implementation file", mapping.source)));
+        }
+        if !root.join(&mapping.source).is_file() {
+            return Err(invalid(format!("{} does not exist", mapping.source)));
+        }
+
+        if let Some(existing) = mapping.candidates.iter().find(|candidate| root.join(candidate).is_file()) {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Spec already exists: {}\n\nNothing was written; add examples to it instead",
+                existing
+            ))]));
+        }
+        let spec_path = &mapping.candidates[0];
+        let contents = skeleton(&mapping, spec_path, spec_dir, SpecHelper::detect(&root, spec_dir));
+        create(&root.join(spec_path), &contents).map_err(|e| McpError::internal_error(e, None))?;
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Created: {}\n\n{}",
+            spec_path, contents
+        ))]))
+    }
+
+    #[tool(
+        description = "List the projects configured on this server, with their roots and RSpec commands. Pass a project's name as 'project' to the other tools to run there",
+        annotations(read_only_hint = true)
+    )]
+    async fn list_projects(&self) -> Result<CallToolResult, McpError> {
+        let mut result_text = format!("Projects: {}\n", self.projects.iter().count());
+        for project in self.projects.iter() {
+            let command = project.command.as_deref().map_or_else(|| "server default".to_string(), shell_join);
+            result_text.push_str(&format!(
+                "\n{}\n  Root: {}\n  Command: {}",
+                project.name,
+                project.root().display(),
+                command
+            ));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(result_text)]))
+    }
+
+    #[tool(
+        description = "Set defaults for this session's runs (run_rspec, rerun_failures, run_rspec_suite, run_rspec_with_coverage): project, workdir, env, formatter, output style and timeout. Arguments a run gives win over them, and other clients aren't affected. Returns the session's defaults; call it without arguments to see them",
+        annotations(read_only_hint = false)
+    )]
+    async fn configure(&self, Parameters(args): Parameters<ConfigureArgs>) -> Result<CallToolResult, McpError> {
+        let invalid = |e: String| McpError::invalid_params(format!("Invalid parameters: {}", e), None);
+        let mut defaults = match args.reset.unwrap_or(false) {
+            true => SessionDefaults::default(),
+            false => self.session_defaults.lock().unwrap().clone(),
+        };
+        if let Some(project) = args.project {
+            defaults.project = Some(project).filter(|name| !name.trim().is_empty());
+            defaults.workdir = None;
+        }
+        if let Some(workdir) = args.workdir {
+            defaults.workdir = Some(workdir).filter(|dir| !dir.trim().is_empty());
+        }
+        if let Some(env) = args.env {
+            self.env_allowlist.check(&env).map_err(invalid)?;
+            defaults.env = env;
+        }
+        defaults.human_formatter = args.human_formatter.or(defaults.human_formatter);
+        defaults.output_style = args.output_style.or(defaults.output_style);
+        defaults.timeout_seconds = args.timeout_seconds.or(defaults.timeout_seconds);
+        self.run_target(defaults.project.as_deref(), defaults.workdir.as_deref()).map_err(invalid)?;
+
+        let mut result_text = String::from("Session defaults:");
+        if defaults.is_empty() {
+            result_text.push_str(" none");
+        }
+        if let Some(project) = &defaults.project {
+            result_text.push_str(&format!("\n  project: {}", project));
+        }
+        if let Some(workdir) = &defaults.workdir {
+            result_text.push_str(&format!("\n  workdir: {}", workdir));
+        }
+        if !defaults.env.is_empty() {
+            let env: Vec<_> = defaults.env.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+            result_text.push_str(&format!("\n  env: {}", env.join(", ")));
+        }
+        if let Some(formatter) = defaults.human_formatter {
+            result_text.push_str(&format!("\n  human_formatter: {}", formatter.as_str()));
+        }
+        if let Some(style) = defaults.output_style.and_then(|style| clap::ValueEnum::to_possible_value(&style)) {
+            result_text.push_str(&format!("\n  output_style: {}", style.get_name()));
+        }
+        if let Some(timeout) = defaults.timeout_seconds {
+            result_text.push_str(&format!("\n  timeout_seconds: {}", timeout));
+        }
+        *self.session_defaults.lock().unwrap() = defaults;
+
+        Ok(CallToolResult::success(vec![Content::text(result_text)]))
+    }
+
+    #[tool(
+        description = "List recent runs, newest first, with their id, status, duration and summary. Pass an id to get_run to see what that run returned, e.g. to compare failures before and after a fix",
+        annotations(read_only_hint = true)
+    )]
+    async fn list_runs(&self, Parameters(args): Parameters<ListRunsArgs>) -> Result<CallToolResult, McpError> {
+        let runs = self.history.recent(args.limit.unwrap_or(10));
+        let mut result_text = format!("Runs: {} (last {} kept)\n", runs.len(), self.history.capacity());
+        for run in &runs {
+            result_text.push_str(&format!(
+                "\n#{} {} in {:.1}s, {}: {}",
+                run.id,
+                run.status,
+                run.duration_secs,
+                run.age(),
+                run.targets
+            ));
+            if let Some(summary) = &run.summary {
+                result_text.push_str(&format!("\n  Summary: {}", summary));
+            }
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(result_text)]))
+    }
+
+    #[tool(
+        description = "Show a recent run: its command, status and duration, then the full text its result returned. Defaults to the latest run",
+        annotations(read_only_hint = true)
+    )]
+    async fn get_run(&self, Parameters(args): Parameters<GetRunArgs>) -> Result<CallToolResult, McpError> {
+        let Some(run) = self.history.get(args.run_id) else {
+            let message = match args.run_id {
+                Some(id) => format!("No run {} in the history; it keeps the last {} runs", id, self.history.capacity()),
+                None => "No runs yet; call run_rspec first".to_string(),
+            };
+            return Err(McpError::invalid_params(message, None));
+        };
+
+        let result_text = format!(
+            "Run: {}\nCommand: {}\nStatus: {}\nExit Code: {}\nDuration: {:.1}s\nFinished: {}\n{}\n{}",
+            run.id,
+            run.command,
+            run.status,
+            run.exit_code,
+            run.duration_secs,
+            run.age(),
+            run.summary.map(|summary| format!("Summary: {}\n", summary)).unwrap_or_default(),
+            run.result
+        );
+
+        Ok(CallToolResult::success(vec![Content::text(result_text)]))
+    }
+
+    #[tool(
+        description = "Get a recent run's results as JUnit XML, for CI dashboards and test-history tools that read JUnit: a test case per example with its failure or pending message. Defaults to the latest run",
+        annotations(read_only_hint = true)
+    )]
+    async fn get_run_report(&self, Parameters(args): Parameters<GetRunReportArgs>) -> Result<CallToolResult, McpError> {
+        let Some(run) = self.history.get(args.run_id) else {
+            let message = match args.run_id {
+                Some(id) => format!("No run {} in the history; it keeps the last {} runs", id, self.history.capacity()),
+                None => "No runs yet; call run_rspec first".to_string(),
+            };
+            return Err(McpError::invalid_params(message, None));
+        };
+        let Some(junit) = run.junit else {
+            return Err(McpError::invalid_params(
+                format!("Run {} has no report; RSpec didn't write one, or it isn't a run_rspec run", run.id),
+                None,
+            ));
+        };
+
+        Ok(CallToolResult::success(vec![Content::text(junit)]))
+    }
+
+    #[tool(
+        description = "Read the full stdout or stderr of a run whose result was truncated, a page at a time. Truncated results name the run in their 'Full Output' line; the last 20 such runs of a session are kept",
+        annotations(read_only_hint = true)
+    )]
+    async fn get_run_output(
+        &self,
+        Parameters(args): Parameters<GetRunOutputArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let stream = args.stream.unwrap_or_default();
+        let limit = args.limit.or(self.max_output_bytes).unwrap_or(usize::MAX);
+        if limit == 0 {
+            return Err(McpError::invalid_params("limit must be at least 1", None));
+        }
+        let page = self
+            .run_outputs
+            .read(args.run_id, stream, args.offset.unwrap_or(0), limit)
+            .map_err(|e| McpError::invalid_params(e, None))?;
+
+        let next = match page.end < page.total {
+            true => format!("Next Offset: {}\n", page.end),
+            false => String::new(),
+        };
+        let result_text = format!(
+            "Run: {} ({})\nBytes: {}-{} of {}\n{}\n{}",
+            args.run_id,
+            match stream {
+                OutputStream::Stdout => "stdout",
+                OutputStream::Stderr => "stderr",
+            },
+            page.start,
+            page.end,
+            page.total,
+            next,
+            page.text
+        );
+
+        Ok(CallToolResult::success(vec![Content::text(result_text)]))
+    }
+
+    #[tool(
+        description = "Run the whole suite, or every spec under a directory such as spec/requests, in one RSpec process. The server may cap its duration and refuse suites with too many examples; narrow the directory then",
+        annotations(read_only_hint = false)
+    )]
+    async fn run_rspec_suite(
+        &self,
+        Parameters(args): Parameters<RunSuiteArgs>,
+        peer: Peer<RoleServer>,
+        meta: Meta,
+        cancel: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let listeners = self.listeners(peer, meta);
+        self.execute_suite(args, listeners, Some(cancel)).await
+    }
+
+    async fn execute_suite(
+        &self,
+        args: RunSuiteArgs,
+        listeners: RunListeners,
+        cancel: Option<CancellationToken>,
+    ) -> Result<CallToolResult, McpError> {
+        let directory = args
+            .directory
+            .map(|dir| dir.trim().to_string())
+            .filter(|dir| !dir.is_empty())
+            .unwrap_or_else(|| self.spec_listing.spec_dir().to_string());
+        let (workdir, command) = self
+            .run_target(args.project.as_deref(), args.workdir.as_deref())
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        let parsed = ParsedFilePath::from_args(&directory, Vec::new())
+            .and_then(|parsed| match parsed.is_directory() {
+                true => self.resolve_target(parsed, workdir.as_deref()),
+                false => Err("directory must be a directory of specs; use run_rspec for a single file".to_string()),
+            })
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        check_spec_directory(workdir.as_deref(), &parsed.file_path)
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+
+        let tags = tag_args(args.tags.as_deref().unwrap_or_default())
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        let parallel = self
+            .parallel_context(args.processes)
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+
+        if let Some(max) = self.suite_limits.max_examples {
+            let context = RunContext {
+                extra_args: ["--dry-run", "-f", "json"].iter().map(|arg| arg.to_string()).chain(tags.clone()).collect(),
+                workdir: workdir.clone(),
+                command: command.clone(),
+                cancel: cancel.clone(),
+                ..Default::default()
+            };
+            // A dry run that can't count leaves the real run to report why
+            if let Some(count) = self.count_examples(&parsed.file_path, &context).await
+                && count > max
+            {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "{} has {} examples, more than the {} allowed per suite run (--suite-max-examples); pass a narrower directory",
+                        parsed.file_path, count, max
+                    ),
+                    None,
+                ));
+            }
+        }
+
+        if let Some(parallel) = parallel
+            && let Some(split) = parallel.parallel
+        {
+            // With --format json the runner's own options already add it
+            let mut extra_args = match self.json_format {
+                true => Vec::new(),
+                false => vec!["-f".to_string(), "json".to_string()],
+            };
+            extra_args.extend(tags);
+            let context = RunContext {
+                extra_args,
+                workdir: workdir.clone(),
+                timeout: self.suite_limits.timeout,
+                cancel,
+                ..parallel
+            };
+            let rerun = RunContext { workdir, command, ..Default::default() };
+            return self.execute_parallel_suite(parsed.file_path, split, context, rerun, listeners).await;
+        }
+
+        let run_args = RspecServerArgs {
+            file: SpecPaths::One(parsed.file_path),
+            workdir: args.workdir,
+            project: args.project,
+            timeout_seconds: self.suite_limits.timeout.map(|timeout| timeout.as_secs().max(1)),
+            tags: args.tags,
+            ..Default::default()
+        };
+        self.execute_run(run_args, listeners, RunKind::Plain, cancel).await
+    }
+
+    /// The command and split for a suite run with `processes` requested,
+    /// or `None` for a single RSpec process.
+    fn parallel_context(&self, processes: Option<u32>) -> Result<Option<RunContext>, String> {
+        match (&self.parallel, processes) {
+            (_, Some(0)) => Err("processes must be at least 1".to_string()),
+            (_, Some(processes)) if processes > MAX_PARALLEL_PROCESSES => Err(format!(
+                "processes must be at most {}, got: {}",
+                MAX_PARALLEL_PROCESSES, processes
+            )),
+            (_, Some(1)) | (None, None) => Ok(None),
+            (None, Some(_)) => Err(
+                "Parallel runs are disabled; start the server with --parallel parallel-tests or --parallel turbo-tests"
+                    .to_string(),
+            ),
+            (Some(config), processes) => Ok(Some(RunContext {
+                command: Some(config.command.clone()),
+                parallel: Some(ParallelRun {
+                    strategy: config.strategy,
+                    processes: processes.or(config.processes),
+                }),
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Runs `path` split across processes in `context` and reports the
+    /// combined JSON reports of every process. The rerun command runs the
+    /// failures with RSpec in `rerun`.
+    async fn execute_parallel_suite(
+        &self,
+        path: String,
+        parallel: ParallelRun,
+        context: RunContext,
+        rerun: RunContext,
+        listeners: RunListeners,
+    ) -> Result<CallToolResult, McpError> {
+        let paths = vec![path.clone()];
+        let started = std::time::Instant::now();
+        let result = match listeners.is_empty() {
+            true => self.runner.run_paths(&paths, &context).await,
+            false => self.run_with_listeners(&paths, &context, listeners).await,
+        }
+        .map_err(|e| McpError::internal_error(format!("Command failed: {}", e), None))?;
+
+        let status = RunStatus::classify(&result);
+        let combined = combine_reports(&result.stdout);
+        let failures = combined.as_ref().map_or_else(Vec::new, |(report, _)| report.failures());
+        let summary = combined
+            .as_ref()
+            .and_then(|(report, _)| report.run_summary())
+            .or_else(|| RunSummary::parse(&result.stdout));
+        let locations: Vec<String> = failures.iter().filter_map(|failure| failure.location.clone()).collect();
+        let rerun_command = (!locations.is_empty()).then(|| self.display_command(&locations, &rerun));
+        let record = RunRecord {
+            id: self.history.next_id(),
+            targets: path.clone(),
+            command: self.display_command(&paths, &context),
+            status: status.label().to_string(),
+            exit_code: result.exit_code,
+            duration_secs: started.elapsed().as_secs_f64(),
+            finished_at: run_history::now(),
+            summary: summary.as_ref().map(ToString::to_string),
+            result: String::new(),
+            junit: match self.junit_reports {
+                true => combined.as_ref().map(|(report, _)| junit_xml(report)),
+                false => None,
+            },
+            failures: match self.history.is_enabled() {
+                true => {
+                    let shown = &failures[..failures.len().min(DEFAULT_MAX_REPORTED_FAILURES)];
+                    recorded_failures(shown, &result.stdout, context.workdir.as_deref())
+                }
+                false => Vec::new(),
+            },
+            rerun_command: rerun_command.clone(),
+        };
+
+        if let Some(webhook) = self.failure_webhook.as_ref().filter(|_| status != RunStatus::Passed) {
+            webhook.notify(FailurePayload {
+                file: path.clone(),
+                failure_count: summary.as_ref().map_or(0, |s| s.failures),
+                status: status.to_string(),
+            });
+        }
+
+        let processes = match parallel.processes {
+            Some(processes) => format!("{} processes", processes),
+            None => "a process per CPU".to_string(),
+        };
+        let mut result_text = format!(
+            "Parallel Suite: {} ({}, {})\n{}Command: {}\nExit Code: {}\nStatus: {}\n",
+            path,
+            parallel.strategy.name(),
+            processes,
+            workdir_line(&context),
+            record.command,
+            result.exit_code,
+            status
+        );
+        if let Some(reason) = &result.abort_reason {
+            result_text.push_str(&format!("Aborted: {}\n", reason));
+        }
+
+        let Some((report, reports)) = combined else {
+            let (stdout, stderr) = match self.max_output_bytes {
+                Some(limit) => {
+                    let (stdout_limit, stderr_limit) = split_output_limit(result.stdout.len(), result.stderr.len(), limit);
+                    (truncate_output(&result.stdout, stdout_limit), truncate_output(&result.stderr, stderr_limit))
+                }
+                None => (result.stdout, result.stderr),
+            };
+            result_text.push_str(&format!(
+                "\nNo JSON reports in the output to combine\n\nOutput:\n{}\n\nErrors:\n{}",
+                stdout, stderr
+            ));
+            return Ok(self.record_run(record, self.run_result(&status, result_text)));
+        };
+
+        if let Some(summary_line) = &report.summary_line {
+            result_text.push_str(&format!(
+                "Summary: {} (combined from {} process report{})\n",
+                summary_line,
+                reports,
+                if reports == 1 { "" } else { "s" }
+            ));
+        }
+        if !failures.is_empty() {
+            result_text.push_str("\nFailures:\n");
+            result_text.push_str(&format_failures(&failures, DEFAULT_MAX_REPORTED_FAILURES));
+            result_text.push_str("\n\nRerun Command:\n");
+            result_text.push_str(&self.display_command(&locations, &rerun));
+        }
+
+        let mut structured_content = report.structured_content(status.label());
+        if let Some(content) = structured_content.as_object_mut() {
+            content.insert("strategy".to_string(), parallel.strategy.name().into());
+            content.insert("process_reports".to_string(), reports.into());
+        }
+        let mut call_result = self.run_result(&status, result_text);
+        call_result.structured_content = Some(structured_content);
+        Ok(self.record_run(record, call_result))
+    }
+
+    /// Examples under `path`, from a `--dry-run -f json` run in `context`.
+    async fn count_examples(&self, path: &str, context: &RunContext) -> Option<u32> {
+        let result = self.runner.run(path, context).await.ok()?;
+        let report = RspecReport::from_output(&result.stdout)?;
+        Some(report.summary.map_or(report.examples.len() as u32, |summary| summary.example_count))
+    }
+
+    #[tool(
+        description = "Find what makes an order-dependent failure fail: runs rspec --bisect with the failing run's seed and returns the minimal command that reproduces it. Progress is reported as bisect works through its rounds",
+        annotations(read_only_hint = false)
+    )]
+    async fn bisect_failure(
+        &self,
+        Parameters(args): Parameters<BisectFailureArgs>,
+        peer: Peer<RoleServer>,
+        meta: Meta,
+        cancel: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let progress = meta.get_progress_token().map(|token| forward_progress(peer, token));
+        self.execute_bisect(args, progress, Some(cancel)).await
+    }
+
+    async fn execute_bisect(
+        &self,
+        args: BisectFailureArgs,
+        progress: Option<UnboundedSender<String>>,
+        cancel: Option<CancellationToken>,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(bisect_timeout) = self.bisect_timeout else {
+            return Err(McpError::invalid_params(
+                "Bisect is disabled; start the server with --enable-bisect",
+                None,
+            ));
+        };
+        let invalid = |e: String| McpError::invalid_params(format!("Invalid parameters: {}", e), None);
+        if args.timeout_seconds == Some(0) {
+            return Err(invalid("timeout_seconds must be at least 1".to_string()));
+        }
+        let (workdir, command) = self
+            .run_target(args.project.as_deref(), args.workdir.as_deref())
+            .map_err(invalid)?;
+        let example = ParsedFilePath::from_args(&args.example, Vec::new())
+            .and_then(|parsed| self.resolve_target(parsed, workdir.as_deref()))
+            .and_then(|parsed| match parsed.is_directory() {
+                true => Err("example must be a single example, such as spec/models/user_spec.rb:42".to_string()),
+                false => Ok(parsed),
+            })
+            .map_err(invalid)?;
+        let files = args.files.unwrap_or_else(|| vec![self.spec_listing.spec_dir().to_string()]);
+        let mut paths = Vec::new();
+        for file in &files {
+            let parsed = ParsedFilePath::from_args(file, Vec::new())
+                .and_then(|parsed| self.resolve_target(parsed, workdir.as_deref()))
+                .map_err(|e| invalid(format!("{}: {}", file.trim(), e)))?;
+            if parsed.is_directory() {
+                check_spec_directory(workdir.as_deref(), &parsed.file_path).map_err(invalid)?;
+            }
+            paths.push(parsed.file_path);
+        }
+        let example_file = example.file_path.trim_start_matches("./");
+        let covered = paths.iter().any(|path| {
+            let path = path.trim_start_matches("./").trim_end_matches('/');
+            example_file == path || example_file.starts_with(&format!("{}/", path))
+        });
+        if !covered {
+            paths.push(example.as_arg());
+        }
+        self.require_flag("--bisect").await?;
+
+        let context = RunContext {
+            extra_args: vec!["--bisect".to_string(), "--seed".to_string(), args.seed.to_string()],
+            workdir,
+            command,
+            timeout: Some(args.timeout_seconds.map_or(bisect_timeout, Duration::from_secs)),
+            cancel,
+            ..Default::default()
+        };
+        let lines = progress.map(|progress| {
+            let (lines, mut received) = mpsc::unbounded_channel::<String>();
+            tokio::spawn(async move {
+                while let Some(line) = received.recv().await {
+                    if is_progress_line(&line) {
+                        let _ = progress.send(line.trim().to_string());
+                    }
+                }
+            });
+            lines
+        });
+        let result = match lines {
+            Some(lines) => self.run_with_listeners(&paths, &context, RunListeners { examples: None, lines: Some(lines) }).await,
+            None => self.runner.run_paths(&paths, &context).await,
+        }
+        .map_err(|e| McpError::internal_error(format!("Command failed: {}", e), None))?;
+
+        let outcome = BisectOutcome::parse(&result.stdout);
+        let mut result_text = format!(
+            "Bisect for: {} (seed {})\n{}Command: {}\nExit Code: {}\nOutcome: {}\n",
+            example.as_arg(),
+            args.seed,
+            workdir_line(&context),
+            self.display_command(&paths, &context),
+            result.exit_code,
+            outcome.describe()
+        );
+        if let Some(reason) = &result.abort_reason {
+            result_text.push_str(&format!("Aborted: {}\n", reason));
+        }
+        if let BisectOutcome::Reproduced { command, .. } = &outcome {
+            result_text.push_str(&format!("\nMinimal Reproduction:\n  {}\n", command));
+            if !command.contains(example_file) {
+                result_text.push_str(&format!(
+                    "  (it reproduces the run's other failures; {} isn't among them with this seed)\n",
+                    example.as_arg()
+                ));
+            }
+        }
+        let (stdout, stderr) = match self.max_output_bytes {
+            Some(limit) => {
+                let (stdout_limit, stderr_limit) = split_output_limit(result.stdout.len(), result.stderr.len(), limit);
+                (truncate_output(&result.stdout, stdout_limit), truncate_output(&result.stderr, stderr_limit))
+            }
+            None => (result.stdout, result.stderr),
+        };
+        result_text.push_str(&format!("\nOutput:\n{}\n\nErrors:\n{}", stdout, stderr));
+
+        Ok(match outcome {
+            BisectOutcome::Incomplete => CallToolResult::error(vec![Content::text(result_text)]),
+            _ => CallToolResult::success(vec![Content::text(result_text)]),
+        })
+    }
+
+    #[tool(
+        description = "List the examples in a spec file or directory without running them (uses --dry-run -f json), as a tree of describe/context blocks with each example's line number and RSpec id (file[1:2:1])",
+        annotations(read_only_hint = false)
+    )]
+    async fn list_examples(
+        &self,
+        Parameters(args): Parameters<ListExamplesArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (workdir, command) = self
+            .run_target(args.project.as_deref(), args.workdir.as_deref())
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        let parsed = ParsedFilePath::from_args(&args.file, Vec::new())
+            .and_then(|parsed| self.resolve_target(parsed, workdir.as_deref()))
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        if parsed.is_directory() {
+            check_spec_directory(workdir.as_deref(), &parsed.file_path)
+                .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        }
+
+        let context = RunContext {
+            extra_args: vec!["--dry-run".to_string(), "-f".to_string(), "json".to_string()],
+            workdir,
+            command,
+            ..Default::default()
+        };
+        let result = self
+            .runner
+            .run(&parsed.file_path, &context)
+            .await
+            .map_err(|e| McpError::internal_error(format!("Command failed: {}", e), None))?;
+
+        let status = RunStatus::classify(&result);
+        if status.is_load_failure() || matches!(status, RunStatus::NotStarted { .. }) {
+            let result_text = format!("Examples in: {}\nStatus: {}", parsed.file_path, status);
+            return Ok(CallToolResult::success(vec![Content::text(result_text)]));
+        }
+        let mut report = RspecReport::from_output(&result.stdout)
+            .ok_or_else(|| McpError::internal_error("Dry run produced no JSON report".to_string(), None))?;
+
+        let (total, files) = (report.examples.len(), report.file_count());
+        report.examples.truncate(MAX_LISTED_EXAMPLES);
+        let tree = report.example_tree();
+        let mut result_text = format!(
+            "Examples in: {}\nExamples: {}\nFiles: {}\n\n{}",
+            parsed.file_path,
+            total,
+            files,
+            format_example_tree(&tree)
+        );
+        if total > MAX_LISTED_EXAMPLES {
+            result_text.push_str(&format!(
+                "\n... and {} more; pass a file or a narrower directory",
+                total - MAX_LISTED_EXAMPLES
+            ));
+        }
+
+        let mut call_result = CallToolResult::success(vec![Content::text(result_text)]);
+        call_result.structured_content = Some(serde_json::json!({ "examples": total, "files": tree }));
+        Ok(call_result)
+    }
+
+    #[tool(
+        description = "Find the slowest specs: runs a spec file or directory with --profile and returns its slowest examples and example groups with their run times, and how much of the run they took",
+        annotations(read_only_hint = false)
+    )]
+    async fn profile_specs(
+        &self,
+        Parameters(args): Parameters<ProfileSpecsArgs>,
+        cancel: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        self.execute_profile(args, Some(cancel)).await
+    }
+
+    async fn execute_profile(
+        &self,
+        args: ProfileSpecsArgs,
+        cancel: Option<CancellationToken>,
+    ) -> Result<CallToolResult, McpError> {
+        let invalid = |e: String| McpError::invalid_params(format!("Invalid parameters: {}", e), None);
+        if args.timeout_seconds == Some(0) {
+            return Err(invalid("timeout_seconds must be at least 1".to_string()));
+        }
+        let count = args.count.unwrap_or(DEFAULT_PROFILE_COUNT);
+        if !(1..=MAX_PROFILE_COUNT).contains(&count) {
+            return Err(invalid(format!("count must be between 1 and {}, got: {}", MAX_PROFILE_COUNT, count)));
+        }
+        let (workdir, command) = self
+            .run_target(args.project.as_deref(), args.workdir.as_deref())
+            .map_err(invalid)?;
+        let path = args.path.unwrap_or_else(|| self.spec_listing.spec_dir().to_string());
+        let parsed = ParsedFilePath::from_args(&path, Vec::new())
+            .and_then(|parsed| self.resolve_target(parsed, workdir.as_deref()))
+            .map_err(invalid)?;
+        if parsed.is_directory() {
+            check_spec_directory(workdir.as_deref(), &parsed.file_path).map_err(invalid)?;
+        }
+
+        let mut extra_args = vec!["--profile".to_string(), count.to_string(), "-f".to_string(), "json".to_string()];
+        extra_args.extend(tag_args(args.tags.as_deref().unwrap_or_default()).map_err(invalid)?);
+        let context = RunContext {
+            extra_args,
+            workdir,
+            command,
+            timeout: args.timeout_seconds.map(Duration::from_secs),
+            cancel,
+            ..Default::default()
+        };
+        let target = parsed.as_arg();
+        let result = self
+            .runner
+            .run(&target, &context)
+            .await
+            .map_err(|e| McpError::internal_error(format!("Command failed: {}", e), None))?;
+
+        let status = RunStatus::classify(&result);
+        let mut result_text = format!(
+            "Profile for: {}\n{}Command: {}\nExit Code: {}\nStatus: {}\n",
+            target,
+            workdir_line(&context),
+            self.display_command(std::slice::from_ref(&target), &context),
+            result.exit_code,
+            status
+        );
+        if let Some(reason) = &result.abort_reason {
+            result_text.push_str(&format!("Aborted: {}\n", reason));
+        }
+        let Some(report) = RspecReport::from_output(&result.stdout) else {
+            let stderr = match self.max_output_bytes {
+                Some(limit) => truncate_output(&result.stderr, limit),
+                None => result.stderr,
+            };
+            result_text.push_str(&format!("\nNo JSON report to profile\n\nErrors:\n{}", stderr));
+            return Ok(CallToolResult::error(vec![Content::text(result_text)]));
+        };
+
+        if let Some(summary_line) = &report.summary_line {
+            result_text.push_str(&format!("Summary: {}\n", summary_line));
+        }
+        let profile = report.profile.unwrap_or_default();
+        match profile.examples.is_empty() {
+            true => result_text.push_str("\nNo examples ran, so there is nothing to profile"),
+            false => result_text.push_str(&format!("\n{}", format_profile(&profile))),
+        }
+
+        let mut call_result = CallToolResult::success(vec![Content::text(result_text)]);
+        call_result.structured_content = Some(serde_json::json!({
+            "path": target,
+            "status": status.label(),
+            "summary": report.summary,
+            "slowest_time": profile.slowest,
+            "total_time": profile.total,
+            "slowest_examples": profile.examples,
+            "slowest_groups": profile.groups,
+        }));
+        Ok(call_result)
+    }
+
+    #[tool(
+        description = "Count pending, skipped and focused examples in a spec file without running it (uses --dry-run -f json). Results are cached until the file changes",
+        annotations(read_only_hint = false)
+    )]
+    async fn spec_markers(
+        &self,
+        Parameters(args): Parameters<SpecMarkersArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (workdir, command) = self
+            .run_target(args.project.as_deref(), args.workdir.as_deref())
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        let parsed_file = ParsedFilePath::from_args(&args.file, Vec::new())
+            .and_then(|parsed| match parsed.is_directory() {
+                true => Err("File must be an RSpec test file (*_spec.rb)".to_string()),
+                false => self.resolve_target(parsed, workdir.as_deref()),
+            })
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+
+        let source_path = match &workdir {
+            Some(dir) => dir.join(&parsed_file.file_path),
+            None => parsed_file.file_path.clone().into(),
+        };
+        let source = std::fs::read_to_string(&source_path).map_err(|e| {
+            McpError::invalid_params(
+                format!("Invalid parameters: cannot read {}: {}", parsed_file.file_path, e),
+                None,
+            )
+        })?;
+
+        let key = MarkerCache::key(&parsed_file.file_path, &source);
+        let counts = match self.markers.get(&key) {
+            Some(counts) => counts,
+            None => {
+                let context = RunContext {
+                    extra_args: vec!["--dry-run".to_string(), "-f".to_string(), "json".to_string()],
+                    workdir,
+                    command,
+                    ..Default::default()
+                };
+                let result = self
+                    .runner
+                    .run(&parsed_file.file_path, &context)
+                    .await
+                    .map_err(|e| McpError::internal_error(format!("Command failed: {}", e), None))?;
+
+                let status = RunStatus::classify(&result);
+                if status.is_load_failure() || matches!(status, RunStatus::NotStarted { .. }) {
+                    let result_text = format!(
+                        "Spec Markers for: {}\nStatus: {}",
+                        parsed_file.file_path, status
+                    );
+                    return Ok(CallToolResult::success(vec![Content::text(result_text)]));
+                }
+
+                let report = RspecReport::from_output(&result.stdout).ok_or_else(|| {
+                    McpError::internal_error("Dry run produced no JSON report".to_string(), None)
+                })?;
+                let counts = MarkerCounts::from_report(&report, &source);
+                self.markers.insert(key, counts);
+                counts
+            }
+        };
+
+        let result_text = format!(
+            "Spec Markers for: {}\nExamples: {}\nPending: {}\nSkipped: {}\nFocused: {}",
+            parsed_file.file_path, counts.examples, counts.pending, counts.skipped, counts.focused
+        );
+        Ok(CallToolResult::success(vec![Content::text(result_text)]))
+    }
+
+    #[tool(
+        description = "Check whether run_rspec would accept a path (and workdir) without running anything, naming the rule that allows or blocks it",
+        annotations(read_only_hint = true)
+    )]
+    async fn check_access(
+        &self,
+        Parameters(args): Parameters<CheckAccessArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let mut result_text = format!("Access check for: {}\n", args.path);
+        let verdict = ParsedFilePath::from_args(&args.path, Vec::new()).and_then(|parsed| {
+            let (workdir, _) = self.run_target(args.project.as_deref(), args.workdir.as_deref())?;
+            let parsed = self.resolve_target(parsed, workdir.as_deref())?;
+            if parsed.is_directory() {
+                check_spec_directory(workdir.as_deref(), &parsed.file_path)?;
+            }
+            Ok((parsed.allowing_rule(), workdir))
+        });
+
+        match verdict {
+            Ok((rule, workdir)) => {
+                result_text.push_str(&format!("Allowed: yes\nRule: {}", rule));
+                if let Some(workdir) = workdir {
+                    result_text.push_str(&format!("\nWorkdir: {}", workdir.display()));
+                }
+            }
+            Err(rule) => result_text.push_str(&format!("Allowed: no\nRule: {}", rule)),
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(result_text)]))
+    }
+
+    #[tool(
+        description = "Run a spec file or directory and save its failures as the known failures in the --baseline-failures file, replacing earlier entries for those specs. run_rspec leaves known failures out of its results",
+        annotations(read_only_hint = false)
+    )]
+    async fn update_baseline(
+        &self,
+        Parameters(args): Parameters<UpdateBaselineArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(baseline) = &self.baseline else {
+            return Err(McpError::invalid_params(
+                "No baseline configured; start the server with --baseline-failures",
+                None,
+            ));
+        };
+        let (workdir, command) = self
+            .run_target(args.project.as_deref(), args.workdir.as_deref())
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        let parsed_file = ParsedFilePath::from_args(&args.file, Vec::new())
+            .and_then(|parsed| self.resolve_target(parsed, workdir.as_deref()))
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        let context = RunContext {
+            workdir,
+            command,
+            ..Default::default()
+        };
+        if parsed_file.is_directory() {
+            check_spec_directory(context.workdir.as_deref(), &parsed_file.file_path)
+                .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        }
+
+        let result = self
+            .runner
+            .run(&parsed_file.as_arg(), &context)
+            .await
+            .map_err(|e| McpError::internal_error(format!("Command failed: {}", e), None))?;
+        // A run that never got to the examples says nothing about them
+        let status = RunStatus::classify(&result);
+        if status.is_load_failure() || matches!(status, RunStatus::Terminated { .. } | RunStatus::NotStarted { .. }) {
+            return Ok(CallToolResult::error(vec![Content::text(format!(
+                "Baseline not updated for: {}\nStatus: {}",
+                parsed_file.file_path, status
+            ))]));
+        }
+
+        let stdout = match self.normalize_newlines {
+            true => normalize_newlines(&result.stdout),
+            false => result.stdout,
+        };
+        let failures = self
+            .stdout_report(&stdout)
+            .map_or_else(|| FailureReport::parse_all(&stdout), |report| report.failures());
+        let total = baseline
+            .update(&parsed_file.file_path, &failures)
+            .map_err(|e| McpError::internal_error(e, None))?;
+
+        let result_text = format!(
+            "Baseline updated for: {}\nKnown Failures: {}\nBaseline Total: {}",
+            parsed_file.file_path,
+            failures.len(),
+            total
+        );
+        Ok(CallToolResult::success(vec![Content::text(result_text)]))
+    }
+
+    #[tool(
+        description = "Rerun only the examples that failed in this session's last run_rspec call, using RSpec's --only-failures, in defined order. Every run_rspec call (and each rerun) refreshes the recorded statuses, so call run_rspec again to change what is rerun",
+        annotations(read_only_hint = false)
+    )]
+    async fn rerun_failures(
+        &self,
+        peer: Peer<RoleServer>,
+        meta: Meta,
+        cancel: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let listeners = self.listeners(peer, meta);
+        self.execute_rerun(listeners, Some(cancel)).await
+    }
+
+    async fn execute_rerun(
+        &self,
+        listeners: RunListeners,
+        cancel: Option<CancellationToken>,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(example_status) = &self.example_status else {
+            return Err(McpError::invalid_params(
+                "Rerunning failures is disabled; start the server with --rerun-failures",
+                None,
+            ));
+        };
+        let Some(last_run) = example_status.last_run() else {
+            return Err(McpError::invalid_params("Nothing to rerun yet; call run_rspec first", None));
+        };
+        if last_run.files.iter().all(|file| example_status.failed_examples(file).is_empty()) {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "No failures to rerun from the last run of: {}",
+                last_run.files.join(" ")
+            ))]));
+        }
+
+        let args = RspecServerArgs {
+            file: SpecPaths::Many(last_run.files),
+            workdir: last_run.workdir,
+            project: last_run.project,
+            ..Default::default()
+        };
+        self.execute_run(args, listeners, RunKind::OnlyFailures, cancel).await
+    }
+
+    #[tool(
+        description = "Run RSpec like run_rspec with SimpleCov coverage turned on, then add a Coverage section with the total line coverage and each file's coverage, with the change since the last coverage run. Use it to check whether new or changed code is exercised by specs",
+        annotations(read_only_hint = false)
+    )]
+    async fn run_rspec_with_coverage(
+        &self,
+        Parameters(args): Parameters<RspecServerArgs>,
+        peer: Peer<RoleServer>,
+        meta: Meta,
+        cancel: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        let listeners = self.listeners(peer, meta);
+        self.execute_coverage_run(args, listeners, Some(cancel)).await
+    }
+
+    async fn execute_coverage_run(
+        &self,
+        args: RspecServerArgs,
+        listeners: RunListeners,
+        cancel: Option<CancellationToken>,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(coverage) = &self.coverage else {
+            return Err(McpError::invalid_params(
+                "Coverage runs are disabled; start the server with --enable-coverage",
+                None,
+            ));
+        };
+        let (workdir, _) = self
+            .run_target(args.project.as_deref(), args.workdir.as_deref())
+            .map_err(|e| McpError::invalid_params(format!("Invalid parameters: {}", e), None))?;
+        let root = match workdir {
+            Some(workdir) => workdir,
+            None => std::env::current_dir().map_err(|e| McpError::internal_error(e.to_string(), None))?,
+        };
+        let dir = root.join(&coverage.dir);
+        let dry_run = args.dry_run_command.unwrap_or(false);
+
+        let before = CoverageSnapshot::read(&dir);
+        let mut result = self.execute_run(args, listeners, RunKind::Coverage, cancel).await?;
+        if dry_run {
+            return Ok(result);
+        }
+        let report = coverage_report(&before, &CoverageSnapshot::read(&dir), &root, &coverage.dir);
+        if let Some(RawContent::Text(text)) = result.content.first_mut().map(|content| &mut content.raw) {
+            text.text.push_str("\n\nCoverage:\n");
+            text.text.push_str(&report);
+        }
+        Ok(result)
+    }
+
+    #[tool(
+        description = "List the ids of the examples that failed in this session's last run_rspec call (or rerun), without running anything. Pass an id's file and scope to run_rspec's example_ids to run one of them",
+        annotations(read_only_hint = true)
+    )]
+    async fn last_failures(&self) -> Result<CallToolResult, McpError> {
+        let Some(example_status) = &self.example_status else {
+            return Err(McpError::invalid_params(
+                "Rerunning failures is disabled; start the server with --rerun-failures",
+                None,
+            ));
+        };
+        let Some(last_run) = example_status.last_run() else {
+            return Err(McpError::invalid_params("No runs yet; call run_rspec first", None));
+        };
+
+        let failed: Vec<String> = last_run.files.iter().flat_map(|file| example_status.failed_examples(file)).collect();
+        let mut result_text = format!("Last run: {}\nFailed examples: {}\n", last_run.files.join(" "), failed.len());
+        if !failed.is_empty() {
+            result_text.push_str(&format!("\n{}", failed.join("\n")));
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(result_text)]))
+    }
+
+    #[tool(
+        description = "Admin: report RSpec child process counts. A non-zero active count with no runs in flight indicates leaked processes",
+        annotations(read_only_hint = true)
+    )]
+    async fn process_stats(&self) -> Result<CallToolResult, McpError> {
+        let stats = self.process_tracker.stats();
+        let result_text = format!(
+            "Active: {}\nSpawned: {}\nReaped: {}",
+            stats.active, stats.spawned, stats.reaped
+        );
+
+        Ok(CallToolResult::success(vec![Content::text(result_text)]))
+    }
+
+    #[tool(
+        description = "Admin: clear cached server state (detected RSpec version, spec marker counts, run history, this session's configure defaults) without restarting. Runs in progress are not affected",
+        annotations(read_only_hint = false)
+    )]
+    async fn reset_state(&self) -> Result<CallToolResult, McpError> {
+        let mut cleared = Vec::new();
+
+        if self.versions.clear().await {
+            cleared.push("RSpec version cache");
+        }
+        if self.markers.clear() {
+            cleared.push("spec marker cache");
+        }
+        if self.history.clear() {
+            cleared.push("run history");
+        }
+        if !std::mem::take(&mut *self.session_defaults.lock().unwrap()).is_empty() {
+            cleared.push("session defaults");
+        }
+
+        let result_text = if cleared.is_empty() {
+            "Nothing to clear".to_string()
+        } else {
+            format!("Cleared: {}", cleared.join(", "))
+        };
+
+        Ok(CallToolResult::success(vec![Content::text(result_text)]))
+    }
+}
+
+/// Names the directory a run happened in, which relative spec paths are
+/// resolved against. Runs in the server's own directory leave it out.
+fn workdir_line(context: &RunContext) -> String {
+    context
+        .workdir
+        .as_ref()
+        .map(|dir| format!("Working Directory: {}\n", dir.display()))
+        .unwrap_or_default()
+}
+
+/// What `execute_run` does beyond the request's arguments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RunKind {
+    Plain,
+    /// Only the examples the session's status file lists as failed.
+    OnlyFailures,
+    /// With the `with_coverage` environment set.
+    Coverage,
+}
+
+/// Where a run's output goes while it runs, besides its final result.
+#[derive(Default)]
+struct RunListeners {
+    /// Example events parsed from the documentation formatter.
+    examples: Option<UnboundedSender<ExampleEvent>>,
+    /// Each stdout line as it is produced.
+    lines: Option<UnboundedSender<String>>,
+}
+
+impl RunListeners {
+    fn is_empty(&self) -> bool {
+        self.examples.is_none() && self.lines.is_none()
+    }
+}
+
+/// Forwards stdout lines to the client as progress notifications for
+/// `token`. The total is unknown, so progress counts lines.
+fn forward_progress(peer: Peer<RoleServer>, token: ProgressToken) -> UnboundedSender<String> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+
+    tokio::spawn(async move {
+        let mut progress = 0.0;
+        while let Some(line) = rx.recv().await {
+            progress += 1.0;
+            let param = ProgressNotificationParam {
+                progress_token: token.clone(),
+                progress,
+                total: None,
+                message: Some(line),
+            };
+            if let Err(e) = peer.notify_progress(param).await {
+                tracing::debug!(error = %e, "failed to send progress");
+                break;
+            }
+        }
+    });
+
+    tx
+}
+
+/// Forwards example events to the client as `rspec.example` log messages.
+fn forward_example_events(peer: Peer<RoleServer>) -> UnboundedSender<ExampleEvent> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<ExampleEvent>();
+
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            let param = LoggingMessageNotificationParam {
+                level: LoggingLevel::Info,
+                logger: Some("rspec.example".to_string()),
+                data: serde_json::to_value(&event).unwrap_or_default(),
+            };
+            if let Err(e) = peer.notify_logging_message(param).await {
+                tracing::debug!(error = %e, "failed to send example event");
+                break;
+            }
+        }
+    });
+
+    tx
+}
+
+impl <R: CommandRunner + Clone + 'static> ServerHandler for RspecServer<R> {
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        self.authorize(&request.name, &context.extensions)?;
+
+        let tcc = ToolCallContext::new(self, request, context);
+        self.tool_router.call(tcc).await
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        Ok(ListToolsResult::with_all_items(self.tool_router.list_all()))
+    }
+
+    async fn list_resources(
+        &self,
+        request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        self.spec_resources(request.and_then(|request| request.cursor))
+    }
+
+    async fn list_resource_templates(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourceTemplatesResult, McpError> {
+        let template = RawResourceTemplate {
+            uri_template: format!("{}{{path}}", SPEC_URI_PREFIX),
+            name: "spec file".to_string(),
+            title: None,
+            description: Some("A *_spec.rb file under the spec directory, by its path from the project root".to_string()),
+            mime_type: Some(SPEC_MIME_TYPE.to_string()),
+        };
+        Ok(ListResourceTemplatesResult::with_all_items(vec![template.no_annotation()]))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        self.read_spec_resource(request.uri)
+    }
+
+    async fn list_prompts(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListPromptsResult, McpError> {
+        let prompts = match self.history.is_enabled() {
+            true => vec![fix_failing_spec_prompt()],
+            false => Vec::new(),
+        };
+        Ok(ListPromptsResult::with_all_items(prompts))
+    }
+
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<GetPromptResult, McpError> {
+        self.prompt(request)
+    }
+
+    async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+        if let Some(subscribers) = &self.watch_subscribers {
+            subscribers.subscribe(context.peer, self.log_level.clone());
+        }
+    }
+
+    async fn set_level(
+        &self,
+        request: SetLevelRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        *self.log_level.lock().unwrap() = request.level;
+        Ok(())
+    }
+
+    fn get_info(&self) -> ServerInfo {
+        let mut capabilities = ServerCapabilities::builder()
+            .enable_tools()
+            .enable_resources()
+            .build();
+        if self.history.is_enabled() {
+            capabilities.prompts = Some(PromptsCapability::default());
+        }
+        if self.watch_subscribers.is_some() {
+            capabilities.logging = Some(JsonObject::new());
+        }
+        ServerInfo {
+            protocol_version: ProtocolVersion::V_2024_11_05,
+            capabilities,
+            server_info: Implementation::from_build_env(),
+            instructions: Some(
+                "Test runner server using configurable command. Tool: run_rspec (run tests for a file)."
+                    .to_string(),
+            ),
+        }
+    }
+
+    async fn initialize(
+        &self,
+        _request: InitializeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<InitializeResult, McpError> {
+        if let Some(http_request_part) = context.extensions.get::<axum::http::request::Parts>() {
+            let initialize_headers = &http_request_part.headers;
+            let initialize_uri = &http_request_part.uri;
+            tracing::info!(?initialize_headers, %initialize_uri, "initialize from http server");
+        }
+        Ok(self.get_info())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthScope;
+    use crate::mock_runner::MockRunner;
+    use crate::parallel::ParallelStrategy;
+    use crate::rspec_output::ExampleStatus;
+    use crate::rspec_version::RspecVersion;
+
+    #[tokio::test]
+    async fn test_run_rspec_tool() {
+        let runner = MockRunner::new();
+        let router = RspecServer::new(runner).tool_router;
+
+        let tools = router.list_all();
+        assert_eq!(tools.len(), 9);
+
+        let tool_names: Vec<&str> = tools.iter().map(|t| t.name.as_ref()).collect();
+        assert!(tool_names.contains(&"run_rspec"));
+        assert!(tool_names.contains(&"run_rspec_suite"));
+        assert!(tool_names.contains(&"list_spec_files"));
+        assert!(tool_names.contains(&"find_spec_for"));
+        assert!(tool_names.contains(&"spec_markers"));
+        assert!(tool_names.contains(&"list_examples"));
+        assert!(tool_names.contains(&"profile_specs"));
+        assert!(tool_names.contains(&"check_access"));
+        assert!(tool_names.contains(&"configure"));
+        assert!(!tool_names.contains(&"get_run_output"));
+    }
+
+    #[test]
+    fn test_logging_capability_with_watch() {
+        let server = RspecServer::new(MockRunner::new());
+        assert!(server.get_info().capabilities.logging.is_none());
+
+        let server = server.with_watch_subscribers(Some(WatchSubscribers::default()));
+        assert!(server.get_info().capabilities.logging.is_some());
+    }
+
+    fn suite_project() -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tmp.path().join("spec/requests")).unwrap();
+        tmp
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_suite() {
+        let tmp = suite_project();
+        let project = tmp.path();
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone())
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.to_path_buf()), vec![]).unwrap())
+            .with_suite_limits(SuiteLimits { timeout: Some(Duration::from_secs(900)), max_examples: None });
+
+        let result = server.execute_suite(RunSuiteArgs::default(), RunListeners::default(), None).await.unwrap();
+        assert!(result.content[0].as_text().unwrap().text.starts_with("Test Results for: spec\n"));
+        let args = RunSuiteArgs {
+            directory: Some("spec/requests".to_string()),
+            tags: Some(vec!["~js".to_string()]),
+            ..Default::default()
+        };
+        server.execute_suite(args, RunListeners::default(), None).await.unwrap();
+
+        assert_eq!(
+            runner.recorded_commands(),
+            vec![vec!["rspec", "spec"], vec!["rspec", "--tag", "~js", "spec/requests"]]
+        );
+        assert_eq!(runner.recorded_contexts()[0].timeout, Some(Duration::from_secs(900)));
+
+        for (directory, message) in [
+            ("spec/models", "Invalid parameters: Directory spec/models does not exist"),
+            ("spec/requests/api_spec.rb", "Invalid parameters: directory must be a directory of specs; use run_rspec for a single file"),
+        ] {
+            let args = RunSuiteArgs { directory: Some(directory.to_string()), ..Default::default() };
+            let err = server.execute_suite(args, RunListeners::default(), None).await.unwrap_err();
+            assert!(err.message.starts_with(message), "{}", err.message);
+        }
+        assert_eq!(runner.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_suite_example_cap() {
+        let tmp = suite_project();
+        let project = tmp.path();
+        let dry_run = r#"{"version":"3.12.2","examples":[],"summary":{"example_count":120,"failure_count":0,"pending_count":0,"errors_outside_of_examples_count":0}}"#;
+        let runner = MockRunner::new().with_sequence(vec![
+            (0, dry_run.to_string(), String::new()),
+            (0, dry_run.to_string(), String::new()),
+            (0, "120 examples, 0 failures".to_string(), String::new()),
+        ]);
+        let server = |max_examples| {
+            RspecServer::new(runner.clone())
+                .with_workdir_policy(WorkdirPolicy::new(Some(project.to_path_buf()), vec![]).unwrap())
+                .with_suite_limits(SuiteLimits { timeout: None, max_examples: Some(max_examples) })
+        };
+
+        let err = server(100).execute_suite(RunSuiteArgs::default(), RunListeners::default(), None).await.unwrap_err();
+        assert_eq!(
+            err.message,
+            "spec has 120 examples, more than the 100 allowed per suite run (--suite-max-examples); pass a narrower directory"
+        );
+        assert_eq!(runner.calls(), 1);
+        assert_eq!(runner.recorded_commands()[0], ["rspec", "--dry-run", "-f", "json", "spec"]);
+
+        let result = server(200).execute_suite(RunSuiteArgs::default(), RunListeners::default(), None).await.unwrap();
+        assert!(result.content[0].as_text().unwrap().text.contains("120 examples, 0 failures"));
+        assert_eq!(runner.recorded_commands()[2], ["rspec", "spec"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_suite_parallel() {
+        let tmp = suite_project();
+        let project = tmp.path();
+        let stdout = r#"{"version":"3.13.0","examples":[{"id":"./spec/requests/api_spec.rb[1:1]","full_description":"API fails","status":"failed","file_path":"./spec/requests/api_spec.rb","line_number":4,"exception":{"class":"RuntimeError","message":"boom"}}],"summary":{"duration":3.0,"example_count":1,"failure_count":1,"pending_count":0,"errors_outside_of_examples_count":0},"summary_line":"1 example, 1 failure"}
+{"version":"3.13.0","examples":[{"id":"./spec/requests/users_spec.rb[1:1]","full_description":"Users lists","status":"passed","file_path":"./spec/requests/users_spec.rb","line_number":2}],"summary":{"duration":2.0,"example_count":1,"failure_count":0,"pending_count":0,"errors_outside_of_examples_count":0},"summary_line":"1 example, 0 failures"}
+
+2 examples, 1 failure
+"#;
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let parallel = ParallelConfig {
+            strategy: ParallelStrategy::ParallelTests,
+            command: vec!["parallel_rspec".to_string()],
+            processes: Some(4),
+        };
+        let server = RspecServer::new(runner.clone())
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.to_path_buf()), vec![]).unwrap())
+            .with_parallel(Some(parallel));
+
+        let args = RunSuiteArgs { tags: Some(vec!["~js".to_string()]), ..Default::default() };
+        let result = server.execute_suite(args, RunListeners::default(), None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with("Parallel Suite: spec (parallel_tests, 4 processes)\n"), "{}", text);
+        assert!(text.contains("Summary: 2 examples, 1 failure (combined from 2 process reports)\n\nFailures:\n  1) API fails (spec/requests/api_spec.rb:4)\n"));
+        assert!(text.contains("\n\nRerun Command:\n") && text.ends_with("&& rspec spec/requests/api_spec.rb:4"), "{}", text);
+        assert_eq!(runner.recorded_commands()[0], ["parallel_rspec", "-f", "json", "--tag", "~js", "spec"]);
+        assert_eq!(
+            runner.recorded_contexts()[0].parallel,
+            Some(ParallelRun { strategy: ParallelStrategy::ParallelTests, processes: Some(4) })
+        );
+        let structured = result.structured_content.unwrap();
+        assert_eq!(structured["summary"]["example_count"], 2);
+        assert_eq!(structured["process_reports"], 2);
+        assert_eq!(structured["examples"][0]["status"], "failed");
+
+        let args = RunSuiteArgs { processes: Some(1), ..Default::default() };
+        server.execute_suite(args, RunListeners::default(), None).await.unwrap();
+        assert_eq!(runner.recorded_commands()[1], ["rspec", "spec"]);
+        assert_eq!(runner.recorded_contexts()[1].parallel, None);
+
+        let args = RunSuiteArgs { processes: Some(2), ..Default::default() };
+        let err = RspecServer::new(runner.clone())
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.to_path_buf()), vec![]).unwrap())
+            .execute_suite(args, RunListeners::default(), None)
+            .await
+            .unwrap_err();
+        assert!(err.message.contains("Parallel runs are disabled"));
+        assert_eq!(runner.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_suite_parallel_records_run() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let hook = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !String::from_utf8_lossy(&request).ends_with('}') {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            socket.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let tmp = suite_project();
+        let stdout = r#"{"version":"3.13.0","examples":[{"id":"./spec/requests/api_spec.rb[1:1]","full_description":"API fails","status":"failed","file_path":"./spec/requests/api_spec.rb","line_number":4,"exception":{"class":"RuntimeError","message":"boom"}}],"summary":{"duration":3.0,"example_count":1,"failure_count":1,"pending_count":0,"errors_outside_of_examples_count":0},"summary_line":"1 example, 1 failure"}"#;
+        let parallel = ParallelConfig {
+            strategy: ParallelStrategy::ParallelTests,
+            command: vec!["parallel_rspec".to_string()],
+            processes: Some(2),
+        };
+        let webhook = FailureWebhook::new(&format!("http://127.0.0.1:{}/hooks", port), None).unwrap();
+        let server = RspecServer::new(MockRunner::with_result(1, stdout.to_string(), String::new()))
+            .with_workdir_policy(WorkdirPolicy::new(Some(tmp.path().to_path_buf()), vec![]).unwrap())
+            .with_parallel(Some(parallel))
+            .with_run_history(RunHistory::new(5, None).unwrap())
+            .with_failure_webhook(Some(webhook));
+
+        let result = server.execute_suite(RunSuiteArgs::default(), RunListeners::default(), None).await.unwrap();
+        let record = server.history.get(None).unwrap();
+        assert_eq!(record.targets, "spec");
+        assert_eq!(record.status, "failed");
+        assert_eq!(record.summary.as_deref(), Some("1 example, 1 failure (run_time: 3s)"));
+        assert_eq!(record.failures[0].location.as_deref(), Some("spec/requests/api_spec.rb:4"));
+        assert!(record.rerun_command.unwrap().ends_with("&& rspec spec/requests/api_spec.rb:4"));
+        assert_eq!(record.result, result.content[0].as_text().unwrap().text);
+
+        let request = hook.await.unwrap();
+        assert!(request.ends_with(r#"{"file":"spec","failure_count":1,"status":"Failed"}"#), "{}", request);
+    }
+
+    #[tokio::test]
+    async fn test_list_spec_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path();
+        std::fs::create_dir_all(project.join("test/models")).unwrap();
+        std::fs::write(project.join("test/models/user_spec.rb"), "").unwrap();
+        std::fs::write(project.join("test/models/order_spec.rb"), "").unwrap();
+        let server = RspecServer::new(MockRunner::new())
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.to_path_buf()), vec![]).unwrap())
+            .with_spec_listing(SpecListing::new("test".to_string(), vec![]));
+
+        let args = ListSpecFilesArgs { filter: Some("user".to_string()), workdir: None, project: None };
+        let result = server.list_spec_files(Parameters(args)).await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            "Spec Files in: test\nFilter: user\nCount: 1\n\ntest/models/user_spec.rb"
+        );
+
+        let args = ListSpecFilesArgs { filter: None, workdir: Some("..".to_string()), project: None };
+        assert!(server.list_spec_files(Parameters(args)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_find_spec_for() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path();
+        std::fs::create_dir_all(project.join("spec/models")).unwrap();
+        std::fs::create_dir_all(project.join("spec/lib")).unwrap();
+        std::fs::write(project.join("spec/models/user_spec.rb"), "").unwrap();
+        std::fs::write(project.join("spec/lib/billing_spec.rb"), "RSpec.describe Billing::Invoice do\nend\n").unwrap();
+        let server = RspecServer::new(MockRunner::new())
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.to_path_buf()), vec![]).unwrap());
+        let find = |file: &str, search| FindSpecForArgs { file: file.to_string(), search, ..Default::default() };
+
+        let result = server.find_spec_for(Parameters(find("app/models/user.rb", None))).await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            "Specs for: app/models/user.rb\n\nspec/models/user_spec.rb (exists)"
+        );
+
+        let result = server.find_spec_for(Parameters(find("lib/billing/invoice.rb", None))).await.unwrap();
+        assert!(result.content[0].as_text().unwrap().text.ends_with("No spec found; pass search: true to look elsewhere in the spec directory"));
+        let result = server.find_spec_for(Parameters(find("lib/billing/invoice.rb", Some(true)))).await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            "Specs for: lib/billing/invoice.rb\n\nspec/billing/invoice_spec.rb (missing)\nspec/lib/billing/invoice_spec.rb (missing)\n\nSearch: 1 spec files named invoice_spec.rb or describing Billing::Invoice\nspec/lib/billing_spec.rb"
+        );
+
+        let absolute = project.join("app/models/user.rb").to_string_lossy().into_owned();
+        assert!(server.find_spec_for(Parameters(find(&absolute, None))).await.is_ok());
+        let err = server.find_spec_for(Parameters(find("../app/models/user.rb", None))).await.unwrap_err();
+        assert_eq!(err.message, "Invalid parameters: Path traversal not allowed");
+    }
+
+    #[tokio::test]
+    async fn test_create_spec() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path();
+        std::fs::create_dir_all(project.join("app/models")).unwrap();
+        std::fs::create_dir_all(project.join("spec")).unwrap();
+        std::fs::write(project.join("app/models/user.rb"), "class User\nend\n").unwrap();
+        std::fs::write(project.join("spec/rails_helper.rb"), "").unwrap();
+        let server = RspecServer::new(MockRunner::new())
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.to_path_buf()), vec![]).unwrap());
+        assert!(!server.tool_router.has_route("create_spec"));
+        let server = server.with_spec_scaffolding(true);
+        assert!(server.tool_router.has_route("create_spec"));
+        let create = |file: &str| CreateSpecArgs { file: file.to_string(), ..Default::default() };
+
+        let result = server.create_spec(Parameters(create("app/models/user.rb"))).await.unwrap();
+        let expected = "require 'rails_helper'\n\nRSpec.describe User, type: :model do\n  pending \"add some examples to (or delete) #{__FILE__}\"\nend\n";
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            format!("Created: spec/models/user_spec.rb\n\n{}", expected)
+        );
+        assert_eq!(std::fs::read_to_string(project.join("spec/models/user_spec.rb")).unwrap(), expected);
+
+        std::fs::write(project.join("spec/models/user_spec.rb"), "# mine").unwrap();
+        let result = server.create_spec(Parameters(create("app/models/user.rb"))).await.unwrap();
+        assert!(result.content[0].as_text().unwrap().text.starts_with("Spec already exists: spec/models/user_spec.rb"));
+        assert_eq!(std::fs::read_to_string(project.join("spec/models/user_spec.rb")).unwrap(), "# mine");
+
+        let err = server.create_spec(Parameters(create("app/models/order.rb"))).await.unwrap_err();
+        assert_eq!(err.message, "Invalid parameters: app/models/order.rb does not exist");
+        let err = server.create_spec(Parameters(create("spec/models/user_spec.rb"))).await.unwrap_err();
+        assert!(err.message.ends_with("is already a spec file; pass the implementation file"));
+    }
+
+    async fn check_access_text(server: &RspecServer<MockRunner>, path: &str, workdir: Option<&str>) -> String {
+        let args = CheckAccessArgs {
+            path: path.to_string(),
+            workdir: workdir.map(str::to_string),
+            project: None,
+        };
+        let result = server.check_access(Parameters(args)).await.unwrap();
+        result.content[0].as_text().unwrap().text.clone()
+    }
+
+    #[tokio::test]
+    async fn test_check_access_allowed() {
+        let project = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).canonicalize().unwrap();
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone())
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.clone()), vec![]).unwrap());
+
+        assert_eq!(
+            check_access_text(&server, "spec/models/user_spec.rb", None).await,
+            format!(
+                "Access check for: spec/models/user_spec.rb\nAllowed: yes\nRule: spec file (*_spec.rb)\nWorkdir: {}",
+                project.display()
+            )
+        );
+        assert!(
+            check_access_text(&server, "src", None)
+                .await
+                .contains("Allowed: yes\nRule: spec directory (no extension or a trailing '/')")
+        );
+        assert_eq!(runner.calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_check_access_denied_by_pattern() {
+        let server = RspecServer::new(MockRunner::new());
+        assert_eq!(
+            check_access_text(&server, "spec/models/user.rb", None).await,
+            "Access check for: spec/models/user.rb\nAllowed: no\nRule: File must be an RSpec test file (*_spec.rb)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_access_blocks_traversal() {
+        let project = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).canonicalize().unwrap();
+        let server = RspecServer::new(MockRunner::new())
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.join("src")), vec![]).unwrap());
+
+        assert_eq!(
+            check_access_text(&server, "../spec/user_spec.rb", None).await,
+            "Access check for: ../spec/user_spec.rb\nAllowed: no\nRule: Path traversal not allowed"
+        );
+        assert!(
+            check_access_text(&server, "spec/user_spec.rb", Some(".."))
+                .await
+                .ends_with("is not within --default-workdir or an --allow-workdir directory")
+        );
+    }
+
+    #[test]
+    fn test_spec_file_resources() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path();
+        std::fs::create_dir_all(project.join("spec/models")).unwrap();
+        std::fs::write(project.join("spec/models/user_spec.rb"), "describe User do\nend\n").unwrap();
+        std::fs::write(project.join("spec/spec_helper.rb"), "").unwrap();
+        let server = RspecServer::new(MockRunner::new())
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.to_path_buf()), vec![]).unwrap());
+
+        assert!(server.get_info().capabilities.resources.is_some());
+        let listed = server.spec_resources(None).unwrap();
+        assert_eq!(listed.next_cursor, None);
+        let uris: Vec<_> = listed.resources.iter().map(|resource| resource.uri.as_str()).collect();
+        assert_eq!(uris, ["spec://spec/models/user_spec.rb"]);
+        assert!(server.spec_resources(Some("1".to_string())).unwrap().resources.is_empty());
+        assert!(server.spec_resources(Some("next".to_string())).is_err());
+
+        let read = server.read_spec_resource("spec://spec/models/user_spec.rb".to_string()).unwrap();
+        match &read.contents[0] {
+            ResourceContents::TextResourceContents { uri, text, .. } => {
+                assert_eq!(uri, "spec://spec/models/user_spec.rb");
+                assert_eq!(text, "describe User do\nend\n");
+            }
+            other => panic!("unexpected contents {:?}", other),
+        }
+        let err = server.read_spec_resource("spec://spec/spec_helper.rb".to_string()).unwrap_err();
+        assert_eq!(err.message, "spec/spec_helper.rb is not a spec file under spec");
+        assert!(server.read_spec_resource("file:///etc/passwd".to_string()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_admin_tools_enabled() {
+        let runner = MockRunner::new();
+        let router = RspecServer::new(runner).with_admin_tools(true).tool_router;
+
+        assert!(router.has_route("run_rspec"));
+        assert!(router.has_route("process_stats"));
+    }
+
+    fn markers_project(source: &str) -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tmp.path().join("spec/models")).unwrap();
+        std::fs::write(tmp.path().join("spec/models/user_spec.rb"), source).unwrap();
+        tmp
+    }
+
+    #[tokio::test]
+    async fn test_spec_markers_tool() {
+        let source = "RSpec.describe User do\n  fit \"saves\" do; end\n  xit \"validates\" do; end\nend\n";
+        let tmp = markers_project(source);
+        let dir = tmp.path();
+        let report = r#"{"version":"3.12.2","examples":[
+            {"id":"./spec/models/user_spec.rb[1:1]","status":"passed"},
+            {"id":"./spec/models/user_spec.rb[1:2]","status":"pending","pending_message":"Temporarily skipped with xit"}
+        ]}"#;
+        let runner = MockRunner::with_result(0, report.to_string(), String::new());
+        let server = RspecServer::new(runner.clone())
+            .with_workdir_policy(WorkdirPolicy::new(Some(dir.to_path_buf()), vec![]).unwrap());
+
+        let args = || SpecMarkersArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+        let result = server.spec_markers(Parameters(args())).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert_eq!(
+            text,
+            "Spec Markers for: spec/models/user_spec.rb\nExamples: 2\nPending: 0\nSkipped: 1\nFocused: 1"
+        );
+        assert_eq!(
+            runner.recorded_commands()[0],
+            vec!["rspec", "--dry-run", "-f", "json", "spec/models/user_spec.rb"]
+        );
+
+        // Unchanged file is served from the cache
+        server.spec_markers(Parameters(args())).await.unwrap();
+        assert_eq!(runner.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_spec_markers_load_error() {
+        let tmp = markers_project("RSpec.describe User do\n");
+        let dir = tmp.path();
+        let stderr = "An error occurred while loading ./spec/models/user_spec.rb.\nSyntaxError:\n./spec/models/user_spec.rb:2: syntax error, unexpected end-of-input\n";
+        let runner = MockRunner::with_result(1, String::new(), stderr.to_string());
+        let server = RspecServer::new(runner)
+            .with_workdir_policy(WorkdirPolicy::new(Some(dir.to_path_buf()), vec![]).unwrap());
+
+        let args = SpecMarkersArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+        let result = server.spec_markers(Parameters(args)).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with("Spec Markers for: spec/models/user_spec.rb\nStatus: SpecSyntaxError in"));
+        assert!(server.markers.get(&MarkerCache::key("spec/models/user_spec.rb", "RSpec.describe User do\n")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_examples_tool() {
+        let tmp = markers_project("RSpec.describe User do\nend\n");
+        let dir = tmp.path();
+        let report = r#"{"examples":[
+            {"id":"./spec/models/user_spec.rb[1:1:1]","description":"saves","full_description":"User #save saves","file_path":"./spec/models/user_spec.rb","line_number":3},
+            {"id":"./spec/models/user_spec.rb[1:2]","description":"has a name","full_description":"User has a name","file_path":"./spec/models/user_spec.rb","line_number":7}
+        ]}"#;
+        let runner = MockRunner::with_result(0, report.to_string(), String::new());
+        let server = RspecServer::new(runner.clone())
+            .with_workdir_policy(WorkdirPolicy::new(Some(dir.to_path_buf()), vec![]).unwrap());
+
+        let args = ListExamplesArgs {
+            file: "spec/models".into(),
+            ..Default::default()
+        };
+        let result = server.list_examples(Parameters(args)).await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            "Examples in: spec/models\nExamples: 2\nFiles: 1\n\nspec/models/user_spec.rb\n  User [1]\n    #save [1:1]\n      - saves (line 3) [1:1:1]\n    - has a name (line 7) [1:2]"
+        );
+        assert_eq!(runner.recorded_commands()[0], vec!["rspec", "--dry-run", "-f", "json", "spec/models"]);
+        let structured = result.structured_content.unwrap();
+        assert_eq!(structured["examples"], 2);
+        assert_eq!(structured["files"][0]["children"][0]["children"][0]["id"], "./spec/models/user_spec.rb[1:1]");
+        assert_eq!(structured["files"][0]["children"][0]["children"][1]["kind"], "example");
+
+        let args = ListExamplesArgs {
+            file: "spec/missing".into(),
+            ..Default::default()
+        };
+        assert!(server.list_examples(Parameters(args)).await.is_err());
+        assert_eq!(runner.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_profile_specs_tool() {
+        let tmp = markers_project("RSpec.describe User do\nend\n");
+        let dir = tmp.path();
+        let report = r#"{"version":"3.13.0","examples":[],"summary":{"duration":1.5,"example_count":3,"failure_count":0,"pending_count":0,"errors_outside_of_examples_count":0},"summary_line":"3 examples, 0 failures","profile":{
+            "examples":[{"id":"./spec/models/user_spec.rb[1:1]","description":"saves","full_description":"User saves","status":"passed","file_path":"./spec/models/user_spec.rb","line_number":3,"run_time":0.75}],
+            "slowest":0.75,"total":1.5,
+            "groups":[{"total_time":1.25,"count":2,"description":"User","average":0.625,"location":"./spec/models/user_spec.rb:1"}]
+        }}"#;
+        let runner = MockRunner::with_result(0, report.to_string(), String::new());
+        let server = RspecServer::new(runner.clone())
+            .with_workdir_policy(WorkdirPolicy::new(Some(dir.to_path_buf()), vec![]).unwrap());
+
+        let args = ProfileSpecsArgs {
+            path: Some("spec/models".into()),
+            count: Some(5),
+            tags: Some(vec!["type:model".into()]),
+            ..Default::default()
+        };
+        let result = server.execute_profile(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with("Profile for: spec/models\n"));
+        assert!(text.contains("Status: Passed\nSummary: 3 examples, 0 failures\n\nSlowest Examples (0.7500 seconds, 50.0% of 1.5000 seconds):\n  0.7500 seconds spec/models/user_spec.rb:3 User saves\n\nSlowest Groups:\n"));
+        assert_eq!(
+            runner.recorded_commands()[0],
+            vec!["rspec", "--profile", "5", "-f", "json", "--tag", "type:model", "spec/models"]
+        );
+        let structured = result.structured_content.unwrap();
+        assert_eq!(structured["slowest_examples"][0]["run_time"], 0.75);
+        assert_eq!(structured["slowest_groups"][0]["average"], 0.625);
+        assert_eq!(structured["summary"]["example_count"], 3);
+
+        let args = ProfileSpecsArgs {
+            count: Some(500),
+            ..Default::default()
+        };
+        assert!(server.execute_profile(args, None).await.is_err());
+        assert_eq!(runner.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reset_state_tool() {
+        let runner = MockRunner::new().with_version(RspecVersion::new(3, 12, 0));
+        let versions = VersionCache::new();
+        versions.get_or_detect(&runner).await;
+
+        let server = RspecServer::new(runner)
+            .with_version_cache(versions.clone())
+            .with_run_history(RunHistory::new(5, None).unwrap());
+        let args = RspecServerArgs { file: "spec/models/user_spec.rb".into(), ..Default::default() };
+        server.execute_rspec(args, None).await.unwrap();
+        server.session_defaults.lock().unwrap().timeout_seconds = Some(30);
+        let result = server.reset_state().await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with("Cleared: RSpec version cache"));
+        assert!(text.contains("run history, session defaults"), "{}", text);
+        assert!(versions.is_empty().await);
+        assert_eq!(server.history.get(None), None);
+        assert!(server.session_defaults.lock().unwrap().is_empty());
+
+        let result = server.reset_state().await.unwrap();
+        assert!(!result.content[0].as_text().unwrap().text.contains("RSpec version cache"));
+    }
+
+    #[tokio::test]
+    async fn test_process_stats_tool() {
+        let tracker = ProcessTracker::new();
+        drop(tracker.spawned(None));
+        tracker.spawned(None).reaped();
+
+        let server = RspecServer::new(MockRunner::new()).with_process_tracker(tracker);
+        let result = server.process_stats().await.unwrap();
+
+        let text = &result.content[0].as_text().unwrap().text;
+        assert_eq!(text, "Active: 1\nSpawned: 2\nReaped: 1");
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_rejects_unsupported_flag() {
+        let runner = MockRunner::new().with_version(RspecVersion::new(2, 99, 0));
+        let server = RspecServer::new(runner);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            line_numbers: Some(vec![37, 87]),
+            ..Default::default()
+        };
+
+        let err = server.execute_rspec(args, None).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("requires RSpec 3.0 or newer"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_with_unknown_version() {
+        let server = RspecServer::new(MockRunner::new());
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            line_numbers: Some(vec![37, 87]),
+            ..Default::default()
+        };
+
+        assert!(server.execute_rspec(args, None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_dry_run_command() {
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone());
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            line_numbers: Some(vec![37]),
+            dry_run_command: Some(true),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert_eq!(text, "rspec spec/models/user_spec.rb:37");
+        assert_eq!(runner.calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_dry_run_command_still_validates() {
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone());
+        let args = RspecServerArgs {
+            file: "../spec/models/user_spec.rb".into(),
+            dry_run_command: Some(true),
+            ..Default::default()
+        };
+
+        let err = server.execute_rspec(args, None).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(runner.calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_streams_example_events() {
+        let stdout = "User\n  saves\n  rejects blanks (FAILED - 1)\n\nFailures:\n\n1 example, 1 failure\n";
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner).with_example_streaming(true);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let result = server.execute_rspec(args, Some(tx)).await.unwrap();
+        assert!(result.content[0].as_text().unwrap().text.contains("Status: Failed"));
+
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            events.push((event.id, event.status));
+        }
+        assert_eq!(
+            events,
+            vec![
+                ("User saves".to_string(), ExampleStatus::Passed),
+                ("User rejects blanks".to_string(), ExampleStatus::Failed),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_forwards_output_lines() {
+        let stdout = "..F\n\nFailures:\n\n3 examples, 1 failure\n";
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let listeners = RunListeners {
+            examples: None,
+            lines: Some(tx),
+        };
+        let result = server.execute_run(args, listeners, RunKind::Plain, None).await.unwrap();
+        assert!(result.content[0].as_text().unwrap().text.contains("Output:\n..F\n\nFailures:"));
+
+        let mut lines = Vec::new();
+        while let Some(line) = rx.recv().await {
+            lines.push(line);
+        }
+        assert_eq!(lines, ["..F", "", "Failures:", "", "3 examples, 1 failure"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_caps_reported_failures() {
+        let mut stdout = String::from("FFFF\n\nFailures:\n\n");
+        for i in 1..=4 {
+            stdout.push_str(&format!("  {i}) User {i} fails\n     Failure/Error: boom\n\n"));
+        }
+        stdout.push_str("Finished in 0.1 seconds (files took 0.2 seconds to load)\n4 examples, 4 failures\n");
+        let runner = MockRunner::with_result(1, stdout, String::new());
+        let server = RspecServer::new(runner).with_run_defaults(RunDefaults {
+            max_reported_failures: Some(3),
+            ..Default::default()
+        });
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Failures (3 of 4 shown):\n  1) User 1 fails\n     Failure/Error: boom"));
+        assert!(text.ends_with("  ... 1 more failure omitted"));
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            max_reported_failures: Some(1),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Failures (1 of 4 shown):"));
+        assert!(text.ends_with("  ... 3 more failures omitted"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_reports_detected_error() {
+        let stderr = "An error occurred while loading ./spec/models/user_spec.rb.\nActiveRecord::NoDatabaseError: database \"app_test\" does not exist\n";
+        let runner = MockRunner::with_result(1, String::new(), stderr.to_string());
+        let server = RspecServer::new(runner)
+            .with_error_patterns(vec!["ActiveRecord::NoDatabaseError".to_string()]);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("\n\nDetected Error: ActiveRecord::NoDatabaseError (check that the test database is running and migrated)"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_compact_output() {
+        let stdout = "Randomized with seed 4242\n.F.\n\nFinished in 0.1 seconds\n3 examples, 1 failure\n";
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            output_style: Some(OutputStyle::Compact),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert_eq!(text, "✗ 1 failed / 3 (seed 4242)");
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_uses_server_defaults() {
+        let stdout = "Randomized with seed 4242\n.F.\n\nFinished in 0.1 seconds\n3 examples, 1 failure\n";
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner).with_run_defaults(RunDefaults {
+            output_style: Some(OutputStyle::Compact),
+            ..Default::default()
+        });
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        assert_eq!(result.content[0].as_text().unwrap().text, "✗ 1 failed / 3 (seed 4242)");
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            output_style: Some(OutputStyle::Full),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        assert!(result.content[0].as_text().unwrap().text.starts_with("Test Results"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_plain_summary_output() {
+        let stdout = "\
+.F.
+
+Failures:
+
+  1) User saves a record
+     Failure/Error: expect(user.save).to be(true)
+     # ./spec/models/user_spec.rb:8:in `block (2 levels)'
+
+Finished in 0.1 seconds
+3 examples, 1 failure
+";
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            output_style: Some(OutputStyle::PlainSummary),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert_eq!(text, "1 of 3 tests failed in user_spec.rb; it was \"User saves a record\".");
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_human_formatter_with_json_report() {
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone());
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            human_formatter: Some(HumanFormatter::Documentation),
+            ..Default::default()
+        };
+        server.execute_rspec(args, None).await.unwrap();
+
+        let extra_args = &runner.recorded_contexts()[0].extra_args;
+        assert_eq!(extra_args[..5], ["-f", "documentation", "-f", "json", "-o"]);
+        assert!(extra_args[5].ends_with(".json"));
+        assert_eq!(extra_args.len(), 6);
+
+        let server = RspecServer::new(runner.clone()).with_example_streaming(true);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            human_formatter: Some(HumanFormatter::Documentation),
+            ..Default::default()
+        };
+        server.execute_rspec(args, None).await.unwrap();
+        assert_eq!(runner.recorded_contexts()[1].extra_args[..3], ["-f", "json", "-o"]);
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            human_formatter: Some(HumanFormatter::Progress),
+            ..Default::default()
+        };
+        assert!(server.execute_rspec(args, None).await.is_err());
+        assert_eq!(runner.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_partial_json_report() {
+        let stdout = r#"{"version":"3.12.2","examples":[{"id":"./spec/models/user_spec.rb[1:1]","description":"saves","full_description":"User saves","status":"passed"},{"id":"./spec/mo"#;
+        let runner = MockRunner::with_result(-1, stdout.to_string(), "[BUG] Segmentation fault".to_string())
+            .with_signal(11);
+        let server = RspecServer::new(runner);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Status: Terminated by signal 11\n"));
+        assert!(text.contains(
+            "\n\nPartial Results (partial: true, the JSON report was cut off; 1 examples completed):\n  [passed] User saves"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_reports_abort_reason() {
+        let runner = MockRunner::with_result(-1, "spam\nspam\n".to_string(), String::new())
+            .with_abort_reason("Output exceeded 10 bytes (--abort-on-output-bytes); the process was killed and the output is partial");
+        let server = RspecServer::new(runner);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with(
+            "Test Results for: spec/models/user_spec.rb\nExit Code: -1\nStatus: Failed\nOutcome: RSpec crashed or could not run (exit code -1); these are not test failures\nAttempts: 1 (failed)\nAborted: Output exceeded 10 bytes"
+        ));
+        assert!(text.contains("Output:\nspam\nspam\n"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_groups_examples() {
+        let stdout = r#"{"version":"3.12.2","examples":[
+            {"id":"./spec/models/user_spec.rb[1:1]","description":"is valid","full_description":"User is valid","status":"passed","file_path":"./spec/models/user_spec.rb"},
+            {"id":"./spec/models/user_spec.rb[1:2]","description":"has a name","full_description":"User has a name","status":"failed","file_path":"./spec/models/user_spec.rb"}
+        ]}"#;
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner);
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            group_examples: Some(true),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.ends_with(
+            "\n\nExample Groups:\n  User (spec/models/user_spec.rb): 1 passed, 1 failed\n    [passed] is valid\n    [failed] has a name"
+        ));
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(!text.contains("Example Groups:"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_retries_failures() {
+        let runner = MockRunner::new().with_sequence(vec![
+            (1, "F\n\n1 example, 1 failure\n".to_string(), String::new()),
+            (1, "F\n\n1 example, 1 failure\n".to_string(), String::new()),
+            (0, ".\n\n1 example, 0 failures\n".to_string(), String::new()),
+        ]);
+        let server = RspecServer::new(runner.clone());
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            retries: Some(3),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Exit Code: 0\nStatus: Passed\nOutcome: passed\nAttempts: 3 (failed, failed, passed)\n"));
+        let structured = result.structured_content.unwrap();
+        assert_eq!(structured["attempts"], 3);
+        assert_eq!(structured["outcomes"], serde_json::json!(["failed", "failed", "passed"]));
+        assert_eq!(runner.calls(), 3);
+    }
+
+    fn failed_examples_output(locations: &[&str]) -> String {
+        let listed: Vec<String> = locations.iter().map(|location| format!("rspec ./{} # fails", location)).collect();
+        format!("F\n\n{} examples, {} failures\n\nFailed examples:\n\n{}\n", locations.len(), locations.len(), listed.join("\n"))
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_retries_only_failing_examples() {
+        let runner = MockRunner::new().with_sequence(vec![
+            (1, failed_examples_output(&["spec/a_spec.rb:3", "spec/a_spec.rb:8", "spec/b_spec.rb:5"]), String::new()),
+            (1, failed_examples_output(&["spec/a_spec.rb:8", "spec/b_spec.rb:5"]), String::new()),
+            (1, failed_examples_output(&["spec/a_spec.rb:8"]), String::new()),
+        ]);
+        let server = RspecServer::new(runner.clone());
+        let args = RspecServerArgs { file: SpecPaths::Many(vec!["spec/a_spec.rb".to_string(), "spec/b_spec.rb".to_string()]), retries: Some(2), ..Default::default() };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Status: Failed\nOutcome: tests failed\nAttempts: 3 (failed, failed, failed)\n"), "{}", text);
+        assert!(text.contains(
+            "Retries (2 of 3 attempts were reruns of the failing examples):\n  spec/a_spec.rb:3: flaky (passed on retry 1)\n  spec/a_spec.rb:8: consistent\n  spec/b_spec.rb:5: flaky (passed on retry 2)"
+        ));
+        assert!(text.contains("3 examples, 3 failures"));
+        assert_eq!(
+            runner.recorded_commands(),
+            vec![
+                vec!["rspec", "spec/a_spec.rb", "spec/b_spec.rb"],
+                vec!["rspec", "spec/a_spec.rb:3", "spec/a_spec.rb:8", "spec/b_spec.rb:5"],
+                vec!["rspec", "spec/a_spec.rb:8", "spec/b_spec.rb:5"],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_retries_flaky_and_unconfirmed() {
+        let runner = MockRunner::new().with_sequence(vec![
+            (1, failed_examples_output(&["spec/a_spec.rb:3"]), String::new()),
+            (0, ".\n\n1 example, 0 failures\n".to_string(), String::new()),
+        ]);
+        let server = RspecServer::new(runner.clone());
+        let args = || RspecServerArgs { file: "spec/a_spec.rb".into(), retries: Some(3), ..Default::default() };
+
+        let result = server.execute_rspec(args(), None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Outcome: tests failed, but every failing example passed on retry (flaky)\nAttempts: 2 (failed, passed)\n"), "{}", text);
+        assert_eq!(runner.calls(), 2);
+
+        let runner = MockRunner::new().with_sequence(vec![
+            (1, failed_examples_output(&["spec/a_spec.rb:3"]), String::new()),
+            (1, "F\n".to_string(), String::new()),
+        ]);
+        let server = RspecServer::new(runner.clone());
+        let result = server.execute_rspec(args(), None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("  spec/a_spec.rb:3: unconfirmed (a retry ended as failed)"), "{}", text);
+        assert_eq!(runner.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_stops_after_retries() {
+        let runner = MockRunner::with_result(1, "F\n".to_string(), String::new());
+        let server = RspecServer::new(runner.clone());
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            retries: Some(1),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Status: Failed\nOutcome: tests failed\nAttempts: 2 (failed, failed)\n"));
+        assert_eq!(runner.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_failure_is_success_when_disabled() {
+        let runner = MockRunner::with_result(1, "F\n".to_string(), String::new());
+        let server = RspecServer::new(runner).with_error_on_failure(false);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        assert_eq!(result.is_error, Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_error_on_failure() {
+        let runner = MockRunner::with_result(1, "F\n".to_string(), String::new());
+        let server = RspecServer::new(runner);
+        let args = || RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args(), None).await.unwrap();
+        assert_eq!(result.is_error, Some(true));
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Exit Code: 1\nStatus: Failed\nOutcome: tests failed\n"));
+
+        let crashed = RspecServer::new(MockRunner::with_result(2, "Randomized with seed 1\n".to_string(), "boom\n".to_string()));
+        let result = crashed.execute_rspec(args(), None).await.unwrap();
+        assert_eq!(result.is_error, Some(true));
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains(
+            "Exit Code: 2\nStatus: Failed\nOutcome: RSpec crashed or could not run (exit code 2); these are not test failures\n"
+        ));
+
+        let passing = RspecServer::new(MockRunner::new());
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+        let result = passing.execute_rspec(args, None).await.unwrap();
+        assert_eq!(result.is_error, Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_verbose() {
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone());
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            verbose: Some(true),
+            ..Default::default()
+        };
+        server.execute_rspec(args, None).await.unwrap();
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+        server.execute_rspec(args, None).await.unwrap();
+
+        let contexts = runner.recorded_contexts();
+        assert_eq!(contexts[0].extra_args, vec!["--backtrace"]);
+        assert_eq!(contexts[0].env, vec![("RAILS_LOG_LEVEL".to_string(), "debug".to_string())]);
+        assert_eq!(contexts[1], RunContext::default());
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            verbose: Some(true),
+            dry_run_command: Some(true),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            "RAILS_LOG_LEVEL=[REDACTED] rspec --backtrace spec/models/user_spec.rb"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_env() {
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone())
+            .with_env_allowlist(EnvAllowlist::new(vec!["COVERAGE".to_string(), "RAILS_LOG_LEVEL".to_string()]));
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            verbose: Some(true),
+            env: Some(BTreeMap::from([
+                ("COVERAGE".to_string(), "1".to_string()),
+                ("RAILS_LOG_LEVEL".to_string(), "info".to_string()),
+            ])),
+            ..Default::default()
+        };
+        server.execute_rspec(args, None).await.unwrap();
+
+        assert_eq!(
+            runner.recorded_contexts()[0].env,
+            [
+                ("RAILS_LOG_LEVEL".to_string(), "debug".to_string()),
+                ("COVERAGE".to_string(), "1".to_string()),
+                ("RAILS_LOG_LEVEL".to_string(), "info".to_string()),
+            ]
+        );
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            env: Some(BTreeMap::from([("DATABASE_URL".to_string(), "postgres://prod".to_string())])),
+            ..Default::default()
+        };
+        let error = server.execute_rspec(args, None).await.unwrap_err();
+        assert_eq!(
+            error.message,
+            "Invalid parameters: Environment variable DATABASE_URL is not allowed; --allow-env permits COVERAGE, RAILS_LOG_LEVEL"
+        );
+        assert_eq!(runner.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_env_values_stay_out_of_commands() {
+        let stdout = "F\n\nFailures:\n\n  1) User saves\n     Failure/Error: boom\n\nFinished in 0.1 seconds (files took 0.2 seconds to load)\n1 example, 1 failure\n\nFailed examples:\n\nrspec ./spec/models/user_spec.rb:3 # User saves\n";
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner)
+            .with_env_allowlist(EnvAllowlist::new(vec!["DATABASE_URL".to_string()]))
+            .with_run_history(RunHistory::new(5, None).unwrap());
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            env: Some(BTreeMap::from([("DATABASE_URL".to_string(), "postgres://admin:hunter2@db".to_string())])),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("DATABASE_URL=[REDACTED] rspec spec/models/user_spec.rb:3"), "{}", text);
+        assert!(!text.contains("hunter2"), "{}", text);
+
+        let record = server.history.get(Some(1)).unwrap();
+        assert_eq!(record.command, "DATABASE_URL=[REDACTED] rspec spec/models/user_spec.rb");
+        assert!(!record.rerun_command.as_deref().unwrap_or_default().contains("hunter2"));
+        let result = server.get_run(Parameters(GetRunArgs { run_id: Some(1) })).await.unwrap();
+        assert!(!result.content[0].as_text().unwrap().text.contains("hunter2"));
+        let result = server.list_runs(Parameters(ListRunsArgs::default())).await.unwrap();
+        assert!(!result.content[0].as_text().unwrap().text.contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_rejects_missing_directory() {
+        let project = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).canonicalize().unwrap();
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone())
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.clone()), vec![]).unwrap());
+        let args = RspecServerArgs {
+            file: "spec/requests".into(),
+            ..Default::default()
+        };
+
+        let error = server.execute_rspec(args, None).await.unwrap_err();
+        assert_eq!(
+            error.message,
+            format!(
+                "Invalid parameters: Directory spec/requests does not exist in {}",
+                project.display()
+            )
+        );
+        assert_eq!(runner.calls(), 0);
+
+        let args = RspecServerArgs {
+            file: "src/".into(),
+            ..Default::default()
+        };
+        server.execute_rspec(args, None).await.unwrap();
+        assert_eq!(runner.recorded_commands(), vec![vec!["rspec", "src/"]]);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_reports_env() {
+        let server = RspecServer::new(MockRunner::new()).with_env_report(Some(vec![
+            ("RAILS_ENV".to_string(), "test".to_string()),
+            ("DATABASE_PASSWORD".to_string(), "hunter2".to_string()),
+        ]));
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            verbose: Some(true),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        let env = text.split("\n\nEnvironment:\n").nth(1).unwrap();
+        assert_eq!(
+            env,
+            "  RAILS_ENV=test\n  DATABASE_PASSWORD=[REDACTED]\n  RAILS_LOG_LEVEL=debug"
+        );
+        assert!(!env.contains("PATH="));
+        assert!(!text.contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_env_not_reported_by_default() {
+        let server = RspecServer::new(MockRunner::new());
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(!text.contains("Environment:"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_git_blame() {
+        let tmp = crate::git_blame::test_repository();
+        let repo = tmp.path();
+        let stdout = "\
+Failures:
+
+  1) User fails
+     Failure/Error: it { expect(1).to eq 2 }
+     # ./spec/user_spec.rb:2:in `block (2 levels)'
+
+  2) User is gone
+     Failure/Error: boom
+     # ./spec/missing_spec.rb:7:in `block (2 levels)'
+
+2 examples, 2 failures
+";
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let args = || RspecServerArgs {
+            file: "spec/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let server = RspecServer::new(runner.clone())
+            .with_workdir_policy(WorkdirPolicy::new(Some(repo.to_path_buf()), vec![]).unwrap())
+            .with_git_blame(true);
+        let result = server.execute_rspec(args(), None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        let blame = text.split("\n\nBlame:\n").nth(1).unwrap();
+        let blame = blame.split("\n\n").next().unwrap();
+        assert!(blame.starts_with("  spec/user_spec.rb:2 "), "{}", blame);
+        assert!(blame.ends_with(" Jane Doe"), "{}", blame);
+        assert_eq!(blame.lines().count(), 1);
+
+        let server = RspecServer::new(runner)
+            .with_workdir_policy(WorkdirPolicy::new(Some(repo.to_path_buf()), vec![]).unwrap());
+        let result = server.execute_rspec(args(), None).await.unwrap();
+        assert!(!result.content[0].as_text().unwrap().text.contains("Blame:"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_leaves_out_baseline_failures() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("baseline.txt");
+        let before = "\
+Failures:
+
+  1) User fails
+     Failure/Error: it { expect(1).to eq 2 }
+     # ./spec/user_spec.rb:2:in `block (2 levels)'
+
+1 example, 1 failure
+";
+        let after = "\
+Failures:
+
+  1) User fails
+     Failure/Error: it { expect(1).to eq 2 }
+     # ./spec/user_spec.rb:9:in `block (2 levels)'
+
+  2) User is gone
+     Failure/Error: boom
+     # ./spec/user_spec.rb:14:in `block (2 levels)'
+
+2 examples, 2 failures
+";
+        let runner = MockRunner::new().with_sequence(vec![
+            (1, before.to_string(), String::new()),
+            (1, after.to_string(), String::new()),
+            (1, before.to_string(), String::new()),
+        ]);
+        let server = RspecServer::new(runner).with_baseline(Some(Baseline::load(path.clone()).unwrap()));
+        assert!(server.tool_router.has_route("update_baseline"));
+
+        let args = UpdateBaselineArgs {
+            file: "spec/user_spec.rb".into(),
+            ..Default::default()
+        };
+        let result = server.update_baseline(Parameters(args)).await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            "Baseline updated for: spec/user_spec.rb\nKnown Failures: 1\nBaseline Total: 1"
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "spec/user_spec.rb User fails\n");
+
+        let args = || RspecServerArgs {
+            file: "spec/user_spec.rb".into(),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args(), None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Failures (1 of 1 shown):\n  1) User is gone (spec/user_spec.rb:14)\n"));
+        assert!(text.contains("\n\nBaseline: 1 known failure not listed\n"));
+
+        let result = server.execute_rspec(args(), None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(!text.contains("Failures ("));
+        assert!(text.contains("\n\nBaseline: 1 known failure not listed; no new failures"));
+        assert!(text.contains("\nExit Code: 1\nStatus: Passed\nOutcome: passed; every failure is a known baseline failure\n"), "{}", text);
+        assert_eq!(result.is_error, Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_rerun_failures() {
+        let server = RspecServer::new(MockRunner::new()).with_rerun_failures(true);
+        assert!(server.tool_router.has_route("rerun_failures"));
+        let err = server.execute_rerun(RunListeners::default(), None).await.unwrap_err();
+        assert_eq!(err.message, "Nothing to rerun yet; call run_rspec first");
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            line_numbers: Some(vec![12]),
+            ..Default::default()
+        };
+        server.execute_rspec(args, None).await.unwrap();
+        let result = server.execute_rerun(RunListeners::default(), None).await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            "No failures to rerun from the last run of: spec/models/user_spec.rb"
+        );
+
+        let example_status = server.example_status.as_ref().unwrap();
+        std::fs::write(
+            example_status.status_path(),
+            "example_id | status | run_time |\n./spec/models/user_spec.rb[1:2] | failed | 0.01 seconds |\n",
+        )
+        .unwrap();
+        let result = server.execute_rerun(RunListeners::default(), None).await.unwrap();
+        assert!(result.content[0].as_text().unwrap().text.starts_with("Test Results for: spec/models/user_spec.rb\n"));
+
+        let commands = server.runner.recorded_commands();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0][1], "--require");
+        assert_eq!(commands[0][3..], ["spec/models/user_spec.rb:12"]);
+        assert_eq!(
+            commands[1][3..],
+            ["--only-failures", "--order", "defined", "spec/models/user_spec.rb"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_with_coverage() {
+        let args = || RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+        let server = RspecServer::new(MockRunner::new());
+        assert!(!server.tool_router.has_route("run_rspec_with_coverage"));
+        let err = server.execute_coverage_run(args(), RunListeners::default(), None).await.unwrap_err();
+        assert_eq!(err.message, "Coverage runs are disabled; start the server with --enable-coverage");
+
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone()).with_coverage(Some(CoverageConfig::default()));
+        assert!(server.tool_router.has_route("run_rspec_with_coverage"));
+        let result = server.execute_coverage_run(args(), RunListeners::default(), None).await.unwrap();
+
+        assert_eq!(runner.recorded_contexts()[0].env, [("COVERAGE".to_string(), "1".to_string())]);
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("\n\nCoverage:\n  No new coverage results in coverage;"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_bisect_failure() {
+        let args = || BisectFailureArgs {
+            example: "spec/models/user_spec.rb:12".to_string(),
+            seed: 1234,
+            files: Some(vec!["spec/models/order_spec.rb".to_string()]),
+            ..Default::default()
+        };
+        let server = RspecServer::new(MockRunner::new());
+        assert!(!server.tool_router.has_route("bisect_failure"));
+        let err = server.execute_bisect(args(), None, None).await.unwrap_err();
+        assert_eq!(err.message, "Bisect is disabled; start the server with --enable-bisect");
+
+        let output = "Bisect started using options: \"--seed 1234\"\nRound 1: bisecting over non-failing examples 1-2 . ignoring example 2 (0.1 seconds)\nFAILED\n\nThe minimal reproduction command is:\n  rspec ./spec/models/order_spec.rb[1:1] ./spec/models/user_spec.rb[1:2] --seed 1234\n";
+        let runner = MockRunner::with_result(0, output.to_string(), String::new());
+        let server = RspecServer::new(runner.clone()).with_bisect(Some(Duration::from_secs(600)));
+        assert!(server.tool_router.has_route("bisect_failure"));
+        let (progress, mut received) = mpsc::unbounded_channel();
+        let result = server.execute_bisect(args(), Some(progress), None).await.unwrap();
+
+        assert_eq!(
+            runner.recorded_commands()[0],
+            ["rspec", "--bisect", "--seed", "1234", "spec/models/order_spec.rb", "spec/models/user_spec.rb:12"]
+        );
+        assert_eq!(runner.recorded_contexts()[0].timeout, Some(Duration::from_secs(600)));
+        let text = &result.content[0].as_text().unwrap().text;
+        assert_eq!(result.is_error, Some(false));
+        assert!(text.starts_with("Bisect for: spec/models/user_spec.rb:12 (seed 1234)\n"), "{}", text);
+        assert!(text.contains(
+            "Outcome: found a minimal reproduction; the failure depends on the examples that run before it\n\nMinimal Reproduction:\n  rspec ./spec/models/order_spec.rb[1:1] ./spec/models/user_spec.rb[1:2] --seed 1234\n\nOutput:\n"
+        ));
+        assert_eq!(received.recv().await.unwrap(), "Bisect started using options: \"--seed 1234\"");
+        assert!(received.recv().await.unwrap().starts_with("Round 1:"));
+
+        let args = BisectFailureArgs { example: "spec/models".to_string(), ..args() };
+        assert!(server.execute_bisect(args, None, None).await.unwrap_err().message.starts_with("Invalid parameters: example must be a single example"));
+    }
+
+    #[tokio::test]
+    async fn test_last_failures() {
+        let server = RspecServer::new(MockRunner::new()).with_rerun_failures(true);
+        assert!(server.tool_router.has_route("last_failures"));
+        assert_eq!(server.last_failures().await.unwrap_err().message, "No runs yet; call run_rspec first");
+
+        let args = RspecServerArgs {
+            file: SpecPaths::Many(vec!["spec/models/user_spec.rb".to_string(), "spec/requests_spec.rb".to_string()]),
+            ..Default::default()
+        };
+        server.execute_rspec(args, None).await.unwrap();
+        let result = server.last_failures().await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            "Last run: spec/models/user_spec.rb spec/requests_spec.rb\nFailed examples: 0\n"
+        );
+
+        std::fs::write(
+            server.example_status.as_ref().unwrap().status_path(),
+            "example_id | status | run_time |\n\
+             ./spec/models/user_spec.rb[1:2] | failed | 0.01 seconds |\n\
+             ./spec/models/user_spec.rb[1:3] | passed | 0.01 seconds |\n\
+             ./spec/requests_spec.rb[2:1] | failed | 0.01 seconds |\n\
+             ./spec/other_spec.rb[1:1] | failed | 0.01 seconds |\n",
+        )
+        .unwrap();
+        let result = server.last_failures().await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            "Last run: spec/models/user_spec.rb spec/requests_spec.rb\nFailed examples: 2\n\n./spec/models/user_spec.rb[1:2]\n./spec/requests_spec.rb[2:1]"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rerun_failures_not_registered_by_default() {
+        let server = RspecServer::new(MockRunner::new());
+        assert!(!server.tool_router.has_route("rerun_failures"));
+        assert!(!server.tool_router.has_route("last_failures"));
+        let err = server.execute_rerun(RunListeners::default(), None).await.unwrap_err();
+        assert_eq!(err.message, "Rerunning failures is disabled; start the server with --rerun-failures");
+    }
+
+    #[tokio::test]
+    async fn test_update_baseline_not_registered_by_default() {
+        let server = RspecServer::new(MockRunner::new()).with_admin_tools(true);
+        assert!(server.tool_router.has_route("process_stats"));
+        assert!(!server.tool_router.has_route("update_baseline"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_steps_down_to_fit_response_budget() {
+        let mut stdout = String::from("Failures:\n\n");
+        for i in 1..=200 {
+            stdout.push_str(&format!("  {i}) User {i} fails\n     Failure/Error: boom\n\n"));
+        }
+        stdout.push_str("Finished in 0.1 seconds (files took 0.2 seconds to load)\n200 examples, 200 failures\n");
+        let args = || RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+        let run = |budget: usize| {
+            let runner = MockRunner::with_result(1, stdout.clone(), String::new());
+            let server = RspecServer::new(runner).with_response_budget(Some(budget));
+            async move {
+                let result = server.execute_rspec(args(), None).await.unwrap();
+                result.content[0].as_text().unwrap().text.clone()
+            }
+        };
+
+        let text = run(1_000_000).await;
+        assert!(text.contains("Detail: full (response budget 1000000 bytes)\n\nOutput:\n"));
+
+        let text = run(2_000).await;
+        assert!(text.contains("Detail: failures_only (response budget 2000 bytes)\n\nFailures (20 of 200 shown):\n"));
+        assert!(!text.contains("Output:"));
+        assert!(text.len() <= 2_000);
+
+        let text = run(200).await;
+        assert!(text.ends_with("Summary: 200 examples, 200 failures (run_time: 0.1s, load_time: 0.2s)\nDetail: summary_only (response budget 200 bytes)"));
+        assert!(!text.contains("Failures ("));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_timeout() {
+        let runner = MockRunner::new().with_delay("spec/models/user_spec.rb", Duration::from_secs(5));
+        let server = RspecServer::new(runner.clone()).with_run_defaults(RunDefaults {
+            timeout_seconds: Some(600),
+            ..Default::default()
+        });
+
+        let args = RspecServerArgs {
+            file: "spec/models/order_spec.rb".into(),
+            ..Default::default()
+        };
+        server.execute_rspec(args, None).await.unwrap();
+        assert_eq!(runner.recorded_contexts()[0].timeout, Some(Duration::from_secs(600)));
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            timeout_seconds: Some(1),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert_eq!(runner.recorded_contexts()[1].timeout, Some(Duration::from_secs(1)));
+        assert!(text.contains("Status: Terminated by signal 9\nOutcome: RSpec was killed by signal 9 before finishing; results are incomplete\nAttempts: 1 (terminated)\nAborted: Timed out\n"));
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            timeout_seconds: Some(0),
+            ..Default::default()
+        };
+        let err = server.execute_rspec(args, None).await.unwrap_err();
+        assert_eq!(err.message, "Invalid parameters: timeout_seconds must be at least 1");
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_json_format() {
+        let stdout = r#"{"version":"3.12.2","examples":[{"id":"./spec/models/user_spec.rb[1:1]","description":"saves","full_description":"User saves","status":"passed","file_path":"./spec/models/user_spec.rb","line_number":4},{"id":"./spec/models/user_spec.rb[1:2]","description":"rejects blanks","full_description":"User rejects blanks","status":"failed","file_path":"./spec/models/user_spec.rb","line_number":12,"exception":{"class":"RuntimeError","message":"boom"}}],"summary":{"duration":0.5,"example_count":2,"failure_count":1,"pending_count":0,"errors_outside_of_examples_count":0},"summary_line":"2 examples, 1 failure"}"#;
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner).with_json_format(true);
+        let args = || RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args(), None).await.unwrap();
+        assert_eq!(result.is_error, Some(true));
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains(
+            "Summary: 2 examples, 1 failure (run_time: 0.5s)\n\nOutput:\n2 examples, 1 failure\n  [failed] spec/models/user_spec.rb:12 User rejects blanks\n  [passed] spec/models/user_spec.rb:4 User saves\n"
+        ));
+        assert!(text.contains("Failures (1 of 1 shown):\n  1) User rejects blanks (spec/models/user_spec.rb:12)\n     RuntimeError:\n     boom"));
+        let structured = result.structured_content.unwrap();
+        assert_eq!(structured["status"], "failed");
+        assert_eq!(structured["summary"]["failure_count"], 1);
+        assert_eq!(structured["examples"][0]["full_description"], "User rejects blanks");
+        assert_eq!(structured["examples"][0]["exception"]["message"], "boom");
+        assert_eq!(structured["examples"][1]["status"], "passed");
+
+        let plain = RspecServer::new(MockRunner::new()).execute_rspec(args(), None).await.unwrap();
+        assert_eq!(
+            plain.structured_content,
+            Some(serde_json::json!({ "status": "passed", "summary": null, "seed": null, "attempts": 1, "outcomes": ["passed"] }))
+        );
+
+        let args = RspecServerArgs {
+            human_formatter: Some(HumanFormatter::Progress),
+            ..args()
+        };
+        assert!(server.execute_rspec(args, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_json_format_source_context() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path();
+        std::fs::create_dir_all(project.join("spec/models")).unwrap();
+        std::fs::write(project.join("spec/models/user_spec.rb"), "describe User do\n  it { expect(1).to eq(2) }\nend\n").unwrap();
+        let stdout = r#"{"examples":[{"id":"./spec/models/user_spec.rb[1:1]","full_description":"User","status":"failed","file_path":"./spec/models/user_spec.rb","line_number":2,"exception":{"class":"RuntimeError","message":"boom","backtrace":["./spec/models/user_spec.rb:2:in `block (2 levels)'"]}}],"summary_line":"1 example, 1 failure"}"#;
+        let server = RspecServer::new(MockRunner::with_result(1, stdout.to_string(), String::new()))
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.to_path_buf()), vec![]).unwrap())
+            .with_json_format(true);
+
+        let args = RspecServerArgs { file: "spec/models/user_spec.rb".into(), ..Default::default() };
+        let structured = server.execute_rspec(args, None).await.unwrap().structured_content.unwrap();
+        assert_eq!(
+            structured["examples"][0]["source_context"],
+            serde_json::json!([{
+                "file": "spec/models/user_spec.rb",
+                "line": 2,
+                "code": "  1 | describe User do\n> 2 |   it { expect(1).to eq(2) }\n  3 | end"
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_json_format_falls_back_to_raw_output() {
+        let stdout = "{\"version\":\"3.12.2\",\"examples\":[{\"id\":\"./spec/mod\n";
+        let stderr = "/usr/lib/ruby/3.2.0/json.rb: [BUG] Segmentation fault\n";
+        let runner = MockRunner::with_result(1, stdout.to_string(), stderr.to_string());
+        let server = RspecServer::new(runner).with_json_format(true);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains(&format!("\nOutput:\n{}\n\nErrors:\n{}", stdout, stderr)));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_reports_signal() {
+        let runner = MockRunner::with_result(-1, "..".to_string(), String::new()).with_signal(11);
+        let server = RspecServer::new(runner);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains(
+            "Exit Code: none (terminated by signal 11)\nStatus: Terminated by signal 11\nOutcome: RSpec was killed by signal 11 before finishing; results are incomplete\nAttempts: 1 (terminated)\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_per_file_timeout() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("spec/models")).unwrap();
+        for file in ["spec/models/order_spec.rb", "spec/models/user_spec.rb"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+
+        let stdout = "..\n\nFinished in 0.1 seconds\n2 examples, 0 failures\n";
+        let runner = MockRunner::with_result(0, stdout.to_string(), String::new())
+            .with_delay("spec/models/order_spec.rb", Duration::from_secs(30));
+        let server = RspecServer::new(runner.clone())
+            .with_workdir_policy(WorkdirPolicy::new(Some(root.to_path_buf()), vec![]).unwrap())
+            .with_per_file_timeout(Some(Duration::from_millis(50)));
+        let args = RspecServerArgs {
+            file: "spec/models".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert_eq!(
+            *text,
+            format!(
+                "Test Results for: spec/models\nWorking Directory: {}\nStatus: Failed\nFiles: 2 (1 passed, 0 failed, 1 timed out)\n\nPer-File Results:\n  spec/models/order_spec.rb: timed out after 0.05 seconds\n  spec/models/user_spec.rb: passed (2 examples, 0 failures (run_time: 0.1s))",
+                root.canonicalize().unwrap().display()
+            )
+        );
+        assert_eq!(runner.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_single_attempt() {
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone());
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            retries: Some(3),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Status: Passed\nOutcome: passed\nAttempts: 1 (passed)\n"));
+        assert_eq!(runner.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_uses_default_workdir() {
+        let project = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).canonicalize().unwrap();
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone())
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.clone()), vec![]).unwrap());
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with(&format!(
+            "Test Results for: spec/models/user_spec.rb\nWorking Directory: {}\nExit Code: 0\n",
+            project.display()
+        )));
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            workdir: Some("src".to_string()),
+            ..Default::default()
+        };
+        server.execute_rspec(args, None).await.unwrap();
+
+        let workdirs: Vec<_> = runner.recorded_contexts().into_iter().map(|c| c.workdir).collect();
+        assert_eq!(workdirs, vec![Some(project.clone()), Some(project.join("src"))]);
+
+        let result = RspecServer::new(MockRunner::new())
+            .execute_rspec(RspecServerArgs { file: "spec/models/user_spec.rb".into(), ..Default::default() }, None)
+            .await
+            .unwrap();
+        assert!(!result.content[0].as_text().unwrap().text.contains("Working Directory:"));
+    }
+
+    #[tokio::test]
+    async fn test_configure_session_defaults() {
+        let runner = MockRunner::new();
+        let make_server = || {
+            RspecServer::new(runner.clone()).with_env_allowlist(EnvAllowlist::new(vec!["COVERAGE".to_string()]))
+        };
+        let server = make_server();
+        let configure = |args: ConfigureArgs| server.configure(Parameters(args));
+
+        let result = configure(ConfigureArgs::default()).await.unwrap();
+        assert_eq!(result.content[0].as_text().unwrap().text, "Session defaults: none");
+        let result = configure(ConfigureArgs {
+            env: Some(BTreeMap::from([("COVERAGE".to_string(), "1".to_string())])),
+            output_style: Some(OutputStyle::Compact),
+            timeout_seconds: Some(90),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            "Session defaults:\n  env: COVERAGE=1\n  output_style: compact\n  timeout_seconds: 90"
+        );
+        let err = configure(ConfigureArgs {
+            env: Some(BTreeMap::from([("PATH".to_string(), "/tmp".to_string())])),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+        assert!(err.message.starts_with("Invalid parameters: Environment variable PATH is not allowed"));
+        assert!(configure(ConfigureArgs { project: Some("api".to_string()), ..Default::default() }).await.is_err());
+
+        let args = RspecServerArgs { file: "spec/models/user_spec.rb".into(), ..Default::default() };
+        server.clone().execute_rspec(args, None).await.unwrap();
+        let args = RspecServerArgs { file: "spec/models/user_spec.rb".into(), timeout_seconds: Some(5), ..Default::default() };
+        make_server().execute_rspec(args, None).await.unwrap();
+
+        let contexts = runner.recorded_contexts();
+        assert_eq!(contexts[0].env, vec![("COVERAGE".to_string(), "1".to_string())]);
+        assert_eq!(contexts[0].timeout, Some(Duration::from_secs(90)));
+        assert_eq!(contexts[1].env, vec![]);
+        assert_eq!(contexts[1].timeout, Some(Duration::from_secs(5)));
+
+        let result = configure(ConfigureArgs { reset: Some(true), ..Default::default() }).await.unwrap();
+        assert_eq!(result.content[0].as_text().unwrap().text, "Session defaults: none");
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_rejects_workdir_outside_allow_list() {
+        let project = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).canonicalize().unwrap();
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone())
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.join("src")), vec![]).unwrap());
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            workdir: Some("..".to_string()),
+            ..Default::default()
+        };
+        let err = server.execute_rspec(args, None).await.unwrap_err();
+        assert!(err.message.starts_with("Invalid parameters: Working directory"));
+        assert_eq!(runner.calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_rejects_smuggled_and_disallowed_paths() {
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone()).with_allowed_paths(vec!["spec/models/**".to_string()]);
+
+        for (file, error) in [
+            ("--require=./evil.rb", "Invalid parameters: File paths can't start with '-'"),
+            ("spec/models/user_spec.rb;id", "Invalid parameters: Shell metacharacter ';' not allowed in file path"),
+            ("spec/features/login_spec.rb", "Invalid parameters: Path spec/features/login_spec.rb isn't matched by --allowed-path (spec/models/**)"),
+        ] {
+            let args = RspecServerArgs { file: file.into(), ..Default::default() };
+            assert_eq!(server.execute_rspec(args, None).await.unwrap_err().message, error);
+        }
+        assert_eq!(runner.calls(), 0);
+
+        let args = RspecServerArgs { file: "spec/models/user_spec.rb".into(), ..Default::default() };
+        assert!(server.execute_rspec(args, None).await.is_ok());
+        assert_eq!(runner.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_absolute_paths_within_project_root() {
+        let project = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).canonicalize().unwrap();
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone())
+            .with_workdir_policy(WorkdirPolicy::new(Some(project.clone()), vec![]).unwrap());
 
-#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
-pub struct RspecServerArgs {
-    #[schemars(
-        description = "RSpec test file path (must be relative to project root and end with '_spec.rb')",
-        example = "spec/models/user_spec.rb"
-    )]
-    pub file: String,
+        let args = RspecServerArgs {
+            file: format!("{}/spec/models/user_spec.rb", project.display()).as_str().into(),
+            line_numbers: Some(vec![4]),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with("Test Results for: spec/models/user_spec.rb:4\n"), "{}", text);
+        assert_eq!(runner.recorded_commands()[0].last().unwrap(), "spec/models/user_spec.rb:4");
 
-    #[schemars(
-        description = "Optional line numbers to target specific tests",
-        example = "[37, 87]"
-    )]
-    pub line_numbers: Option<Vec<i32>>,
-}
+        let args = RspecServerArgs {
+            file: "/tmp/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+        let err = server.execute_rspec(args, None).await.unwrap_err();
+        assert_eq!(
+            err.message,
+            format!("Invalid parameters: Path /tmp/models/user_spec.rb is outside the project root {}", project.display())
+        );
+        assert_eq!(runner.calls(), 1);
+    }
 
-#[derive(Clone)]
-pub struct RspecServer<R: CommandRunner + Clone + 'static> {
-    tool_router: ToolRouter<RspecServer<R>>,
-    runner: R,
-}
+    fn projects_fixture() -> (std::path::PathBuf, Projects) {
+        let project = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).canonicalize().unwrap();
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("projects.json");
+        let text = format!(
+            r#"{{"projects": [{{"name": "billing", "root": "{}/src", "command": "bin/rspec --no-color"}}, {{"name": "storefront", "root": "{}"}}]}}"#,
+            project.display(),
+            project.display()
+        );
+        std::fs::write(&file, text).unwrap();
+        let projects = Projects::load(&file).unwrap();
+        (project, projects)
+    }
 
-#[tool_router]
-impl <R: CommandRunner + Clone + 'static> RspecServer<R> {
-    pub fn new(runner: R) -> Self {
-        Self {
-            tool_router: Self::tool_router(),
-            runner,
-        }
+    #[tokio::test]
+    async fn test_run_rspec_in_project() {
+        let (project, projects) = projects_fixture();
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone()).with_projects(projects);
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            project: Some("billing".to_string()),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains(&format!("Working Directory: {}/src\n", project.display())), "{}", text);
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            project: Some("storefront".to_string()),
+            ..Default::default()
+        };
+        server.execute_rspec(args, None).await.unwrap();
+
+        let contexts = runner.recorded_contexts();
+        assert_eq!(contexts[0].command, Some(vec!["bin/rspec".to_string(), "--no-color".to_string()]));
+        assert_eq!(contexts[1].workdir, Some(project.clone()));
+        assert_eq!(contexts[1].command, None);
+        assert_eq!(runner.recorded_commands()[0][..2], ["bin/rspec", "--no-color"]);
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            project: Some("admin".to_string()),
+            ..Default::default()
+        };
+        let err = server.execute_rspec(args, None).await.unwrap_err();
+        assert_eq!(err.message, "Invalid parameters: Unknown project 'admin'; known projects: billing, storefront");
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            project: Some("billing".to_string()),
+            workdir: Some("..".to_string()),
+            ..Default::default()
+        };
+        assert!(server.execute_rspec(args, None).await.is_err());
+        assert_eq!(runner.calls(), 2);
     }
 
-    #[tool(
-        description = "Run RSpec tests for a specific file with optional line number targeting. Accepts file paths relative to project root, ending in '_spec.rb' with optional array of line numbers"
-    )]
-    async fn run_rspec(
-        &self,
-        Parameters(args): Parameters<RspecServerArgs>,
-    ) -> Result<CallToolResult, McpError> {
-        // Parse the file path and validate format
-        let line_numbers = args.line_numbers.unwrap_or_default();
-        let parsed_file = match ParsedFilePath::from_args(&args.file, line_numbers) {
-            Ok(parsed) => parsed,
-            Err(e) => {
-                return Err(McpError::invalid_params(
-                    format!("Invalid parameters: {}", e),
-                    None,
-                ));
-            }
+    #[tokio::test]
+    async fn test_list_projects() {
+        assert!(!RspecServer::new(MockRunner::new()).tool_router.has_route("list_projects"));
+
+        let (project, projects) = projects_fixture();
+        let server = RspecServer::new(MockRunner::new()).with_projects(projects);
+        assert!(server.tool_router.has_route("list_projects"));
+
+        let result = server.list_projects().await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            format!(
+                "Projects: 2\n\nbilling\n  Root: {}/src\n  Command: bin/rspec --no-color\nstorefront\n  Root: {}\n  Command: server default",
+                project.display(),
+                project.display()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_summary_survives_truncation() {
+        let stdout = format!(
+            "{}\n\nFinished in 0.5 seconds (files took 1 second to load)\n500 examples, 0 failures\n",
+            ".".repeat(500)
+        );
+        let runner = MockRunner::with_result(0, stdout, "warning: deprecated\n".to_string());
+        let server = RspecServer::new(runner).with_max_output_bytes(Some(120));
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
         };
 
-        // Build the RSpec file argument from parsed components
-        let rspec_arg = parsed_file.as_arg();
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Summary: 500 examples, 0 failures (run_time: 0.5s, load_time: 1s)\n"));
+        assert!(text.contains(&format!(
+            "Output:\n{}\n... [481 bytes truncated] ...\n0.5 seconds (files took 1 second to load)\n",
+            ".".repeat(33)
+        )));
+        assert!(!text.contains("Finished in"));
+        assert!(text.ends_with("500 examples, 0 failures\n\n\nErrors:\nwarning: deprecated\n"));
+    }
 
-        match self.runner.run(&rspec_arg).await {
-            Ok(CommandResult { exit_code, stdout, stderr }) => {
-                let result_text = format!(
-                    "Test Results for: {}\nExit Code: {}\n\nOutput:\n{}\n\nErrors:\n{}",
-                    rspec_arg, exit_code, stdout, stderr
-                );
+    #[tokio::test]
+    async fn test_run_history() {
+        let stdout = "F.\n\nFinished in 0.1 seconds (files took 0.2 seconds to load)\n2 examples, 1 failure\n";
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner).with_run_history(RunHistory::new(5, None).unwrap());
+        assert!(server.tool_router.has_route("list_runs"));
+        assert!(server.tool_router.has_route("get_run"));
+        let err = server.get_run(Parameters(GetRunArgs::default())).await.unwrap_err();
+        assert_eq!(err.message, "No runs yet; call run_rspec first");
 
-                Ok(CallToolResult::success(vec![Content::text(result_text)]))
-            }
-            Err(e) => Err(McpError::internal_error(
-                format!("Command failed: {}", e),
-                None,
-            )),
-        }
+        let args = |file: &str| RspecServerArgs { file: file.into(), ..Default::default() };
+        let first = server.execute_rspec(args("spec/models/user_spec.rb"), None).await.unwrap();
+        server.execute_rspec(RspecServerArgs { output_style: Some(OutputStyle::Compact), ..args("spec/models/order_spec.rb") }, None).await.unwrap();
+
+        let result = server.list_runs(Parameters(ListRunsArgs::default())).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with("Runs: 2 (last 5 kept)\n\n#2 failed in "), "{}", text);
+        assert!(text.contains("s ago: spec/models/order_spec.rb\n  Summary: 2 examples, 1 failure (run_time: 0.1s, load_time: 0.2s)\n#1 failed in "));
+        assert!(text.ends_with("s ago: spec/models/user_spec.rb\n  Summary: 2 examples, 1 failure (run_time: 0.1s, load_time: 0.2s)"));
+
+        let result = server.get_run(Parameters(GetRunArgs { run_id: Some(1) })).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with("Run: 1\nCommand: rspec spec/models/user_spec.rb\nStatus: failed\nExit Code: 1\nDuration: "));
+        assert!(text.ends_with(&format!("\n{}", first.content[0].as_text().unwrap().text)), "{}", text);
+        let result = server.get_run(Parameters(GetRunArgs::default())).await.unwrap();
+        assert!(result.content[0].as_text().unwrap().text.ends_with("\n✗ 1 failed / 2"), "{}", result.content[0].as_text().unwrap().text);
+
+        let err = server.get_run(Parameters(GetRunArgs { run_id: Some(9) })).await.unwrap_err();
+        assert_eq!(err.message, "No run 9 in the history; it keeps the last 5 runs");
+        assert!(!RspecServer::new(MockRunner::new()).tool_router.has_route("list_runs"));
     }
-}
 
-#[tool_handler]
-impl <R: CommandRunner + Clone + 'static> ServerHandler for RspecServer<R> {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo {
-            protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities::builder()
-                .enable_tools()
-                .build(),
-            server_info: Implementation::from_build_env(),
-            instructions: Some(
-                "Test runner server using configurable command. Tool: run_rspec (run tests for a file)."
-                    .to_string(),
-            ),
-        }
+    #[tokio::test]
+    async fn test_get_run_report() {
+        let stdout = r#"{"version":"3.13.0","examples":[{"id":"./spec/models/user_spec.rb[1:1]","full_description":"User saves","status":"failed","file_path":"./spec/models/user_spec.rb","run_time":0.1,"exception":{"class":"RuntimeError","message":"boom","backtrace":[]}}],"summary":{"duration":0.2,"example_count":1,"failure_count":1,"pending_count":0,"errors_outside_of_examples_count":0},"summary_line":"1 example, 1 failure"}"#;
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner.clone()).with_junit_reports(true);
+        assert!(!server.tool_router.has_route("get_run_report"));
+        let server = RspecServer::new(runner.clone())
+            .with_run_history(RunHistory::new(5, None).unwrap())
+            .with_junit_reports(true);
+        assert!(server.tool_router.has_route("get_run_report"));
+        let err = server.get_run_report(Parameters(GetRunReportArgs::default())).await.unwrap_err();
+        assert_eq!(err.message, "No runs yet; call run_rspec first");
+
+        let args = RspecServerArgs { file: "spec/models/user_spec.rb".into(), ..Default::default() };
+        server.execute_rspec(args, None).await.unwrap();
+        let extra_args = &runner.recorded_contexts()[0].extra_args;
+        assert_eq!(extra_args[..4], ["-f", "progress", "-f", "json"]);
+
+        let result = server.get_run_report(Parameters(GetRunReportArgs { run_id: Some(1) })).await.unwrap();
+        let xml = &result.content[0].as_text().unwrap().text;
+        assert!(xml.contains("<testsuite name=\"rspec\" tests=\"1\" skipped=\"0\" failures=\"1\" errors=\"0\" time=\"0.200000\">"), "{}", xml);
+        assert!(xml.contains("<failure message=\"boom\" type=\"RuntimeError\">boom</failure>"));
     }
 
-    async fn initialize(
-        &self,
-        _request: InitializeRequestParam,
-        context: RequestContext<RoleServer>,
-    ) -> Result<InitializeResult, McpError> {
-        if let Some(http_request_part) = context.extensions.get::<axum::http::request::Parts>() {
-            let initialize_headers = &http_request_part.headers;
-            let initialize_uri = &http_request_part.uri;
-            tracing::info!(?initialize_headers, %initialize_uri, "initialize from http server");
-        }
-        Ok(self.get_info())
+    #[tokio::test]
+    async fn test_fix_failing_spec_prompt() {
+        let stdout = "F.\n\nFailures:\n\n  1) User saves\n     Failure/Error: expect(user.save).to be(true)\n\n       expected true\n            got false\n     # ./spec/models/user_spec.rb:4:in `block (2 levels)'\n\nFinished in 0.1 seconds\n2 examples, 1 failure\n\nFailed examples:\n\nrspec ./spec/models/user_spec.rb:3 # User saves\n";
+        let server = RspecServer::new(MockRunner::with_result(1, stdout.to_string(), String::new()))
+            .with_run_history(RunHistory::new(5, None).unwrap());
+        assert!(server.get_info().capabilities.prompts.is_some());
+        let request = |run_id: Option<&str>| GetPromptRequestParam {
+            name: FIX_FAILING_SPEC.to_string(),
+            arguments: run_id.map(|id| serde_json::json!({ "run_id": id }).as_object().unwrap().clone()),
+        };
+        assert_eq!(server.prompt(request(None)).unwrap_err().message, "No runs yet; call run_rspec first");
+
+        let args = RspecServerArgs { file: "spec/models/user_spec.rb".into(), ..Default::default() };
+        server.execute_rspec(args, None).await.unwrap();
+        let prompt = server.prompt(request(Some("1"))).unwrap();
+        let PromptMessageContent::Text { text } = &prompt.messages[0].content else { panic!("not text") };
+        assert!(text.starts_with("Fix the failing specs from run 1.\n\nRun: `rspec spec/models/user_spec.rb`\nStatus: failed\nSummary: 2 examples, 1 failure (run_time: 0.1s)\n"), "{}", text);
+        assert!(text.contains("\n    rspec spec/models/user_spec.rb:3\n\n## 1. User saves\n\nLocation: spec/models/user_spec.rb:4\n"), "{}", text);
+        assert!(text.contains("expected true\ngot false"), "{}", text);
+
+        assert!(server.prompt(request(Some("x"))).unwrap_err().message.starts_with("Invalid parameters: run_id"));
+        let unknown = GetPromptRequestParam { name: "other".to_string(), arguments: None };
+        assert_eq!(server.prompt(unknown).unwrap_err().message, "Unknown prompt other");
+        let server = RspecServer::new(MockRunner::new()).with_run_history(RunHistory::new(0, None).unwrap());
+        assert!(server.get_info().capabilities.prompts.is_none());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::mock_runner::MockRunner;
+    #[tokio::test]
+    async fn test_get_run_output_after_truncation() {
+        let stdout = format!("{}\n\n1 example, 0 failures\n", ".".repeat(300));
+        let runner = MockRunner::with_result(0, stdout.clone(), "warning: deprecated\n".to_string());
+        let server = RspecServer::new(runner).with_max_output_bytes(Some(100));
+        assert!(server.tool_router.has_route("get_run_output"));
+        let args = || RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args(), None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains(
+            "Full Output: run 1 (324 bytes of stdout, 20 of stderr; the output below is truncated, read it all with get_run_output)\n"
+        ), "{}", text);
+
+        let output_args = |offset, limit| GetRunOutputArgs { run_id: 1, offset, limit, ..Default::default() };
+        let result = server.get_run_output(Parameters(output_args(None, None))).await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            format!("Run: 1 (stdout)\nBytes: 0-100 of 324\nNext Offset: 100\n\n{}", ".".repeat(100))
+        );
+        let result = server.get_run_output(Parameters(output_args(Some(300), Some(1000)))).await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            format!("Run: 1 (stdout)\nBytes: 300-324 of 324\n\n{}", &stdout[300..])
+        );
+        let args_stderr = GetRunOutputArgs { run_id: 1, stream: Some(OutputStream::Stderr), ..Default::default() };
+        let result = server.get_run_output(Parameters(args_stderr)).await.unwrap();
+        assert!(result.content[0].as_text().unwrap().text.ends_with("\nwarning: deprecated\n"));
+
+        let err = server.get_run_output(Parameters(GetRunOutputArgs { run_id: 7, ..Default::default() })).await.unwrap_err();
+        assert!(err.message.starts_with("No output kept for run 7"), "{}", err.message);
+
+        // Output that fits isn't kept
+        let server = RspecServer::new(MockRunner::new()).with_max_output_bytes(Some(100));
+        let result = server.execute_rspec(args(), None).await.unwrap();
+        assert!(!result.content[0].as_text().unwrap().text.contains("Full Output:"));
+    }
 
     #[tokio::test]
-    async fn test_run_rspec_tool() {
-        let runner = MockRunner::new();
-        let router = RspecServer::new(runner).tool_router;
+    async fn test_run_rspec_normalizes_newlines() {
+        let stdout = "F\r\n\r\nFailures:\r\n\r\n  1) User fails\r\n     Failure/Error: boom\r\n\r\nFinished in 0.1 seconds (files took 0.2 seconds to load)\r\n1 example, 1 failure\r\n";
+        let runner = MockRunner::with_result(1, stdout.to_string(), "warning\r\n".to_string());
+        let args = || RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
 
-        let tools = router.list_all();
-        assert_eq!(tools.len(), 1);
+        let server = RspecServer::new(runner.clone());
+        let result = server.execute_rspec(args(), None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(!text.contains('\r'));
+        assert!(text.contains("Summary: 1 example, 1 failure (run_time: 0.1s, load_time: 0.2s)\n"));
+        assert!(text.contains("Failures (1 of 1 shown):\n  1) User fails\n     Failure/Error: boom"));
 
-        let tool_names: Vec<&str> = tools.iter().map(|t| t.name.as_ref()).collect();
-        assert!(tool_names.contains(&"run_rspec"));
+        let server = RspecServer::new(runner).with_newline_normalization(false);
+        let result = server.execute_rspec(args(), None).await.unwrap();
+        assert!(result.content[0].as_text().unwrap().text.contains("warning\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_rerun_command() {
+        let stdout = "\
+FF.F
+
+Failures:
+
+  1) User saves
+     Failure/Error: boom
+     # ./spec/models/user_spec.rb:12:in `block (2 levels)'
+
+Finished in 0.1 seconds (files took 0.2 seconds to load)
+4 examples, 3 failures
+
+Failed examples:
+
+rspec ./spec/models/user_spec.rb:12 # User saves
+rspec ./spec/models/user_spec.rb:40 # User validates
+rspec ./spec/models/order_spec.rb:5 # Order totals
+";
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains(
+            "\n\nRerun Command:\nrspec spec/models/user_spec.rb:12 spec/models/user_spec.rb:40 spec/models/order_spec.rb:5"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_reports_run_options() {
+        let stdout = "Run options: include {:focus=>true}\n\nRandomized with seed 99\n.\n\nFinished in 0.1 seconds\n1 example, 0 failures\n";
+        let runner = MockRunner::with_result(0, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Run Options: include {:focus=>true}; seed 99\n"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_reports_summary() {
+        let stdout = "..\n\nFinished in 1 minute 2 seconds (files took 3.5 seconds to load)\n2 examples, 0 failures\n";
+        let runner = MockRunner::with_result(0, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with(
+            "Test Results for: spec/models/user_spec.rb\nExit Code: 0\nStatus: Passed\nOutcome: passed\nAttempts: 1 (passed)\nSummary: 2 examples, 0 failures (run_time: 62s, load_time: 3.5s)\n\nOutput:\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_reports_peak_memory() {
+        let stdout = "..\n\nFinished in 0.1 seconds\n2 examples, 0 failures\n";
+        let args = || RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let runner = MockRunner::with_result(0, stdout.to_string(), String::new()).with_peak_memory(312.25);
+        let result = RspecServer::new(runner).execute_rspec(args(), None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("(run_time: 0.1s)\nPeak Memory: 312.2 MB\n\nOutput:\n"));
+
+        let runner = MockRunner::with_result(0, stdout.to_string(), String::new());
+        let result = RspecServer::new(runner).execute_rspec(args(), None).await.unwrap();
+        assert!(!result.content[0].as_text().unwrap().text.contains("Peak Memory"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_reports_slowest_files() {
+        let stdout = r#"{"version":"3.12.2","examples":[
+            {"file_path":"./spec/models/user_spec.rb","run_time":0.5},
+            {"file_path":"./spec/models/order_spec.rb","run_time":1.5}
+        ]}"#;
+        let runner = MockRunner::with_result(0, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner).with_slowest_files(1);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.ends_with("Slowest Files:\n  1.5000 seconds spec/models/order_spec.rb (1 example)"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_load_diagnostics() {
+        let stderr = "MCP_RSPEC_LOAD_PATH_BEGIN\n/app/lib\nMCP_RSPEC_LOAD_PATH_END\n";
+        let stdout = "An error occurred while loading ./spec/models/user_spec.rb.\n\nLoadError:\n  cannot load such file -- spec_helper\n";
+        let runner = MockRunner::with_result(1, stdout.to_string(), stderr.to_string());
+        let server = RspecServer::new(runner.clone()).with_load_diagnostics(true);
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Status: LoadError in ./spec/models/user_spec.rb: LoadError: cannot load such file -- spec_helper"));
+        assert!(text.contains("Load Diagnostics:\n$LOAD_PATH:\n  /app/lib\n"));
+
+        let commands = runner.recorded_commands();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[1][1..3], ["--dry-run".to_string(), "--require".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_load_diagnostics_disabled() {
+        let stdout = "An error occurred while loading ./spec/models/user_spec.rb.\n\nLoadError:\n  cannot load such file -- spec_helper\n";
+        let runner = MockRunner::with_result(1, stdout.to_string(), String::new());
+        let server = RspecServer::new(runner.clone());
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args, None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(!text.contains("Load Diagnostics"));
+        assert_eq!(runner.calls(), 1);
+    }
+
+    #[test]
+    fn test_authorize_with_runs_scope() {
+        let policy = AuthPolicy::new(Some("secret".to_string()), AuthScope::Runs);
+        let server = RspecServer::new(MockRunner::new()).with_auth_policy(policy);
+
+        let mut extensions = Extensions::new();
+        assert!(server.authorize("list_spec_files", &extensions).is_ok());
+
+        let err = server.authorize("run_rspec", &extensions).unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+
+        let (mut parts, _) = axum::http::Request::new(()).into_parts();
+        parts
+            .headers
+            .insert("authorization", "Bearer secret".parse().unwrap());
+        extensions.insert(parts);
+        assert!(server.authorize("run_rspec", &extensions).is_ok());
+    }
+
+    #[test]
+    fn test_authorize_admin_tools_with_runs_scope() {
+        let policy = AuthPolicy::new(Some("secret".to_string()), AuthScope::Runs);
+        let server = RspecServer::new(MockRunner::new())
+            .with_admin_tools(true)
+            .with_auth_policy(policy);
+
+        let extensions = Extensions::new();
+        for tool in ["reset_state", "process_stats"] {
+            let err = server.authorize(tool, &extensions).unwrap_err();
+            assert_eq!(err.message, format!("Unauthorized: tool '{}' requires a bearer token", tool));
+        }
+    }
+
+    #[test]
+    fn test_runs_scope_protects_tools_that_change_state() {
+        // Checked by hand; every other tool runs specs or changes state
+        const OPEN_TOOLS: &[&str] = &[
+            "check_access",
+            "find_spec_for",
+            "get_run",
+            "get_run_output",
+            "get_run_report",
+            "last_failures",
+            "list_projects",
+            "list_runs",
+            "list_spec_files",
+        ];
+        let policy = AuthPolicy::new(Some("secret".to_string()), AuthScope::Runs);
+        let mut server = RspecServer::new(MockRunner::new()).with_auth_policy(policy);
+        server.tool_router = RspecServer::tool_router();
+
+        let extensions = Extensions::new();
+        for tool in server.tool_router.list_all() {
+            let hint = tool.annotations.as_ref().and_then(|annotations| annotations.read_only_hint);
+            assert!(hint.is_some(), "{} doesn't declare read_only_hint", tool.name);
+            let open = server.authorize(&tool.name, &extensions).is_ok();
+            assert_eq!(open, OPEN_TOOLS.contains(&tool.name.as_ref()), "{}", tool.name);
+        }
     }
 
     #[test]
@@ -135,10 +5545,41 @@ mod tests {
         "#;
 
         let args: RspecServerArgs = serde_json::from_str(json).unwrap();
-        assert_eq!(args.file, "spec/models/user_spec.rb");
+        assert_eq!(args.file.as_slice(), ["spec/models/user_spec.rb"]);
         assert_eq!(args.line_numbers, None);
     }
 
+    #[test]
+    fn test_test_runner_args_with_several_files() {
+        let json = r#"{"file": ["spec/models", "spec/requests/api_spec.rb"]}"#;
+
+        let args: RspecServerArgs = serde_json::from_str(json).unwrap();
+        assert_eq!(args.file.as_slice(), ["spec/models", "spec/requests/api_spec.rb"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_several_files() {
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone());
+        let args = |line_numbers| RspecServerArgs {
+            file: SpecPaths::Many(vec!["spec/models/user_spec.rb".to_string(), "spec/requests/api_spec.rb".to_string()]),
+            line_numbers,
+            ..Default::default()
+        };
+
+        let result = server.execute_rspec(args(None), None).await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with("Test Results for: spec/models/user_spec.rb spec/requests/api_spec.rb\n"));
+        assert_eq!(
+            runner.recorded_commands(),
+            vec![vec!["rspec", "spec/models/user_spec.rb", "spec/requests/api_spec.rb"]]
+        );
+
+        let err = server.execute_rspec(args(Some(vec![12])), None).await.unwrap_err();
+        assert_eq!(err.message, "Invalid parameters: Line numbers can only be used with a single file");
+        assert_eq!(runner.calls(), 1);
+    }
+
     #[test]
     fn test_test_runner_args_with_line_numbers() {
         let json = r#"
@@ -149,7 +5590,223 @@ mod tests {
         "#;
 
         let args: RspecServerArgs = serde_json::from_str(json).unwrap();
-        assert_eq!(args.file, "spec/models/user_spec.rb");
+        assert_eq!(args.file.as_slice(), ["spec/models/user_spec.rb"]);
         assert_eq!(args.line_numbers, Some(vec![37, 87]));
     }
+
+    #[test]
+    fn test_test_runner_args_with_example() {
+        let json = r#"
+        {
+            "file": "spec/models/user_spec.rb",
+            "line_numbers": [37],
+            "example": "rejects blank names"
+        }
+        "#;
+
+        let args: RspecServerArgs = serde_json::from_str(json).unwrap();
+        assert_eq!(args.line_numbers, Some(vec![37]));
+        assert_eq!(args.example.as_deref(), Some("rejects blank names"));
+
+        let args: RspecServerArgs = serde_json::from_str(r#"{"file": "spec/models/user_spec.rb"}"#).unwrap();
+        assert_eq!(args.example, None);
+    }
+
+    #[test]
+    fn test_test_runner_args_with_order_and_seed() {
+        let json = r#"{"file": "spec/models/user_spec.rb", "order": "random", "seed": 4242}"#;
+        let args: RspecServerArgs = serde_json::from_str(json).unwrap();
+        assert_eq!(args.order, Some(ExampleOrder::Random));
+        assert_eq!(args.seed, Some(4242));
+
+        let args: RspecServerArgs = serde_json::from_str(r#"{"file": "spec/a_spec.rb", "order": "defined"}"#).unwrap();
+        assert_eq!(args.order, Some(ExampleOrder::Defined));
+        assert_eq!(args.seed, None);
+
+        assert!(serde_json::from_str::<RspecServerArgs>(r#"{"file": "spec/a_spec.rb", "order": "rand"}"#).is_err());
+        assert!(serde_json::from_str::<RspecServerArgs>(r#"{"file": "spec/a_spec.rb", "seed": -1}"#).is_err());
+    }
+
+    #[test]
+    fn test_tag_args() {
+        let tags = ["fast", "~js", "type:model", "~speed:slow"].map(str::to_string);
+        assert_eq!(
+            tag_args(&tags).unwrap(),
+            ["--tag", "fast", "--tag", "~js", "--tag", "type:model", "--tag", "~speed:slow"]
+        );
+        assert!(tag_args(&[]).unwrap().is_empty());
+        for tag in ["", "~", ":fast", "fast tag", "--fail-fast", "js;rm"] {
+            assert!(tag_args(&[tag.to_string()]).is_err(), "{}", tag);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_with_tags() {
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone());
+        let args = |tags: &[&str]| RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+            ..Default::default()
+        };
+
+        let err = server.execute_rspec(args(&["fast", "bad tag"]), None).await.unwrap_err();
+        assert_eq!(
+            err.message,
+            "Invalid parameters: Invalid tag 'bad tag'; use a name with an optional ':value', e.g. 'fast', 'type:model' or '~js'"
+        );
+
+        server.execute_rspec(args(&["fast", "~js"]), None).await.unwrap();
+        assert_eq!(
+            runner.recorded_commands()[0],
+            ["rspec", "--tag", "fast", "--tag", "~js", "spec/models/user_spec.rb"]
+        );
+    }
+
+    #[test]
+    fn test_order_args() {
+        assert!(order_args(None, None).unwrap().is_empty());
+        assert_eq!(order_args(None, Some(4242)).unwrap(), ["--order", "random:4242"]);
+        assert_eq!(order_args(Some(ExampleOrder::Random), Some(7)).unwrap(), ["--order", "random:7"]);
+        assert_eq!(order_args(Some(ExampleOrder::Random), None).unwrap(), ["--order", "random"]);
+        assert_eq!(order_args(Some(ExampleOrder::Defined), None).unwrap(), ["--order", "defined"]);
+        assert_eq!(
+            order_args(Some(ExampleOrder::Defined), Some(7)).unwrap_err(),
+            "seed can only be used with order 'random'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_not_started() {
+        let runner = MockRunner::with_result(10, String::new(), "Could not locate Gemfile or .bundle/ directory\n".to_string());
+        let server = RspecServer::new(runner.clone()).with_baseline(None);
+
+        let args = RspecServerArgs { file: "spec/models/user_spec.rb".into(), retries: Some(2), ..Default::default() };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        assert_eq!(result.is_error, Some(true));
+        assert!(result.content[0].as_text().unwrap().text.starts_with(
+            "Test Results for: spec/models/user_spec.rb\nExit Code: 10\nStatus: NotStarted (exit code 10): Could not locate Gemfile or .bundle/ directory\nOutcome: The RSpec command failed before any specs ran (exit code 10); this is a setup problem, not a test failure\nHint: `rspec` exited before RSpec ran; check that it and rspec are installed and on PATH, and that the project's gems are installed (`bundle install`)\nAttempts: 1 (not_started)\n"
+        ));
+        assert_eq!(runner.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_cancelled() {
+        let runner = MockRunner::new().with_delay("spec/models/user_spec.rb", Duration::from_secs(30));
+        let server = RspecServer::new(runner.clone());
+        let cancel = CancellationToken::new();
+        let args = RspecServerArgs { file: "spec/models/user_spec.rb".into(), retries: Some(2), ..Default::default() };
+
+        let run = server.execute_run(args, RunListeners::default(), RunKind::Plain, Some(cancel.clone()));
+        let (result, _) = tokio::join!(run, async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            cancel.cancel();
+        });
+        let result = result.unwrap();
+        assert_eq!(result.is_error, Some(true));
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Outcome: cancelled by the client before finishing; results are incomplete\nAttempts: 1 (terminated)\nAborted: Cancelled\n"));
+        assert_eq!(runner.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_with_seed() {
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone());
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            seed: Some(4242),
+            ..Default::default()
+        };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        assert!(result.content[0].as_text().unwrap().text.contains("\nSeed: 4242 (requested)\n"));
+        assert_eq!(result.structured_content.unwrap()["seed"], 4242);
+        assert_eq!(
+            runner.recorded_commands()[0],
+            ["rspec", "--order", "random:4242", "spec/models/user_spec.rb"]
+        );
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            order: Some(ExampleOrder::Defined),
+            seed: Some(4242),
+            ..Default::default()
+        };
+        let err = server.execute_rspec(args, None).await.unwrap_err();
+        assert_eq!(err.message, "Invalid parameters: seed can only be used with order 'random'");
+        assert_eq!(runner.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_reports_random_seed() {
+        let stdout = "Randomized with seed 31337\n.F\n\nFinished in 0.1 seconds\n2 examples, 1 failure\n\nRandomized with seed 31337\n";
+        let server = RspecServer::new(MockRunner::with_result(1, stdout.to_string(), String::new()));
+
+        let args = RspecServerArgs { file: "spec/models/user_spec.rb".into(), ..Default::default() };
+        let result = server.execute_rspec(args, None).await.unwrap();
+        assert!(result.content[0].as_text().unwrap().text.contains("\nSeed: 31337 (random order; pass seed: 31337 to reproduce it)\n"));
+        let structured = result.structured_content.unwrap();
+        assert_eq!(structured["seed"], 31337);
+        assert_eq!(structured["status"], "failed");
+        assert_eq!(structured["summary"]["failure_count"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_example_filter() {
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone());
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            line_numbers: Some(vec![37]),
+            example: Some("rejects blank names".to_string()),
+            ..Default::default()
+        };
+        server.execute_rspec(args, None).await.unwrap();
+        assert_eq!(
+            runner.recorded_commands()[0],
+            ["rspec", "-e", "rejects blank names", "spec/models/user_spec.rb:37"]
+        );
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            example: Some("  ".to_string()),
+            ..Default::default()
+        };
+        let err = server.execute_rspec(args, None).await.unwrap_err();
+        assert_eq!(err.message, "Invalid parameters: example must be non-empty text without null bytes");
+        assert_eq!(runner.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_rspec_example_ids() {
+        let runner = MockRunner::new();
+        let server = RspecServer::new(runner.clone());
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            example_ids: Some(vec!["1:2:1".to_string(), "./spec/models/user_spec.rb[1:3]".to_string()]),
+            ..Default::default()
+        };
+        server.execute_rspec(args, None).await.unwrap();
+        assert_eq!(runner.recorded_commands()[0], ["rspec", "spec/models/user_spec.rb[1:2:1,1:3]"]);
+
+        let args = RspecServerArgs {
+            file: SpecPaths::Many(vec!["spec/models/user_spec.rb".to_string(), "spec/models/order_spec.rb".to_string()]),
+            example_ids: Some(vec!["1:1".to_string()]),
+            ..Default::default()
+        };
+        let err = server.execute_rspec(args, None).await.unwrap_err();
+        assert_eq!(err.message, "Invalid parameters: Example ids can only be used with a single file");
+
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            example_ids: Some(vec!["first".to_string()]),
+            ..Default::default()
+        };
+        let err = server.execute_rspec(args, None).await.unwrap_err();
+        assert!(err.message.starts_with("Invalid parameters: Invalid example id 'first'"), "{}", err.message);
+        assert_eq!(runner.calls(), 1);
+    }
 }
@@ -0,0 +1,181 @@
+use std::fmt;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::command_runner::CommandRunner;
+
+/// Flags (or argument forms) that only exist from a given rspec-core version.
+const CAPABILITIES: &[(&str, RspecVersion)] = &[
+    ("file:LINE:LINE", RspecVersion::new(3, 0, 0)),
+    ("file[ID]", RspecVersion::new(3, 3, 0)),
+    ("--only-failures", RspecVersion::new(3, 3, 0)),
+    ("--next-failure", RspecVersion::new(3, 3, 0)),
+    ("--bisect", RspecVersion::new(3, 3, 0)),
+    ("--force-color", RspecVersion::new(3, 9, 0)),
+    ("--example-matches", RspecVersion::new(3, 10, 0)),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RspecVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl RspecVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses the output of `rspec --version`, which is either a bare version
+    /// (`3.4.4`) or a per-gem listing that includes `- rspec-core 3.12.2`.
+    pub fn parse(output: &str) -> Option<Self> {
+        let core_version = output.lines().find_map(|line| {
+            line.trim()
+                .trim_start_matches("- ")
+                .strip_prefix("rspec-core ")
+                .and_then(|v| Self::parse_number(v.trim()))
+        });
+
+        core_version.or_else(|| {
+            output
+                .split_whitespace()
+                .find_map(Self::parse_number)
+        })
+    }
+
+    fn parse_number(value: &str) -> Option<Self> {
+        let mut parts = value.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        let patch = match parts.next() {
+            Some(patch) => patch.parse().ok()?,
+            None => 0,
+        };
+
+        Some(Self::new(major, minor, patch))
+    }
+
+    /// Checks that `flag` is supported, returning a descriptive error when the
+    /// capability map says it needs a newer RSpec.
+    pub fn require(&self, flag: &str) -> Result<(), String> {
+        match CAPABILITIES.iter().find(|(name, _)| *name == flag) {
+            Some((_, since)) if self < since => Err(format!(
+                "{} requires RSpec {}.{} or newer, but the configured command reports RSpec {}",
+                flag, since.major, since.minor, self
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for RspecVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Detected RSpec version shared by every session. Detection runs lazily and
+/// the result (including "unknown") is kept until [`VersionCache::clear`].
+#[derive(Debug, Clone, Default)]
+pub struct VersionCache {
+    inner: Arc<Mutex<Option<Option<RspecVersion>>>>,
+}
+
+impl VersionCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn get_or_detect<R: CommandRunner>(&self, runner: &R) -> Option<RspecVersion> {
+        let mut cached = self.inner.lock().await;
+        if let Some(version) = *cached {
+            return version;
+        }
+
+        let version = runner.detect_version().await;
+        *cached = Some(version);
+        version
+    }
+
+    /// Forgets the detected version, returning whether one was cached.
+    pub async fn clear(&self) -> bool {
+        self.inner.lock().await.take().is_some()
+    }
+
+    #[cfg(test)]
+    pub async fn is_empty(&self) -> bool {
+        self.inner.lock().await.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gem_listing() {
+        let output = "RSpec 3.12\n  - rspec-core 3.12.2\n  - rspec-expectations 3.12.3\n  - rspec-mocks 3.12.6\n";
+        assert_eq!(RspecVersion::parse(output), Some(RspecVersion::new(3, 12, 2)));
+    }
+
+    #[test]
+    fn test_parse_bare_version() {
+        assert_eq!(RspecVersion::parse("3.4.4\n"), Some(RspecVersion::new(3, 4, 4)));
+        assert_eq!(RspecVersion::parse("RSpec 3.9"), Some(RspecVersion::new(3, 9, 0)));
+    }
+
+    #[test]
+    fn test_parse_unrecognized_output() {
+        assert_eq!(RspecVersion::parse("bundler: command not found: rspec"), None);
+        assert_eq!(RspecVersion::parse(""), None);
+    }
+
+    #[test]
+    fn test_require_supported_flag() {
+        let version = RspecVersion::new(3, 12, 2);
+        assert!(version.require("--example-matches").is_ok());
+        assert!(version.require("file:LINE:LINE").is_ok());
+    }
+
+    #[test]
+    fn test_require_unsupported_flag() {
+        let version = RspecVersion::new(3, 8, 0);
+        assert_eq!(
+            version.require("--example-matches").unwrap_err(),
+            "--example-matches requires RSpec 3.10 or newer, but the configured command reports RSpec 3.8.0"
+        );
+        assert!(version.require("--only-failures").is_ok());
+    }
+
+    #[test]
+    fn test_require_gates_on_boundary_versions() {
+        assert!(RspecVersion::new(3, 2, 9).require("--bisect").is_err());
+        assert!(RspecVersion::new(3, 3, 0).require("--bisect").is_ok());
+        assert!(RspecVersion::new(2, 99, 0).require("file:LINE:LINE").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_version_cache() {
+        let cache = VersionCache::new();
+        assert!(cache.is_empty().await);
+
+        let runner = crate::mock_runner::MockRunner::new();
+        assert_eq!(cache.get_or_detect(&runner).await, None);
+        assert!(!cache.is_empty().await);
+
+        assert!(cache.clear().await);
+        assert!(cache.is_empty().await);
+        assert!(!cache.clear().await);
+    }
+
+    #[test]
+    fn test_require_unknown_flag_is_allowed() {
+        assert!(RspecVersion::new(2, 0, 0).require("--format").is_ok());
+    }
+}
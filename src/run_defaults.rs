@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use crate::json_report::HumanFormatter;
+use crate::rspec_server::{OutputStyle, RspecServerArgs};
+
+pub const DEFAULT_MAX_REPORTED_FAILURES: usize = 20;
+
+/// Server-side values for the optional `run_rspec` arguments, used when a
+/// request leaves them out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunDefaults {
+    pub max_reported_failures: Option<usize>,
+    pub output_style: Option<OutputStyle>,
+    pub group_examples: Option<bool>,
+    pub retries: Option<u32>,
+    pub verbose: Option<bool>,
+    pub human_formatter: Option<HumanFormatter>,
+    pub timeout_seconds: Option<u64>,
+}
+
+impl Default for RunDefaults {
+    fn default() -> Self {
+        Self {
+            max_reported_failures: Some(DEFAULT_MAX_REPORTED_FAILURES),
+            output_style: None,
+            group_examples: None,
+            retries: None,
+            verbose: None,
+            human_formatter: None,
+            timeout_seconds: None,
+        }
+    }
+}
+
+/// Fills every argument the request omitted from `defaults`; anything the
+/// request sets wins.
+pub fn apply_defaults(args: RspecServerArgs, defaults: &RunDefaults) -> RspecServerArgs {
+    RspecServerArgs {
+        max_reported_failures: args.max_reported_failures.or(defaults.max_reported_failures),
+        output_style: args.output_style.or(defaults.output_style),
+        group_examples: args.group_examples.or(defaults.group_examples),
+        retries: args.retries.or(defaults.retries),
+        verbose: args.verbose.or(defaults.verbose),
+        human_formatter: args.human_formatter.or(defaults.human_formatter),
+        timeout_seconds: args.timeout_seconds.or(defaults.timeout_seconds),
+        ..args
+    }
+}
+
+/// Defaults a client sets for its own session with `configure`. A request
+/// still wins over them, and they win over the server's `RunDefaults`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionDefaults {
+    pub project: Option<String>,
+    /// Only used by requests that don't name a `project` of their own.
+    pub workdir: Option<String>,
+    /// Merged with a request's `env`, whose values win.
+    pub env: BTreeMap<String, String>,
+    pub human_formatter: Option<HumanFormatter>,
+    pub output_style: Option<OutputStyle>,
+    pub timeout_seconds: Option<u64>,
+}
+
+impl SessionDefaults {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Fills the arguments `args` omitted from this session's defaults.
+    pub fn apply(&self, args: RspecServerArgs) -> RspecServerArgs {
+        let env = match (self.env.is_empty(), args.env) {
+            (true, env) => env,
+            (false, env) => {
+                let mut merged = self.env.clone();
+                merged.extend(env.unwrap_or_default());
+                Some(merged)
+            }
+        };
+        RspecServerArgs {
+            workdir: match args.project {
+                Some(_) => args.workdir,
+                None => args.workdir.or_else(|| self.workdir.clone()),
+            },
+            project: args.project.or_else(|| self.project.clone()),
+            env,
+            human_formatter: args.human_formatter.or(self.human_formatter),
+            output_style: args.output_style.or(self.output_style),
+            timeout_seconds: args.timeout_seconds.or(self.timeout_seconds),
+            ..args
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> RunDefaults {
+        RunDefaults {
+            max_reported_failures: Some(5),
+            output_style: Some(OutputStyle::Compact),
+            group_examples: Some(true),
+            retries: Some(2),
+            verbose: Some(true),
+            human_formatter: Some(HumanFormatter::Progress),
+            timeout_seconds: Some(600),
+        }
+    }
+
+    #[test]
+    fn test_defaults_fill_omitted_fields() {
+        let args = apply_defaults(RspecServerArgs::default(), &defaults());
+        assert_eq!(args.max_reported_failures, Some(5));
+        assert_eq!(args.output_style, Some(OutputStyle::Compact));
+        assert_eq!(args.group_examples, Some(true));
+        assert_eq!(args.retries, Some(2));
+        assert_eq!(args.verbose, Some(true));
+        assert_eq!(args.human_formatter, Some(HumanFormatter::Progress));
+        assert_eq!(args.timeout_seconds, Some(600));
+    }
+
+    #[test]
+    fn test_request_overrides_defaults() {
+        let args = RspecServerArgs {
+            file: "spec/models/user_spec.rb".into(),
+            max_reported_failures: Some(1),
+            output_style: Some(OutputStyle::Full),
+            group_examples: Some(false),
+            retries: Some(0),
+            verbose: Some(false),
+            human_formatter: Some(HumanFormatter::Documentation),
+            timeout_seconds: Some(30),
+            ..Default::default()
+        };
+        let args = apply_defaults(args, &defaults());
+        assert_eq!(args.file, "spec/models/user_spec.rb".into());
+        assert_eq!(args.max_reported_failures, Some(1));
+        assert_eq!(args.output_style, Some(OutputStyle::Full));
+        assert_eq!(args.group_examples, Some(false));
+        assert_eq!(args.retries, Some(0));
+        assert_eq!(args.verbose, Some(false));
+        assert_eq!(args.human_formatter, Some(HumanFormatter::Documentation));
+        assert_eq!(args.timeout_seconds, Some(30));
+    }
+
+    #[test]
+    fn test_session_defaults() {
+        let session = SessionDefaults {
+            project: Some("api".to_string()),
+            workdir: Some("engines/billing".to_string()),
+            env: BTreeMap::from([("COVERAGE".to_string(), "1".to_string()), ("RAILS_ENV".to_string(), "test".to_string())]),
+            timeout_seconds: Some(120),
+            ..Default::default()
+        };
+        assert!(SessionDefaults::default().is_empty() && !session.is_empty());
+
+        let args = session.apply(RspecServerArgs {
+            env: Some(BTreeMap::from([("COVERAGE".to_string(), "0".to_string())])),
+            timeout_seconds: Some(30),
+            ..Default::default()
+        });
+        assert_eq!(args.project.as_deref(), Some("api"));
+        assert_eq!(args.workdir.as_deref(), Some("engines/billing"));
+        assert_eq!(args.env.unwrap(), BTreeMap::from([("COVERAGE".to_string(), "0".to_string()), ("RAILS_ENV".to_string(), "test".to_string())]));
+        assert_eq!(args.timeout_seconds, Some(30));
+
+        let args = session.apply(RspecServerArgs { project: Some("web".to_string()), ..Default::default() });
+        assert_eq!(args.project.as_deref(), Some("web"));
+        assert_eq!(args.workdir, None);
+        assert_eq!(SessionDefaults::default().apply(RspecServerArgs::default()).env, None);
+    }
+
+    #[test]
+    fn test_no_defaults_leaves_args_unset() {
+        let args = apply_defaults(RspecServerArgs::default(), &RunDefaults::default());
+        assert_eq!(args.max_reported_failures, Some(DEFAULT_MAX_REPORTED_FAILURES));
+        assert_eq!(args.output_style, None);
+        assert_eq!(args.retries, None);
+    }
+}
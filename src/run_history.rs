@@ -0,0 +1,261 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::source_context::SourceSnippet;
+
+/// One finished run, as `list_runs` and `get_run` report it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub id: u64,
+    pub targets: String,
+    pub command: String,
+    pub status: String,
+    pub exit_code: i32,
+    pub duration_secs: f64,
+    /// Unix time the run finished at.
+    pub finished_at: u64,
+    pub summary: Option<String>,
+    /// The text the run's result returned.
+    pub result: String,
+    /// The run's report as JUnit XML, with `--junit-reports`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub junit: Option<String>,
+    /// The failures the run reported, for the `fix_failing_spec` prompt.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<RecordedFailure>,
+    /// The command that reruns just the failures.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rerun_command: Option<String>,
+}
+
+/// A failed example of a run, with the code around where it failed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFailure {
+    pub description: String,
+    pub location: Option<String>,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_context: Vec<SourceSnippet>,
+}
+
+impl RunRecord {
+    /// How long ago the run finished, e.g. `42s ago` or `3m ago`.
+    pub fn age(&self) -> String {
+        let secs = now().saturating_sub(self.finished_at);
+        match secs {
+            0..60 => format!("{}s ago", secs),
+            60..3600 => format!("{}m ago", secs / 60),
+            3600..86400 => format!("{}h ago", secs / 3600),
+            _ => format!("{}d ago", secs / 86400),
+        }
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[derive(Serialize, Deserialize)]
+struct HistoryFile {
+    runs: Vec<RunRecord>,
+}
+
+/// The last `capacity` runs of the server, across sessions, optionally
+/// saved to a JSON file so they outlive restarts. Also hands out run ids,
+/// which keep counting when nothing is kept.
+#[derive(Debug, Clone)]
+pub struct RunHistory {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    capacity: usize,
+    file: Option<PathBuf>,
+    next_id: AtomicU64,
+    runs: Mutex<VecDeque<RunRecord>>,
+}
+
+impl RunHistory {
+    /// Reads `file` when it exists, keeping its newest `capacity` runs.
+    pub fn new(capacity: usize, file: Option<PathBuf>) -> Result<Self, String> {
+        let mut runs = match &file {
+            Some(path) if path.exists() => load(path)?,
+            _ => VecDeque::new(),
+        };
+        while runs.len() > capacity {
+            runs.pop_front();
+        }
+        let next_id = runs.iter().map(|run| run.id).max().unwrap_or(0) + 1;
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                capacity,
+                file,
+                next_id: AtomicU64::new(next_id),
+                runs: Mutex::new(runs),
+            }),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.capacity > 0
+    }
+
+    pub fn next_id(&self) -> u64 {
+        self.inner.next_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity
+    }
+
+    pub fn record(&self, record: RunRecord) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut runs = self.inner.runs.lock().unwrap();
+        runs.push_back(record);
+        while runs.len() > self.inner.capacity {
+            runs.pop_front();
+        }
+        self.save(&runs);
+    }
+
+    /// Forgets every run, in `--run-history-file` too, returning whether
+    /// there were any. Ids keep counting so they stay unique.
+    pub fn clear(&self) -> bool {
+        let mut runs = self.inner.runs.lock().unwrap();
+        if runs.is_empty() {
+            return false;
+        }
+        runs.clear();
+        self.save(&runs);
+        true
+    }
+
+    fn save(&self, runs: &VecDeque<RunRecord>) {
+        if let Some(path) = &self.inner.file {
+            let file = HistoryFile { runs: runs.iter().cloned().collect() };
+            let saved = serde_json::to_string(&file)
+                .map_err(|e| e.to_string())
+                .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+            if let Err(e) = saved {
+                tracing::warn!(path = %path.display(), error = %e, "failed to save run history");
+            }
+        }
+    }
+
+    /// Newest first.
+    pub fn recent(&self, limit: usize) -> Vec<RunRecord> {
+        self.inner.runs.lock().unwrap().iter().rev().take(limit).cloned().collect()
+    }
+
+    /// The run with `id`, or the latest when `id` is `None`.
+    pub fn get(&self, id: Option<u64>) -> Option<RunRecord> {
+        let runs = self.inner.runs.lock().unwrap();
+        match id {
+            Some(id) => runs.iter().find(|run| run.id == id).cloned(),
+            None => runs.back().cloned(),
+        }
+    }
+}
+
+/// Keeps nothing, only counting ids.
+impl Default for RunHistory {
+    fn default() -> Self {
+        Self::new(0, None).expect("no file to read")
+    }
+}
+
+fn load(path: &Path) -> Result<VecDeque<RunRecord>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read run history {}: {}", path.display(), e))?;
+    let file: HistoryFile = serde_json::from_str(&text)
+        .map_err(|e| format!("Invalid run history {}: {}", path.display(), e))?;
+    Ok(file.runs.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(history: &RunHistory, targets: &str) -> RunRecord {
+        RunRecord {
+            id: history.next_id(),
+            targets: targets.to_string(),
+            command: format!("rspec {}", targets),
+            status: "failed".to_string(),
+            exit_code: 1,
+            duration_secs: 1.5,
+            finished_at: now(),
+            summary: Some("2 examples, 1 failure".to_string()),
+            result: "Test Results for: ...".to_string(),
+            junit: None,
+            failures: Vec::new(),
+            rerun_command: None,
+        }
+    }
+
+    #[test]
+    fn test_keeps_recent_runs() {
+        let history = RunHistory::new(2, None).unwrap();
+        for targets in ["spec/a_spec.rb", "spec/b_spec.rb", "spec/c_spec.rb"] {
+            history.record(record(&history, targets));
+        }
+
+        let recent: Vec<_> = history.recent(10).into_iter().map(|run| (run.id, run.targets)).collect();
+        assert_eq!(recent, [(3, "spec/c_spec.rb".to_string()), (2, "spec/b_spec.rb".to_string())]);
+        assert_eq!(history.get(None).unwrap().id, 3);
+        assert_eq!(history.get(Some(2)).unwrap().targets, "spec/b_spec.rb");
+        assert_eq!(history.get(Some(1)), None);
+        assert_eq!(history.recent(1).len(), 1);
+    }
+
+    #[test]
+    fn test_disabled_history_still_counts_ids() {
+        let history = RunHistory::default();
+        assert!(!history.is_enabled());
+        assert_eq!(history.next_id(), 1);
+        history.record(record(&history, "spec"));
+        assert_eq!(history.get(None), None);
+        assert_eq!(history.next_id(), 3);
+    }
+
+    #[test]
+    fn test_history_file_outlives_restarts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("history.json");
+
+        let history = RunHistory::new(5, Some(path.clone())).unwrap();
+        history.record(record(&history, "spec/a_spec.rb"));
+        history.record(record(&history, "spec/b_spec.rb"));
+
+        let reloaded = RunHistory::new(1, Some(path.clone())).unwrap();
+        assert_eq!(reloaded.recent(10), history.recent(1));
+        assert_eq!(reloaded.next_id(), 3);
+
+        assert!(history.clear());
+        assert!(!history.clear());
+        assert_eq!(RunHistory::new(5, Some(path.clone())).unwrap().get(None), None);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(RunHistory::new(5, Some(path.clone())).unwrap_err().starts_with("Invalid run history"));
+    }
+
+    #[test]
+    fn test_age() {
+        let mut run = record(&RunHistory::default(), "spec");
+        assert_eq!(run.age(), "0s ago");
+        run.finished_at -= 150;
+        assert_eq!(run.age(), "2m ago");
+        run.finished_at -= 2 * 86400;
+        assert_eq!(run.age(), "2d ago");
+    }
+}
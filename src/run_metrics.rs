@@ -0,0 +1,135 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Upper bounds, in seconds, of the run duration histogram's buckets.
+const DURATION_BUCKETS: &[f64] = &[1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0];
+
+/// Counts RSpec runs and how long they take, for `/metrics`. A run counts
+/// from the moment it has a `--max-in-flight` slot until its process exits.
+#[derive(Debug, Clone, Default)]
+pub struct RunMetrics {
+    inner: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    runs: AtomicU64,
+    failures: AtomicU64,
+    active: AtomicU64,
+    durations: Mutex<Histogram>,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Runs at most as long as each bucket's bound, not cumulative.
+    buckets: [u64; DURATION_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl RunMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn started(&self) -> ActiveRun {
+        self.inner.active.fetch_add(1, Ordering::SeqCst);
+        ActiveRun {
+            metrics: self.clone(),
+            started: Instant::now(),
+        }
+    }
+
+    fn observe(&self, seconds: f64, passed: bool) {
+        self.inner.runs.fetch_add(1, Ordering::SeqCst);
+        if !passed {
+            self.inner.failures.fetch_add(1, Ordering::SeqCst);
+        }
+        let mut durations = self.inner.durations.lock().unwrap();
+        if let Some(bucket) = DURATION_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            durations.buckets[bucket] += 1;
+        }
+        durations.sum += seconds;
+        durations.count += 1;
+    }
+
+    /// The counters in Prometheus' text format.
+    pub fn render_metrics(&self) -> String {
+        let durations = self.inner.durations.lock().unwrap();
+        let mut text = format!(
+            "# HELP mcp_rspec_runs_total RSpec runs finished.\n\
+             # TYPE mcp_rspec_runs_total counter\n\
+             mcp_rspec_runs_total {}\n\
+             # HELP mcp_rspec_run_failures_total RSpec runs that didn't pass: failed, killed, or couldn't start.\n\
+             # TYPE mcp_rspec_run_failures_total counter\n\
+             mcp_rspec_run_failures_total {}\n\
+             # HELP mcp_rspec_runs_active RSpec runs in progress.\n\
+             # TYPE mcp_rspec_runs_active gauge\n\
+             mcp_rspec_runs_active {}\n\
+             # HELP mcp_rspec_run_duration_seconds How long RSpec runs took.\n\
+             # TYPE mcp_rspec_run_duration_seconds histogram\n",
+            self.inner.runs.load(Ordering::SeqCst),
+            self.inner.failures.load(Ordering::SeqCst),
+            self.inner.active.load(Ordering::SeqCst)
+        );
+        let mut cumulative = 0;
+        for (bound, count) in DURATION_BUCKETS.iter().zip(durations.buckets) {
+            cumulative += count;
+            text.push_str(&format!("mcp_rspec_run_duration_seconds_bucket{{le=\"{}\"}} {}\n", bound, cumulative));
+        }
+        text.push_str(&format!(
+            "mcp_rspec_run_duration_seconds_bucket{{le=\"+Inf\"}} {}\n\
+             mcp_rspec_run_duration_seconds_sum {}\n\
+             mcp_rspec_run_duration_seconds_count {}\n",
+            durations.count, durations.sum, durations.count
+        ));
+        text
+    }
+}
+
+/// A run in progress, counted as active until dropped.
+#[derive(Debug)]
+pub struct ActiveRun {
+    metrics: RunMetrics,
+    started: Instant,
+}
+
+impl ActiveRun {
+    pub fn finished(self, passed: bool) {
+        self.metrics.observe(self.started.elapsed().as_secs_f64(), passed);
+    }
+}
+
+impl Drop for ActiveRun {
+    fn drop(&mut self) {
+        self.metrics.inner.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_runs() {
+        let metrics = RunMetrics::new();
+        let first = metrics.started();
+        let second = metrics.started();
+        assert!(metrics.render_metrics().contains("mcp_rspec_runs_active 2\n"));
+
+        first.finished(true);
+        second.finished(false);
+        metrics.observe(45.0, true);
+
+        let text = metrics.render_metrics();
+        assert!(text.contains("mcp_rspec_runs_total 3\n"));
+        assert!(text.contains("mcp_rspec_run_failures_total 1\n"));
+        assert!(text.contains("mcp_rspec_runs_active 0\n"));
+        assert!(text.contains("mcp_rspec_run_duration_seconds_bucket{le=\"1\"} 2\n"));
+        assert!(text.contains("mcp_rspec_run_duration_seconds_bucket{le=\"30\"} 2\n"));
+        assert!(text.contains("mcp_rspec_run_duration_seconds_bucket{le=\"60\"} 3\n"));
+        assert!(text.contains("mcp_rspec_run_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("mcp_rspec_run_duration_seconds_count 3\n"));
+    }
+}
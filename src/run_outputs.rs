@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Most truncated runs whose full output is kept; older ones are deleted.
+pub const MAX_KEPT_RUNS: usize = 20;
+
+/// Distinguishes the files of concurrent sessions.
+static SESSIONS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    #[default]
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    fn extension(&self) -> &'static str {
+        match self {
+            OutputStream::Stdout => "stdout",
+            OutputStream::Stderr => "stderr",
+        }
+    }
+}
+
+/// A slice of a kept output, by byte offsets into it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputPage {
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+    pub total: usize,
+}
+
+/// The full stdout and stderr of runs whose results were truncated by
+/// `--max-output-bytes`, in temp files so `get_run_output` can page through
+/// them. The files are removed when the last clone is dropped.
+#[derive(Debug, Clone)]
+pub struct RunOutputs {
+    store: Arc<Store>,
+}
+
+#[derive(Debug)]
+struct Store {
+    prefix: PathBuf,
+    kept: Mutex<VecDeque<u64>>,
+}
+
+impl Store {
+    fn path(&self, id: u64, stream: OutputStream) -> PathBuf {
+        let mut path = self.prefix.clone().into_os_string();
+        path.push(format!("-{}.{}", id, stream.extension()));
+        PathBuf::from(path)
+    }
+
+    fn remove(&self, id: u64) {
+        for stream in [OutputStream::Stdout, OutputStream::Stderr] {
+            let _ = std::fs::remove_file(self.path(id, stream));
+        }
+    }
+}
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        let kept = std::mem::take(self.kept.get_mut().unwrap());
+        for id in kept {
+            self.remove(id);
+        }
+    }
+}
+
+impl Default for RunOutputs {
+    fn default() -> Self {
+        let session = SESSIONS.fetch_add(1, Ordering::SeqCst);
+        let name = format!("mcp-rspec-output-{}-{}", std::process::id(), session);
+
+        Self {
+            store: Arc::new(Store {
+                prefix: std::env::temp_dir().join(name),
+                kept: Mutex::new(VecDeque::new()),
+            }),
+        }
+    }
+}
+
+impl RunOutputs {
+    /// Keeps the output of run `id`.
+    pub fn save(&self, id: u64, stdout: &str, stderr: &str) -> Result<(), String> {
+        for (stream, text) in [(OutputStream::Stdout, stdout), (OutputStream::Stderr, stderr)] {
+            if let Err(e) = std::fs::write(self.store.path(id, stream), text) {
+                self.store.remove(id);
+                return Err(format!("Failed to save the run's output: {}", e));
+            }
+        }
+
+        let mut kept = self.store.kept.lock().unwrap();
+        kept.push_back(id);
+        while kept.len() > MAX_KEPT_RUNS {
+            if let Some(oldest) = kept.pop_front() {
+                self.store.remove(oldest);
+            }
+        }
+        Ok(())
+    }
+
+    /// Up to `limit` bytes of a kept output from `offset`, both moved
+    /// forward to a UTF-8 character boundary.
+    pub fn read(&self, id: u64, stream: OutputStream, offset: usize, limit: usize) -> Result<OutputPage, String> {
+        if !self.store.kept.lock().unwrap().contains(&id) {
+            return Err(format!(
+                "No output kept for run {}; only the last {} truncated runs of this session are kept",
+                id, MAX_KEPT_RUNS
+            ));
+        }
+        let text = std::fs::read_to_string(self.store.path(id, stream))
+            .map_err(|e| format!("Cannot read the output of run {}: {}", id, e))?;
+
+        let boundary = |mut index: usize| {
+            index = index.min(text.len());
+            while !text.is_char_boundary(index) {
+                index += 1;
+            }
+            index
+        };
+        let start = boundary(offset);
+        let end = boundary(start.saturating_add(limit));
+
+        Ok(OutputPage {
+            text: text[start..end].to_string(),
+            start,
+            end,
+            total: text.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_read_pages() {
+        let outputs = RunOutputs::default();
+        let id = 4;
+        outputs.save(id, "..F..\nFailures:\n", "warning\n").unwrap();
+
+        let page = outputs.read(id, OutputStream::Stdout, 0, 6).unwrap();
+        assert_eq!(page, OutputPage { text: "..F..\n".to_string(), start: 0, end: 6, total: 16 });
+        let page = outputs.read(id, OutputStream::Stdout, 6, 100).unwrap();
+        assert_eq!((page.text.as_str(), page.end), ("Failures:\n", 16));
+        assert_eq!(outputs.read(id, OutputStream::Stderr, 0, 100).unwrap().text, "warning\n");
+        assert_eq!(outputs.read(id, OutputStream::Stdout, 100, 10).unwrap().text, "");
+    }
+
+    #[test]
+    fn test_read_keeps_characters_whole() {
+        let outputs = RunOutputs::default();
+        let id = 1;
+        outputs.save(id, "✓ ✗", "").unwrap();
+
+        assert_eq!(outputs.read(id, OutputStream::Stdout, 0, 2).unwrap().text, "✓");
+        assert_eq!(outputs.read(id, OutputStream::Stdout, 1, 4).unwrap().text, " ✗");
+    }
+
+    #[test]
+    fn test_keeps_only_recent_runs() {
+        let outputs = RunOutputs::default();
+        let first = 1;
+        outputs.save(first, "first", "").unwrap();
+        let first_path = outputs.store.path(first, OutputStream::Stdout);
+        for id in 0..MAX_KEPT_RUNS as u64 {
+            outputs.save(first + 1 + id, "later", "").unwrap();
+        }
+
+        assert!(!first_path.exists());
+        assert_eq!(
+            outputs.read(first, OutputStream::Stdout, 0, 10).unwrap_err(),
+            format!("No output kept for run {}; only the last 20 truncated runs of this session are kept", first)
+        );
+        assert!(outputs.read(first + 1, OutputStream::Stdout, 0, 10).is_ok());
+
+        let last_path = outputs.store.path(first + MAX_KEPT_RUNS as u64, OutputStream::Stdout);
+        drop(outputs);
+        assert!(!last_path.exists());
+    }
+}
@@ -0,0 +1,77 @@
+use std::str::FromStr;
+
+/// Characters a shell would interpret. The prefix is executed directly, not
+/// through a shell, so these would reach the sandbox tool literally.
+const SHELL_METACHARACTERS: &[char] = &[';', '|', '&', '`', '$', '<', '>', '(', ')', '\'', '"', '\\', '*', '?'];
+
+/// Command that every run is wrapped in, e.g. `firejail --net=none`. Its
+/// tokens are placed before the assembled RSpec argv.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SandboxPrefix {
+    tokens: Vec<String>,
+}
+
+impl SandboxPrefix {
+    pub fn wrap(&self, argv: Vec<String>) -> Vec<String> {
+        self.tokens.iter().cloned().chain(argv).collect()
+    }
+}
+
+impl FromStr for SandboxPrefix {
+    type Err = String;
+
+    fn from_str(prefix: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<String> = prefix.split_whitespace().map(str::to_string).collect();
+
+        if tokens.is_empty() {
+            return Err("Sandbox prefix cannot be empty".to_string());
+        }
+
+        if let Some(token) = tokens.iter().find(|t| t.contains(SHELL_METACHARACTERS)) {
+            return Err(format!(
+                "Sandbox prefix token '{}' contains shell metacharacters; the prefix is not run through a shell",
+                token
+            ));
+        }
+
+        Ok(Self { tokens })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_wrap_prepends_prefix() {
+        let prefix: SandboxPrefix = "firejail --net=none --private=.".parse().unwrap();
+        assert_eq!(
+            prefix.wrap(strings(&["bundle", "exec", "rspec", "spec/models/user_spec.rb"])),
+            strings(&["firejail", "--net=none", "--private=.", "bundle", "exec", "rspec", "spec/models/user_spec.rb"])
+        );
+    }
+
+    #[test]
+    fn test_default_is_empty() {
+        let argv = strings(&["rspec", "spec/models/user_spec.rb"]);
+        assert_eq!(SandboxPrefix::default().wrap(argv.clone()), argv);
+    }
+
+    #[test]
+    fn test_rejects_unsafe_prefixes() {
+        assert_eq!(
+            "   ".parse::<SandboxPrefix>().unwrap_err(),
+            "Sandbox prefix cannot be empty"
+        );
+        assert_eq!(
+            "nsjail --quiet; rm".parse::<SandboxPrefix>().unwrap_err(),
+            "Sandbox prefix token '--quiet;' contains shell metacharacters; the prefix is not run through a shell"
+        );
+        assert!("firejail --whitelist=\"$HOME\"".parse::<SandboxPrefix>().is_err());
+        assert!("firejail | tee".parse::<SandboxPrefix>().is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::Write;
+use std::path::Path;
+
+/// A Ruby script RSpec loads with `--require`, in a new directory only this
+/// user can enter. The script is created with `create_new`, so nothing
+/// planted at its path is followed or loaded instead. The directory and
+/// everything in it are removed when dropped.
+#[derive(Debug)]
+pub struct ScriptFile {
+    dir: tempfile::TempDir,
+    name: String,
+}
+
+impl ScriptFile {
+    pub fn create(name: &str, contents: &str) -> Result<Self, String> {
+        let mut builder = tempfile::Builder::new();
+        builder.prefix("mcp-rspec-");
+        #[cfg(unix)]
+        builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o700));
+        let dir = builder
+            .tempdir()
+            .map_err(|e| format!("Failed to create a directory for {}: {}", name, e))?;
+        let path = dir.path().join(name);
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(&path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+        Ok(Self { dir, name: name.to_string() })
+    }
+
+    /// The private directory holding the script, for files that go with it.
+    pub fn dir(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn require_args(&self) -> Vec<String> {
+        let path = self.dir.path().join(&self.name);
+        vec!["--require".to_string(), path.to_string_lossy().to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create() {
+        let script = ScriptFile::create("setup.rb", "puts 1\n").unwrap();
+        let args = script.require_args();
+        assert_eq!(args[0], "--require");
+        assert_eq!(std::fs::read_to_string(&args[1]).unwrap(), "puts 1\n");
+        let dir = script.dir().to_path_buf();
+        assert_eq!(Path::new(&args[1]).parent(), Some(dir.as_path()));
+        assert_ne!(ScriptFile::create("setup.rb", "").unwrap().dir(), dir);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(Path::new(&args[1])), 0o600);
+            assert_eq!(mode(&dir), 0o700);
+        }
+
+        drop(script);
+        assert!(!dir.exists());
+    }
+}
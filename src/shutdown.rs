@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use crate::process_tracker::ProcessTracker;
+
+/// How long runs get to exit after SIGTERM before they are killed.
+const TERMINATE_GRACE: Duration = Duration::from_secs(5);
+
+/// How long the transport keeps going once runs finish during shutdown, so
+/// their results reach the clients that asked for them.
+const RESPONSE_GRACE: Duration = Duration::from_secs(1);
+
+/// What happens to runs still in progress when the server is stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum ShutdownMode {
+    /// Let runs finish and return their results, for up to the grace period
+    #[default]
+    Wait,
+    /// Send runs SIGTERM at once, and SIGKILL after the grace period
+    Terminate,
+}
+
+/// Resolves on Ctrl+C or, on Unix, SIGTERM.
+pub async fn signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            signal = tokio::signal::ctrl_c() => signal,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
+/// Deals with the runs still in progress at shutdown, before the transport
+/// stops. Waiting runs that outlast `grace` are sent SIGTERM and then
+/// SIGKILL, like those stopped with `ShutdownMode::Terminate`; either way
+/// the results of the runs, partial or not, are returned to their clients.
+pub async fn stop_runs(tracker: &ProcessTracker, mode: ShutdownMode, grace: Duration) {
+    let running = tracker.running();
+    if running == 0 {
+        return;
+    }
+
+    if mode == ShutdownMode::Wait {
+        tracing::info!(running, "Waiting up to {} seconds for runs to finish", grace.as_secs());
+        if tracker.wait_for_exit(grace).await {
+            tokio::time::sleep(RESPONSE_GRACE).await;
+            return;
+        }
+    }
+    let grace = match mode {
+        ShutdownMode::Wait => TERMINATE_GRACE,
+        ShutdownMode::Terminate => grace,
+    };
+    tracing::warn!(running = tracker.running(), "Sending SIGTERM to runs still in progress");
+    #[cfg(unix)]
+    tracker.signal_all(libc::SIGTERM);
+    if !tracker.wait_for_exit(grace).await {
+        tracing::warn!(running = tracker.running(), "Killing runs that ignored SIGTERM");
+        #[cfg(unix)]
+        tracker.signal_all(libc::SIGKILL);
+    }
+    tokio::time::sleep(RESPONSE_GRACE).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_runner::{CommandRunner, RunContext};
+    use crate::rspec_runner::RspecRunner;
+
+    #[tokio::test]
+    async fn test_stop_runs_waits_for_run() {
+        let tracker = ProcessTracker::new();
+        let runner = RspecRunner::new("sh -c".to_string()).with_process_tracker(tracker.clone());
+        let run = tokio::spawn(async move { runner.run("sleep 0.2; echo done", &RunContext::default()).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        stop_runs(&tracker, ShutdownMode::Wait, Duration::from_secs(5)).await;
+
+        let result = run.await.unwrap().unwrap();
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(result.stdout.trim(), "done");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stop_runs_terminates_run() {
+        let tracker = ProcessTracker::new();
+        let runner = RspecRunner::new("sh -c".to_string()).with_process_tracker(tracker.clone());
+        let run = tokio::spawn(async move { runner.run("echo started; exec sleep 5", &RunContext::default()).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        stop_runs(&tracker, ShutdownMode::Terminate, Duration::from_secs(5)).await;
+
+        let result = run.await.unwrap().unwrap();
+        assert_eq!(result.signal, Some(libc::SIGTERM));
+        assert_eq!(result.stdout.trim(), "started");
+        assert_eq!(tracker.running(), 0);
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::rspec_result::ExampleReport;
+
+/// Lines shown on each side of the line a snippet is about.
+const CONTEXT_LINES: usize = 3;
+
+/// Failed examples given source context; the rest only get their backtrace.
+const MAX_EXAMPLES_WITH_CONTEXT: usize = 20;
+
+/// Files larger than this aren't read for context.
+const MAX_SOURCE_BYTES: u64 = 1024 * 1024;
+
+/// A few numbered lines of a project file around `line`, which is marked
+/// with `>`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct SourceSnippet {
+    /// Relative to the project root.
+    pub file: String,
+    pub line: u32,
+    pub code: String,
+}
+
+/// Reads project files for snippets, each at most once.
+pub struct SourceContext {
+    root: Option<PathBuf>,
+    files: HashMap<String, Option<Vec<String>>>,
+}
+
+impl SourceContext {
+    /// Snippets come only from files under `root`.
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.canonicalize().ok(),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Adds snippets to the first failed examples: the failing line of the
+    /// spec, and the deepest backtrace frame in the project outside spec
+    /// files, where the error was raised.
+    pub fn annotate(&mut self, examples: &mut [ExampleReport]) {
+        for example in examples
+            .iter_mut()
+            .filter(|example| example.status == "failed")
+            .take(MAX_EXAMPLES_WITH_CONTEXT)
+        {
+            example.source_context = self.snippets(example);
+        }
+    }
+
+    /// A snippet around a `path:line` location, such as a failure's, when
+    /// the file is in the project.
+    pub fn around(&mut self, location: &str) -> Option<SourceSnippet> {
+        let (path, line) = frame_location(location)?;
+        let path = self.project_path(path)?;
+        self.snippet(&path, line)
+    }
+
+    fn snippets(&mut self, example: &ExampleReport) -> Vec<SourceSnippet> {
+        let spec_file = example.file_path.strip_prefix("./").unwrap_or(&example.file_path).to_string();
+        let frames: Vec<(String, u32)> = example
+            .exception
+            .iter()
+            .flat_map(|exception| &exception.backtrace)
+            .filter_map(|frame| frame_location(frame))
+            .filter_map(|(path, line)| Some((self.project_path(path)?, line)))
+            .collect();
+
+        let spec_line = frames
+            .iter()
+            .find(|(path, _)| *path == spec_file)
+            .map(|(_, line)| *line)
+            .or(example.line_number);
+        let raised_at = frames.iter().find(|(path, _)| !path.ends_with("_spec.rb"));
+
+        let mut snippets = Vec::new();
+        if let Some(line) = spec_line {
+            snippets.extend(self.snippet(&spec_file, line));
+        }
+        if let Some((path, line)) = raised_at {
+            snippets.extend(self.snippet(path, *line));
+        }
+        snippets
+    }
+
+    /// `path` relative to the root, when it is inside it and not installed
+    /// gem code.
+    fn project_path(&self, path: &str) -> Option<String> {
+        let path = path.strip_prefix("./").unwrap_or(path);
+        let relative = match Path::new(path).is_absolute() {
+            true => Path::new(path).strip_prefix(self.root.as_ref()?).ok()?.to_string_lossy().into_owned(),
+            false => path.to_string(),
+        };
+        let installed = relative.starts_with("vendor/") || relative.contains("/gems/");
+        (!relative.is_empty() && !installed && !relative.split('/').any(|part| part == "..")).then_some(relative)
+    }
+
+    fn snippet(&mut self, file: &str, line: u32) -> Option<SourceSnippet> {
+        let root = self.root.as_ref()?;
+        let lines = self
+            .files
+            .entry(file.to_string())
+            .or_insert_with(|| read_lines(&root.join(file)))
+            .as_ref()?;
+
+        let index = (line as usize).checked_sub(1).filter(|index| *index < lines.len())?;
+        let start = index.saturating_sub(CONTEXT_LINES);
+        let end = (index + CONTEXT_LINES + 1).min(lines.len());
+        let width = end.to_string().len();
+        let code = (start..end)
+            .map(|i| {
+                let marker = if i == index { '>' } else { ' ' };
+                format!("{} {:>width$} | {}", marker, i + 1, lines[i])
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Some(SourceSnippet { file: file.to_string(), line, code })
+    }
+}
+
+fn read_lines(path: &Path) -> Option<Vec<String>> {
+    if std::fs::metadata(path).ok()?.len() > MAX_SOURCE_BYTES {
+        return None;
+    }
+    let contents = std::fs::read_to_string(path).ok()?;
+    Some(contents.lines().map(str::to_string).collect())
+}
+
+/// The `path` and line of a backtrace frame such as
+/// `./app/models/user.rb:12:in 'save'`.
+fn frame_location(frame: &str) -> Option<(&str, u32)> {
+    let frame = frame.trim().trim_start_matches("# ");
+    frame.match_indices(':').find_map(|(colon, _)| {
+        let rest = &frame[colon + 1..];
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let ends_location = rest[digits..].is_empty() || rest[digits..].starts_with(':');
+        match digits > 0 && ends_location && colon > 0 {
+            true => Some((&frame[..colon], rest[..digits].parse().ok()?)),
+            false => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rspec_result::ExampleException;
+
+    fn project() -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("spec/models")).unwrap();
+        std::fs::create_dir_all(root.join("app/models")).unwrap();
+        let spec: Vec<String> = (1..=20).map(|n| format!("spec line {}", n)).collect();
+        std::fs::write(root.join("spec/models/user_spec.rb"), spec.join("\n")).unwrap();
+        std::fs::write(root.join("app/models/user.rb"), "class User\n  def save\n    raise 'boom'\n  end\nend\n").unwrap();
+        tmp
+    }
+
+    fn failed(backtrace: Vec<String>) -> ExampleReport {
+        ExampleReport {
+            status: "failed".to_string(),
+            file_path: "./spec/models/user_spec.rb".to_string(),
+            line_number: Some(10),
+            exception: Some(ExampleException { backtrace, ..Default::default() }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_frame_location() {
+        assert_eq!(frame_location("./app/models/user.rb:12:in `save'"), Some(("./app/models/user.rb", 12)));
+        assert_eq!(frame_location("# /srv/app/lib/a.rb:3"), Some(("/srv/app/lib/a.rb", 3)));
+        assert_eq!(frame_location("C:/app/a.rb:7:in 'x'"), Some(("C:/app/a.rb", 7)));
+        assert_eq!(frame_location("no location here"), None);
+    }
+
+    #[test]
+    fn test_annotates_spec_and_raising_frame() {
+        let tmp = project();
+        let root = tmp.path();
+        let mut examples = vec![
+            failed(vec![
+                format!("{}/app/models/user.rb:3:in 'save'", root.canonicalize().unwrap().display()),
+                "/usr/lib/ruby/gems/3.3.0/gems/activerecord/lib/base.rb:9:in 'x'".to_string(),
+                "./spec/models/user_spec.rb:12:in `block (2 levels)'".to_string(),
+            ]),
+            ExampleReport { status: "passed".to_string(), ..failed(vec![]) },
+        ];
+
+        SourceContext::new(root).annotate(&mut examples);
+
+        assert_eq!(
+            examples[0].source_context,
+            [
+                SourceSnippet {
+                    file: "spec/models/user_spec.rb".to_string(),
+                    line: 12,
+                    code: "   9 | spec line 9\n  10 | spec line 10\n  11 | spec line 11\n> 12 | spec line 12\n  13 | spec line 13\n  14 | spec line 14\n  15 | spec line 15".to_string(),
+                },
+                SourceSnippet {
+                    file: "app/models/user.rb".to_string(),
+                    line: 3,
+                    code: "  1 | class User\n  2 |   def save\n> 3 |     raise 'boom'\n  4 |   end\n  5 | end".to_string(),
+                },
+            ]
+        );
+        assert!(examples[1].source_context.is_empty());
+    }
+
+    #[test]
+    fn test_around() {
+        let tmp = project();
+        let root = tmp.path();
+        let mut source = SourceContext::new(root);
+        assert_eq!(source.around("./app/models/user.rb:1").unwrap().code, "> 1 | class User\n  2 |   def save\n  3 |     raise 'boom'\n  4 |   end");
+        assert_eq!(source.around("app/models/user.rb:40"), None);
+        assert_eq!(source.around("../outside.rb:1"), None);
+    }
+
+    #[test]
+    fn test_falls_back_to_example_line() {
+        let tmp = project();
+        let root = tmp.path();
+        let mut examples = vec![failed(vec!["/elsewhere/lib/a.rb:3".to_string(), "../outside.rb:1".to_string()])];
+
+        SourceContext::new(root).annotate(&mut examples);
+
+        assert_eq!(examples[0].source_context.len(), 1);
+        assert_eq!(examples[0].source_context[0].line, 10);
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Most paths `list_spec_files` returns; the rest are only counted.
+pub const MAX_LISTED_FILES: usize = 500;
+
+/// Spec files are MCP resources `spec://PATH`, with PATH as `list` gives it.
+pub const SPEC_URI_PREFIX: &str = "spec://";
+
+/// Where `list_spec_files` looks: `--spec-dir` under the run's directory,
+/// minus hidden entries and `--spec-exclude` patterns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecListing {
+    spec_dir: String,
+    excludes: Vec<String>,
+}
+
+impl Default for SpecListing {
+    fn default() -> Self {
+        Self::new("spec".to_string(), Vec::new())
+    }
+}
+
+impl SpecListing {
+    pub fn new(spec_dir: String, excludes: Vec<String>) -> Self {
+        Self {
+            spec_dir: spec_dir.trim_end_matches('/').to_string(),
+            excludes: excludes
+                .into_iter()
+                .map(|pattern| pattern.trim().trim_end_matches('/').to_string())
+                .filter(|pattern| !pattern.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn spec_dir(&self) -> &str {
+        &self.spec_dir
+    }
+
+    /// The `*_spec.rb` files under the spec directory of `root`, relative
+    /// to `root` and sorted. `filter` keeps paths containing it, or matching
+    /// it as a glob when it has `*` or `?`. Symlinked directories are
+    /// followed once each, so links back up the tree can't loop.
+    pub fn list(&self, root: &Path, filter: Option<&str>) -> Result<Vec<String>, String> {
+        let start = root.join(&self.spec_dir);
+        if !start.is_dir() {
+            return Err(format!("Spec directory {} does not exist in {}", self.spec_dir, root.display()));
+        }
+
+        let mut visited = HashSet::new();
+        let mut files = Vec::new();
+        let mut pending = vec![PathBuf::from(&self.spec_dir)];
+
+        while let Some(relative) = pending.pop() {
+            let dir = root.join(&relative);
+            let Ok(canonical) = dir.canonicalize() else { continue };
+            if !visited.insert(canonical) {
+                continue;
+            }
+            let entries = std::fs::read_dir(&dir).map_err(|e| format!("Cannot list {}: {}", relative.display(), e))?;
+
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let path = relative.join(&name);
+                let path_text = path.to_string_lossy().to_string();
+                if name.starts_with('.') || self.is_excluded(&name, &path_text) {
+                    continue;
+                }
+                // Follows symlinks, unlike `DirEntry::file_type`
+                match std::fs::metadata(entry.path()) {
+                    Ok(meta) if meta.is_dir() => pending.push(path),
+                    Ok(_) if name.ends_with("_spec.rb") && matches_filter(&path_text, filter) => files.push(path_text),
+                    _ => {}
+                }
+            }
+        }
+
+        files.sort();
+        Ok(files)
+    }
+
+    /// Contents of a file `list` could return, by its path relative to
+    /// `root`; other paths are refused without touching the filesystem.
+    pub fn read(&self, root: &Path, path: &str) -> Result<String, String> {
+        let path = path.trim_start_matches("./");
+        let not_listed = || format!("{} is not a spec file under {}", path, self.spec_dir);
+        let rest = path.strip_prefix(&format!("{}/", self.spec_dir)).ok_or_else(not_listed)?;
+
+        let mut listed = PathBuf::from(&self.spec_dir);
+        for name in rest.split('/') {
+            listed.push(name);
+            if name.is_empty() || name.starts_with('.') || self.is_excluded(name, &listed.to_string_lossy()) {
+                return Err(not_listed());
+            }
+        }
+        if !path.ends_with("_spec.rb") {
+            return Err(not_listed());
+        }
+
+        std::fs::read_to_string(root.join(path)).map_err(|e| format!("Cannot read {}: {}", path, e))
+    }
+
+    /// Listed spec files named `file_name`, or with a `describe` of
+    /// `constant`, for when a file's spec isn't where convention puts it.
+    pub fn search(&self, root: &Path, file_name: Option<&str>, constant: Option<&str>) -> Result<Vec<String>, String> {
+        let describes = |path: &str| {
+            let Some(constant) = constant else { return false };
+            let Ok(contents) = std::fs::read_to_string(root.join(path)) else { return false };
+            contents
+                .lines()
+                .any(|line| line.contains("describe") && mentions_constant(line, constant))
+        };
+
+        Ok(self
+            .list(root, None)?
+            .into_iter()
+            .filter(|path| file_name.is_some_and(|name| path.rsplit('/').next() == Some(name)) || describes(path))
+            .collect())
+    }
+
+    /// Gitignore-style: patterns without a `/` match any file or directory
+    /// name; patterns with one match the path from the root.
+    fn is_excluded(&self, name: &str, path: &str) -> bool {
+        self.excludes.iter().any(|pattern| match pattern.contains('/') {
+            true => glob_match(pattern.trim_start_matches('/'), path),
+            false => glob_match(pattern, name),
+        })
+    }
+}
+
+/// Whether `line` names `constant` itself, not a longer name containing it.
+fn mentions_constant(line: &str, constant: &str) -> bool {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == ':';
+    line.match_indices(constant).any(|(start, _)| {
+        !line[..start].ends_with(is_name) && !line[start + constant.len()..].starts_with(is_name)
+    })
+}
+
+fn matches_filter(path: &str, filter: Option<&str>) -> bool {
+    match filter.map(str::trim).filter(|f| !f.is_empty()) {
+        Some(glob) if glob.contains(['*', '?']) => glob_match(glob, path),
+        Some(text) => path.contains(text),
+        None => true,
+    }
+}
+
+/// `*` matches within a path component, `**` across components, `?` one
+/// character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_match_from(&pattern, &text)
+}
+
+fn glob_match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            let rest = rest.strip_prefix(&['/']).unwrap_or(rest);
+            (0..=text.len()).any(|i| glob_match_from(rest, &text[i..]))
+        }
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| glob_match_from(rest, &text[i..])),
+        ['?', rest @ ..] => text.first().is_some_and(|c| *c != '/') && glob_match_from(rest, &text[1..]),
+        [c, rest @ ..] => text.first() == Some(c) && glob_match_from(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project() -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        for dir in ["spec/models/admin", "spec/.hidden", "spec/fixtures", "spec/support"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "spec/models/user_spec.rb",
+            "spec/models/admin/role_spec.rb",
+            "spec/models/helper.rb",
+            "spec/.hidden/secret_spec.rb",
+            "spec/fixtures/sample_spec.rb",
+            "spec/requests_spec.rb",
+        ] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        tmp
+    }
+
+    #[test]
+    fn test_list_skips_hidden_and_excluded() {
+        let tmp = project();
+        let root = tmp.path();
+        let listing = SpecListing::new("spec/".to_string(), vec!["fixtures/".to_string()]);
+
+        assert_eq!(
+            listing.list(root, None).unwrap(),
+            vec!["spec/models/admin/role_spec.rb", "spec/models/user_spec.rb", "spec/requests_spec.rb"]
+        );
+        let listing = SpecListing::new("spec".to_string(), vec!["spec/models/admin".to_string(), "*_helper.rb".to_string()]);
+        assert_eq!(
+            listing.list(root, None).unwrap(),
+            vec!["spec/fixtures/sample_spec.rb", "spec/models/user_spec.rb", "spec/requests_spec.rb"]
+        );
+    }
+
+    #[test]
+    fn test_list_with_filter() {
+        let tmp = project();
+        let root = tmp.path();
+        let listing = SpecListing::default();
+
+        assert_eq!(listing.list(root, Some("user")).unwrap(), vec!["spec/models/user_spec.rb"]);
+        assert_eq!(
+            listing.list(root, Some("spec/models/**/*_spec.rb")).unwrap(),
+            vec!["spec/models/admin/role_spec.rb", "spec/models/user_spec.rb"]
+        );
+        assert_eq!(listing.list(root, Some("spec/*_spec.rb")).unwrap(), vec!["spec/requests_spec.rb"]);
+        assert!(listing.list(root, Some("nothing")).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_list_survives_symlink_loop() {
+        let tmp = project();
+        let root = tmp.path();
+        std::os::unix::fs::symlink(root.join("spec"), root.join("spec/models/loop")).unwrap();
+        let listing = SpecListing::new("spec".to_string(), vec!["fixtures".to_string()]);
+
+        let files = listing.list(root, Some("user")).unwrap();
+        assert_eq!(files, vec!["spec/models/user_spec.rb"]);
+    }
+
+    #[test]
+    fn test_search_by_name_and_describe() {
+        let tmp = project();
+        let root = tmp.path();
+        std::fs::write(root.join("spec/models/admin/role_spec.rb"), "RSpec.describe Admin::Role do\nend\n").unwrap();
+        std::fs::write(root.join("spec/requests_spec.rb"), "RSpec.describe Role, type: :model do\nend\n").unwrap();
+        let listing = SpecListing::new("spec".to_string(), vec!["fixtures".to_string()]);
+
+        assert_eq!(
+            listing.search(root, Some("user_spec.rb"), Some("Role")).unwrap(),
+            vec!["spec/models/user_spec.rb", "spec/requests_spec.rb"]
+        );
+        assert_eq!(listing.search(root, None, Some("Admin::Role")).unwrap(), vec!["spec/models/admin/role_spec.rb"]);
+        assert!(listing.search(root, None, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_only_listed_files() {
+        let tmp = project();
+        let root = tmp.path();
+        std::fs::write(root.join("spec/models/user_spec.rb"), "describe User do\nend\n").unwrap();
+        let listing = SpecListing::new("spec".to_string(), vec!["fixtures".to_string()]);
+
+        assert_eq!(listing.read(root, "spec/models/user_spec.rb").unwrap(), "describe User do\nend\n");
+        assert_eq!(listing.read(root, "./spec/requests_spec.rb").unwrap(), "");
+        for path in [
+            "spec/fixtures/sample_spec.rb",
+            "spec/.hidden/secret_spec.rb",
+            "spec/models/helper.rb",
+            "spec/../spec/requests_spec.rb",
+            "/etc/passwd",
+            "Cargo.toml",
+        ] {
+            let err = listing.read(root, path).unwrap_err();
+            assert!(err.ends_with("is not a spec file under spec"), "{}: {}", path, err);
+        }
+        assert!(listing.read(root, "spec/models/missing_spec.rb").unwrap_err().starts_with("Cannot read"));
+    }
+
+    #[test]
+    fn test_missing_spec_dir() {
+        let tmp = project();
+        let root = tmp.path();
+        let err = SpecListing::new("test".to_string(), vec![]).list(root, None).unwrap_err();
+        assert!(err.starts_with("Spec directory test does not exist in "));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*_spec.rb", "user_spec.rb"));
+        assert!(!glob_match("*_spec.rb", "models/user_spec.rb"));
+        assert!(glob_match("**/user_spec.rb", "spec/models/user_spec.rb"));
+        assert!(glob_match("**/user_spec.rb", "user_spec.rb"));
+        assert!(glob_match("spec/?odels/*", "spec/models/a_spec.rb"));
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use crate::rspec_result::RspecReport;
+
+/// Pending message RSpec uses for `xit`, `xdescribe` and friends.
+const SKIPPED_PREFIX: &str = "Temporarily skipped with ";
+
+/// Source forms that focus an example or group. RSpec's JSON report has no
+/// metadata, so focus comes from the spec source instead.
+const FOCUS_MARKERS: &[&str] = &[
+    "fit ", "fit(", "fdescribe ", "fdescribe(", "fcontext ", "fcontext(", "fspecify ", "fexample ",
+    "focus: true", ":focus =>", ", :focus",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MarkerCounts {
+    pub examples: usize,
+    pub pending: usize,
+    pub skipped: usize,
+    pub focused: usize,
+}
+
+impl MarkerCounts {
+    /// Counts from a `--dry-run -f json` report of the file plus its source.
+    pub fn from_report(report: &RspecReport, source: &str) -> Self {
+        let mut counts = MarkerCounts {
+            examples: report.examples.len(),
+            ..Default::default()
+        };
+
+        for example in report.examples.iter().filter(|e| e.status == "pending") {
+            match &example.pending_message {
+                Some(message) if message.starts_with(SKIPPED_PREFIX) => counts.skipped += 1,
+                _ => counts.pending += 1,
+            }
+        }
+
+        counts.focused = source
+            .lines()
+            .map(str::trim_start)
+            .filter(|line| !line.starts_with('#'))
+            .filter(|line| FOCUS_MARKERS.iter().any(|marker| line.contains(marker)))
+            .count();
+
+        counts
+    }
+}
+
+/// Marker counts keyed by file path and a hash of its contents, so an
+/// unchanged file doesn't need another dry run.
+#[derive(Debug, Clone, Default)]
+pub struct MarkerCache {
+    entries: Arc<Mutex<HashMap<(String, u64), MarkerCounts>>>,
+}
+
+impl MarkerCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn key(file: &str, source: &str) -> (String, u64) {
+        let mut hasher = DefaultHasher::new();
+        source.hash(&mut hasher);
+        (file.to_string(), hasher.finish())
+    }
+
+    pub fn get(&self, key: &(String, u64)) -> Option<MarkerCounts> {
+        self.entries.lock().unwrap().get(key).copied()
+    }
+
+    pub fn insert(&self, key: (String, u64), counts: MarkerCounts) {
+        let mut entries = self.entries.lock().unwrap();
+        // Only the latest contents of a file are worth keeping
+        entries.retain(|(file, _), _| *file != key.0);
+        entries.insert(key, counts);
+    }
+
+    /// Forgets every entry, returning whether there were any.
+    pub fn clear(&self) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let had_entries = !entries.is_empty();
+        entries.clear();
+        had_entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIXED_REPORT: &str = r#"{
+        "version": "3.12.2",
+        "examples": [
+            {"id": "./spec/models/user_spec.rb[1:1]", "status": "passed", "pending_message": null},
+            {"id": "./spec/models/user_spec.rb[1:2]", "status": "pending", "pending_message": "Temporarily skipped with xit"},
+            {"id": "./spec/models/user_spec.rb[1:3]", "status": "pending", "pending_message": "waiting on the API"},
+            {"id": "./spec/models/user_spec.rb[1:4]", "status": "pending", "pending_message": "Temporarily skipped with xdescribe"},
+            {"id": "./spec/models/user_spec.rb[1:5]", "status": "passed", "pending_message": null}
+        ]
+    }"#;
+
+    const SOURCE: &str = r#"
+RSpec.describe User do
+  it "saves" do; end
+  xit "validates" do; end
+  it "syncs", skip: "waiting on the API" do; end
+  fit "has a name" do; end
+  # fit "commented out" do; end
+  it "is focused", focus: true do; end
+end
+"#;
+
+    #[test]
+    fn test_from_report_with_mixed_markers() {
+        let report = RspecReport::from_output(MIXED_REPORT).unwrap();
+        assert_eq!(
+            MarkerCounts::from_report(&report, SOURCE),
+            MarkerCounts {
+                examples: 5,
+                pending: 1,
+                skipped: 2,
+                focused: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_cache_keyed_by_contents() {
+        let cache = MarkerCache::new();
+        let counts = MarkerCounts {
+            examples: 1,
+            ..Default::default()
+        };
+
+        let key = MarkerCache::key("spec/models/user_spec.rb", SOURCE);
+        cache.insert(key.clone(), counts);
+        assert_eq!(cache.get(&key), Some(counts));
+
+        let changed = MarkerCache::key("spec/models/user_spec.rb", "RSpec.describe User do; end");
+        assert_eq!(cache.get(&changed), None);
+
+        cache.insert(changed.clone(), counts);
+        assert_eq!(cache.get(&key), None);
+
+        assert!(cache.clear());
+        assert!(!cache.clear());
+    }
+}
@@ -0,0 +1,134 @@
+use std::io::Write;
+use std::path::Path;
+
+use crate::file_path_parser::SpecMapping;
+
+/// rspec-rails spec types, by the spec directory they are conventionally
+/// kept in.
+const RAILS_SPEC_TYPES: &[(&str, &str)] = &[
+    ("models", "model"),
+    ("controllers", "controller"),
+    ("requests", "request"),
+    ("helpers", "helper"),
+    ("mailers", "mailer"),
+    ("jobs", "job"),
+    ("views", "view"),
+    ("channels", "channel"),
+];
+
+/// The helper a new spec requires, after what the project's spec directory
+/// has.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpecHelper {
+    /// `rails_helper`, from rspec-rails
+    Rails,
+    Plain,
+    /// Neither helper exists, so nothing is required
+    None,
+}
+
+impl SpecHelper {
+    pub fn detect(root: &Path, spec_dir: &str) -> Self {
+        let spec_dir = root.join(spec_dir);
+        if spec_dir.join("rails_helper.rb").is_file() {
+            Self::Rails
+        } else if spec_dir.join("spec_helper.rb").is_file() {
+            Self::Plain
+        } else {
+            Self::None
+        }
+    }
+}
+
+/// A spec for `mapping`'s source at `spec_path` describing its constant (or,
+/// for a view, its template), with a pending example to fill in. Rails
+/// specs get the `type:` of the directory they are in.
+pub fn skeleton(mapping: &SpecMapping, spec_path: &str, spec_dir: &str, helper: SpecHelper) -> String {
+    let mut spec = match helper {
+        SpecHelper::Rails => "require 'rails_helper'\n\n".to_string(),
+        SpecHelper::Plain => "require 'spec_helper'\n\n".to_string(),
+        SpecHelper::None => String::new(),
+    };
+
+    let subject = match &mapping.constant {
+        Some(constant) => constant.clone(),
+        None => format!("\"{}\"", mapping.source.strip_prefix("app/views/").unwrap_or(&mapping.source)),
+    };
+    let kind = spec_path
+        .strip_prefix(spec_dir)
+        .and_then(|rest| rest.trim_start_matches('/').split('/').next())
+        .and_then(|dir| RAILS_SPEC_TYPES.iter().find(|(spec_dir, _)| *spec_dir == dir))
+        .filter(|_| helper == SpecHelper::Rails);
+    let described = match kind {
+        Some((_, kind)) => format!("{}, type: :{}", subject, kind),
+        None => subject,
+    };
+
+    spec.push_str(&format!(
+        "RSpec.describe {} do\n  pending \"add some examples to (or delete) #{{__FILE__}}\"\nend\n",
+        described
+    ));
+    spec
+}
+
+/// Writes `contents` to a new file at `path`, creating its directory. Fails
+/// rather than overwrite a file that is already there.
+pub fn create(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => format!("{} already exists", path.display()),
+            _ => format!("Could not create {}: {}", path.display(), e),
+        })?;
+    file.write_all(contents.as_bytes())
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skeleton() {
+        let mapping = SpecMapping::for_source("app/models/admin/user_account.rb", "spec").unwrap();
+        assert_eq!(
+            skeleton(&mapping, &mapping.candidates[0], "spec", SpecHelper::Rails),
+            "require 'rails_helper'\n\nRSpec.describe Admin::UserAccount, type: :model do\n  pending \"add some examples to (or delete) #{__FILE__}\"\nend\n"
+        );
+
+        let mapping = SpecMapping::for_source("lib/billing/invoice.rb", "spec").unwrap();
+        assert_eq!(
+            skeleton(&mapping, &mapping.candidates[0], "spec", SpecHelper::Plain),
+            "require 'spec_helper'\n\nRSpec.describe Billing::Invoice do\n  pending \"add some examples to (or delete) #{__FILE__}\"\nend\n"
+        );
+
+        let mapping = SpecMapping::for_source("app/views/users/index.html.erb", "spec").unwrap();
+        assert!(
+            skeleton(&mapping, &mapping.candidates[0], "spec", SpecHelper::Rails)
+                .contains("RSpec.describe \"users/index.html.erb\", type: :view do")
+        );
+        assert!(skeleton(&mapping, &mapping.candidates[0], "spec", SpecHelper::None).starts_with("RSpec.describe \"users/index.html.erb\" do"));
+    }
+
+    #[test]
+    fn test_detect_helper_and_create() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("spec")).unwrap();
+        assert_eq!(SpecHelper::detect(root, "spec"), SpecHelper::None);
+        std::fs::write(root.join("spec/spec_helper.rb"), "").unwrap();
+        assert_eq!(SpecHelper::detect(root, "spec"), SpecHelper::Plain);
+        std::fs::write(root.join("spec/rails_helper.rb"), "").unwrap();
+        assert_eq!(SpecHelper::detect(root, "spec"), SpecHelper::Rails);
+
+        let path = root.join("spec/models/user_spec.rb");
+        create(&path, "first").unwrap();
+        assert!(create(&path, "second").unwrap_err().ends_with("user_spec.rb already exists"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first");
+    }
+}
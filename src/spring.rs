@@ -0,0 +1,131 @@
+use std::path::Path;
+
+use crate::command_runner::CommandResult;
+
+/// How Spring's binstub runs RSpec, with `spring-commands-rspec`.
+pub const SPRING_COMMAND: &[&str] = &["bin/spring", "rspec"];
+
+/// Output meaning Spring itself couldn't run the specs, as opposed to the
+/// specs failing under it.
+const SPRING_ERROR_PATTERNS: &[&str] = &[
+    "Spring::",
+    "Spring server",
+    "spring server",
+    "cannot load such file -- spring",
+    "spring-commands-rspec",
+];
+
+/// Whether runs go through Spring's preloaded Rails app, which saves the
+/// app's boot on every run after the first.
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum SpringMode {
+    /// Run the RSpec command as configured
+    #[default]
+    Off,
+    /// Use `bin/spring rspec` where the workdir has `bin/spring` and
+    /// `spring-commands-rspec` in its Gemfile.lock
+    Auto,
+    /// Always use `bin/spring rspec`
+    On,
+}
+
+impl SpringMode {
+    /// Whether a run in `root` goes through Spring, or why not. `env` is
+    /// the run's environment on top of the server's, which counts unless
+    /// `inherited` is false; `DISABLE_SPRING` in either turns Spring off.
+    pub fn choose(self, root: &Path, env: &[(String, String)], inherited: bool) -> Result<(), &'static str> {
+        let disable = env
+            .iter()
+            .rev()
+            .find(|(key, _)| key == "DISABLE_SPRING")
+            .map(|(_, value)| value.clone())
+            .or_else(|| inherited.then(|| std::env::var("DISABLE_SPRING").ok()).flatten());
+        if disable.is_some_and(|value| !value.is_empty() && value != "0") {
+            return Err("DISABLE_SPRING is set");
+        }
+        match self {
+            SpringMode::Off => Err("--spring is off"),
+            SpringMode::On => Ok(()),
+            SpringMode::Auto if detect(root) => Ok(()),
+            SpringMode::Auto => Err("no bin/spring with spring-commands-rspec in the workdir"),
+        }
+    }
+}
+
+fn detect(root: &Path) -> bool {
+    root.join(SPRING_COMMAND[0]).is_file()
+        && std::fs::read_to_string(root.join("Gemfile.lock")).is_ok_and(|lock| lock.contains("spring-commands-rspec"))
+}
+
+/// Why a run through Spring should be retried without it: Spring failed
+/// before RSpec reported anything. Runs that timed out or were cancelled
+/// aren't retried.
+pub fn failure(result: &CommandResult) -> Option<String> {
+    if result.exit_code == 0 || result.timed_out || result.cancelled {
+        return None;
+    }
+    let ran_specs = [&result.stdout, &result.stderr]
+        .iter()
+        .any(|output| output.contains("examples, ") || output.contains("example, ") || output.contains("\"summary_line\""));
+    if ran_specs {
+        return None;
+    }
+    if matches!(result.exit_code, 126 | 127) {
+        return Some(format!("{} exited with {}", SPRING_COMMAND[0], result.exit_code));
+    }
+    result
+        .stderr
+        .lines()
+        .chain(result.stdout.lines())
+        .find(|line| SPRING_ERROR_PATTERNS.iter().any(|pattern| line.contains(pattern)))
+        .map(|line| line.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(exit_code: i32, stdout: &str, stderr: &str) -> CommandResult {
+        CommandResult {
+            exit_code,
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+            abort_reason: None,
+            signal: None,
+            timed_out: false,
+            cancelled: false,
+            peak_memory_mb: None,
+            launcher: None,
+        }
+    }
+
+    #[test]
+    fn test_choose() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("bin")).unwrap();
+        assert_eq!(SpringMode::Auto.choose(root, &[], false), Err("no bin/spring with spring-commands-rspec in the workdir"));
+        std::fs::write(root.join("bin/spring"), "").unwrap();
+        std::fs::write(root.join("Gemfile.lock"), "    spring-commands-rspec (1.0.4)\n").unwrap();
+
+        assert_eq!(SpringMode::Auto.choose(root, &[], false), Ok(()));
+        assert_eq!(SpringMode::On.choose(Path::new("/nonexistent"), &[], false), Ok(()));
+        assert_eq!(SpringMode::Off.choose(root, &[], false), Err("--spring is off"));
+        let disabled = [("DISABLE_SPRING".to_string(), "1".to_string())];
+        assert_eq!(SpringMode::On.choose(root, &disabled, false), Err("DISABLE_SPRING is set"));
+        let enabled = [disabled[0].clone(), ("DISABLE_SPRING".to_string(), "0".to_string())];
+        assert_eq!(SpringMode::On.choose(root, &enabled, false), Ok(()));
+    }
+
+    #[test]
+    fn test_failure() {
+        let refused = result(1, "", "Error connecting to Spring server: Connection refused\n");
+        assert_eq!(failure(&refused).as_deref(), Some("Error connecting to Spring server: Connection refused"));
+        assert_eq!(failure(&result(127, "", "")).as_deref(), Some("bin/spring exited with 127"));
+
+        assert_eq!(failure(&result(1, "F\n\n1 example, 1 failure\n", "Spring::Watcher warning\n")), None);
+        assert_eq!(failure(&result(1, "", "NameError: uninitialized constant User\n")), None);
+        assert_eq!(failure(&result(0, "", "")), None);
+        assert_eq!(failure(&CommandResult { timed_out: true, ..result(127, "", "") }), None);
+    }
+}
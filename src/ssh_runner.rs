@@ -0,0 +1,258 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::command_runner::{CommandResult, shell_join};
+use crate::container_runner::{replace_paths, replace_root};
+use crate::process_runner::{CommandSpec, ProcessRunner};
+
+/// The remote host runs execute on, and where the project lives there.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SshTarget {
+    /// `host` or `user@host`, as `ssh` accepts it.
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_file: Option<PathBuf>,
+    /// The remote checkout that the local project root corresponds to.
+    pub remote_root: Option<String>,
+    /// The local project root; the server's working directory when unset.
+    pub local_root: Option<PathBuf>,
+}
+
+/// Runs each command on a remote host with `ssh`, by handing `local` an
+/// `ssh` invocation in its place. Working on the fully assembled command
+/// lets every argument, option and variable be quoted for the remote shell.
+///
+/// The run's working directory, and arguments under the local project root,
+/// are moved under `remote_root`, and remote paths in the output point at
+/// the local root again. Killing a run stops the local `ssh`; without a
+/// terminal, the remote side may not notice.
+pub struct SshProcessRunner<P> {
+    target: SshTarget,
+    local: P,
+}
+
+impl<P: ProcessRunner> SshProcessRunner<P> {
+    pub fn new(target: SshTarget, local: P) -> Self {
+        Self { target, local }
+    }
+
+    fn local_root(&self) -> Option<String> {
+        self.target
+            .local_root
+            .clone()
+            .or_else(|| std::env::current_dir().ok())
+            .map(|root| root.to_string_lossy().into_owned())
+    }
+
+    fn to_remote(&self, path: &str) -> String {
+        match (self.local_root(), &self.target.remote_root) {
+            (Some(local), Some(remote)) => replace_root(path, &local, remote).unwrap_or_else(|| path.to_string()),
+            _ => path.to_string(),
+        }
+    }
+
+    /// The remote and local roots output is translated between, when both
+    /// are known.
+    fn output_roots(&self) -> Option<(String, String)> {
+        let remote = self.target.remote_root.clone().filter(|remote| remote != "/")?;
+        Some((remote, self.local_root()?))
+    }
+
+    /// The shell command line the remote host runs for `spec`.
+    fn remote_command(&self, spec: &CommandSpec) -> String {
+        // Directories outside the local root fall back to the remote one
+        let workdir = self.target.remote_root.as_ref().map(|root| {
+            spec.workdir
+                .as_ref()
+                .zip(self.local_root())
+                .and_then(|(workdir, local)| replace_root(&workdir.to_string_lossy(), &local, root))
+                .unwrap_or_else(|| root.clone())
+        });
+
+        let mut command = Vec::new();
+        if let Some(workdir) = workdir {
+            command.push(format!("cd {} &&", shell_join(&[workdir])));
+        }
+        command.push("exec".to_string());
+        if spec.env_clear || !spec.env.is_empty() {
+            command.push("env".to_string());
+        }
+        if spec.env_clear {
+            command.push("-i PATH=\"$PATH\"".to_string());
+        }
+        let env: Vec<String> = spec.env.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+        let argv: Vec<String> = spec.argv.iter().map(|arg| self.to_remote(arg)).collect();
+        command.push(shell_join(&[env, argv].concat()));
+        command.join(" ")
+    }
+
+    fn ssh_argv(&self, spec: &CommandSpec) -> Vec<String> {
+        let mut argv = vec!["ssh".to_string(), "-o".to_string(), "BatchMode=yes".to_string()];
+        if let Some(user) = &self.target.user {
+            argv.extend(["-l".to_string(), user.clone()]);
+        }
+        if let Some(port) = self.target.port {
+            argv.extend(["-p".to_string(), port.to_string()]);
+        }
+        if let Some(identity_file) = &self.target.identity_file {
+            argv.extend(["-i".to_string(), identity_file.to_string_lossy().into_owned()]);
+        }
+        argv.extend(["--".to_string(), self.target.host.clone(), self.remote_command(spec)]);
+        argv
+    }
+}
+
+#[async_trait]
+impl<P: ProcessRunner> ProcessRunner for SshProcessRunner<P> {
+    async fn spawn(&self, spec: &CommandSpec, lines: Option<UnboundedSender<String>>) -> Result<CommandResult, String> {
+        // The variables are set remotely; ssh itself keeps the server's
+        let ssh = CommandSpec {
+            argv: self.ssh_argv(spec),
+            env: Vec::new(),
+            env_clear: false,
+            ..spec.clone()
+        };
+
+        let roots = self.output_roots();
+        let to_local = move |text: &str| match &roots {
+            Some((remote, local)) => replace_paths(text, remote, local),
+            None => text.to_string(),
+        };
+
+        let forward = lines.map(|lines| {
+            let (remote_lines, mut received) = tokio::sync::mpsc::unbounded_channel::<String>();
+            let to_local = to_local.clone();
+            let task = tokio::spawn(async move {
+                while let Some(line) = received.recv().await {
+                    let _ = lines.send(to_local(&line));
+                }
+            });
+            (remote_lines, task)
+        });
+        let (remote_lines, task) = forward.unzip();
+
+        let result = self.local.spawn(&ssh, remote_lines).await;
+        if let Some(task) = task {
+            let _ = task.await;
+        }
+        let mut result = result?;
+        result.stdout = to_local(&result.stdout);
+        result.stderr = to_local(&result.stderr);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use crate::command_runner::{CommandRunner, RunContext};
+    use crate::rspec_runner::RspecRunner;
+
+    /// Records each spec, replying with `stdout` line by line.
+    #[derive(Clone, Default)]
+    struct RecordingProcess {
+        specs: Arc<Mutex<Vec<CommandSpec>>>,
+        stdout: String,
+    }
+
+    #[async_trait]
+    impl ProcessRunner for RecordingProcess {
+        async fn spawn(&self, spec: &CommandSpec, lines: Option<UnboundedSender<String>>) -> Result<CommandResult, String> {
+            self.specs.lock().unwrap().push(spec.clone());
+            for line in self.stdout.lines() {
+                if let Some(lines) = &lines {
+                    let _ = lines.send(line.to_string());
+                }
+            }
+            Ok(CommandResult {
+                exit_code: 0,
+                stdout: self.stdout.clone(),
+                stderr: String::new(),
+                abort_reason: None,
+                signal: None,
+                timed_out: false,
+                cancelled: false,
+                peak_memory_mb: None,
+                launcher: None,
+            })
+        }
+    }
+
+    fn target() -> SshTarget {
+        SshTarget {
+            host: "ci-box".to_string(),
+            user: Some("deploy".to_string()),
+            port: Some(2222),
+            identity_file: Some(PathBuf::from("/home/me/.ssh/ci")),
+            remote_root: Some("/srv/app".to_string()),
+            local_root: Some(PathBuf::from("/home/me/app")),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_runs_over_ssh() {
+        let process = RecordingProcess::default();
+        let runner = RspecRunner::new("bundle exec rspec".to_string())
+            .with_env(vec![("RAILS_ENV".to_string(), "test".to_string())])
+            .with_process_runner(Arc::new(SshProcessRunner::new(target(), process.clone())));
+        let context = RunContext {
+            extra_args: vec!["-e".to_string(), "it's saved".to_string()],
+            workdir: Some(PathBuf::from("/home/me/app/engines/billing")),
+            ..Default::default()
+        };
+
+        runner.run("/home/me/app/spec/a_spec.rb:3", &context).await.unwrap();
+
+        let spec = process.specs.lock().unwrap()[0].clone();
+        assert_eq!(
+            spec.argv,
+            [
+                "ssh", "-o", "BatchMode=yes", "-l", "deploy", "-p", "2222", "-i", "/home/me/.ssh/ci", "--", "ci-box",
+                "cd /srv/app/engines/billing && exec env RAILS_ENV=test bundle exec rspec -e 'it'\\''s saved' /srv/app/spec/a_spec.rb:3",
+            ]
+        );
+        assert!(spec.env.is_empty());
+        assert_eq!(spec.workdir, Some(PathBuf::from("/home/me/app/engines/billing")));
+    }
+
+    #[test]
+    fn test_remote_command_without_root() {
+        let ssh = SshProcessRunner::new(
+            SshTarget { host: "me@ci-box".to_string(), ..Default::default() },
+            RecordingProcess::default(),
+        );
+        let spec = CommandSpec {
+            argv: vec!["rspec".to_string(), "spec".to_string()],
+            env_clear: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            ssh.ssh_argv(&spec),
+            ["ssh", "-o", "BatchMode=yes", "--", "me@ci-box", "exec env -i PATH=\"$PATH\" rspec spec"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_output_points_at_local_root() {
+        let process = RecordingProcess {
+            stdout: "# /srv/app/spec/a_spec.rb:3\n".to_string(),
+            ..Default::default()
+        };
+        let ssh = SshProcessRunner::new(target(), process);
+        let (lines, mut received) = tokio::sync::mpsc::unbounded_channel();
+
+        let result = ssh
+            .spawn(&CommandSpec { argv: vec!["rspec".to_string()], ..Default::default() }, Some(lines))
+            .await
+            .unwrap();
+
+        assert_eq!(result.stdout, "# /home/me/app/spec/a_spec.rb:3\n");
+        assert_eq!(received.recv().await.unwrap(), "# /home/me/app/spec/a_spec.rb:3");
+    }
+}
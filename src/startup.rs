@@ -0,0 +1,88 @@
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::process::Command;
+
+/// Runs `--startup-command` through `sh -c`, so operators can chain setup
+/// steps such as `bundle install && bin/rails db:prepare`. Returns the
+/// combined output on success.
+pub async fn run_startup_command(
+    command: &str,
+    workdir: Option<&Path>,
+    timeout: Duration,
+) -> Result<String, String> {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+    if let Some(workdir) = workdir {
+        cmd.current_dir(workdir);
+    }
+
+    let output = match tokio::time::timeout(timeout, cmd.output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(format!("Startup command failed to start: {}", e)),
+        Err(_) => {
+            return Err(format!(
+                "Startup command timed out after {} seconds",
+                timeout.as_secs_f64()
+            ));
+        }
+    };
+
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    if output.status.success() {
+        Ok(text)
+    } else {
+        let status = match output.status.code() {
+            Some(code) => format!("exit code {}", code),
+            None => "a signal".to_string(),
+        };
+        Err(format!("Startup command failed with {}:\n{}", status, text.trim_end()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[tokio::test]
+    async fn test_startup_command_success() {
+        let output = run_startup_command("echo preparing && echo ready >&2", None, TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(output, "preparing\nready\n");
+    }
+
+    #[tokio::test]
+    async fn test_startup_command_failure() {
+        let err = run_startup_command("echo migration pending; exit 3", None, TIMEOUT)
+            .await
+            .unwrap_err();
+        assert_eq!(err, "Startup command failed with exit code 3:\nmigration pending");
+    }
+
+    #[tokio::test]
+    async fn test_startup_command_timeout() {
+        let err = run_startup_command("sleep 5", None, Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert_eq!(err, "Startup command timed out after 0.1 seconds");
+    }
+
+    #[tokio::test]
+    async fn test_startup_command_workdir() {
+        let dir = std::env::temp_dir().canonicalize().unwrap();
+        let output = run_startup_command("pwd", Some(&dir), TIMEOUT).await.unwrap();
+        assert_eq!(output.trim(), dir.to_string_lossy());
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+
+use crate::tls::TlsListener;
+
+/// How clients talk to the server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum Transport {
+    /// HTTP with Server-Sent Events, on --hostname and --port
+    #[default]
+    Sse,
+    /// stdin and stdout, for clients that launch the server as a subprocess
+    Stdio,
+    /// MCP's Streamable HTTP, at /mcp on --hostname and --port
+    StreamableHttp,
+}
+
+/// Longest wait between two attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How often binding or serving is retried before giving up. The wait starts
+/// at `initial_backoff` and doubles after every failed attempt.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub initial_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(retries: u32) -> Self {
+        Self {
+            retries,
+            initial_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Runs `op` until it succeeds or `policy.retries` retries have failed,
+/// logging each failure. Returns the last error once retries run out.
+pub async fn retry_with_backoff<T, E, F, Fut>(what: &str, policy: RetryPolicy, mut op: F) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut backoff = policy.initial_backoff;
+    let mut attempt = 0;
+
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < policy.retries => {
+                attempt += 1;
+                tracing::warn!(
+                    "Failed to {} (retry {} of {}): {}; retrying in {:?}",
+                    what,
+                    attempt,
+                    policy.retries,
+                    e,
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Serves `router` on `bind` until `ct` is cancelled, over HTTPS when `tls`
+/// is given. A failed bind, or the server stopping with an error, is retried
+/// per `policy`; the error is returned once retries run out.
+pub async fn serve_with_retry(
+    bind: SocketAddr,
+    router: axum::Router,
+    ct: CancellationToken,
+    policy: RetryPolicy,
+    tls: Option<TlsAcceptor>,
+) -> std::io::Result<()> {
+    retry_with_backoff("bind or serve the HTTP transport", policy, || {
+        let router = router.clone();
+        let ct = ct.clone();
+        let tls = tls.clone();
+        async move {
+            // A failed bind uses up one of the same retries as serving
+            let listener = TcpListener::bind(bind).await?;
+            let shutdown = async move {
+                ct.cancelled().await;
+                tracing::info!("sse server cancelled");
+            };
+            match tls {
+                Some(acceptor) => {
+                    axum::serve(TlsListener::new(listener, acceptor), router)
+                        .with_graceful_shutdown(shutdown)
+                        .await
+                }
+                None => axum::serve(listener, router).with_graceful_shutdown(shutdown).await,
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn policy(retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            retries,
+            initial_backoff: Duration::from_millis(10),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let attempts = AtomicU32::new(0);
+        let result = retry_with_backoff("bind", policy(3), || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err("address in use"),
+                _ => Ok("bound"),
+            }
+        })
+        .await;
+
+        assert_eq!(result, Ok("bound"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), _> = retry_with_backoff("bind", policy(2), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err("address in use")
+        })
+        .await;
+
+        assert_eq!(result, Err("address in use"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_bind_succeeds_once_port_is_released() {
+        let holder = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = holder.local_addr().unwrap();
+        assert!(TcpListener::bind(addr).await.is_err());
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(holder);
+        });
+
+        let listener = retry_with_backoff("bind", policy(8), || TcpListener::bind(addr)).await;
+        assert_eq!(listener.unwrap().local_addr().unwrap(), addr);
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use notify::{RecursiveMode, Watcher};
+use rmcp::model::{LoggingLevel, LoggingMessageNotificationParam};
+use rmcp::{Peer, RoleServer};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+use crate::command_runner::{CommandRunner, RunContext};
+use crate::file_path_parser::SpecMapping;
+use crate::rspec_output::{FailureReport, RunStatus, RunSummary};
+use crate::rspec_result::ReportSummary;
+
+/// Logger name of the notifications watch runs send.
+const WATCH_LOGGER: &str = "rspec.watch";
+
+/// Directories whose changes never trigger a run.
+const IGNORED_DIRS: &[&str] = &["tmp", "log", "coverage", "vendor", "node_modules"];
+
+/// Failures listed in a watch notification; the rest are counted.
+const MAX_NOTIFIED_FAILURES: usize = 10;
+
+/// What `--watch` watches and how long it waits for edits to settle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchConfig {
+    pub root: PathBuf,
+    pub spec_dir: String,
+    pub debounce: Duration,
+}
+
+/// The minimum level a client asked for with `logging/setLevel`, shared
+/// by its session's server.
+pub type LogLevel = Arc<Mutex<LoggingLevel>>;
+
+/// A connected client and the level it wants notifications at.
+type Subscriber = (Peer<RoleServer>, LogLevel);
+
+/// The connected clients watch runs are reported to.
+#[derive(Clone, Default)]
+pub struct WatchSubscribers {
+    peers: Arc<Mutex<Vec<Subscriber>>>,
+}
+
+impl WatchSubscribers {
+    pub fn subscribe(&self, peer: Peer<RoleServer>, level: LogLevel) {
+        self.peers.lock().unwrap().push((peer, level));
+    }
+
+    /// Sends `message` to every client whose level lets it through,
+    /// forgetting those that have disconnected.
+    async fn broadcast(&self, message: LoggingMessageNotificationParam) {
+        let peers: Vec<Subscriber> = {
+            let mut peers = self.peers.lock().unwrap();
+            peers.retain(|(peer, _)| !peer.is_transport_closed());
+            peers.clone()
+        };
+        for (peer, level) in peers {
+            let minimum = *level.lock().unwrap();
+            if severity(message.level) >= severity(minimum) {
+                let _ = peer.notify_logging_message(message.clone()).await;
+            }
+        }
+    }
+}
+
+fn severity(level: LoggingLevel) -> u8 {
+    level as u8
+}
+
+/// The specs to run for files that changed under `root`: spec files
+/// themselves, and the conventional spec of other Ruby files when it
+/// exists. Hidden, temporary and installed files are skipped.
+pub fn specs_for_changes(root: &Path, spec_dir: &str, changed: &[PathBuf]) -> Vec<String> {
+    let mut specs = BTreeSet::new();
+    for path in changed {
+        let Ok(relative) = path.strip_prefix(root) else {
+            continue;
+        };
+        let relative = relative.to_string_lossy();
+        let ignored = relative.split('/').any(|part| part.starts_with('.'))
+            || relative.split('/').next().is_some_and(|top| IGNORED_DIRS.contains(&top));
+        if ignored || !relative.ends_with(".rb") {
+            continue;
+        }
+        let Ok(mapping) = SpecMapping::for_source(&relative, spec_dir) else {
+            continue;
+        };
+        if let Some(spec) = mapping.candidates.into_iter().find(|spec| root.join(spec).is_file()) {
+            specs.insert(spec);
+        }
+    }
+    specs.into_iter().collect()
+}
+
+/// Starts watching `config.root`, running the specs for each settled batch
+/// of changes with `runner` and reporting the result to `subscribers` as a
+/// logging notification. Watching stops when the returned watcher drops.
+pub fn spawn_watcher<R: CommandRunner + 'static>(
+    runner: R,
+    config: WatchConfig,
+    subscribers: WatchSubscribers,
+) -> notify::Result<notify::RecommendedWatcher> {
+    let (events, received) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event
+            && (event.kind.is_create() || event.kind.is_modify())
+        {
+            let _ = events.send(event.paths);
+        }
+    })?;
+    // Events name paths under the directory as it was watched
+    let root = config.root.canonicalize().unwrap_or_else(|_| config.root.clone());
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+    tokio::spawn(watch_loop(runner, WatchConfig { root, ..config }, subscribers, received));
+    Ok(watcher)
+}
+
+async fn watch_loop<R: CommandRunner>(
+    runner: R,
+    config: WatchConfig,
+    subscribers: WatchSubscribers,
+    mut received: UnboundedReceiver<Vec<PathBuf>>,
+) {
+    let root = config.root;
+    while let Some(mut changed) = received.recv().await {
+        // Editors write a file in several steps; run once they are done
+        while let Ok(Some(more)) = tokio::time::timeout(config.debounce, received.recv()).await {
+            changed.extend(more);
+        }
+        let specs = specs_for_changes(&root, &config.spec_dir, &changed);
+        if specs.is_empty() {
+            continue;
+        }
+
+        tracing::info!(specs = ?specs, "running specs for changed files");
+        let context = RunContext { workdir: Some(root.clone()), ..Default::default() };
+        let message = match runner.run_paths(&specs, &context).await {
+            Ok(result) => {
+                let status = RunStatus::classify(&result);
+                let summary = RunSummary::parse(&result.stdout);
+                let failures = FailureReport::parse_all(&result.stdout);
+                watch_message(&changed, &root, &specs, &status, summary.as_ref(), &failures)
+            }
+            Err(e) => LoggingMessageNotificationParam {
+                level: LoggingLevel::Error,
+                logger: Some(WATCH_LOGGER.to_string()),
+                data: serde_json::json!({ "specs": specs, "error": e }),
+            },
+        };
+        subscribers.broadcast(message).await;
+    }
+}
+
+/// The notification for a watch run: its status and summary, with a
+/// one-line `message` for clients that only show text.
+fn watch_message(
+    changed: &[PathBuf],
+    root: &Path,
+    specs: &[String],
+    status: &RunStatus,
+    summary: Option<&RunSummary>,
+    failures: &[FailureReport],
+) -> LoggingMessageNotificationParam {
+    let changed: BTreeSet<String> = changed
+        .iter()
+        .filter_map(|path| Some(path.strip_prefix(root).ok()?.to_string_lossy().into_owned()))
+        .collect();
+    let counts = match summary {
+        Some(summary) => format!(
+            "{} example{}, {} failure{}",
+            summary.examples,
+            if summary.examples == 1 { "" } else { "s" },
+            summary.failures,
+            if summary.failures == 1 { "" } else { "s" }
+        ),
+        None => status.to_string(),
+    };
+    let listed: Vec<serde_json::Value> = failures
+        .iter()
+        .take(MAX_NOTIFIED_FAILURES)
+        .map(|failure| serde_json::json!({ "description": failure.description, "location": failure.location }))
+        .collect();
+
+    LoggingMessageNotificationParam {
+        level: match status {
+            RunStatus::Passed => LoggingLevel::Info,
+            _ => LoggingLevel::Warning,
+        },
+        logger: Some(WATCH_LOGGER.to_string()),
+        data: serde_json::json!({
+            "message": format!("{}: {}", specs.join(" "), counts),
+            "changed": changed,
+            "specs": specs,
+            "status": status.label(),
+            "summary": summary.map(ReportSummary::from),
+            "failures": listed,
+            "failure_count": failures.len(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_specs_for_changes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("spec/models")).unwrap();
+        std::fs::write(root.join("spec/models/user_spec.rb"), "").unwrap();
+
+        let changed = [
+            root.join("app/models/user.rb"),
+            root.join("spec/models/user_spec.rb"),
+            root.join("app/models/order.rb"),
+            root.join("tmp/cache/user.rb"),
+            root.join(".git/index"),
+            root.join("config/routes.yml"),
+            PathBuf::from("/elsewhere/app/models/user.rb"),
+        ];
+        assert_eq!(specs_for_changes(root, "spec", &changed), ["spec/models/user_spec.rb"]);
+    }
+
+    #[test]
+    fn test_watch_message() {
+        let root = Path::new("/app");
+        let summary = RunSummary { examples: 3, failures: 1, ..Default::default() };
+        let failures = [FailureReport {
+            description: "User saves".to_string(),
+            location: Some("spec/models/user_spec.rb:4".to_string()),
+            message: String::new(),
+            sub_failures: Vec::new(),
+        }];
+
+        let message = watch_message(
+            &[PathBuf::from("/app/app/models/user.rb")],
+            root,
+            &["spec/models/user_spec.rb".to_string()],
+            &RunStatus::Failed,
+            Some(&summary),
+            &failures,
+        );
+
+        assert_eq!(message.level, LoggingLevel::Warning);
+        assert_eq!(message.data["message"], "spec/models/user_spec.rb: 3 examples, 1 failure");
+        assert_eq!(message.data["changed"][0], "app/models/user.rb");
+        assert_eq!(message.data["failures"][0]["location"], "spec/models/user_spec.rb:4");
+        assert!(severity(LoggingLevel::Warning) > severity(LoggingLevel::Info));
+    }
+}
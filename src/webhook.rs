@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Upper bound on connecting, sending and reading the response.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub const SIGNATURE_HEADER: &str = "X-MCP-RSpec-Signature";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FailurePayload {
+    pub file: String,
+    pub failure_count: u32,
+    pub status: String,
+}
+
+/// POSTs a [`FailurePayload`] to `--failure-webhook` when a run fails, over
+/// `http://` or `https://` such as a Slack or PagerDuty endpoint.
+#[derive(Debug, Clone)]
+pub struct FailureWebhook {
+    url: reqwest::Url,
+    client: reqwest::Client,
+    secret: Option<String>,
+}
+
+impl FailureWebhook {
+    pub fn new(url: &str, secret: Option<String>) -> Result<Self, String> {
+        let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid failure webhook URL '{}': {}", url, e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("Failure webhook must be an http:// or https:// URL, got '{}'", url));
+        }
+        if parsed.host_str().is_none_or(str::is_empty) {
+            return Err(format!("Missing host in failure webhook URL '{}'", url));
+        }
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to set up the failure webhook client: {}", e))?;
+
+        Ok(Self {
+            url: parsed,
+            client,
+            secret,
+        })
+    }
+
+    /// Sends the payload in the background. Failures are logged and never
+    /// affect the run result.
+    pub fn notify(&self, payload: FailurePayload) {
+        let webhook = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = webhook.send(&payload).await {
+                tracing::warn!(error = %e, "failure webhook failed");
+            }
+        });
+    }
+
+    async fn send(&self, payload: &FailurePayload) -> Result<(), String> {
+        let body = serde_json::to_string(payload).map_err(|e| e.to_string())?;
+
+        let mut request = self
+            .client
+            .post(self.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, signature(secret, &body));
+        }
+
+        let response = request
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send failure webhook: {}", e))?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(format!("Failure webhook responded with {}", status.as_u16())),
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// HMAC-SHA256 of `body` keyed with `secret`, as sent in the signature
+/// header.
+fn signature(secret: &str, body: &str) -> String {
+    use hmac::Mac;
+
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex(&mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_signature() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            signature("Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_new_parses_url() {
+        let webhook = FailureWebhook::new("http://hooks.local:8080/rspec/failed", None).unwrap();
+        assert_eq!(webhook.url.host_str(), Some("hooks.local"));
+        assert_eq!(webhook.url.port_or_known_default(), Some(8080));
+        assert_eq!(webhook.url.path(), "/rspec/failed");
+
+        let webhook = FailureWebhook::new("https://hooks.slack.com/services/T0/B0/x", None).unwrap();
+        assert_eq!(webhook.url.port_or_known_default(), Some(443));
+    }
+
+    #[test]
+    fn test_new_rejects_unsupported_urls() {
+        assert_eq!(
+            FailureWebhook::new("ftp://hooks.local/x", None).unwrap_err(),
+            "Failure webhook must be an http:// or https:// URL, got 'ftp://hooks.local/x'"
+        );
+        assert!(FailureWebhook::new("http://:80/", None).is_err());
+        assert!(FailureWebhook::new("http://host:port/", None).is_err());
+        assert!(FailureWebhook::new("hooks.local/x", None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_posts_signed_payload() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if text.split_once("\r\n\r\n").is_some_and(|(_, body)| body.ends_with('}')) {
+                    break;
+                }
+            }
+            socket.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let url = format!("http://127.0.0.1:{}/hooks/rspec", port);
+        let webhook = FailureWebhook::new(&url, Some("secret".to_string())).unwrap();
+        let payload = FailurePayload {
+            file: "spec/models/user_spec.rb".to_string(),
+            failure_count: 2,
+            status: "Failed".to_string(),
+        };
+        webhook.send(&payload).await.unwrap();
+
+        let request = server.await.unwrap();
+        let (head, body) = request.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("POST /hooks/rspec HTTP/1.1\r\n"));
+        assert_eq!(body, r#"{"file":"spec/models/user_spec.rb","failure_count":2,"status":"Failed"}"#);
+
+        let header = format!("{}: {}", SIGNATURE_HEADER, signature("secret", body)).to_lowercase();
+        assert!(head.to_lowercase().contains(&header));
+    }
+
+    #[tokio::test]
+    async fn test_send_reports_error_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+            let _ = socket.write_all(b"HTTP/1.1 500 Internal Server Error\r\n\r\n").await;
+        });
+
+        let webhook = FailureWebhook::new(&format!("http://127.0.0.1:{}/", port), None).unwrap();
+        let payload = FailurePayload {
+            file: "spec/models/user_spec.rb".to_string(),
+            failure_count: 1,
+            status: "Failed".to_string(),
+        };
+        assert_eq!(
+            webhook.send(&payload).await.unwrap_err(),
+            "Failure webhook responded with 500"
+        );
+    }
+}
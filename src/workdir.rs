@@ -0,0 +1,268 @@
+use std::path::{Path, PathBuf};
+
+/// Where runs happen: `--default-workdir` when a request doesn't name one,
+/// and the directories a request may pick instead.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WorkdirPolicy {
+    default: Option<PathBuf>,
+    allowed: Vec<PathBuf>,
+}
+
+impl WorkdirPolicy {
+    /// Fails if the default or any allowed directory doesn't exist.
+    pub fn new(default: Option<PathBuf>, allowed: Vec<PathBuf>) -> Result<Self, String> {
+        let default = default.map(|dir| canonical_dir(&dir)).transpose()?;
+        let allowed = allowed
+            .iter()
+            .map(|dir| canonical_dir(dir))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { default, allowed })
+    }
+
+    pub fn default_workdir(&self) -> Option<&Path> {
+        self.default.as_deref()
+    }
+
+    /// The directory to run in. A requested directory is resolved relative
+    /// to the default, and must be inside the default or an allowed directory.
+    pub fn resolve(&self, requested: Option<&str>) -> Result<Option<PathBuf>, String> {
+        let Some(requested) = requested.map(str::trim).filter(|r| !r.is_empty()) else {
+            return Ok(self.default.clone());
+        };
+
+        let path = match &self.default {
+            Some(default) => default.join(requested),
+            None => PathBuf::from(requested),
+        };
+        let path = canonical_dir(&path)?;
+
+        let permitted = self
+            .default
+            .iter()
+            .chain(&self.allowed)
+            .any(|root| path.starts_with(root));
+
+        if permitted {
+            Ok(Some(path))
+        } else {
+            Err(format!(
+                "Working directory {} is not within --default-workdir or an --allow-workdir directory",
+                path.display()
+            ))
+        }
+    }
+}
+
+/// Checks that a spec directory exists inside the directory the run happens
+/// in (`workdir`, or the server's own working directory).
+pub fn check_spec_directory(workdir: Option<&Path>, dir: &str) -> Result<(), String> {
+    let base = base_dir(workdir)?;
+
+    let path = base.join(dir);
+    match path.canonicalize() {
+        Ok(path) if !path.starts_with(&base) => {
+            Err(format!("Directory {} is not within the working directory", dir))
+        }
+        Ok(path) if path.is_dir() => Ok(()),
+        Ok(_) => Err(format!("{} is not a directory", dir)),
+        Err(_) => Err(format!("Directory {} does not exist in {}", dir, base.display())),
+    }
+}
+
+/// `path` relative to the directory the run happens in. Absolute paths
+/// must point inside it and are made relative, so RSpec, results and status
+/// files all name the spec the same way. Paths that exist are resolved
+/// first, so a symlink can't lead a relative path out of it either.
+pub fn relative_spec_path(workdir: Option<&Path>, path: &str) -> Result<String, String> {
+    let base = base_dir(workdir)?;
+    let base = base.canonicalize().unwrap_or(base);
+    let absolute = Path::new(path);
+    if !absolute.is_absolute() {
+        return match base.join(absolute).canonicalize() {
+            Ok(resolved) if !resolved.starts_with(&base) => {
+                Err(format!("Path {} resolves outside the project root {}", path, base.display()))
+            }
+            _ => Ok(path.to_string()),
+        };
+    }
+
+    let resolved = absolute.canonicalize().unwrap_or_else(|_| absolute.to_path_buf());
+    match resolved.strip_prefix(&base) {
+        Ok(relative) if relative.as_os_str().is_empty() => Err(format!(
+            "Path {} is the project root; name a spec file or directory inside it",
+            path
+        )),
+        Ok(relative) => {
+            let relative = relative.to_string_lossy();
+            Ok(match path.ends_with('/') {
+                true => format!("{}/", relative),
+                false => relative.to_string(),
+            })
+        }
+        Err(_) => Err(format!("Path {} is outside the project root {}", path, base.display())),
+    }
+}
+
+/// `workdir`, or the server's own working directory.
+fn base_dir(workdir: Option<&Path>) -> Result<PathBuf, String> {
+    match workdir {
+        Some(workdir) => Ok(workdir.to_path_buf()),
+        None => std::env::current_dir()
+            .and_then(|dir| dir.canonicalize())
+            .map_err(|e| format!("Cannot determine the working directory: {}", e)),
+    }
+}
+
+/// The `*_spec.rb` files under `dir` (relative to `workdir`, as for
+/// [`check_spec_directory`]), sorted, as paths starting with `dir`.
+pub fn spec_files_in(workdir: Option<&Path>, dir: &str) -> Result<Vec<String>, String> {
+    let base = workdir.map(Path::to_path_buf).unwrap_or_default();
+    let dir = dir.trim_end_matches('/');
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::from(dir)];
+
+    while let Some(relative) = pending.pop() {
+        let entries = std::fs::read_dir(base.join(&relative))
+            .map_err(|e| format!("Cannot list {}: {}", relative.display(), e))?;
+        for entry in entries.flatten() {
+            let path = relative.join(entry.file_name());
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => pending.push(path),
+                Ok(_) if path.to_string_lossy().ends_with("_spec.rb") => {
+                    files.push(path.to_string_lossy().to_string())
+                }
+                _ => {}
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+fn canonical_dir(dir: &Path) -> Result<PathBuf, String> {
+    let path = dir
+        .canonicalize()
+        .map_err(|e| format!("Working directory {} does not exist: {}", dir.display(), e))?;
+
+    if path.is_dir() {
+        Ok(path)
+    } else {
+        Err(format!("Working directory {} is not a directory", dir.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).canonicalize().unwrap()
+    }
+
+    #[test]
+    fn test_default_used_when_request_omits_workdir() {
+        let policy = WorkdirPolicy::new(Some(project_dir()), vec![]).unwrap();
+        assert_eq!(policy.resolve(None).unwrap(), Some(project_dir()));
+        assert_eq!(policy.resolve(Some("  ")).unwrap(), Some(project_dir()));
+    }
+
+    #[test]
+    fn test_check_spec_directory() {
+        let project = project_dir();
+        assert!(check_spec_directory(Some(&project), "src").is_ok());
+        assert!(check_spec_directory(Some(&project), "src/").is_ok());
+        assert_eq!(
+            check_spec_directory(Some(&project), "spec/missing").unwrap_err(),
+            format!("Directory spec/missing does not exist in {}", project.display())
+        );
+        assert_eq!(
+            check_spec_directory(Some(&project), "Cargo").unwrap_err(),
+            format!("Directory Cargo does not exist in {}", project.display())
+        );
+        assert_eq!(
+            check_spec_directory(Some(&project.join("src")), "/").unwrap_err(),
+            "Directory / is not within the working directory"
+        );
+    }
+
+    #[test]
+    fn test_relative_spec_path() {
+        let project = project_dir();
+        let inside = format!("{}/src/main.rs", project.display());
+        assert_eq!(relative_spec_path(Some(&project), "spec/models/user_spec.rb").unwrap(), "spec/models/user_spec.rb");
+        assert_eq!(relative_spec_path(Some(&project), &inside).unwrap(), "src/main.rs");
+        assert_eq!(relative_spec_path(Some(&project), &format!("{}/src/", project.display())).unwrap(), "src/");
+        assert_eq!(
+            relative_spec_path(Some(&project.join("src")), "/etc/passwd_spec.rb").unwrap_err(),
+            format!("Path /etc/passwd_spec.rb is outside the project root {}", project.join("src").display())
+        );
+        assert!(relative_spec_path(Some(&project), &format!("{}/src/../..", project.display())).is_err());
+        assert!(relative_spec_path(Some(&project), &project.display().to_string()).unwrap_err().contains("is the project root"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_relative_spec_path_through_symlink() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("spec")).unwrap();
+        std::os::unix::fs::symlink("/etc", root.join("spec/outside")).unwrap();
+
+        assert_eq!(relative_spec_path(Some(root), "spec").unwrap(), "spec");
+        assert_eq!(
+            relative_spec_path(Some(root), "spec/outside").unwrap_err(),
+            format!("Path spec/outside resolves outside the project root {}", root.canonicalize().unwrap().display())
+        );
+    }
+
+    #[test]
+    fn test_spec_files_in() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("spec/models/admin")).unwrap();
+        for file in ["spec/models/user_spec.rb", "spec/models/admin/role_spec.rb", "spec/models/helper.rb"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+
+        assert_eq!(
+            spec_files_in(Some(root), "spec/models/").unwrap(),
+            vec!["spec/models/admin/role_spec.rb", "spec/models/user_spec.rb"]
+        );
+    }
+
+    #[test]
+    fn test_no_default() {
+        let policy = WorkdirPolicy::default();
+        assert_eq!(policy.resolve(None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_request_overrides_within_default() {
+        let policy = WorkdirPolicy::new(Some(project_dir()), vec![]).unwrap();
+        assert_eq!(policy.resolve(Some("src")).unwrap(), Some(project_dir().join("src")));
+    }
+
+    #[test]
+    fn test_request_outside_allow_list_rejected() {
+        let policy = WorkdirPolicy::new(Some(project_dir().join("src")), vec![]).unwrap();
+        let err = policy.resolve(Some("..")).unwrap_err();
+        assert!(err.ends_with("is not within --default-workdir or an --allow-workdir directory"));
+
+        let policy = WorkdirPolicy::new(Some(project_dir().join("src")), vec![project_dir()]).unwrap();
+        assert_eq!(policy.resolve(Some("..")).unwrap(), Some(project_dir()));
+    }
+
+    #[test]
+    fn test_missing_directories_rejected() {
+        let missing = project_dir().join("no-such-dir");
+        assert!(WorkdirPolicy::new(Some(missing.clone()), vec![]).unwrap_err().starts_with("Working directory"));
+        assert!(WorkdirPolicy::new(Some(project_dir().join("Cargo.toml")), vec![])
+            .unwrap_err()
+            .ends_with("is not a directory"));
+
+        let policy = WorkdirPolicy::new(Some(project_dir()), vec![]).unwrap();
+        assert!(policy.resolve(Some("no-such-dir")).unwrap_err().contains("does not exist"));
+    }
+}